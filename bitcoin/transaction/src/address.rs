@@ -0,0 +1,305 @@
+// Addresses are a human friendly encoding of a script pub key. Segwit outputs use bech32 (BIP173)
+// for witness version 0 and bech32m (BIP350) for version 1 and above: the human readable part
+// followed by the witness version and the witness program in base32.
+
+use std::error::Error;
+use std::fmt;
+use bech32::{FromBase32, ToBase32, Variant, u5};
+use crate::base58::{self, Base58Error};
+use crate::interpreter::witness_program;
+use crate::network::Network;
+use crate::opcodes;
+use crate::script::{BuildError, Script, ScriptBuilder, ScriptPubKey};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+	/// Neither a valid bech32 nor a valid Base58 string.
+	InvalidBase58,
+	/// The Base58Check checksum doesn't match.
+	InvalidChecksum,
+	/// The payload of a Base58 address is a version byte and a 20 byte hash.
+	InvalidLength(usize),
+	/// Unknown version byte of a Base58 address.
+	UnknownPrefix(u8),
+	/// The string looks like a segwit address but isn't valid bech32.
+	Bech32(bech32::Error),
+	/// Unknown human readable part of a segwit address.
+	UnknownHrp(String),
+	/// Witness versions go from 0 to 16.
+	InvalidWitnessVersion(u8),
+	/// Witness programs are 2 to 40 bytes, version 0 programs are 20 or 32 bytes.
+	InvalidWitnessProgramLength(usize),
+	/// Version 0 addresses use bech32 and later versions bech32m (BIP350).
+	InvalidVariant,
+	/// A valid address, but not one of this network.
+	WrongNetwork(Network),
+}
+
+impl fmt::Display for AddressError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AddressError::InvalidBase58 => write!(f, "address is not valid base58 or bech32"),
+			AddressError::InvalidChecksum => write!(f, "base58 checksum mismatch"),
+			AddressError::InvalidLength(len) => write!(f, "invalid address length of {} bytes", len),
+			AddressError::UnknownPrefix(prefix) => write!(f, "unknown address prefix {}", prefix),
+			AddressError::Bech32(e) => write!(f, "invalid bech32: {}", e),
+			AddressError::UnknownHrp(hrp) => write!(f, "unknown human readable part \"{}\"", hrp),
+			AddressError::InvalidWitnessVersion(v) => write!(f, "invalid witness version {}", v),
+			AddressError::InvalidWitnessProgramLength(len) => write!(f, "invalid witness program length of {} bytes", len),
+			AddressError::InvalidVariant => write!(f, "witness version 0 uses bech32, later versions bech32m"),
+			AddressError::WrongNetwork(network) => write!(f, "address is not for the {} network", network),
+		}
+	}
+}
+
+impl Error for AddressError {}
+
+impl From<Base58Error> for AddressError {
+	fn from(e: Base58Error) -> Self {
+		match e {
+			Base58Error::InvalidBase58 => AddressError::InvalidBase58,
+			Base58Error::InvalidChecksum => AddressError::InvalidChecksum,
+		}
+	}
+}
+
+impl From<bech32::Error> for AddressError {
+	fn from(e: bech32::Error) -> Self {
+		AddressError::Bech32(e)
+	}
+}
+
+/// Encode a witness program as a segwit address. Returns None if the program can't be encoded,
+/// i.e. it isn't 2 to 40 bytes long, or 20 or 32 bytes for version 0.
+pub fn encode_segwit(hrp: &str, version: u8, program: &[u8]) -> Option<String> {
+	if version > 16 || !(2..=40).contains(&program.len()) {
+		return None;
+	}
+	if version == 0 && program.len() != 20 && program.len() != 32 {
+		return None;
+	}
+
+	let variant = if version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+	let mut data = vec![u5::try_from_u8(version).ok()?];
+	data.extend(program.to_base32());
+	bech32::encode(hrp, data, variant).ok()
+}
+
+impl ScriptPubKey {
+	/// Parse an address into the script pub key it pays to. The network of the script is the
+	/// network of the address. Testnet and signet addresses are the same so they're parsed as
+	/// testnet.
+	pub fn from_address(address: &str) -> Result<Self, AddressError> {
+		// no Base58 address starts with a human readable part and its separator
+		let lowercase = address.to_lowercase();
+		let segwit = [Network::Mainnet, Network::Testnet, Network::Regtest].iter()
+			.any(|network| lowercase.starts_with(&format!("{}1", network.bech32_hrp())));
+		if segwit {
+			let (hrp, data, variant) = bech32::decode(address)?;
+			decode_segwit(&hrp, &data, variant)
+		} else {
+			decode_base58(address)
+		}
+	}
+
+	/// Parse an address of the given network. The test networks share their Base58 prefixes and
+	/// testnet and signet share their bech32 prefix, so those addresses are accepted on all of them.
+	pub fn from_address_on(address: &str, network: Network) -> Result<Self, AddressError> {
+		let mut script = ScriptPubKey::from_address(address)?;
+		let same_prefix = if witness_program(&script.script).is_some() {
+			script.network().bech32_hrp() == network.bech32_hrp()
+		} else {
+			script.network().p2pkh_prefix() == network.p2pkh_prefix()
+		};
+		if !same_prefix {
+			return Err(AddressError::WrongNetwork(network));
+		}
+		script.set_network(network);
+		Ok(script)
+	}
+}
+
+fn decode_segwit(hrp: &str, data: &[u5], variant: Variant) -> Result<ScriptPubKey, AddressError> {
+	let network = match hrp {
+		"bc" => Network::Mainnet,
+		"tb" => Network::Testnet,
+		"bcrt" => Network::Regtest,
+		_ => return Err(AddressError::UnknownHrp(hrp.to_string())),
+	};
+	let (version, program) = data.split_first().ok_or(AddressError::Bech32(bech32::Error::InvalidLength))?;
+	let version = version.to_u8();
+	if version > 16 {
+		return Err(AddressError::InvalidWitnessVersion(version));
+	}
+	let program = Vec::<u8>::from_base32(program)?;
+	let mut script = ScriptPubKey::new_witness_program(version, &program).map_err(|e| match e {
+		BuildError::InvalidWitnessProgramLength(len) => AddressError::InvalidWitnessProgramLength(len),
+		_ => AddressError::InvalidWitnessVersion(version),
+	})?;
+	if (version == 0) != (variant == Variant::Bech32) {
+		return Err(AddressError::InvalidVariant);
+	}
+	script.set_network(network);
+	Ok(script)
+}
+
+fn decode_base58(address: &str) -> Result<ScriptPubKey, AddressError> {
+	let payload = base58::decode_check(address)?;
+	if payload.len() != 21 {
+		return Err(AddressError::InvalidLength(payload.len()));
+	}
+
+	let prefix = payload[0];
+	let hash = &payload[1..];
+	for network in [Network::Mainnet, Network::Testnet] {
+		let mut builder = ScriptBuilder::new();
+		if prefix == network.p2pkh_prefix() {
+			builder.push_opcode(opcodes::all::OP_DUP);
+			builder.push_opcode(opcodes::all::OP_HASH160);
+			builder.push_slice(hash);
+			builder.push_opcode(opcodes::all::OP_EQUALVERIFY);
+			builder.push_opcode(opcodes::all::OP_CHECKSIG);
+		} else if prefix == network.p2sh_prefix() {
+			builder.push_opcode(opcodes::all::OP_HASH160);
+			builder.push_slice(hash);
+			builder.push_opcode(opcodes::all::OP_EQUAL);
+		} else {
+			continue;
+		}
+		let mut script: ScriptPubKey = builder.into_script();
+		script.set_network(network);
+		return Ok(script);
+	}
+	Err(AddressError::UnknownPrefix(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::base58;
+	use crate::interpreter::witness_program;
+	use crate::network::Network;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::txio::{Decodable, Encodable};
+	use super::{encode_segwit, AddressError};
+
+	fn encode(hrp: &str, script_pub_key: &str) -> Option<String> {
+		let script = script_pub_key.decode_hex_be().unwrap();
+		let (version, program) = witness_program(&script).unwrap();
+		encode_segwit(hrp, version, program)
+	}
+
+	#[test]
+	fn bip173_bip350_vectors() {
+		let vectors = [
+			("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+			("tb", "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+			("bc", "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
+			("bc", "bc1sw50qgdz25j", "6002751e"),
+			("bc", "bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", "5210751e76e8199196d454941c45d1b3a323"),
+			("tb", "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy", "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+			("tb", "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+			("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+		];
+		for (hrp, address, script_pub_key) in vectors {
+			assert_eq!(encode(hrp, script_pub_key).as_deref(), Some(address));
+		}
+	}
+
+	#[test]
+	fn invalid_witness_programs() {
+		// version 0 programs are either 20 or 32 bytes
+		assert_eq!(encode("bc", "0010751e76e8199196d454941c45d1b3a323"), None);
+		assert_eq!(encode_segwit("bc", 1, &[0]), None);
+		assert_eq!(encode_segwit("bc", 17, &[0; 32]), None);
+	}
+
+	#[test]
+	fn script_pub_key_address() {
+		// the P2WPKH output of decode_transaction_pre_segwit_4
+		let script = ScriptPubKey::new("00141e129251311437eea493fce2a3644a5a1af8d407".decode_hex_be().unwrap(), Network::Testnet);
+		assert_eq!(script.get_address().as_deref(), Some("tb1qrcffy5f3zsm7afynln32xez2tgd034q8560vwl"));
+
+		let script = ScriptPubKey::new("5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433".decode_hex_be().unwrap(), Network::Signet);
+		assert_eq!(script.get_address().as_deref(), Some("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c"));
+	}
+
+	#[test]
+	fn addresses_per_network() {
+		// P2PKH and P2SH of the hash160 of the generator point
+		let mut p2pkh = ScriptPubKey::from_str("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+		assert_eq!(p2pkh.get_address().as_deref(), Some("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"));
+		p2pkh.set_network(Network::Testnet);
+		assert_eq!(p2pkh.get_address().as_deref(), Some("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r"));
+
+		let p2sh = ScriptPubKey::from_str("a914751e76e8199196d454941c45d1b3a323f1433bd687").unwrap();
+		assert_eq!(p2sh.get_address().as_deref(), Some("3CNHUhP3uyB9EUtRLsmvFUmvGdjGdkTxJw"));
+
+		let mut p2wpkh = ScriptPubKey::new("0014751e76e8199196d454941c45d1b3a323f1433bd6".decode_hex_be().unwrap(), Network::Regtest);
+		assert!(p2wpkh.get_address().unwrap().starts_with("bcrt1q"));
+		p2wpkh.set_network(Network::Mainnet);
+		assert_eq!(p2wpkh.network(), Network::Mainnet);
+		assert_eq!(p2wpkh.get_address().as_deref(), Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+	}
+
+	#[test]
+	fn parse_addresses() {
+		let vectors = [
+			("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", Network::Mainnet, "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+			("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", Network::Testnet, "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+			("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", Network::Mainnet, "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
+			("BC1SW50QGDZ25J", Network::Mainnet, "6002751e"),
+			("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", Network::Mainnet, "5210751e76e8199196d454941c45d1b3a323"),
+			("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", Network::Testnet, "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+			("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", Network::Mainnet, "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+			("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r", Network::Testnet, "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+			("3CNHUhP3uyB9EUtRLsmvFUmvGdjGdkTxJw", Network::Mainnet, "a914751e76e8199196d454941c45d1b3a323f1433bd687"),
+		];
+		for (address, network, script_pub_key) in vectors {
+			let script = ScriptPubKey::from_address(address).unwrap();
+			assert_eq!(script.script.encode_hex_be(), script_pub_key);
+			assert_eq!(script.network(), network);
+			// segwit addresses are encoded lowercase
+			assert!(script.get_address().unwrap().eq_ignore_ascii_case(address));
+		}
+
+		let regtest = ScriptPubKey::new("0014751e76e8199196d454941c45d1b3a323f1433bd6".decode_hex_be().unwrap(), Network::Regtest).get_address().unwrap();
+		assert_eq!(ScriptPubKey::from_address(&regtest).unwrap().network(), Network::Regtest);
+
+		// the test networks share their Base58 prefixes, signet and regtest addresses parse as testnet
+		let mut p2pkh = ScriptPubKey::from_str("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+		p2pkh.set_network(Network::Regtest);
+		assert_eq!(ScriptPubKey::from_address(&p2pkh.get_address().unwrap()).unwrap().network(), Network::Testnet);
+	}
+
+	#[test]
+	fn invalid_addresses() {
+		let vectors = [
+			// only the human readable parts of a known network are tried as bech32
+			("tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut", AddressError::InvalidBase58),
+			("tb1qqqqqqqqqqqqqqqqqqqq", AddressError::Bech32(bech32::Error::InvalidChecksum)),
+			// bech32 instead of bech32m and the other way round
+			("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd", AddressError::InvalidVariant),
+			("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh", AddressError::InvalidVariant),
+			("bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4", AddressError::Bech32(bech32::Error::InvalidChar('o'))),
+			("BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R", AddressError::InvalidWitnessVersion(17)),
+			("bc1pw5dgrnzv", AddressError::InvalidWitnessProgramLength(1)),
+			("BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P", AddressError::InvalidWitnessProgramLength(16)),
+			("tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq", AddressError::Bech32(bech32::Error::MixedCase)),
+			("tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j", AddressError::Bech32(bech32::Error::InvalidPadding)),
+			("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5", AddressError::Bech32(bech32::Error::InvalidChecksum)),
+			// Base58
+			("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMh", AddressError::InvalidChecksum),
+			("1BgGZ9tcN4rm9KBzDn7KprQz87SZ2", AddressError::InvalidChecksum),
+			("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAM0", AddressError::InvalidBase58),
+		];
+		for (address, error) in vectors {
+			assert_eq!(ScriptPubKey::from_address(address), Err(error), "{}", address);
+		}
+
+		// valid Base58Check with an unknown version byte or a hash of the wrong length
+		let mut bytes = vec![42];
+		bytes.extend_from_slice(&[0; 20]);
+		assert_eq!(ScriptPubKey::from_address(&base58::encode_check(&bytes)), Err(AddressError::UnknownPrefix(42)));
+		assert_eq!(ScriptPubKey::from_address(&base58::encode_check(&bytes[..18])), Err(AddressError::InvalidLength(18)));
+	}
+}
@@ -0,0 +1,53 @@
+// Base58Check is how legacy addresses, WIF private keys and extended keys are written: Base58 of
+// the payload followed by the first 4 bytes of its hash256 as a checksum. The Base58 alphabet
+// leaves out 0, O, I and l so that they can't be confused.
+
+use crate::hash;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Base58Error {
+	/// A character outside of the Base58 alphabet.
+	InvalidBase58,
+	/// The checksum doesn't match the payload, or the string is too short to have one.
+	InvalidChecksum,
+}
+
+/// Base58 of the payload followed by the first 4 bytes of its hash256.
+pub(crate) fn encode_check(payload: &[u8]) -> String {
+	let mut bytes = payload.to_vec();
+	bytes.extend_from_slice(&hash::hash256(payload)[..4]);
+	bs58::encode(bytes).into_string()
+}
+
+/// Decode a Base58Check string and return the payload without the checksum.
+pub(crate) fn decode_check(s: &str) -> Result<Vec<u8>, Base58Error> {
+	let mut bytes = bs58::decode(s).into_vec().map_err(|_| Base58Error::InvalidBase58)?;
+	if bytes.len() < 4 {
+		return Err(Base58Error::InvalidChecksum);
+	}
+	let checksum = bytes.split_off(bytes.len() - 4);
+	if hash::hash256(&bytes)[..4] != *checksum {
+		return Err(Base58Error::InvalidChecksum);
+	}
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{decode_check, encode_check, Base58Error};
+
+	#[test]
+	fn base58_check() {
+		// the P2PKH address of hash160 751e76e8199196d454941c45d1b3a323f1433bd6
+		let mut payload = vec![0];
+		payload.extend_from_slice(&[0x75, 0x1e, 0x76, 0xe8, 0x19, 0x91, 0x96, 0xd4, 0x54, 0x94, 0x1c, 0x45,
+			0xd1, 0xb3, 0xa3, 0x23, 0xf1, 0x43, 0x3b, 0xd6]);
+		assert_eq!(encode_check(&payload), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+		assert_eq!(decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"), Ok(payload));
+		assert_eq!(decode_check(&encode_check(&[])), Ok(vec![]));
+
+		assert_eq!(decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMh"), Err(Base58Error::InvalidChecksum));
+		assert_eq!(decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAM0"), Err(Base58Error::InvalidBase58));
+		assert_eq!(decode_check("111"), Err(Base58Error::InvalidChecksum));
+	}
+}
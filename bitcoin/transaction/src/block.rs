@@ -0,0 +1,257 @@
+// A block is a header followed by the transactions it confirms. The header commits to the
+// transactions through the merkle root and to the previous block through its hash.
+
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+use crate::error::DecodeError;
+use crate::hash;
+use crate::transaction::Transaction;
+use crate::txio::{check_consumed, Encodable, HexBytes, ReadExt, WriteExt};
+use crate::Deserialize;
+
+#[derive(Clone, PartialEq)]
+pub struct BlockHeader {
+	pub version: u32,
+	/// Hash of the previous block, in the byte order it's hashed in. It's shown reversed, like a
+	/// txid.
+	pub prev_blockhash: [u8; 32],
+	/// Root of the merkle tree of the txids of the block, in the same order as `prev_blockhash`.
+	pub merkle_root: [u8; 32],
+	/// Unix timestamp set by the miner.
+	pub time: u32,
+	/// The target the block hash has to be below, in compact form. See `BlockHeader::target`.
+	pub bits: u32,
+	pub nonce: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Block {
+	pub header: BlockHeader,
+	pub transactions: Vec<Transaction>,
+}
+
+impl BlockHeader {
+	/// The header is always 80 bytes.
+	pub const SIZE: usize = 80;
+
+	pub(crate) fn read_from(stream: &mut Cursor<HexBytes>) -> Result<Self, DecodeError> {
+		Ok(BlockHeader {
+			version: stream.read_u32_le()?,
+			prev_blockhash: (*stream.read_hex256()?).try_into().expect("32 bytes"),
+			merkle_root: (*stream.read_hex256()?).try_into().expect("32 bytes"),
+			time: stream.read_u32_le()?,
+			bits: stream.read_u32_le()?,
+			nonce: stream.read_u32_le()?,
+		})
+	}
+
+	/// hash256 of the header, in display order. Blocks are identified by it.
+	pub fn block_hash(&self) -> String {
+		hash::hash256(&self.as_bytes()).encode_hex_le()
+	}
+
+	/// Expand `bits` into the 256 bit target, big-endian. The compact form is like floating point:
+	/// the first byte is the length of the target in bytes and the other three are its most
+	/// significant bytes. Returns None for a negative or overflowing target, which Core rejects.
+	pub fn target(&self) -> Option<[u8; 32]> {
+		let exponent = (self.bits >> 24) as usize;
+		let mantissa = self.bits & 0x007fffff;
+		if self.bits & 0x00800000 != 0 && mantissa != 0 {
+			return None;
+		}
+
+		let mut target = [0u8; 32];
+		for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+			// byte i of the mantissa ends up exponent - i bytes from the end
+			let shift = exponent as isize - i as isize;
+			if shift <= 0 {
+				continue;
+			}
+			if shift > 32 {
+				if *byte != 0 {
+					return None;
+				}
+				continue;
+			}
+			target[32 - shift as usize] = *byte;
+		}
+		Some(target)
+	}
+
+	/// Proof of work: the block hash read as a 256 bit number has to be at most the target.
+	pub fn check_pow(&self) -> bool {
+		let target = match self.target() {
+			Some(target) if target != [0u8; 32] => target,
+			_ => return false,
+		};
+		let hash = hash::hash256(&self.as_bytes());
+		// the hash is a little-endian number, reverse it to compare with the big-endian target
+		hash.iter().rev().cmp(target.iter()) != std::cmp::Ordering::Greater
+	}
+}
+
+impl Deserialize for BlockHeader {
+	fn decode_raw(bytes: HexBytes) -> Result<Self, Box<dyn Error>> {
+		let mut stream = Cursor::new(bytes);
+		let header = BlockHeader::read_from(&mut stream)?;
+		check_consumed(&stream)?;
+		Ok(header)
+	}
+
+	fn as_bytes(&self) -> HexBytes {
+		let mut stream = Vec::with_capacity(BlockHeader::SIZE);
+		stream.write_u32_le(self.version);
+		stream.extend_from_slice(&self.prev_blockhash);
+		stream.extend_from_slice(&self.merkle_root);
+		stream.write_u32_le(self.time);
+		stream.write_u32_le(self.bits);
+		stream.write_u32_le(self.nonce);
+		stream.into_boxed_slice()
+	}
+}
+
+// The hashes are shown in display order instead of as arrays of bytes
+impl fmt::Debug for BlockHeader {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("BlockHeader")
+			.field("version", &self.version)
+			.field("prev_blockhash", &self.prev_blockhash.encode_hex_le())
+			.field("merkle_root", &self.merkle_root.encode_hex_le())
+			.field("time", &self.time)
+			.field("bits", &self.bits)
+			.field("nonce", &self.nonce)
+			.finish()
+	}
+}
+
+impl Block {
+	/// The hash of the block header.
+	pub fn block_hash(&self) -> String {
+		self.header.block_hash()
+	}
+}
+
+impl Deserialize for Block {
+	fn decode_raw(bytes: HexBytes) -> Result<Self, Box<dyn Error>> {
+		let mut stream = Cursor::new(bytes);
+		let header = BlockHeader::read_from(&mut stream)?;
+
+		let tx_count = stream.read_compact_size()?;
+		let mut transactions = Vec::new();
+		for _ in 0..tx_count {
+			transactions.push(Transaction::read_from(&mut stream)?);
+		}
+		check_consumed(&stream)?;
+
+		Ok(Block {
+			header,
+			transactions,
+		})
+	}
+
+	fn as_bytes(&self) -> HexBytes {
+		let mut stream = self.header.as_bytes().to_vec();
+		stream.write_varint(self.transactions.len() as u64);
+		for tx in &self.transactions {
+			tx.consensus_encode(&mut stream, true).expect("fields of a transaction are valid hex");
+		}
+		stream.into_boxed_slice()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::Deserialize;
+	use crate::error::DecodeError;
+	use crate::txio::{Decodable, Encodable};
+	use super::{Block, BlockHeader};
+
+	// Mainnet block 00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7: a coinbase and
+	// one transaction spending three outputs.
+	const BLOCK: &str = "010000004ddccd549d28f385ab457e98d1b11ce80bfea2c5ab93015ade4973e400000000bf4473e53794be\
+	ae34e64fccc471dace6ae544180816f89591894e0f417a914cd74d6e49ffff001d323b3a7b020100000001000000000000000000000000000\
+	0000000000000000000000000000000000000ffffffff0804ffff001d026e04ffffffff0100f2052a0100000043410446ef0102d1ec5240f\
+	0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e7cfa99c48b0e3f39c51347a19a5819651503a5a\
+	c00000000010000000321f75f3139a013f50f315b23b0c9a2b6eac31e2bec98e5891c924664889942260000000049483045022100cb2c6b3\
+	46a978ab8c61b18b5e9397755cbd17d6eb2fe0083ef32e067fa6c785a02206ce44e613f31d9a6b0517e46f3db1576e9812cc98d159bfdaf7\
+	59a5014081b5c01ffffffff79cda0945903627c3da1f85fc95d0b8ee3e76ae0cfdc9a65d09744b1f8fc85430000000049483045022047957\
+	cdd957cfd0becd642f6b84d82f49b6cb4c51a91f49246908af7c3cfdf4a022100e96b46621f1bffcf5ea5982f88cef651e9354f5791602369\
+	bf5a82a6cd61a62501fffffffffe09f5fe3ffbf5ee97a54eb5e5069e9da6b4856ee86fc52938c2f979b0f38e82000000004847304402204\
+	165be9a4cbab8049e1af9723b96199bfd3e85f44c6b4c0177e3962686b26073022028f638da23fc003760861ad481ead4099312c60030d4c\
+	b57820ce4d33812a5ce01ffffffff01009d966b01000000434104ea1feff861b51fe3f5f8a3b12d0f4712db80e919548a80839fc47c6a21e\
+	66d957e9c5d8cd108c7a2d2324bad71f9904ac0ae7336507d785b17a2c115e427a32fac00000000";
+
+	#[test]
+	fn decode_block() {
+		let bytes = BLOCK.decode_hex_be().unwrap();
+		let block = Block::decode_raw(bytes.clone()).unwrap();
+
+		assert_eq!(block.header.version, 1);
+		assert_eq!(block.header.prev_blockhash.encode_hex_le(), "00000000e47349de5a0193abc5a2fe0be81cb1d1987e45ab85f3289d54cddc4d");
+		assert_eq!(block.header.merkle_root.encode_hex_le(), "4c917a410f4e899195f816081844e56aceda71c4cc4fe634aebe9437e57344bf");
+		assert!(format!("{:?}", block.header).contains("prev_blockhash: \"00000000e47349de5a0193abc5a2fe0be81cb1d1987e45ab85f3289d54cddc4d\""));
+		assert_eq!(block.header.time, 1231965655);
+		assert_eq!(block.header.bits, 486604799);
+		assert_eq!(block.header.nonce, 2067413810);
+		assert_eq!(block.block_hash(), "00000000b0c5a240b2a61d2e75692224efd4cbecdf6eaf4cc2cf477ca7c270e7");
+
+		assert_eq!(block.transactions.len(), 2);
+		assert_eq!(block.transactions[1].inputs.len(), 3);
+		assert_eq!(block.transactions[1].outputs[0].amount, 6100000000);
+
+		assert_eq!(block.as_bytes(), bytes);
+
+		// the whole block has to be used
+		let padded = [&bytes[..], &[0]].concat().into_boxed_slice();
+		let error = Block::decode_raw(padded).unwrap_err();
+		assert_eq!(error.downcast_ref::<DecodeError>(), Some(&DecodeError::TrailingBytes(1)));
+		let error = BlockHeader::decode_raw(bytes.clone()).unwrap_err();
+		assert_eq!(error.downcast_ref::<DecodeError>(), Some(&DecodeError::TrailingBytes(bytes.len() - BlockHeader::SIZE)));
+		let error = Block::decode_raw(bytes[..bytes.len() - 1].into()).unwrap_err();
+		assert_eq!(error.downcast_ref::<DecodeError>(), Some(&DecodeError::UnexpectedEof));
+	}
+
+	#[test]
+	fn proof_of_work() {
+		let bytes = BLOCK.decode_hex_be().unwrap();
+		let mut header = BlockHeader::decode_raw(bytes[..BlockHeader::SIZE].into()).unwrap();
+		assert_eq!(header.as_bytes(), bytes[..BlockHeader::SIZE].into());
+
+		// 0x1d00ffff is the difficulty 1 target 0x00000000ffff0000...
+		let mut target = [0u8; 32];
+		target[4] = 0xff;
+		target[5] = 0xff;
+		assert_eq!(header.target(), Some(target));
+		assert!(header.check_pow());
+
+		header.nonce += 1;
+		assert!(!header.check_pow());
+	}
+
+	#[test]
+	fn compact_target() {
+		let header = |bits| BlockHeader {
+			version: 1,
+			prev_blockhash: [0; 32],
+			merkle_root: [0; 32],
+			time: 0,
+			bits,
+			nonce: 0,
+		};
+
+		// regtest, 0x7fffff00...
+		let target = header(0x207fffff).target().unwrap();
+		assert_eq!(target[..3].encode_hex_be(), "7fffff");
+		assert!(target[3..].iter().all(|b| *b == 0));
+
+		// exponent smaller than 3 shifts the mantissa right
+		let target = header(0x02123456).target().unwrap();
+		assert_eq!(target[30..].encode_hex_be(), "1234");
+
+		// negative and overflowing
+		assert_eq!(header(0x04923456).target(), None);
+		assert_eq!(header(0x23000001).target(), None);
+		assert!(!header(0x00000000).check_pow());
+	}
+}
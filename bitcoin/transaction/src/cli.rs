@@ -0,0 +1,1385 @@
+// Non-interactive commands so the tool can be used from scripts, e.g.
+// `transaction decode-tx <hex> --json`. Text output is for people, --json prints the same JSON
+// as bitcoind's RPCs.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use serde_json::Value;
+use crate::block::Block;
+use crate::client::{BitcoindClient, ChainClient, EsploraClient};
+use crate::descriptor;
+use crate::json;
+use crate::key::{DerivationPath, ExtendedPrivateKey, ExtendedPublicKey, PrivateKey, PublicKey};
+use crate::merkle::MerkleBlock;
+use crate::mnemonic::Mnemonic;
+use crate::network::Network;
+use crate::prevout::{MemoryPrevouts, PrevoutProvider};
+use crate::psbt::{Psbt, PsbtInput, PsbtOutput, PSBT_MAGIC};
+use crate::script::{Script, ScriptPubKey, ScriptSig};
+use crate::sighash::{self, TapLeaf, SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_DEFAULT, SIGHASH_NONE, SIGHASH_SINGLE, TAPROOT_LEAF_TAPSCRIPT};
+use crate::sign::Signer;
+use crate::transaction::{Input, Output, Transaction};
+use crate::txio::{Decodable, Encodable, HexBytes};
+use crate::{Deserialize, Serialize};
+
+pub const USAGE: &str = "\
+Usage: transaction [<command> [<args>] [--json] [--network main|test|signet|regtest]]
+
+Without a command an interactive menu is shown.
+
+Commands:
+  decode-tx <hex|file|->       Decode a raw transaction, like decoderawtransaction
+      --utxos <file>           Spent outputs from a .json or .csv UTXO file, to show the fee
+      --esplora <url>          Spent outputs from an Esplora server, to show the fee
+  decode-script <hex|file|->   Decode a script, like decodescript
+  create-tx                    Create an unsigned transaction, like createrawtransaction
+      --input <txid>:<vout>[:<sequence>]   Repeat for every input
+      --output <address>:<sats>            Repeat for every output
+      --output data:<hex>                  An OP_RETURN output
+      --version <n>            Defaults to 2
+      --locktime <n>           Defaults to 0
+  sign-tx <hex|file|->         Sign every input of a transaction, like signrawtransactionwithkey
+      --key <wif>              Repeat for every key
+      --utxos <file>           The spent outputs from a UTXO file
+      --esplora <url>          The spent outputs from an Esplora server
+      --script <hex>           A redeem or witness script, repeat for every script
+      --sighash <type>         ALL, NONE or SINGLE, optionally followed by |ANYONECANPAY
+  sighash <hex|file|->         The hash an input's signature signs, legacy, segwit v0 or taproot
+      --input <n>              Index of the input
+      --utxos <file>           The spent outputs from a UTXO file
+      --esplora <url>          The spent outputs from an Esplora server
+      --script <hex>           The redeem, witness or tapscript signed, if not in the input
+      --sighash <type>         Like sign-tx, defaults to ALL or DEFAULT for taproot
+  address <address>            Show the script pub key of an address, like validateaddress
+  create-multisig <n> <pubkey>...  An n-of-m multisig address, like createmultisig
+      --type <type>            legacy, p2sh-segwit or bech32, defaults to legacy
+  psbt-create <hex|file|->     Wrap an unsigned transaction, e.g. from create-tx, in a PSBT
+      --utxos <file>           Add the outputs spent by segwit inputs from a UTXO file
+      --esplora <url>          Add the outputs spent by segwit inputs from an Esplora server
+      --prev-tx <hex|file>     A transaction spent from, needed to sign inputs that aren't segwit
+      --script <hex>           A redeem or witness script of an input, repeat for every script
+  psbt-new                     Create a version 2 PSBT without inputs and outputs, like createpsbt
+      --version <n>            Version of the transaction, defaults to 2
+      --locktime <n>           Lock time used if no input requires one
+  psbt-add <psbt|file|->       Add inputs and outputs to a version 2 PSBT that allows it
+      --input <txid>:<vout>[:<sequence>]   Repeat for every input
+      --output <address>:<sats>            Repeat for every output, or data:<hex>
+  psbt-update <psbt|file|->    Add spent outputs and scripts, takes the options of psbt-create
+  psbt-sign <psbt|file|->      Sign the inputs the keys can sign
+      --key <wif>              Repeat for every key
+  psbt-combine <psbt|file|->...  Merge PSBTs of the same transaction, like combinepsbt
+  psbt-finalize <psbt|file|->  Build the final script sigs and witnesses, like finalizepsbt
+  psbt-extract <psbt|file|->   The signed transaction of a finalized PSBT
+  mnemonic                     Generate a BIP39 mnemonic
+      --words <n>              12, 15, 18, 21 or 24, defaults to 12
+      --entropy <hex>          The mnemonic of this entropy instead of a random one
+  derive <xprv|xpub|seed|mnemonic>  Derive a child of an extended key or of the master key of a
+                               hex seed or mnemonic sentence
+      --path <path>            e.g. m/84'/0'/0'/0/5, defaults to m
+      --passphrase <text>      The BIP39 passphrase of the mnemonic
+  txout-proof <block|file|-> <txid>...  Prove that transactions are in a block, like gettxoutproof
+  verify-proof <hex|file|->    The txids a proof commits to, like verifytxoutproof
+      --esplora <url>          Also check that the block is known to an Esplora server
+      --bitcoind <url>         Or to a bitcoind node
+  utxos <address>              The unspent outputs of an address, as a UTXO file for --utxos
+  broadcast <hex|file|->       Send a signed transaction to the network, like sendrawtransaction
+  estimate-fee                 Fee rate in sat/vB, like estimatesmartfee
+      --blocks <n>             Confirmation target, defaults to 6
+  help                         Show this message
+
+Commands that talk to a server take --esplora <url> or --bitcoind <url>. The user and password of
+bitcoind are given with --rpcauth <user>:<password>.
+
+Hex can also be read from a file (hex or binary) or from stdin with -. PSBTs are base64, a file
+can also hold a binary PSBT.
+";
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+	/// The command line is wrong, the usage is shown with the message.
+	Usage(String),
+	/// The command ran but failed, e.g. the transaction couldn't be decoded.
+	Failed(String),
+}
+
+impl fmt::Display for CliError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+			CliError::Failed(message) => write!(f, "{}", message),
+		}
+	}
+}
+
+impl Error for CliError {}
+
+fn failed(context: &str, e: impl fmt::Display) -> CliError {
+	CliError::Failed(format!("{}: {}", context, e))
+}
+
+/// Options of a command. Options that take a value can be repeated.
+struct Args {
+	positional: Vec<String>,
+	options: Vec<(String, String)>,
+	json: bool,
+	network: Network,
+}
+
+impl Args {
+	fn parse(args: &[String], options_with_value: &[&str]) -> Result<Self, CliError> {
+		let mut parsed = Args { positional: Vec::new(), options: Vec::new(), json: false, network: Network::default() };
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			let mut value = |name: &str| {
+				args.next().cloned().ok_or_else(|| CliError::Usage(format!("{} needs a value", name)))
+			};
+			match arg.as_str() {
+				"--json" => parsed.json = true,
+				"--network" => {
+					let network = value(arg)?;
+					parsed.network = Network::from_str(&network).map_err(|e| CliError::Usage(e.to_string()))?;
+				}
+				name if options_with_value.contains(&name) => {
+					let value = value(name)?;
+					parsed.options.push((name.to_string(), value));
+				}
+				name if name.starts_with("--") => return Err(CliError::Usage(format!("unknown option {}", name))),
+				_ => parsed.positional.push(arg.clone()),
+			}
+		}
+		Ok(parsed)
+	}
+
+	fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+		self.options.iter().filter(move |(key, _)| key == name).map(|(_, value)| value.as_str())
+	}
+
+	fn value<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+		self.values(name).last()
+	}
+
+	/// The single positional argument of commands like decode-tx.
+	fn single(&self, what: &str) -> Result<&str, CliError> {
+		match &self.positional[..] {
+			[arg] => Ok(arg),
+			[] => Err(CliError::Usage(format!("missing {}", what))),
+			_ => Err(CliError::Usage(format!("expected a single {}", what))),
+		}
+	}
+}
+
+const SERVER_OPTIONS: &[&str] = &["--esplora", "--bitcoind", "--rpcauth"];
+
+/// Run a command and return what should be printed.
+pub fn run(args: &[String]) -> Result<String, CliError> {
+	let Some((command, args)) = args.split_first() else {
+		return Err(CliError::Usage("missing command".to_string()));
+	};
+	match command.as_str() {
+		"decode-tx" => decode_tx(&Args::parse(args, &["--utxos", "--esplora"])?),
+		"decode-script" => decode_script(&Args::parse(args, &[])?),
+		"create-tx" => create_tx(&Args::parse(args, &["--input", "--output", "--version", "--locktime"])?),
+		"sign-tx" => sign_tx(&Args::parse(args, &["--key", "--utxos", "--esplora", "--script", "--sighash"])?),
+		"sighash" => sighash(&Args::parse(args, &["--input", "--utxos", "--esplora", "--script", "--sighash"])?),
+		"address" => address(&Args::parse(args, &[])?),
+		"create-multisig" => create_multisig(&Args::parse(args, &["--type"])?),
+		"psbt-create" => psbt_create(&Args::parse(args, &["--utxos", "--esplora", "--prev-tx", "--script"])?),
+		"psbt-new" => psbt_new(&Args::parse(args, &["--version", "--locktime"])?),
+		"psbt-add" => psbt_add(&Args::parse(args, &["--input", "--output"])?),
+		"psbt-update" => psbt_update(&Args::parse(args, &["--utxos", "--esplora", "--prev-tx", "--script"])?),
+		"psbt-sign" => psbt_sign(&Args::parse(args, &["--key"])?),
+		"psbt-combine" => psbt_combine(&Args::parse(args, &[])?),
+		"psbt-finalize" => psbt_finalize(&Args::parse(args, &[])?),
+		"psbt-extract" => psbt_extract(&Args::parse(args, &[])?),
+		"mnemonic" => mnemonic(&Args::parse(args, &["--words", "--entropy"])?),
+		"derive" => derive(&Args::parse(args, &["--path", "--passphrase"])?),
+		"txout-proof" => txout_proof(&Args::parse(args, &[])?),
+		"verify-proof" => verify_proof(&Args::parse(args, SERVER_OPTIONS)?),
+		"utxos" => utxos(&Args::parse(args, SERVER_OPTIONS)?),
+		"broadcast" => broadcast(&Args::parse(args, SERVER_OPTIONS)?),
+		"estimate-fee" => estimate_fee(&Args::parse(args, &["--esplora", "--bitcoind", "--rpcauth", "--blocks"])?),
+		"help" | "--help" | "-h" => Ok(USAGE.trim_end().to_string()),
+		_ => Err(CliError::Usage(format!("unknown command {}", command))),
+	}
+}
+
+/// Hex given directly, in a file or on stdin. Files that aren't hex are taken as raw bytes.
+fn read_bytes(arg: &str) -> Result<HexBytes, CliError> {
+	let content = if arg == "-" {
+		let mut content = Vec::new();
+		io::stdin().read_to_end(&mut content).map_err(|e| failed("couldn't read stdin", e))?;
+		content
+	} else if Path::new(arg).is_file() {
+		fs::read(arg).map_err(|e| failed(arg, e))?
+	} else {
+		return arg.decode_hex_be().map_err(|e| failed("invalid hex", e));
+	};
+
+	match std::str::from_utf8(&content).map(|text| text.trim().decode_hex_be()) {
+		Ok(Ok(bytes)) => Ok(bytes),
+		_ => Ok(content.into_boxed_slice()),
+	}
+}
+
+/// A base64 PSBT given directly, in a file or on stdin. Files can also hold the binary format.
+fn read_psbt(arg: &str) -> Result<Psbt, CliError> {
+	let content = if arg == "-" {
+		let mut content = Vec::new();
+		io::stdin().read_to_end(&mut content).map_err(|e| failed("couldn't read stdin", e))?;
+		content
+	} else if Path::new(arg).is_file() {
+		fs::read(arg).map_err(|e| failed(arg, e))?
+	} else {
+		return Psbt::from_base64(arg).map_err(|e| failed("invalid PSBT", e));
+	};
+
+	let psbt = if content.starts_with(&PSBT_MAGIC) {
+		Psbt::from_bytes(&content)
+	} else {
+		Psbt::from_base64(&String::from_utf8_lossy(&content))
+	};
+	psbt.map_err(|e| failed("invalid PSBT", e))
+}
+
+fn to_json(value: &Value) -> String {
+	serde_json::to_string_pretty(value).expect("values can always be serialized")
+}
+
+fn decode_tx(args: &Args) -> Result<String, CliError> {
+	let mut tx = read_tx_with_prevouts(args)?;
+	tx.set_network(args.network);
+
+	if args.json {
+		return Ok(to_json(&json::transaction_to_json(&tx)));
+	}
+	Ok(transaction_to_text(&tx))
+}
+
+fn transaction_to_text(tx: &Transaction) -> String {
+	let mut lines = vec![
+		format!("txid      {}", tx.txid()),
+		format!("hash      {}", tx.wtxid()),
+		format!("version   {}", tx.version),
+		format!("size      {} bytes, {} vbytes, {} weight units", tx.size(), tx.vsize(), tx.weight()),
+		format!("locktime  {}", tx.lock_time),
+	];
+	if let Some(extra_info) = &tx.extra_info {
+		lines.push(format!("fee       {} sats ({:.2} sat/vB)", extra_info.miner_fee, extra_info.fee_rate));
+	}
+
+	lines.push("inputs".to_string());
+	let witness_data = tx.witness_data.as_deref().unwrap_or_default();
+	for (i, input) in tx.inputs.iter().enumerate() {
+		lines.push(format!("  {}  {}:{}  sequence {}", i, input.previous_tx, input.tx_index, input.sequence));
+		if !input.script_sig.script.is_empty() {
+			lines.push(format!("     script sig  {}", json::script_to_asm(&input.script_sig.script, true)));
+		}
+		if let Some(stack) = witness_data.get(i).filter(|stack| !stack.0.is_empty()) {
+			lines.push(format!("     witness     {}", stack.0.join(" ")));
+		}
+		if let Some(prevout) = &input.prevout {
+			lines.push(format!("     spends      {} sats  {}", prevout.amount, describe(&prevout.script_pub_key)));
+		}
+	}
+
+	lines.push("outputs".to_string());
+	for (i, output) in tx.outputs.iter().enumerate() {
+		lines.push(format!("  {}  {} sats  {}", i, output.amount, describe(&output.script_pub_key)));
+		lines.push(format!("     script pub key  {}", json::script_to_asm(&output.script_pub_key.script, false)));
+	}
+	lines.join("\n")
+}
+
+/// The address of a script, or its type if it has none.
+fn describe(script_pub_key: &ScriptPubKey) -> String {
+	script_pub_key.get_address().unwrap_or_else(|| script_pub_key.get_type().to_string())
+}
+
+fn decode_script(args: &Args) -> Result<String, CliError> {
+	let bytes = read_bytes(args.single("script")?)?;
+	let mut script = ScriptPubKey::decode_raw(bytes).map_err(|e| failed("couldn't decode the script", e))?;
+	script.set_network(args.network);
+	let decoded = json::decode_script_to_json(&script);
+	if args.json {
+		return Ok(to_json(&decoded));
+	}
+
+	let field = |value: &Value| value.as_str().unwrap_or_default().to_string();
+	let mut lines = vec![
+		format!("type         {}", field(&decoded["type"])),
+		format!("asm          {}", field(&decoded["asm"])),
+		format!("descriptor   {}", field(&decoded["desc"])),
+	];
+	if let Some(address) = decoded.get("address") {
+		lines.push(format!("address      {}", field(address)));
+	}
+	if let Some(p2sh) = decoded.get("p2sh") {
+		lines.push(format!("p2sh         {}", field(p2sh)));
+	}
+	if let Some(segwit) = decoded.get("segwit") {
+		lines.push(format!("segwit       {}", field(&segwit["address"])));
+		lines.push(format!("p2sh-segwit  {}", field(&segwit["p2sh-segwit"])));
+	}
+	Ok(lines.join("\n"))
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, CliError> {
+	value.parse().map_err(|_| CliError::Usage(format!("invalid {} {}", name, value)))
+}
+
+/// An unsigned input, <txid>:<vout>[:<sequence>].
+fn parse_input(input: &str) -> Result<Input, CliError> {
+	let invalid = || CliError::Usage(format!("invalid input {}, expected <txid>:<vout>[:<sequence>]", input));
+	let mut parts = input.split(':');
+	let (Some(txid), Some(vout)) = (parts.next(), parts.next()) else {
+		return Err(invalid());
+	};
+	if !matches!(txid.decode_hex_be(), Ok(bytes) if bytes.len() == 32) {
+		return Err(invalid());
+	}
+	let tx_index = vout.parse().map_err(|_| invalid())?;
+	// replaceable by default, like bitcoind
+	let sequence: u32 = parts.next().map(|n| n.parse().map_err(|_| invalid())).transpose()?.unwrap_or(0xfffffffd);
+	if parts.next().is_some() {
+		return Err(invalid());
+	}
+	Ok(Input {
+		previous_tx: txid.to_lowercase(),
+		tx_index,
+		script_sig: ScriptSig::new(Box::new([])),
+		sequence: format!("{:08x}", sequence),
+		prevout: None,
+	})
+}
+
+/// An output paying <address>:<sats>, or an OP_RETURN output data:<hex>.
+fn parse_output(output: &str, network: Network) -> Result<Output, CliError> {
+	let invalid = || CliError::Usage(format!("invalid output {}, expected <address>:<sats> or data:<hex>", output));
+	let (destination, value) = output.rsplit_once(':').ok_or_else(invalid)?;
+	if destination == "data" {
+		let data = value.decode_hex_be().map_err(|_| invalid())?;
+		return Ok(Output { amount: 0, script_pub_key: ScriptPubKey::new_op_return(&data) });
+	}
+	let script_pub_key = ScriptPubKey::from_address_on(destination, network).map_err(|e| failed(destination, e))?;
+	Ok(Output { amount: value.parse().map_err(|_| invalid())?, script_pub_key })
+}
+
+fn create_tx(args: &Args) -> Result<String, CliError> {
+	if let Some(arg) = args.positional.first() {
+		return Err(CliError::Usage(format!("unexpected argument {}", arg)));
+	}
+	let lock_time = args.value("--locktime").map(|n| parse_number("locktime", n)).transpose()?.unwrap_or(0);
+	let version = args.value("--version").map(|n| parse_number("version", n)).transpose()?.unwrap_or(2);
+
+	let inputs = args.values("--input").map(parse_input).collect::<Result<Vec<_>, _>>()?;
+	let outputs = args.values("--output").map(|output| parse_output(output, args.network)).collect::<Result<Vec<_>, _>>()?;
+	if inputs.is_empty() || outputs.is_empty() {
+		return Err(CliError::Usage("a transaction needs at least one --input and one --output".to_string()));
+	}
+
+	let mut tx = Transaction {
+		version,
+		flag: None,
+		in_counter: inputs.len() as u64,
+		inputs,
+		out_counter: outputs.len() as u64,
+		outputs,
+		witness_data: None,
+		lock_time,
+		extra_info: None,
+	};
+	tx.set_network(args.network);
+
+	if args.json {
+		return Ok(to_json(&json::transaction_to_json(&tx)));
+	}
+	Ok(tx.as_hex())
+}
+
+/// A sighash type the way signrawtransactionwithkey names them, e.g. ALL|ANYONECANPAY.
+fn parse_sighash_type(name: &str) -> Result<u32, CliError> {
+	let (base, anyone_can_pay) = match name.strip_suffix("|ANYONECANPAY") {
+		Some(base) => (base, SIGHASH_ANYONECANPAY),
+		None => (name, 0),
+	};
+	let base = match base {
+		"ALL" => SIGHASH_ALL,
+		"NONE" => SIGHASH_NONE,
+		"SINGLE" => SIGHASH_SINGLE,
+		_ => return Err(CliError::Usage(format!("invalid sighash type {}", name))),
+	};
+	Ok(base | anyone_can_pay)
+}
+
+/// Decode a transaction and look up its spent outputs in the --utxos file or --esplora server.
+fn read_tx_with_prevouts(args: &Args) -> Result<Transaction, CliError> {
+	let bytes = read_bytes(args.single("transaction")?)?;
+	let mut tx = Transaction::decode_raw(bytes).map_err(|e| failed("couldn't decode the transaction", e))?;
+	if let Some(path) = args.value("--utxos") {
+		let prevouts = MemoryPrevouts::from_file(Path::new(path)).map_err(|e| failed(path, e))?;
+		tx.fetch_prevouts(&prevouts).map_err(|e| failed("couldn't look up the spent outputs", e))?;
+	}
+	if let Some(url) = args.value("--esplora") {
+		tx.fetch_prevouts(&EsploraClient::new(url)).map_err(|e| failed("couldn't look up the spent outputs", e))?;
+	}
+	Ok(tx)
+}
+
+fn sighash(args: &Args) -> Result<String, CliError> {
+	let tx = read_tx_with_prevouts(args)?;
+	let index: usize = parse_number("--input", args.value("--input").ok_or_else(|| CliError::Usage("missing --input".to_string()))?)?;
+	let input = tx.inputs.get(index).ok_or_else(|| CliError::Failed(format!("input {} does not exist", index)))?;
+	let prevout = input.prevout.as_ref()
+		.ok_or_else(|| CliError::Failed(format!("the output spent by input {} is unknown, use --utxos or --esplora", index)))?;
+	let script_pub_key = &prevout.script_pub_key;
+	let script = args.value("--script")
+		.map(|script| script.decode_hex_be().map_err(|_| CliError::Usage(format!("invalid script {}", script))))
+		.transpose()?;
+	let sighash_type = args.value("--sighash").map(parse_sighash_type).transpose()?;
+
+	let (kind, hash) = if script_pub_key.is_p2tr() {
+		let spent_outputs = tx.spent_outputs().map_err(|e| failed("taproot signs every spent output", e))?;
+		let witness = tx.witness_data.as_ref().and_then(|witnesses| witnesses.get(index));
+		let annex = witness.and_then(|witness| witness.annex());
+		// a script path spend ends with the script and the control block, whose first byte has the
+		// leaf version
+		let leaf_script = match script {
+			Some(script) => Some((TAPROOT_LEAF_TAPSCRIPT, script)),
+			None => match witness.map(|witness| witness.without_annex()) {
+				Some([.., script, control_block]) => {
+					let invalid = |_| CliError::Failed(format!("invalid witness of input {}", index));
+					let control_block = control_block.decode_hex_be().map_err(invalid)?;
+					let version = control_block.first().ok_or_else(|| CliError::Failed(format!("invalid witness of input {}", index)))?;
+					Some((version & 0xfe, script.decode_hex_be().map_err(invalid)?))
+				}
+				_ => None,
+			},
+		};
+		let leaf_hash = leaf_script.map(|(version, script)| sighash::tap_leaf_hash(version, &script));
+		let leaf = leaf_hash.as_deref().map(|leaf_hash| TapLeaf { leaf_hash, code_separator_position: u32::MAX });
+		("taproot", tx.taproot_signature_hash(index, &spent_outputs, annex.as_deref(), leaf, sighash_type.unwrap_or(SIGHASH_DEFAULT)))
+	} else if script_pub_key.is_p2wpkh() || script_pub_key.is_p2wsh() || (script_pub_key.is_p2sh() && tx.segwit_v0_script_code(index).is_ok()) {
+		let script_code = match script {
+			Some(script) => script,
+			None => tx.segwit_v0_script_code(index).map_err(|e| failed("use --script", e))?,
+		};
+		("segwit_v0", tx.segwit_v0_signature_hash(index, &script_code, Some(prevout.amount), sighash_type.unwrap_or(SIGHASH_ALL)))
+	} else {
+		// the script pub key, or the redeem script of P2SH
+		let script_code = script.unwrap_or_else(|| script_pub_key.script.clone());
+		("legacy", tx.legacy_signature_hash(index, &script_code, sighash_type.unwrap_or(SIGHASH_ALL)))
+	};
+	let hash = hash.map_err(|e| failed("couldn't compute the signature hash", e))?.encode_hex_be();
+
+	if args.json {
+		return Ok(to_json(&serde_json::json!({ "sighash": hash, "sigversion": kind })));
+	}
+	Ok(hash)
+}
+
+fn sign_tx(args: &Args) -> Result<String, CliError> {
+	let mut tx = read_tx_with_prevouts(args)?;
+	let mut signer = Signer::new(keys(args, "sign-tx")?);
+	for script in args.values("--script") {
+		let script = script.decode_hex_be().map_err(|_| CliError::Usage(format!("invalid script {}", script)))?;
+		signer = signer.with_script(script);
+	}
+	if let Some(name) = args.value("--sighash") {
+		signer = signer.with_sighash_type(parse_sighash_type(name)?);
+	}
+
+	signer.sign(&mut tx).map_err(|e| failed("couldn't sign the transaction", e))?;
+	tx.set_network(args.network);
+	if args.json {
+		return Ok(to_json(&json::transaction_to_json(&tx)));
+	}
+	Ok(tx.as_hex())
+}
+
+fn psbt_create(args: &Args) -> Result<String, CliError> {
+	let bytes = read_bytes(args.single("transaction")?)?;
+	let tx = Transaction::decode_raw(bytes).map_err(|e| failed("couldn't decode the transaction", e))?;
+	let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| failed("couldn't create the PSBT", e))?;
+	update_psbt(&mut psbt, args)?;
+	Ok(psbt.to_base64())
+}
+
+fn psbt_new(args: &Args) -> Result<String, CliError> {
+	if let Some(arg) = args.positional.first() {
+		return Err(CliError::Usage(format!("unexpected argument {}", arg)));
+	}
+	let lock_time = args.value("--locktime").map(|n| parse_number("locktime", n)).transpose()?;
+	let version = args.value("--version").map(|n| parse_number("version", n)).transpose()?.unwrap_or(2);
+	Ok(Psbt::new_v2(version, lock_time).to_base64())
+}
+
+fn psbt_add(args: &Args) -> Result<String, CliError> {
+	let mut psbt = read_psbt(args.single("PSBT")?)?;
+	for input in args.values("--input") {
+		let input = parse_input(input)?;
+		psbt.add_input(input, PsbtInput::default()).map_err(|e| failed("couldn't add the input", e))?;
+	}
+	for output in args.values("--output") {
+		let output = parse_output(output, args.network)?;
+		psbt.add_output(output, PsbtOutput::default()).map_err(|e| failed("couldn't add the output", e))?;
+	}
+	Ok(psbt.to_base64())
+}
+
+fn psbt_update(args: &Args) -> Result<String, CliError> {
+	let mut psbt = read_psbt(args.single("PSBT")?)?;
+	update_psbt(&mut psbt, args)?;
+	Ok(psbt.to_base64())
+}
+
+/// Updater: the spent outputs, previous transactions and scripts given with --utxos, --esplora,
+/// --prev-tx and --script.
+fn update_psbt(psbt: &mut Psbt, args: &Args) -> Result<(), CliError> {
+	let mut providers: Vec<Box<dyn PrevoutProvider>> = Vec::new();
+	if let Some(path) = args.value("--utxos") {
+		providers.push(Box::new(MemoryPrevouts::from_file(Path::new(path)).map_err(|e| failed(path, e))?));
+	}
+	if let Some(url) = args.value("--esplora") {
+		providers.push(Box::new(EsploraClient::new(url)));
+	}
+	let scripts = args.values("--script")
+		.map(|script| script.decode_hex_be().map_err(|_| CliError::Usage(format!("invalid script {}", script))))
+		.collect::<Result<Vec<_>, _>>()?;
+	for provider in &providers {
+		psbt.add_witness_utxos(provider.as_ref()).map_err(|e| failed("couldn't look up the spent outputs", e))?;
+	}
+
+	for arg in args.values("--prev-tx") {
+		let prev_tx = Transaction::decode_raw(read_bytes(arg)?).map_err(|e| failed("couldn't decode the previous transaction", e))?;
+		let txid = prev_tx.txid();
+		let spending: Vec<usize> = (0..psbt.inputs.len()).filter(|i| psbt.unsigned_tx.inputs[*i].previous_tx == txid).collect();
+		if spending.is_empty() {
+			return Err(CliError::Failed(format!("no input spends from {}", txid)));
+		}
+		for i in spending {
+			let prev_tx = Transaction::decode_raw(prev_tx.as_bytes()).expect("the transaction was just decoded");
+			psbt.set_non_witness_utxo(i, prev_tx).map_err(|e| failed("invalid previous transaction", e))?;
+		}
+	}
+	// scripts are matched against the spent outputs, so they're added last
+	psbt.add_scripts(&scripts);
+	Ok(())
+}
+
+/// The private keys given with --key.
+fn keys(args: &Args, command: &str) -> Result<Vec<PrivateKey>, CliError> {
+	let keys = args.values("--key")
+		.map(|wif| PrivateKey::from_wif(wif).map_err(|e| failed("invalid key", e)))
+		.collect::<Result<Vec<_>, _>>()?;
+	if keys.is_empty() {
+		return Err(CliError::Usage(format!("{} needs at least one --key", command)));
+	}
+	Ok(keys)
+}
+
+fn psbt_sign(args: &Args) -> Result<String, CliError> {
+	let mut psbt = read_psbt(args.single("PSBT")?)?;
+	let keys = keys(args, "psbt-sign")?;
+	psbt.sign(&keys).map_err(|e| failed("couldn't sign the PSBT", e))?;
+	Ok(psbt.to_base64())
+}
+
+fn psbt_combine(args: &Args) -> Result<String, CliError> {
+	let mut psbts = args.positional.iter().map(|arg| read_psbt(arg));
+	let Some(psbt) = psbts.next() else {
+		return Err(CliError::Usage("missing PSBT".to_string()));
+	};
+	let mut combined = psbt?;
+	for psbt in psbts {
+		combined.combine(psbt?).map_err(|e| failed("couldn't combine the PSBTs", e))?;
+	}
+	Ok(combined.to_base64())
+}
+
+fn psbt_finalize(args: &Args) -> Result<String, CliError> {
+	let mut psbt = read_psbt(args.single("PSBT")?)?;
+	let result = psbt.finalize();
+	if args.json {
+		// like finalizepsbt, the inputs that could be finalized are kept
+		return Ok(to_json(&serde_json::json!({ "psbt": psbt.to_base64(), "complete": result.is_ok() })));
+	}
+	result.map_err(|e| failed("couldn't finalize the PSBT", e))?;
+	Ok(psbt.to_base64())
+}
+
+fn psbt_extract(args: &Args) -> Result<String, CliError> {
+	let psbt = read_psbt(args.single("PSBT")?)?;
+	let mut tx = psbt.extract_tx().map_err(|e| failed("couldn't extract the transaction", e))?;
+	tx.set_network(args.network);
+	if args.json {
+		return Ok(to_json(&json::transaction_to_json(&tx)));
+	}
+	Ok(tx.as_hex())
+}
+
+fn mnemonic(args: &Args) -> Result<String, CliError> {
+	if !args.positional.is_empty() {
+		return Err(CliError::Usage("mnemonic takes no arguments".to_string()));
+	}
+	let mnemonic = match (args.value("--entropy"), args.value("--words")) {
+		(Some(_), Some(_)) => return Err(CliError::Usage("--entropy and --words can't be combined".to_string())),
+		(Some(entropy), None) => {
+			let entropy = entropy.decode_hex_be().map_err(|_| CliError::Usage(format!("invalid entropy {}", entropy)))?;
+			Mnemonic::from_entropy(&entropy)
+		}
+		(None, words) => Mnemonic::generate(words.map_or(Ok(12), |words| parse_number("--words", words))?),
+	}.map_err(|e| failed("couldn't create the mnemonic", e))?;
+
+	if args.json {
+		return Ok(to_json(&serde_json::json!({ "mnemonic": mnemonic.to_string(), "entropy": mnemonic.entropy().encode_hex_be() })));
+	}
+	Ok(mnemonic.to_string())
+}
+
+/// An extended key, or the master key of a seed given in hex or as a mnemonic. Testnet, signet and
+/// regtest share the tprv and tpub prefixes, so the key's network is the one of the command.
+fn read_extended_key(arg: &str, passphrase: Option<&str>, network: Network) -> Result<(Option<ExtendedPrivateKey>, ExtendedPublicKey), CliError> {
+	if passphrase.is_some() && !arg.contains(char::is_whitespace) {
+		return Err(CliError::Usage("--passphrase is only used with a mnemonic".to_string()));
+	}
+	let (xprv, mut xpub) = if arg.contains(char::is_whitespace) {
+		let mnemonic = Mnemonic::from_str(arg).map_err(|e| failed("invalid mnemonic", e))?;
+		let xprv = mnemonic.to_master_key(passphrase.unwrap_or(""), network).map_err(|e| failed("invalid seed", e))?;
+		(Some(xprv), xprv.extended_public_key())
+	} else if let Ok(seed) = arg.decode_hex_be() {
+		let xprv = ExtendedPrivateKey::new_master(&seed, network).map_err(|e| failed("invalid seed", e))?;
+		(Some(xprv), xprv.extended_public_key())
+	} else if let Ok(xprv) = ExtendedPrivateKey::from_str(arg) {
+		(Some(xprv), xprv.extended_public_key())
+	} else {
+		let xpub = ExtendedPublicKey::from_str(arg).map_err(|e| failed("invalid extended key", e))?;
+		(None, xpub)
+	};
+	if xpub.info.network.xpub_prefix() != network.xpub_prefix() {
+		return Err(CliError::Failed(format!("the key isn't for {}", network)));
+	}
+	xpub.info.network = network;
+	let xprv = xprv.map(|mut xprv| {
+		xprv.info.network = network;
+		xprv.private_key.network = network;
+		xprv
+	});
+	Ok((xprv, xpub))
+}
+
+fn derive(args: &Args) -> Result<String, CliError> {
+	let (xprv, xpub) = read_extended_key(args.single("extended key")?, args.value("--passphrase"), args.network)?;
+	let path = match args.value("--path") {
+		Some(path) => DerivationPath::from_str(path).map_err(|e| CliError::Usage(e.to_string()))?,
+		None => DerivationPath::default(),
+	};
+	let fingerprint = xpub.fingerprint().encode_hex_be();
+	let (xprv, xpub) = match xprv {
+		Some(xprv) => {
+			let child = xprv.derive_path(&path).map_err(|e| failed("couldn't derive the key", e))?;
+			(Some(child), child.extended_public_key())
+		}
+		None => (None, xpub.derive_path(&path).map_err(|e| failed("couldn't derive the key", e))?),
+	};
+	let mut script_pub_key = ScriptPubKey::new_p2wpkh(xpub.public_key.as_bytes());
+	script_pub_key.set_network(args.network);
+	let address = script_pub_key.get_address().expect("P2WPKH has an address");
+
+	if args.json {
+		let mut json = serde_json::json!({
+			"fingerprint": fingerprint,
+			"path": path.to_string(),
+			"xpub": xpub.to_string(),
+			"pubkey": xpub.public_key.to_string(),
+			"address": address,
+		});
+		if let Some(xprv) = xprv {
+			json["xprv"] = Value::from(xprv.to_string());
+			json["wif"] = Value::from(xprv.private_key.to_wif());
+		}
+		return Ok(to_json(&json));
+	}
+	let mut lines = vec![
+		format!("fingerprint  {}", fingerprint),
+		format!("path         {}", path),
+	];
+	if let Some(xprv) = xprv {
+		lines.push(format!("xprv         {}", xprv));
+		lines.push(format!("wif          {}", xprv.private_key.to_wif()));
+	}
+	lines.push(format!("xpub         {}", xpub));
+	lines.push(format!("public key   {}", xpub.public_key));
+	lines.push(format!("address      {}", address));
+	Ok(lines.join("\n"))
+}
+
+fn txout_proof(args: &Args) -> Result<String, CliError> {
+	let Some((block, txids)) = args.positional.split_first() else {
+		return Err(CliError::Usage("missing block".to_string()));
+	};
+	if txids.is_empty() {
+		return Err(CliError::Usage("missing txid".to_string()));
+	}
+	let block = Block::decode_raw(read_bytes(block)?).map_err(|e| failed("couldn't decode the block", e))?;
+	// like gettxoutproof, every txid has to be in the block
+	if let Some(txid) = txids.iter().find(|txid| !block.transactions.iter().any(|tx| tx.txid() == **txid)) {
+		return Err(CliError::Failed(format!("{} is not in the block", txid)));
+	}
+	let proof = MerkleBlock::from_block(&block, txids).map_err(|e| failed("couldn't build the proof", e))?;
+	Ok(proof.as_bytes().encode_hex_be())
+}
+
+fn verify_proof(args: &Args) -> Result<String, CliError> {
+	let bytes = read_bytes(args.single("proof")?)?;
+	let proof = MerkleBlock::decode_raw(bytes).map_err(|e| failed("couldn't decode the proof", e))?;
+	let txids = proof.verify().map_err(|e| failed("invalid proof", e))?;
+	if let Some(client) = chain_client(args)? {
+		let block_hash = proof.header.block_hash();
+		let header = client.get_block_header(&block_hash).map_err(|e| failed("couldn't look up the block", e))?;
+		if header.as_ref() != Some(&proof.header) {
+			return Err(CliError::Failed(format!("block {} is unknown to the server", block_hash)));
+		}
+	}
+	if args.json {
+		return Ok(to_json(&serde_json::json!(txids)));
+	}
+	Ok(txids.join("\n"))
+}
+
+/// The server given with --esplora or --bitcoind, if any.
+fn chain_client(args: &Args) -> Result<Option<Box<dyn ChainClient>>, CliError> {
+	match (args.value("--esplora"), args.value("--bitcoind")) {
+		(Some(_), Some(_)) => Err(CliError::Usage("--esplora and --bitcoind can't be combined".to_string())),
+		(Some(url), None) => Ok(Some(Box::new(EsploraClient::new(url)))),
+		(None, Some(url)) => {
+			let auth = args.value("--rpcauth")
+				.map(|auth| auth.split_once(':').ok_or_else(|| CliError::Usage("--rpcauth is <user>:<password>".to_string())))
+				.transpose()?;
+			Ok(Some(Box::new(BitcoindClient::new(url, auth))))
+		}
+		(None, None) => Ok(None),
+	}
+}
+
+fn required_chain_client(args: &Args) -> Result<Box<dyn ChainClient>, CliError> {
+	chain_client(args)?.ok_or_else(|| CliError::Usage("needs --esplora or --bitcoind".to_string()))
+}
+
+fn utxos(args: &Args) -> Result<String, CliError> {
+	let address = args.single("address")?;
+	ScriptPubKey::from_address_on(address, args.network).map_err(|e| failed("invalid address", e))?;
+	let utxos = required_chain_client(args)?.get_utxos(address).map_err(|e| failed("couldn't look up the UTXOs", e))?;
+	if args.json {
+		// the format of UTXO files, so the output can be given to --utxos
+		return Ok(to_json(&utxos.iter().map(|utxo| serde_json::json!({
+			"txid": utxo.txid,
+			"vout": utxo.vout,
+			"value": utxo.output.amount,
+			"scriptpubkey": utxo.output.script_pub_key.script.encode_hex_be(),
+			"height": utxo.height,
+		})).collect()));
+	}
+	Ok(utxos.iter().map(|utxo| {
+		let height = utxo.height.map_or("unconfirmed".to_string(), |height| format!("height {}", height));
+		format!("{}:{}  {} sats  {}", utxo.txid, utxo.vout, utxo.output.amount, height)
+	}).collect::<Vec<_>>().join("\n"))
+}
+
+fn broadcast(args: &Args) -> Result<String, CliError> {
+	let bytes = read_bytes(args.single("transaction")?)?;
+	let tx = Transaction::decode_raw(bytes).map_err(|e| failed("couldn't decode the transaction", e))?;
+	required_chain_client(args)?.broadcast(&tx).map_err(|e| failed("couldn't broadcast the transaction", e))
+}
+
+fn estimate_fee(args: &Args) -> Result<String, CliError> {
+	if !args.positional.is_empty() {
+		return Err(CliError::Usage("estimate-fee takes no arguments".to_string()));
+	}
+	let blocks = args.value("--blocks").map_or(Ok(6), |blocks| parse_number("--blocks", blocks))?;
+	let fee_rate = required_chain_client(args)?.estimate_fee(blocks).map_err(|e| failed("couldn't estimate the fee", e))?;
+	if args.json {
+		return Ok(to_json(&serde_json::json!({ "feerate": fee_rate, "blocks": blocks })));
+	}
+	match fee_rate {
+		Some(fee_rate) => Ok(format!("{} sat/vB", fee_rate)),
+		None => Err(CliError::Failed(format!("no fee estimate for {} blocks", blocks))),
+	}
+}
+
+fn address(args: &Args) -> Result<String, CliError> {
+	let address = args.single("address")?;
+	let script_pub_key = match ScriptPubKey::from_address_on(address, args.network) {
+		Ok(script_pub_key) => script_pub_key,
+		// validateaddress answers for invalid addresses too
+		Err(e) if args.json => return Ok(to_json(&serde_json::json!({ "isvalid": false, "error_locations": [], "error": e.to_string() }))),
+		Err(e) => return Err(failed("invalid address", e)),
+	};
+	if args.json {
+		return Ok(to_json(&json::address_to_json(address, &script_pub_key)));
+	}
+	Ok([
+		format!("network         {}", script_pub_key.network()),
+		format!("type            {}", script_pub_key.get_type()),
+		format!("script pub key  {}", script_pub_key.script.encode_hex_be()),
+		format!("asm             {}", json::script_to_asm(&script_pub_key.script, false)),
+	].join("\n"))
+}
+
+fn create_multisig(args: &Args) -> Result<String, CliError> {
+	let Some((required, keys)) = args.positional.split_first() else {
+		return Err(CliError::Usage("missing the number of required signatures".to_string()));
+	};
+	let required: usize = parse_number("number of required signatures", required)?;
+	let keys = keys.iter()
+		.map(|key| PublicKey::from_str(key).map_err(|e| failed(key, e)))
+		.collect::<Result<Vec<_>, _>>()?;
+	let address_type = args.value("--type").unwrap_or("legacy");
+	// Like bitcoind, segwit scripts only take compressed keys
+	if address_type != "legacy" && keys.iter().any(|key| !key.compressed) {
+		return Err(CliError::Failed("segwit multisig needs compressed keys".to_string()));
+	}
+
+	let public_keys: Vec<HexBytes> = keys.iter().map(|key| key.as_bytes()).collect();
+	let redeem_script = ScriptPubKey::new_multisig(required, &public_keys).map_err(|e| failed("couldn't create the script", e))?;
+	let multi = format!("multi({},{})", required, keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(","));
+	let (mut script_pub_key, descriptor) = match address_type {
+		"legacy" => (ScriptPubKey::new_p2sh(redeem_script.script.clone()), format!("sh({})", multi)),
+		"p2sh-segwit" => {
+			let p2wsh = ScriptPubKey::new_p2wsh(redeem_script.script.clone());
+			(ScriptPubKey::new_p2sh(p2wsh.script), format!("sh(wsh({}))", multi))
+		}
+		"bech32" => (ScriptPubKey::new_p2wsh(redeem_script.script.clone()), format!("wsh({})", multi)),
+		other => return Err(CliError::Usage(format!("unknown address type {}", other))),
+	};
+	script_pub_key.set_network(args.network);
+	let address = script_pub_key.get_address().expect("p2sh and p2wsh scripts have an address");
+	let descriptor = format!("{}#{}", descriptor, descriptor::checksum(&descriptor).expect("keys are hex"));
+
+	if args.json {
+		return Ok(to_json(&serde_json::json!({
+			"address": address,
+			"redeemScript": redeem_script.script.encode_hex_be(),
+			"descriptor": descriptor,
+		})));
+	}
+	Ok([
+		format!("address        {}", address),
+		format!("redeem script  {}", redeem_script.script.encode_hex_be()),
+		format!("descriptor     {}", descriptor),
+	].join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+	use serde_json::Value;
+	use crate::key::PrivateKey;
+	use crate::merkle::tests::BLOCK;
+	use crate::mock_server::MockServer;
+	use crate::network::Network;
+	use crate::prevout::MemoryPrevouts;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::transaction::Transaction;
+	use crate::txio::{Decodable, Encodable};
+	use crate::{Deserialize, Serialize};
+	use super::{run, CliError};
+
+	const TX: &str = "01000000015dcb2625cc55a00079d49f38a7da8806b18cb60f8afa45279b174c89c5a86a65000000001976a91488fed7b8\
+	154069b5d2ace12fa4b7f96ab73d59df88acffffffff01e80300000000000003abcdef00000000";
+
+	fn run_args(args: &[&str]) -> Result<String, CliError> {
+		run(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+	}
+
+	fn run_json(args: &[&str]) -> Value {
+		serde_json::from_str(&run_args(args).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn decode_tx_command() {
+		let json = run_json(&["decode-tx", TX, "--json"]);
+		assert_eq!(json["txid"], "a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694");
+		assert_eq!(json["vout"][0]["value"], 0.00001);
+		assert!(json.get("fee").is_none());
+
+		let text = run_args(&["decode-tx", TX]).unwrap();
+		assert!(text.starts_with("txid      a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694\n"));
+		assert!(text.contains("  0  1000 sats  nonstandard"));
+
+		// hex or binary files and the fee from a UTXO file
+		let dir = std::env::temp_dir();
+		let hex_file = dir.join(format!("cli-tx-{}.hex", std::process::id()));
+		let binary_file = dir.join(format!("cli-tx-{}.bin", std::process::id()));
+		let utxos = dir.join(format!("cli-utxos-{}.csv", std::process::id()));
+		fs::write(&hex_file, format!("{}\n", TX)).unwrap();
+		fs::write(&binary_file, TX.decode_hex_be().unwrap()).unwrap();
+		fs::write(&utxos, "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d,0,1500,0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+		for file in [&hex_file, &binary_file] {
+			let json = run_json(&["decode-tx", file.to_str().unwrap(), "--json", "--utxos", utxos.to_str().unwrap()]);
+			assert_eq!(json["fee"], 0.000005);
+		}
+		for file in [hex_file, binary_file, utxos] {
+			fs::remove_file(file).unwrap();
+		}
+
+		assert!(matches!(run_args(&["decode-tx", "0100"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["decode-tx", "xyz"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["decode-tx"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["decode-tx", TX, "--bogus"]), Err(CliError::Usage(_))));
+	}
+
+	#[test]
+	fn decode_script_command() {
+		let json = run_json(&["decode-script", "0014751e76e8199196d454941c45d1b3a323f1433bd6", "--json", "--network", "test"]);
+		assert_eq!(json["type"], "witness_v0_keyhash");
+		assert_eq!(json["address"], "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx");
+		assert!(json["p2sh"].as_str().unwrap().starts_with('2'));
+
+		let text = run_args(&["decode-script", "76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac"]).unwrap();
+		assert!(text.contains("address      1DVNBsH8KkW4r8voRwChVj68Nnyy1JXY5F"));
+		assert!(text.contains("segwit       bc1q3rld0wq4gp5mt54vuyh6fdled2mn6kwlhpy2m7"));
+		assert!(matches!(run_args(&["decode-script", "00", "--network", "moon"]), Err(CliError::Usage(_))));
+	}
+
+	#[test]
+	fn create_tx_command() {
+		let raw = run_args(&["create-tx",
+			"--input", "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d:0:4294967295",
+			"--output", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4:1000",
+			"--output", "data:68656c6c6f",
+			"--version", "1"]).unwrap();
+		assert_eq!(raw, "01000000015dcb2625cc55a00079d49f38a7da8806b18cb60f8afa45279b174c89c5a86a650000000000ffffffff02\
+		e803000000000000160014751e76e8199196d454941c45d1b3a323f1433bd60000000000000000076a0568656c6c6f00000000");
+
+		let json = run_json(&["create-tx", "--json", "--locktime", "800000",
+			"--input", "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d:1",
+			"--output", "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH:2500"]);
+		assert_eq!(json["version"], 2);
+		assert_eq!(json["locktime"], 800000);
+		assert_eq!(json["vin"][0]["sequence"], 0xfffffffdu32);
+		assert_eq!(json["vout"][0]["scriptPubKey"]["address"], "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+
+		assert!(matches!(run_args(&["create-tx", "--input", "abcd:0", "--output", "data:00"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["create-tx", "--output", "data:00"]), Err(CliError::Usage(_))));
+		let input = "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d:0";
+		assert!(matches!(run_args(&["create-tx", "--input", input, "--output", "bc1qnope:1"]), Err(CliError::Failed(_))));
+		// like createrawtransaction, addresses of another network are rejected instead of relabelled
+		let testnet = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx:1000";
+		assert!(matches!(run_args(&["create-tx", "--input", input, "--output", testnet]), Err(CliError::Failed(_))));
+		let json = run_json(&["create-tx", "--json", "--network", "test", "--input", input, "--output", testnet]);
+		assert_eq!(json["vout"][0]["scriptPubKey"]["address"], "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx");
+	}
+
+	#[test]
+	fn address_command() {
+		let json = run_json(&["address", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", "--json"]);
+		assert_eq!(json["isvalid"], true);
+		assert_eq!(json["witness_program"], "751e76e8199196d454941c45d1b3a323f1433bd6");
+		let json = run_json(&["address", "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMX", "--json"]);
+		assert_eq!(json["isvalid"], false);
+		// a testnet address isn't valid on mainnet
+		let json = run_json(&["address", "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", "--json"]);
+		assert_eq!(json["isvalid"], false);
+		let json = run_json(&["address", "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", "--network", "signet", "--json"]);
+		assert_eq!(json["isvalid"], true);
+
+		let text = run_args(&["address", "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"]).unwrap();
+		assert!(text.contains("type            pubkeyhash"));
+		assert!(matches!(run_args(&["address", "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMX"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["address", "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", "--network", "regtest"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["nope"]), Err(CliError::Usage(_))));
+		assert!(run_args(&["help"]).unwrap().starts_with("Usage:"));
+	}
+
+	#[test]
+	fn create_multisig_command() {
+		let keys = ["03789ed0bb717d88f7d321a368d905e7430207ebbd82bd342cf11ae157a7ace5fd",
+			"03dbc6764b8884a92e871274b87583e6d5c2a58819473e17e107ef3f6aa5a61626"];
+		let multi = format!("multi(2,{},{})", keys[0], keys[1]);
+		let json = run_json(&["create-multisig", "2", keys[0], keys[1], "--json"]);
+		assert_eq!(json["address"], "3QsFXpFJf2ZY6GLWVoNFFd2xSDwdS713qX");
+		assert_eq!(json["redeemScript"], format!("5221{}21{}52ae", keys[0], keys[1]));
+		assert_eq!(json["descriptor"], format!("sh({})#4djp057k", multi));
+		let json = run_json(&["create-multisig", "2", keys[0], keys[1], "--type", "p2sh-segwit", "--json"]);
+		assert_eq!(json["address"], "33JUDjenJMFLaocwxiyRazubQ7aoVis8tF");
+		assert_eq!(json["descriptor"], format!("sh(wsh({}))#du4tngj2", multi));
+		let text = run_args(&["create-multisig", "2", keys[0], keys[1], "--type", "bech32"]).unwrap();
+		assert!(text.contains("address        bc1q0jnggjwnn22a4ywxc2pcw86c0d6tghqkgk3hlryrxl7nmxkylmnq6smlx3"));
+		assert!(text.contains(&format!("descriptor     wsh({})#5wacx8g6", multi)));
+
+		let uncompressed = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+		assert!(run_args(&["create-multisig", "1", uncompressed]).is_ok());
+		assert!(matches!(run_args(&["create-multisig", "1", uncompressed, "--type", "bech32"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["create-multisig", "3", keys[0], keys[1]]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["create-multisig", "1", "02aabb"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["create-multisig", "0", keys[0]]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["create-multisig", "1", keys[0], "--type", "taproot"]), Err(CliError::Usage(_))));
+	}
+
+	#[test]
+	fn psbt_commands() {
+		let keys: Vec<PrivateKey> = (1..=3u8).map(|n| PrivateKey::from_bytes(&[n; 32], true, Network::Mainnet).unwrap()).collect();
+		let wif = |i: usize| keys[i].to_wif();
+		let script = |i: usize| ScriptPubKey::new_p2wpkh(keys[i].public_key().as_bytes()).script.encode_hex_be();
+		let txid = "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d";
+
+		// two P2WPKH inputs signed by different people, then combined
+		let unsigned = run_args(&["create-tx", "--input", &format!("{}:0", txid), "--input", &format!("{}:1", txid),
+			"--output", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4:15000"]).unwrap();
+		let utxos = std::env::temp_dir().join(format!("cli-psbt-utxos-{}.csv", std::process::id()));
+		fs::write(&utxos, format!("{txid},0,10000,{}\n{txid},1,6000,{}", script(0), script(1))).unwrap();
+		let created = run_args(&["psbt-create", &unsigned, "--utxos", utxos.to_str().unwrap()]).unwrap();
+		fs::remove_file(utxos).unwrap();
+
+		let first = run_args(&["psbt-sign", &created, "--key", &wif(0)]).unwrap();
+		let second = run_args(&["psbt-sign", &created, "--key", &wif(1)]).unwrap();
+		assert!(matches!(run_args(&["psbt-finalize", &first]), Err(CliError::Failed(_))));
+		assert_eq!(run_json(&["psbt-finalize", &first, "--json"])["complete"], false);
+		assert!(matches!(run_args(&["psbt-extract", &first]), Err(CliError::Failed(_))));
+
+		// PSBTs can be read from files
+		let file = std::env::temp_dir().join(format!("cli-psbt-{}.txt", std::process::id()));
+		fs::write(&file, &second).unwrap();
+		let combined = run_args(&["psbt-combine", &first, file.to_str().unwrap()]).unwrap();
+		fs::remove_file(file).unwrap();
+		let finalized = run_args(&["psbt-finalize", &combined]).unwrap();
+		assert_eq!(run_json(&["psbt-finalize", &combined, "--json"])["psbt"], finalized.as_str());
+		let signed = run_args(&["psbt-extract", &finalized]).unwrap();
+
+		let mut tx = Transaction::decode_raw(signed.decode_hex_be().unwrap()).unwrap();
+		let mut prevouts = MemoryPrevouts::new();
+		for (i, amount) in [(0, 10000), (1, 6000)] {
+			let script_pub_key = ScriptPubKey::from_str(&script(i)).unwrap();
+			prevouts.insert(txid, i as u32, crate::transaction::Output { amount, script_pub_key });
+		}
+		tx.fetch_prevouts(&prevouts).unwrap();
+		assert_eq!(tx.verify(), Ok(()));
+		assert_eq!(tx.extra_info.as_ref().unwrap().miner_fee, 1000);
+		assert_eq!(run_json(&["psbt-extract", &finalized, "--json"])["txid"], tx.txid());
+
+		// a P2PKH input needs the transaction it spends from
+		let mut p2pkh = ScriptPubKey::new_p2pkh(keys[2].public_key().as_bytes());
+		p2pkh.set_network(Network::Mainnet);
+		let prev_tx = run_args(&["create-tx", "--input", &format!("{}:2", txid),
+			"--output", &format!("{}:5000", p2pkh.get_address().unwrap())]).unwrap();
+		let prev_txid = Transaction::decode_raw(prev_tx.decode_hex_be().unwrap()).unwrap().txid();
+		let unsigned = run_args(&["create-tx", "--input", &format!("{}:0", prev_txid),
+			"--output", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4:4000"]).unwrap();
+		let created = run_args(&["psbt-create", &unsigned]).unwrap();
+		assert!(matches!(run_args(&["psbt-sign", &created, "--key", &wif(2)]), Err(CliError::Failed(_))));
+		let created = run_args(&["psbt-create", &unsigned, "--prev-tx", &prev_tx]).unwrap();
+		let signed = run_args(&["psbt-sign", &created, "--key", &wif(2)]).unwrap();
+		let finalized = run_args(&["psbt-finalize", &signed]).unwrap();
+		let json = run_json(&["psbt-extract", &finalized, "--json"]);
+		assert_eq!(json["vin"][0]["txid"], prev_txid.as_str());
+		assert!(json["vin"][0]["scriptSig"]["asm"].as_str().unwrap().ends_with(&keys[2].public_key().as_bytes().encode_hex_be()));
+
+		assert!(matches!(run_args(&["psbt-create", &unsigned, "--prev-tx", &unsigned]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-create", &signed]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-sign", &created]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["psbt-sign", &created, "--key", "nope"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-combine", &created, &first]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-finalize", "cHNidP8="]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-combine"]), Err(CliError::Usage(_))));
+	}
+
+	#[test]
+	fn psbt_v2_commands() {
+		let key = PrivateKey::from_bytes(&[1; 32], true, Network::Mainnet).unwrap();
+		let script = ScriptPubKey::new_p2wpkh(key.public_key().as_bytes()).script.encode_hex_be();
+		let txid = "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d";
+
+		// a Constructor adds the inputs and outputs after the PSBT is created
+		let psbt = run_args(&["psbt-new", "--locktime", "800000"]).unwrap();
+		let psbt = run_args(&["psbt-add", &psbt, "--input", &format!("{}:0", txid)]).unwrap();
+		let psbt = run_args(&["psbt-add", &psbt, "--output", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4:9000"]).unwrap();
+		let utxos = std::env::temp_dir().join(format!("cli-psbt-v2-utxos-{}.csv", std::process::id()));
+		fs::write(&utxos, format!("{},0,10000,{}", txid, script)).unwrap();
+		let psbt = run_args(&["psbt-update", &psbt, "--utxos", utxos.to_str().unwrap()]).unwrap();
+		fs::remove_file(utxos).unwrap();
+		let signed = run_args(&["psbt-sign", &psbt, "--key", &key.to_wif()]).unwrap();
+
+		// SIGHASH_ALL commits to every input and output
+		assert!(matches!(run_args(&["psbt-add", &signed, "--input", &format!("{}:1", txid)]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-add", &signed, "--output", "data:00"]), Err(CliError::Failed(_))));
+
+		let finalized = run_args(&["psbt-finalize", &signed]).unwrap();
+		let tx = run_args(&["psbt-extract", &finalized]).unwrap();
+		let mut tx = Transaction::decode_raw(tx.decode_hex_be().unwrap()).unwrap();
+		assert_eq!(tx.lock_time, 800000);
+		let mut prevouts = MemoryPrevouts::new();
+		prevouts.insert(txid, 0, crate::transaction::Output { amount: 10000, script_pub_key: ScriptPubKey::from_str(&script).unwrap() });
+		tx.fetch_prevouts(&prevouts).unwrap();
+		assert_eq!(tx.verify(), Ok(()));
+
+		// version 0 PSBTs have a fixed transaction
+		let unsigned = run_args(&["create-tx", "--input", &format!("{}:0", txid), "--output", "data:00"]).unwrap();
+		let psbt = run_args(&["psbt-create", &unsigned]).unwrap();
+		assert!(matches!(run_args(&["psbt-add", &psbt, "--output", "data:00"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["psbt-new", "2"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["psbt-add", &psbt, "--input", "abcd:0"]), Err(CliError::Usage(_))));
+	}
+
+	#[test]
+	fn merkle_proof_commands() {
+		let txid = "f9fc751cb7dc372406a9f8d738d5e6f8f63bab71986a39cf36ee70ee17036d07";
+		let other = "74d681e0e03bafa802c8aa084379aa98d9fcd632ddc2ed9782b586ec87451f20";
+		// the txids are proven in block order
+		let proof = run_args(&["txout-proof", BLOCK, other, txid]).unwrap();
+		assert_eq!(run_json(&["verify-proof", &proof, "--json"]), serde_json::json!([txid, other]));
+		assert_eq!(run_args(&["verify-proof", &proof]).unwrap(), format!("{}\n{}", txid, other));
+
+		// the header commits to the tree, a different root doesn't verify
+		let mut tampered = proof.decode_hex_be().unwrap();
+		tampered[40] ^= 1;
+		assert!(matches!(run_args(&["verify-proof", &tampered.encode_hex_be()]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["txout-proof", BLOCK, &"00".repeat(32)]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["txout-proof", BLOCK]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["verify-proof", "00"]), Err(CliError::Failed(_))));
+	}
+
+	#[test]
+	fn derive_command() {
+		// BIP32 test vector 1
+		let seed = "000102030405060708090a0b0c0d0e0f";
+		let json = run_json(&["derive", seed, "--path", "m/0'/1/2h", "--json"]);
+		assert_eq!(json["fingerprint"], "3442193e");
+		assert_eq!(json["path"], "m/0'/1/2'");
+		assert_eq!(json["xprv"], "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM");
+		let xpub = "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5";
+		assert_eq!(json["xpub"], xpub);
+
+		// the public children of the xpub are the same as those of the xprv
+		let json = run_json(&["derive", xpub, "--path", "2/1000000000", "--json"]);
+		assert_eq!(json["xpub"], "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy");
+		assert!(json.get("xprv").is_none());
+		let text = run_args(&["derive", seed, "--path", "m/0'/1/2'/2/1000000000"]).unwrap();
+		assert!(text.contains(&format!("address      {}", json["address"].as_str().unwrap())));
+		assert!(text.contains("xprv         xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76"));
+
+		// the private key signs for the address
+		let wif = run_json(&["derive", seed, "--path", "m/84'/0'/0'/0/0", "--json"])["wif"].as_str().unwrap().to_string();
+		let key = PrivateKey::from_wif(&wif).unwrap();
+		let mut script_pub_key = ScriptPubKey::new_p2wpkh(key.public_key().as_bytes());
+		script_pub_key.set_network(Network::Mainnet);
+		assert_eq!(run_json(&["derive", seed, "--path", "m/84'/0'/0'/0/0", "--json"])["address"], script_pub_key.get_address().unwrap());
+
+		// tpub keys are valid on all the test networks
+		let json = run_json(&["derive", seed, "--network", "regtest", "--json"]);
+		assert!(json["xprv"].as_str().unwrap().starts_with("tprv"));
+		assert!(json["address"].as_str().unwrap().starts_with("bcrt1"));
+		let tpub = json["xpub"].as_str().unwrap();
+		assert!(run_json(&["derive", tpub, "--network", "signet", "--json"])["address"].as_str().unwrap().starts_with("tb1"));
+		assert!(matches!(run_args(&["derive", tpub]), Err(CliError::Failed(_))));
+
+		assert!(matches!(run_args(&["derive", xpub, "--path", "0'"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["derive", xpub, "--path", "m/x"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["derive", "0011"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["derive", "xpubnope"]), Err(CliError::Failed(_))));
+	}
+
+	#[test]
+	fn mnemonic_command() {
+		// BIP39 test vector with the passphrase TREZOR
+		let json = run_json(&["mnemonic", "--entropy", "00000000000000000000000000000000", "--json"]);
+		let sentence = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+		assert_eq!(json["mnemonic"], sentence);
+		let xprv = "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF";
+		assert_eq!(run_json(&["derive", sentence, "--passphrase", "TREZOR", "--json"])["xprv"], xprv);
+		assert_ne!(run_json(&["derive", sentence, "--json"])["xprv"], xprv);
+
+		let words = run_args(&["mnemonic", "--words", "24"]).unwrap();
+		assert_eq!(words.split(' ').count(), 24);
+		assert!(run_args(&["derive", &words, "--path", "m/84'/0'/0'/0/0"]).is_ok());
+		assert_eq!(run_json(&["mnemonic", "--json"])["entropy"].as_str().unwrap().len(), 32);
+
+		assert!(matches!(run_args(&["derive", &sentence.replace("about", "abandon")]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["derive", "000102030405060708090a0b0c0d0e0f", "--passphrase", "TREZOR"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["mnemonic", "--words", "13"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["mnemonic", "--entropy", "00", "--words", "12"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["mnemonic", "--entropy", "0011"]), Err(CliError::Failed(_))));
+	}
+
+	#[test]
+	fn server_commands() {
+		let block_hash = "0000000000013b8ab2cd513b0261a14096412195a72a0c4827d229dcc7e0f7af";
+		let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+		let server = MockServer::start(move |request| {
+			match (request.method.as_str(), request.path.as_str()) {
+				("GET", path) if path == format!("/block/{}/header", block_hash) => (200, BLOCK[..160].to_string()),
+				("GET", path) if path == format!("/address/{}/utxo", address) => (200, r#"[
+					{"txid": "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d", "vout": 0, "status": {"confirmed": true, "block_height": 800000}, "value": 1500}
+				]"#.to_string()),
+				("GET", "/fee-estimates") => (200, r#"{"1": 20.5, "6": 5.0}"#.to_string()),
+				("POST", "/tx") if request.body == TX => (200, "a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694".to_string()),
+				_ => (404, "Not Found".to_string()),
+			}
+		});
+		let url = server.url.as_str();
+
+		let proof = run_args(&["txout-proof", BLOCK, "f9fc751cb7dc372406a9f8d738d5e6f8f63bab71986a39cf36ee70ee17036d07"]).unwrap();
+		assert!(run_args(&["verify-proof", &proof, "--esplora", url]).is_ok());
+		let genesis = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f\
+		617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c01000000013ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a0101";
+		assert!(run_args(&["verify-proof", genesis]).is_ok());
+		assert!(matches!(run_args(&["verify-proof", genesis, "--esplora", url]), Err(CliError::Failed(_))));
+
+		// the UTXOs can be saved and given to --utxos
+		let utxos = run_args(&["utxos", address, "--esplora", url, "--json"]).unwrap();
+		let file = std::env::temp_dir().join(format!("cli-server-utxos-{}.json", std::process::id()));
+		fs::write(&file, utxos).unwrap();
+		assert_eq!(run_json(&["decode-tx", TX, "--json", "--utxos", file.to_str().unwrap()])["fee"], 0.000005);
+		fs::remove_file(file).unwrap();
+		let text = run_args(&["utxos", address, "--esplora", url]).unwrap();
+		assert_eq!(text, "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d:0  1500 sats  height 800000");
+
+		assert_eq!(run_args(&["estimate-fee", "--esplora", url]).unwrap(), "5 sat/vB");
+		assert_eq!(run_json(&["estimate-fee", "--blocks", "2", "--esplora", url, "--json"])["feerate"], 20.5);
+		assert!(matches!(run_args(&["estimate-fee", "--blocks", "0", "--esplora", url]), Err(CliError::Failed(_))));
+		assert_eq!(run_args(&["broadcast", TX, "--esplora", url]).unwrap(), "a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694");
+
+		assert!(matches!(run_args(&["utxos", address]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["utxos", address, "--esplora", url, "--network", "test"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["broadcast", TX, "--esplora", url, "--bitcoind", url]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["estimate-fee", "--bitcoind", url, "--rpcauth", "user"]), Err(CliError::Usage(_))));
+	}
+
+	#[test]
+	fn bitcoind_server_commands() {
+		let server = MockServer::start(|request| {
+			// user:pass
+			if request.header("authorization") != Some("Basic dXNlcjpwYXNz") {
+				return (401, String::new());
+			}
+			let call: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+			let result = match call["method"].as_str().unwrap() {
+				"estimatesmartfee" => serde_json::json!({"feerate": 0.00012, "blocks": 6}),
+				"sendrawtransaction" => serde_json::json!("a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694"),
+				_ => return (404, serde_json::json!({"result": null, "error": {"code": -32601, "message": "Method not found"}}).to_string()),
+			};
+			(200, serde_json::json!({"result": result, "error": null, "id": call["id"]}).to_string())
+		});
+		let url = server.url.as_str();
+		assert_eq!(run_args(&["estimate-fee", "--bitcoind", url, "--rpcauth", "user:pass"]).unwrap(), "12 sat/vB");
+		assert!(run_args(&["broadcast", TX, "--bitcoind", url, "--rpcauth", "user:pass"]).is_ok());
+		assert!(matches!(run_args(&["broadcast", TX, "--bitcoind", url]), Err(CliError::Failed(_))));
+	}
+
+	#[test]
+	fn sign_tx_command() {
+		let keys: Vec<PrivateKey> = (1..=2u8).map(|n| PrivateKey::from_bytes(&[n; 32], true, Network::Mainnet).unwrap()).collect();
+		let p2wpkh = ScriptPubKey::new_p2wpkh(keys[0].public_key().as_bytes()).script.encode_hex_be();
+		let p2pkh = ScriptPubKey::new_p2pkh(keys[1].public_key().as_bytes()).script.encode_hex_be();
+		let txid = "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d";
+		let unsigned = run_args(&["create-tx", "--input", &format!("{}:0", txid), "--input", &format!("{}:1", txid),
+			"--output", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4:15000"]).unwrap();
+		let utxos = std::env::temp_dir().join(format!("cli-sign-utxos-{}.csv", std::process::id()));
+		fs::write(&utxos, format!("{txid},0,10000,{}\n{txid},1,6000,{}", p2wpkh, p2pkh)).unwrap();
+		let utxos = utxos.to_str().unwrap();
+
+		let signed = run_args(&["sign-tx", &unsigned, "--key", &keys[0].to_wif(), "--key", &keys[1].to_wif(), "--utxos", utxos]).unwrap();
+		let json = run_json(&["decode-tx", &signed, "--json", "--utxos", utxos]);
+		assert_eq!(json["fee"], 0.00001);
+		let mut tx = Transaction::decode_raw(signed.decode_hex_be().unwrap()).unwrap();
+		let mut prevouts = MemoryPrevouts::new();
+		prevouts.insert(txid, 0, crate::transaction::Output { amount: 10000, script_pub_key: ScriptPubKey::from_str(&p2wpkh).unwrap() });
+		prevouts.insert(txid, 1, crate::transaction::Output { amount: 6000, script_pub_key: ScriptPubKey::from_str(&p2pkh).unwrap() });
+		tx.fetch_prevouts(&prevouts).unwrap();
+		assert_eq!(tx.verify(), Ok(()));
+
+		// the sighash type is appended to the signatures
+		let json = run_json(&["sign-tx", &unsigned, "--key", &keys[0].to_wif(), "--key", &keys[1].to_wif(), "--utxos", utxos,
+			"--sighash", "NONE|ANYONECANPAY", "--json"]);
+		assert!(json["vin"][0]["txinwitness"][0].as_str().unwrap().ends_with("82"));
+		assert!(json["vin"][1]["scriptSig"]["asm"].as_str().unwrap().contains("[NONE|ANYONECANPAY]"));
+
+		// every input has to be signed
+		assert!(matches!(run_args(&["sign-tx", &unsigned, "--key", &keys[0].to_wif(), "--utxos", utxos]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["sign-tx", &unsigned, "--key", &keys[0].to_wif()]), Err(CliError::Failed(_))));
+		assert!(matches!(run_args(&["sign-tx", &unsigned, "--key", &keys[0].to_wif(), "--sighash", "SOME"]), Err(CliError::Usage(_))));
+		assert!(matches!(run_args(&["sign-tx", &unsigned]), Err(CliError::Usage(_))));
+		fs::remove_file(utxos).unwrap();
+	}
+
+	/// Run the sighash command with the spent outputs in a temporary UTXO file.
+	fn run_sighash(tx: &str, utxos: &str, args: &[&str]) -> Result<String, CliError> {
+		let file = std::env::temp_dir().join(format!("cli-sighash-{}-{}.csv", std::process::id(), &tx[tx.len() - 8..]));
+		fs::write(&file, utxos).unwrap();
+		let mut command = vec!["sighash", tx, "--utxos", file.to_str().unwrap()];
+		command.extend_from_slice(args);
+		let result = run_args(&command);
+		fs::remove_file(file).unwrap();
+		result
+	}
+
+	#[test]
+	fn sighash_command() {
+		// BIP143 P2WPKH example, the witness program gives the script code
+		let tx = "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d2\
+		79655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7\
+		a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000";
+		let utxos = "8ac60eb9575db5b2d987e29f301b5b819ea83a5c6579d282d189cc04b8e151ef,1,600000000,00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1";
+		assert_eq!(run_sighash(tx, utxos, &["--input", "1"]).unwrap(), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+		assert!(matches!(run_args(&["sighash", tx, "--input", "1"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_sighash(tx, utxos, &["--input", "2"]), Err(CliError::Failed(_))));
+		assert!(matches!(run_sighash(tx, utxos, &[]), Err(CliError::Usage(_))));
+
+		// a legacy input signs its script pub key
+		let p2pkh = "76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac";
+		let utxos = format!("656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d,0,1500,{}", p2pkh);
+		let json: Value = serde_json::from_str(&run_sighash(TX, &utxos, &["--input", "0", "--sighash", "SINGLE", "--json"]).unwrap()).unwrap();
+		let expected = Transaction::decode_raw(TX.decode_hex_be().unwrap()).unwrap()
+			.legacy_signature_hash(0, &p2pkh.decode_hex_be().unwrap(), crate::sighash::SIGHASH_SINGLE).unwrap();
+		assert_eq!(json["sighash"], expected.encode_hex_be());
+		assert_eq!(json["sigversion"], "legacy");
+
+		// taproot key path with ALL|ANYONECANPAY
+		let tx = "0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae000000001611d0d603d9dc0e000000000017a914459b6d7\
+		d6bbb4d8837b4bf7e9a4556f952da2f5c8758020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000001976a91\
+		41dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f";
+		let utxos = format!("{},1081796,51201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e2d22e65c", outpoint(tx));
+		assert_eq!(run_sighash(tx, &utxos, &["--input", "0", "--sighash", "ALL|ANYONECANPAY"]).unwrap(),
+			"dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c");
+
+		// the annex is taken from the witness
+		let annex = "507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e";
+		let tx = with_witness("0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea\
+		7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36e\
+		a7a094db2f4940fc09edf154e86de7bd787f6020000", &["aa".repeat(64), annex.to_string()]);
+		let utxos = format!("{},2509290,5120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010", outpoint(&tx));
+		let json: Value = serde_json::from_str(&run_sighash(&tx, &utxos, &["--input", "0", "--sighash", "SINGLE|ANYONECANPAY", "--json"]).unwrap()).unwrap();
+		assert_eq!(json["sighash"], "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c");
+		assert_eq!(json["sigversion"], "taproot");
+
+		// script path: the tapscript is given or found in front of the control block
+		let tx = "020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae29062195010000004445b5c7044561320000000000160014331414\
+		dbdada7fb578f700f38fb69995fc9b5ab958020000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac580200000000000017a914ec0\
+		0dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49a0bae3956df9c17cb4440a673ac0df6f010000";
+		let tapscript = "20cc4e1107aea1d170c5ff5b6817e1303010049724fb3caa7941792ea9d29b3e2bacab";
+		let utxos = format!("{},3468315,512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893d79f7182", outpoint(tx));
+		let expected = "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e";
+		assert_eq!(run_sighash(tx, &utxos, &["--input", "0", "--sighash", "ALL", "--script", tapscript]).unwrap(), expected);
+		let signed = with_witness(tx, &["aa".repeat(64), tapscript.to_string(), format!("c1{}", "11".repeat(32))]);
+		assert_eq!(run_sighash(&signed, &utxos, &["--input", "0", "--sighash", "ALL"]).unwrap(), expected);
+		// without a leaf it's a key path spend
+		assert_ne!(run_sighash(tx, &utxos, &["--input", "0", "--sighash", "ALL"]).unwrap(), expected);
+	}
+
+	/// The outpoint spent by the first input, as txid,vout.
+	fn outpoint(tx: &str) -> String {
+		let tx = Transaction::decode_raw(tx.decode_hex_be().unwrap()).unwrap();
+		format!("{},{}", tx.inputs[0].previous_tx, tx.inputs[0].tx_index)
+	}
+
+	/// The transaction with a witness for its only input.
+	fn with_witness(tx: &str, witness: &[String]) -> String {
+		let mut tx = Transaction::decode_raw(tx.decode_hex_be().unwrap()).unwrap();
+		tx.flag = Some(1);
+		tx.witness_data = Some(vec![crate::transaction::WitnessStack(witness.to_vec())]);
+		tx.as_hex()
+	}
+}
@@ -0,0 +1,485 @@
+// Talking to the network. Two kinds of servers are supported: Esplora's REST API (electrs,
+// mempool, blockstream.info) and bitcoind's JSON-RPC. Both answer the same questions, the
+// ChainClient trait hides which one is used.
+
+use std::error::Error;
+use std::fmt;
+use crate::address::AddressError;
+use crate::block::BlockHeader;
+use crate::prevout::{PrevoutError, PrevoutProvider};
+use crate::script::{Script, ScriptPubKey};
+use crate::transaction::{Output, Transaction};
+use crate::txio::Decodable;
+use crate::{Deserialize, Serialize};
+
+/// RPC_INVALID_ADDRESS_OR_KEY, what bitcoind answers for unknown transactions and blocks.
+const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+	/// The request couldn't be sent or the response couldn't be read.
+	Http(String),
+	/// The server answered with an error status.
+	Status(u16, String),
+	/// The response isn't what the API returns.
+	InvalidResponse(String),
+	/// bitcoind returned an error, e.g. for a transaction rejected by its mempool.
+	Rpc { code: i64, message: String },
+	/// The address to look up UTXOs for couldn't be parsed.
+	InvalidAddress(AddressError),
+	/// A txid or block hash that isn't 32 bytes of hex.
+	InvalidHash(String),
+}
+
+impl fmt::Display for ClientError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ClientError::Http(e) => write!(f, "request failed: {}", e),
+			ClientError::Status(status, body) => write!(f, "server answered {}: {}", status, body),
+			ClientError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+			ClientError::Rpc { code, message } => write!(f, "rpc error {}: {}", code, message),
+			ClientError::InvalidAddress(e) => write!(f, "invalid address: {}", e),
+			ClientError::InvalidHash(hash) => write!(f, "invalid hash {}", hash),
+		}
+	}
+}
+
+impl Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+	fn from(e: reqwest::Error) -> Self {
+		ClientError::Http(e.to_string())
+	}
+}
+
+impl From<AddressError> for ClientError {
+	fn from(e: AddressError) -> Self {
+		ClientError::InvalidAddress(e)
+	}
+}
+
+/// An unspent output and where it is.
+#[derive(Debug, PartialEq)]
+pub struct Utxo {
+	pub txid: String,
+	pub vout: u32,
+	pub output: Output,
+	/// Height of the block it was confirmed in, None while it's in the mempool.
+	pub height: Option<u32>,
+}
+
+/// What can be asked to a server. Transactions and blocks the server doesn't know are Ok(None).
+pub trait ChainClient {
+	fn get_transaction(&self, txid: &str) -> Result<Option<Transaction>, ClientError>;
+	/// Unspent outputs paying to an address.
+	fn get_utxos(&self, address: &str) -> Result<Vec<Utxo>, ClientError>;
+	fn get_block_header(&self, block_hash: &str) -> Result<Option<BlockHeader>, ClientError>;
+	/// Fee rate in sat/vB to confirm within `target` blocks, None if the server has no estimate.
+	fn estimate_fee(&self, target: u16) -> Result<Option<f64>, ClientError>;
+	/// Send a transaction to the network, returns its txid.
+	fn broadcast(&self, tx: &Transaction) -> Result<String, ClientError>;
+}
+
+/// Every client can find the outputs spent by inputs in the transactions they come from.
+fn get_prevout<C: ChainClient>(client: &C, txid: &str, index: u32) -> Result<Option<Output>, PrevoutError> {
+	check_hash(txid).map_err(PrevoutError::Client)?;
+	let Some(mut tx) = client.get_transaction(txid).map_err(PrevoutError::Client)? else {
+		return Ok(None);
+	};
+	if index as usize >= tx.outputs.len() {
+		return Err(PrevoutError::OutputNotFound { txid: txid.to_string(), index });
+	}
+	Ok(Some(tx.outputs.swap_remove(index as usize)))
+}
+
+/// An Esplora server, like a local electrs or mempool instance.
+pub struct EsploraClient {
+	/// For example "http://localhost:3002" or "https://blockstream.info/api".
+	pub base_url: String,
+	http: reqwest::blocking::Client,
+}
+
+impl EsploraClient {
+	pub fn new(base_url: &str) -> Self {
+		EsploraClient {
+			base_url: base_url.trim_end_matches('/').to_string(),
+			http: reqwest::blocking::Client::new(),
+		}
+	}
+
+	/// GET a path, None on 404.
+	fn get(&self, path: &str) -> Result<Option<String>, ClientError> {
+		let response = self.http.get(format!("{}{}", self.base_url, path)).send()?;
+		if response.status() == reqwest::StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+		read_body(response).map(Some)
+	}
+
+	fn get_json(&self, path: &str) -> Result<serde_json::Value, ClientError> {
+		let body = self.get(path)?.ok_or_else(|| ClientError::Status(404, path.to_string()))?;
+		serde_json::from_str(&body).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+	}
+}
+
+impl ChainClient for EsploraClient {
+	fn get_transaction(&self, txid: &str) -> Result<Option<Transaction>, ClientError> {
+		check_hash(txid)?;
+		self.get(&format!("/tx/{}/hex", txid))?.map(|hex| decode_hex(&hex)).transpose()
+	}
+
+	fn get_utxos(&self, address: &str) -> Result<Vec<Utxo>, ClientError> {
+		// Esplora doesn't return the script, it's the one of the address
+		let script = ScriptPubKey::from_address(address)?.script;
+		let utxos = self.get_json(&format!("/address/{}/utxo", address))?;
+		let utxos = utxos.as_array().ok_or_else(|| invalid("expected an array"))?;
+		utxos.iter().map(|utxo| {
+			let height = if utxo["status"]["confirmed"].as_bool() == Some(true) {
+				Some(to_u32(utxo["status"]["block_height"].as_u64().ok_or_else(|| invalid("missing block_height"))?, "block_height")?)
+			} else {
+				None
+			};
+			Ok(Utxo {
+				txid: utxo["txid"].as_str().ok_or_else(|| invalid("missing txid"))?.to_string(),
+				vout: to_u32(utxo["vout"].as_u64().ok_or_else(|| invalid("missing vout"))?, "vout")?,
+				output: Output {
+					amount: utxo["value"].as_u64().ok_or_else(|| invalid("missing value"))?,
+					script_pub_key: ScriptPubKey::from_bytes(script.clone()),
+				},
+				height,
+			})
+		}).collect()
+	}
+
+	fn get_block_header(&self, block_hash: &str) -> Result<Option<BlockHeader>, ClientError> {
+		check_hash(block_hash)?;
+		self.get(&format!("/block/{}/header", block_hash))?.map(|hex| decode_hex(&hex)).transpose()
+	}
+
+	fn estimate_fee(&self, target: u16) -> Result<Option<f64>, ClientError> {
+		// Only some targets have an estimate. Use the closest one that's at least as fast.
+		let estimates = self.get_json("/fee-estimates")?;
+		let estimates = estimates.as_object().ok_or_else(|| invalid("expected an object"))?;
+		let mut best: Option<(u16, f64)> = None;
+		for (blocks, fee_rate) in estimates {
+			let blocks: u16 = blocks.parse().map_err(|_| invalid("invalid target"))?;
+			let fee_rate = fee_rate.as_f64().ok_or_else(|| invalid("invalid fee rate"))?;
+			if blocks <= target && best.is_none_or(|(best_blocks, _)| blocks > best_blocks) {
+				best = Some((blocks, fee_rate));
+			}
+		}
+		Ok(best.map(|(_, fee_rate)| fee_rate))
+	}
+
+	fn broadcast(&self, tx: &Transaction) -> Result<String, ClientError> {
+		let response = self.http.post(format!("{}/tx", self.base_url)).body(tx.as_hex()).send()?;
+		Ok(read_body(response)?.trim().to_string())
+	}
+}
+
+impl PrevoutProvider for EsploraClient {
+	fn get_prevout(&self, txid: &str, index: u32) -> Result<Option<Output>, PrevoutError> {
+		get_prevout(self, txid, index)
+	}
+}
+
+/// A bitcoind node's JSON-RPC interface. Looking up transactions that aren't in the mempool or
+/// the wallet needs `-txindex`.
+pub struct BitcoindClient {
+	/// For example "http://localhost:8332" on mainnet or "http://localhost:18443" on regtest.
+	pub url: String,
+	/// `-rpcuser` and `-rpcpassword`, or the contents of the .cookie file split at ':'.
+	auth: Option<(String, String)>,
+	http: reqwest::blocking::Client,
+}
+
+impl BitcoindClient {
+	pub fn new(url: &str, auth: Option<(&str, &str)>) -> Self {
+		BitcoindClient {
+			url: url.to_string(),
+			auth: auth.map(|(user, password)| (user.to_string(), password.to_string())),
+			http: reqwest::blocking::Client::new(),
+		}
+	}
+
+	/// Call a method and return its result, or the error bitcoind returned.
+	fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, ClientError> {
+		let request = serde_json::json!({ "jsonrpc": "1.0", "id": method, "method": method, "params": params });
+		let mut builder = self.http.post(&self.url).body(request.to_string());
+		if let Some((user, password)) = &self.auth {
+			builder = builder.basic_auth(user, Some(password));
+		}
+		let response = builder.send()?;
+
+		// errors are returned with a 404 or 500 status, the body tells which one
+		let status = response.status();
+		let body = response.text()?;
+		let Ok(mut response) = serde_json::from_str::<serde_json::Value>(&body) else {
+			return Err(ClientError::Status(status.as_u16(), body));
+		};
+		let error = &response["error"];
+		if !error.is_null() {
+			return Err(ClientError::Rpc {
+				code: error["code"].as_i64().unwrap_or_default(),
+				message: error["message"].as_str().unwrap_or_default().to_string(),
+			});
+		}
+		Ok(response["result"].take())
+	}
+
+	/// Same as `call` but unknown transactions and blocks are Ok(None).
+	fn call_optional(&self, method: &str, params: serde_json::Value) -> Result<Option<serde_json::Value>, ClientError> {
+		match self.call(method, params) {
+			Err(ClientError::Rpc { code: RPC_INVALID_ADDRESS_OR_KEY, .. }) => Ok(None),
+			result => result.map(Some),
+		}
+	}
+}
+
+impl ChainClient for BitcoindClient {
+	fn get_transaction(&self, txid: &str) -> Result<Option<Transaction>, ClientError> {
+		check_hash(txid)?;
+		let Some(hex) = self.call_optional("getrawtransaction", serde_json::json!([txid, false]))? else {
+			return Ok(None);
+		};
+		decode_hex(hex.as_str().ok_or_else(|| invalid("expected a hex string"))?).map(Some)
+	}
+
+	fn get_utxos(&self, address: &str) -> Result<Vec<Utxo>, ClientError> {
+		// scantxoutset only looks at the UTXO set, unconfirmed outputs aren't in it
+		ScriptPubKey::from_address(address)?;
+		let descriptor = format!("addr({})", address);
+		let scan = self.call("scantxoutset", serde_json::json!(["start", [descriptor]]))?;
+		let unspents = scan["unspents"].as_array().ok_or_else(|| invalid("missing unspents"))?;
+		unspents.iter().map(|utxo| {
+			let script = utxo["scriptPubKey"].as_str().ok_or_else(|| invalid("missing scriptPubKey"))?
+				.decode_hex_be().map_err(|_| invalid("invalid scriptPubKey"))?;
+			let amount = utxo["amount"].as_f64().ok_or_else(|| invalid("missing amount"))?;
+			Ok(Utxo {
+				txid: utxo["txid"].as_str().ok_or_else(|| invalid("missing txid"))?.to_string(),
+				vout: to_u32(utxo["vout"].as_u64().ok_or_else(|| invalid("missing vout"))?, "vout")?,
+				output: Output { amount: btc_to_sats(amount), script_pub_key: ScriptPubKey::from_bytes(script) },
+				height: utxo["height"].as_u64().map(|height| to_u32(height, "height")).transpose()?,
+			})
+		}).collect()
+	}
+
+	fn get_block_header(&self, block_hash: &str) -> Result<Option<BlockHeader>, ClientError> {
+		check_hash(block_hash)?;
+		let Some(hex) = self.call_optional("getblockheader", serde_json::json!([block_hash, false]))? else {
+			return Ok(None);
+		};
+		decode_hex(hex.as_str().ok_or_else(|| invalid("expected a hex string"))?).map(Some)
+	}
+
+	fn estimate_fee(&self, target: u16) -> Result<Option<f64>, ClientError> {
+		// BTC per kvB, missing when the node hasn't seen enough transactions yet
+		let estimate = self.call("estimatesmartfee", serde_json::json!([target]))?;
+		Ok(estimate["feerate"].as_f64().map(|fee_rate| btc_to_sats(fee_rate) as f64 / 1000.0))
+	}
+
+	fn broadcast(&self, tx: &Transaction) -> Result<String, ClientError> {
+		let txid = self.call("sendrawtransaction", serde_json::json!([tx.as_hex()]))?;
+		Ok(txid.as_str().ok_or_else(|| invalid("expected a txid"))?.to_string())
+	}
+}
+
+impl PrevoutProvider for BitcoindClient {
+	fn get_prevout(&self, txid: &str, index: u32) -> Result<Option<Output>, PrevoutError> {
+		get_prevout(self, txid, index)
+	}
+}
+
+fn read_body(response: reqwest::blocking::Response) -> Result<String, ClientError> {
+	let status = response.status();
+	let body = response.text()?;
+	if !status.is_success() {
+		return Err(ClientError::Status(status.as_u16(), body));
+	}
+	Ok(body)
+}
+
+fn decode_hex<T: Deserialize>(hex: &str) -> Result<T, ClientError> {
+	let bytes = hex.trim().decode_hex_be().map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+	T::decode_raw(bytes).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
+
+fn invalid(reason: &str) -> ClientError {
+	ClientError::InvalidResponse(reason.to_string())
+}
+
+/// Output indexes and block heights are 32 bits, anything bigger is a bad response.
+fn to_u32(n: u64, field: &str) -> Result<u32, ClientError> {
+	u32::try_from(n).map_err(|_| invalid(&format!("{} out of range", field)))
+}
+
+/// Amounts in JSON-RPC are BTC. Rounded since floats can't represent most of them exactly.
+fn btc_to_sats(amount: f64) -> u64 {
+	(amount * 100_000_000.0).round() as u64
+}
+
+/// Txids and block hashes are 32 bytes of hex. Checked before they end up in a url or a request.
+fn check_hash(hash: &str) -> Result<(), ClientError> {
+	match hash.decode_hex_be() {
+		Ok(bytes) if bytes.len() == 32 => Ok(()),
+		_ => Err(ClientError::InvalidHash(hash.to_string())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::block::BlockHeader;
+	use crate::mock_server::MockServer;
+	use crate::prevout::{PrevoutError, PrevoutProvider};
+	use crate::script::Script;
+	use crate::txio::{Decodable, Encodable};
+	use crate::{Deserialize, Serialize};
+	use super::{BitcoindClient, ChainClient, ClientError, EsploraClient};
+
+	// decode_transaction_p2wpkh, two outputs
+	const TXID: &str = "663becacc6368150a46725e404ccdfa34d1fffbececa784c31f0a7849b4dad08";
+	const TX: &str = "020000000001015ce1d4ffc716022f83cc0d557e6dad0500eeff9e9623bde014bdc09c5b672d750000000000fd\
+	ffffff025fb7460b000000001600142cf4c1dc0352e0658971ca62a7457a1cd8c3389c4ce3a2000000000016001433f57fe374c6ceab61\
+	c8639128c038ac2a8c8db60247304402203cb50efb5c4a9aa7fd369ab6f4b226db99f44f9c610b5b50bc42f343a6aa401302201af79154\
+	2eee6c1b11705e8895cc5adc36458910dc91aadcafb76a6478a29b9f01210242e811e66fd17e9a6e4ef772766c668d6e0595ca1d7f0583\
+	148bc460b575fbfdf0df0b00";
+	const UNKNOWN_TXID: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+	const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+	const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b\
+	12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+	const ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+	#[test]
+	fn esplora_client() {
+		let server = MockServer::start(|request| {
+			match (request.method.as_str(), request.path.as_str()) {
+				("GET", path) if path == format!("/tx/{}/hex", TXID) => (200, TX.to_string()),
+				("GET", path) if path == format!("/block/{}/header", GENESIS_HASH) => (200, GENESIS_HEADER.to_string()),
+				("GET", path) if path == format!("/address/{}/utxo", ADDRESS) => (200, format!(r#"[
+					{{"txid": "{}", "vout": 1, "status": {{"confirmed": true, "block_height": 800000}}, "value": 10000}},
+					{{"txid": "{}", "vout": 0, "status": {{"confirmed": false}}, "value": 2500}}
+				]"#, TXID, UNKNOWN_TXID)),
+				("GET", "/address/1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2/utxo") => (200, format!(r#"[
+					{{"txid": "{}", "vout": 4294967296, "status": {{"confirmed": false}}, "value": 2500}}
+				]"#, TXID)),
+				("GET", "/fee-estimates") => (200, r#"{"1": 20.5, "3": 10.1, "6": 5.0, "144": 1.0}"#.to_string()),
+				("POST", "/tx") if request.body == TX => (200, TXID.to_string()),
+				("POST", "/tx") => (400, "sendrawtransaction RPC error: {\"code\":-22,\"message\":\"TX decode failed\"}".to_string()),
+				_ => (404, "Not Found".to_string()),
+			}
+		});
+		let client = EsploraClient::new(&format!("{}/", server.url));
+
+		let tx = client.get_transaction(TXID).unwrap().unwrap();
+		assert_eq!(tx.txid(), TXID);
+		assert_eq!(client.get_transaction(UNKNOWN_TXID), Ok(None));
+		assert_eq!(client.get_transaction("abcd"), Err(ClientError::InvalidHash("abcd".to_string())));
+
+		let prevout = client.get_prevout(TXID, 1).unwrap().unwrap();
+		assert_eq!(prevout.amount, 0x00a2e34c);
+		assert!(prevout.script_pub_key.is_p2wpkh());
+		assert_eq!(client.get_prevout(TXID, 2), Err(PrevoutError::OutputNotFound { txid: TXID.to_string(), index: 2 }));
+		assert_eq!(client.get_prevout(UNKNOWN_TXID, 0), Ok(None));
+
+		let utxos = client.get_utxos(ADDRESS).unwrap();
+		assert_eq!(utxos.len(), 2);
+		assert_eq!((utxos[0].txid.as_str(), utxos[0].vout, utxos[0].height), (TXID, 1, Some(800000)));
+		assert_eq!((utxos[1].output.amount, utxos[1].height), (2500, None));
+		assert_eq!(utxos[1].output.script_pub_key.script.encode_hex_be(), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+		assert!(matches!(client.get_utxos("bc1qinvalid"), Err(ClientError::InvalidAddress(_))));
+		// the output index doesn't fit in 32 bits
+		assert_eq!(client.get_utxos("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"), Err(ClientError::InvalidResponse("vout out of range".to_string())));
+
+		let header = client.get_block_header(GENESIS_HASH).unwrap().unwrap();
+		assert_eq!(header.block_hash(), GENESIS_HASH);
+		assert_eq!(client.get_block_header(&TXID.replace('6', "7")), Ok(None));
+
+		// the closest target that isn't slower
+		assert_eq!(client.estimate_fee(4), Ok(Some(10.1)));
+		assert_eq!(client.estimate_fee(1008), Ok(Some(1.0)));
+		assert_eq!(client.estimate_fee(0), Ok(None));
+
+		assert_eq!(client.broadcast(&tx), Ok(TXID.to_string()));
+		let mut invalid = tx;
+		invalid.lock_time += 1;
+		assert!(matches!(client.broadcast(&invalid), Err(ClientError::Status(400, _))));
+
+		let requests = server.requests();
+		assert!(requests.iter().any(|request| request.method == "POST" && request.body == TX));
+		assert!(requests.iter().all(|request| !request.path.starts_with("//")));
+	}
+
+	#[test]
+	fn bitcoind_client() {
+		let server = MockServer::start(|request| {
+			// user:pass
+			if request.header("authorization") != Some("Basic dXNlcjpwYXNz") {
+				return (401, String::new());
+			}
+			let call: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+			let params = &call["params"];
+			let result = match call["method"].as_str().unwrap() {
+				"getrawtransaction" if params[0] == TXID && params[1] == false => serde_json::json!(TX),
+				"getblockheader" if params[0] == GENESIS_HASH && params[1] == false => serde_json::json!(GENESIS_HEADER),
+				"getrawtransaction" | "getblockheader" => {
+					let error = serde_json::json!({"result": null, "error": {"code": -5, "message": "No such mempool or blockchain transaction"}, "id": call["id"]});
+					return (500, error.to_string());
+				}
+				"scantxoutset" if params[1][0] == format!("addr({})", ADDRESS) => serde_json::json!({
+					"success": true,
+					"unspents": [{"txid": TXID, "vout": 1, "scriptPubKey": "0014751e76e8199196d454941c45d1b3a323f1433bd6", "amount": 0.0001, "height": 800000}],
+				}),
+				"estimatesmartfee" if params[0] == 2 => serde_json::json!({"feerate": 0.00012, "blocks": 2}),
+				"estimatesmartfee" => serde_json::json!({"errors": ["Insufficient data or no feerate found"], "blocks": 0}),
+				"sendrawtransaction" if params[0] == TX => serde_json::json!(TXID),
+				"sendrawtransaction" => {
+					let error = serde_json::json!({"result": null, "error": {"code": -26, "message": "min relay fee not met"}, "id": call["id"]});
+					return (500, error.to_string());
+				}
+				_ => return (404, serde_json::json!({"result": null, "error": {"code": -32601, "message": "Method not found"}}).to_string()),
+			};
+			(200, serde_json::json!({"result": result, "error": null, "id": call["id"]}).to_string())
+		});
+		let client = BitcoindClient::new(&server.url, Some(("user", "pass")));
+
+		let tx = client.get_transaction(TXID).unwrap().unwrap();
+		assert_eq!(tx.as_hex(), TX);
+		assert_eq!(client.get_transaction(UNKNOWN_TXID), Ok(None));
+		assert!(client.get_prevout(TXID, 0).unwrap().unwrap().script_pub_key.is_p2wpkh());
+
+		let utxos = client.get_utxos(ADDRESS).unwrap();
+		assert_eq!(utxos.len(), 1);
+		assert_eq!((utxos[0].output.amount, utxos[0].height), (10000, Some(800000)));
+		assert!(utxos[0].output.script_pub_key.is_p2wpkh());
+
+		let header = client.get_block_header(GENESIS_HASH).unwrap().unwrap();
+		assert_eq!(header, BlockHeader::decode_raw(GENESIS_HEADER.decode_hex_be().unwrap()).unwrap());
+		assert_eq!(client.get_block_header(UNKNOWN_TXID), Ok(None));
+
+		// 0.00012 BTC/kvB
+		assert_eq!(client.estimate_fee(2), Ok(Some(12.0)));
+		assert_eq!(client.estimate_fee(1), Ok(None));
+
+		assert_eq!(client.broadcast(&tx), Ok(TXID.to_string()));
+		let mut invalid = tx;
+		invalid.version = 1;
+		assert_eq!(client.broadcast(&invalid), Err(ClientError::Rpc { code: -26, message: "min relay fee not met".to_string() }));
+
+		let request = &server.requests()[0];
+		assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/"));
+
+		// wrong credentials get an empty 401
+		let client = BitcoindClient::new(&server.url, Some(("user", "wrong")));
+		assert_eq!(client.estimate_fee(2), Err(ClientError::Status(401, String::new())));
+	}
+
+	#[test]
+	fn unreachable_server() {
+		// nothing listens on port 1
+		let client = EsploraClient::new("http://127.0.0.1:1");
+		assert!(matches!(client.get_transaction(TXID), Err(ClientError::Http(_))));
+		assert!(matches!(client.get_prevout(TXID, 0), Err(PrevoutError::Client(ClientError::Http(_)))));
+		let client = BitcoindClient::new("http://127.0.0.1:1", None);
+		assert!(matches!(client.estimate_fee(1), Err(ClientError::Http(_))));
+	}
+}
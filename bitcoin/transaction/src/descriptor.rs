@@ -0,0 +1,128 @@
+// Output script descriptors (BIP380) describe a script pub key in a readable form, e.g.
+// pk(<key>) or addr(<address>), followed by a checksum. bitcoind shows one for every script it
+// decodes. Only inferring descriptors from scripts is supported, not parsing them.
+
+use crate::interpreter;
+use crate::script::{parse_multisig, Script, ScriptPubKey, ScriptType};
+use crate::txio::Encodable;
+
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+
+fn polymod(mut c: u64, value: u64) -> u64 {
+	let top = c >> 35;
+	c = ((c & 0x7ffffffff) << 5) ^ value;
+	for (i, generator) in GENERATOR.iter().enumerate() {
+		if (top >> i) & 1 == 1 {
+			c ^= generator;
+		}
+	}
+	c
+}
+
+/// The 8 character checksum of a descriptor. None if it has a character descriptors can't
+/// contain.
+pub fn checksum(descriptor: &str) -> Option<String> {
+	let mut c = 1;
+	let mut class = 0;
+	let mut class_count = 0;
+	for ch in descriptor.chars() {
+		let position = INPUT_CHARSET.find(ch)? as u64;
+		// the lower 5 bits of each character go in directly, the upper bits in groups of 3
+		c = polymod(c, position & 31);
+		class = class * 3 + (position >> 5);
+		class_count += 1;
+		if class_count == 3 {
+			c = polymod(c, class);
+			class = 0;
+			class_count = 0;
+		}
+	}
+	if class_count > 0 {
+		c = polymod(c, class);
+	}
+	for _ in 0..8 {
+		c = polymod(c, 0);
+	}
+	c ^= 1;
+
+	let checksum = (0..8).map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char).collect();
+	Some(checksum)
+}
+
+/// The descriptor bitcoind infers for a script pub key it has no keys or scripts for, with its
+/// checksum: pk() and multi() for bare keys, rawtr() for taproot outputs, addr() for anything
+/// else with an address and raw() for the rest.
+pub fn infer(script_pub_key: &ScriptPubKey) -> String {
+	let descriptor = infer_without_checksum(script_pub_key);
+	let checksum = checksum(&descriptor).expect("inferred descriptors only contain valid characters");
+	format!("{}#{}", descriptor, checksum)
+}
+
+fn infer_without_checksum(script_pub_key: &ScriptPubKey) -> String {
+	let script = &script_pub_key.script;
+	match script_pub_key.get_type() {
+		ScriptType::P2PK if is_valid_pubkey(&script[1..script.len() - 1]) => {
+			return format!("pk({})", script[1..script.len() - 1].encode_hex_be());
+		}
+		ScriptType::Multisig => {
+			let (required, pubkeys) = parse_multisig(script).expect("multisig scripts can be parsed");
+			if pubkeys.iter().all(|key| is_valid_pubkey(key)) {
+				let keys: Vec<String> = pubkeys.iter().map(|key| key.encode_hex_be()).collect();
+				return format!("multi({},{})", required, keys.join(","));
+			}
+		}
+		ScriptType::P2TR => {
+			let (_, output_key) = interpreter::witness_program(script).expect("taproot outputs are witness programs");
+			if secp256k1::XOnlyPublicKey::from_slice(output_key).is_ok() {
+				return format!("rawtr({})", output_key.encode_hex_be());
+			}
+		}
+		_ => (),
+	}
+
+	match script_pub_key.get_address() {
+		Some(address) => format!("addr({})", address),
+		None => format!("raw({})", script.encode_hex_be()),
+	}
+}
+
+/// Same as Core's CPubKey::IsValid, only the size and first byte are checked.
+fn is_valid_pubkey(key: &[u8]) -> bool {
+	match key.first() {
+		Some(2) | Some(3) => key.len() == 33,
+		Some(4) | Some(6) | Some(7) => key.len() == 65,
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::script::{Script, ScriptPubKey};
+	use super::{checksum, infer};
+
+	#[test]
+	fn descriptor_checksums() {
+		// BIP380 test vectors
+		assert_eq!(checksum("raw(deadbeef)").as_deref(), Some("89f8spxm"));
+		assert_eq!(checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").as_deref(), Some("02wpgw69"));
+		assert_eq!(checksum("raw(deadbeef)\u{e9}"), None);
+	}
+
+	#[test]
+	fn infer_descriptors() {
+		let infer = |hex: &str| infer(&ScriptPubKey::from_str(hex).unwrap());
+
+		let p2pkh = infer("76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac");
+		assert!(p2pkh.starts_with("addr(1DVNBsH8KkW4r8voRwChVj68Nnyy1JXY5F)#"), "{}", p2pkh);
+		let p2pk = infer("2103a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bdac");
+		assert!(p2pk.starts_with("pk(03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)#"), "{}", p2pk);
+		let multisig = infer("5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae");
+		assert!(multisig.starts_with("multi(1,022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e,03a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c0)#"), "{}", multisig);
+		let p2tr = infer("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+		assert!(p2tr.starts_with("rawtr(a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c)#"), "{}", p2tr);
+		let null_data = infer("6a0568656c6c6f");
+		assert_eq!(null_data, format!("raw(6a0568656c6c6f)#{}", checksum("raw(6a0568656c6c6f)").unwrap()));
+	}
+}
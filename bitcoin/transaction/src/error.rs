@@ -0,0 +1,55 @@
+// Errors of the decoders. Raw transactions, scripts and blocks come from outside so anything can
+// be in them, decoding returns these instead of panicking.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+	/// The data ended in the middle of a field.
+	UnexpectedEof,
+	/// A compact size that could have been encoded in fewer bytes.
+	NonCanonicalCompactSize,
+	/// A compact size larger than MAX_SIZE, no message can be that big.
+	OversizedCompactSize(u64),
+	/// Bytes left over after the end of the data.
+	TrailingBytes(usize),
+	/// A hex string of odd length or with a character that isn't hex.
+	BadHex,
+	/// A transaction without inputs.
+	NoInputs,
+	/// A transaction without outputs.
+	NoOutputs,
+	/// A segwit marker and flag but every witness is empty.
+	SuperfluousWitness,
+	/// Reading failed for another reason than running out of data.
+	Io(io::ErrorKind),
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DecodeError::UnexpectedEof => write!(f, "unexpected end of data"),
+			DecodeError::NonCanonicalCompactSize => write!(f, "non-canonical compact size"),
+			DecodeError::OversizedCompactSize(size) => write!(f, "compact size {} is too large", size),
+			DecodeError::TrailingBytes(n) => write!(f, "{} trailing bytes after the end of the data", n),
+			DecodeError::BadHex => write!(f, "invalid hex"),
+			DecodeError::NoInputs => write!(f, "transaction has no inputs"),
+			DecodeError::NoOutputs => write!(f, "transaction has no outputs"),
+			DecodeError::SuperfluousWitness => write!(f, "superfluous witness record"),
+			DecodeError::Io(kind) => write!(f, "read error: {}", kind),
+		}
+	}
+}
+
+impl Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+	fn from(e: io::Error) -> Self {
+		match e.kind() {
+			io::ErrorKind::UnexpectedEof => DecodeError::UnexpectedEof,
+			kind => DecodeError::Io(kind),
+		}
+	}
+}
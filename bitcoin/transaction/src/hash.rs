@@ -1,16 +1,57 @@
-
+use hmac::{Hmac, Mac};
 use ripemd::Ripemd160;
-use sha2::{Sha256, Digest};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512, Digest};
 
 // Note: hash of hex_string != hash of bytes. We need to hash at byte level.
 pub fn hash160(bytes: &[u8]) -> Vec<u8> {
 	let sha256 = Sha256::digest(bytes);
 	let hash160 = Ripemd160::digest(sha256);
-	return hash160.to_vec()
+	hash160.to_vec()
 }
 
 pub fn hash256(bytes: &[u8]) -> Vec<u8> {
 	let sha256 = Sha256::digest(bytes);
 	let hash256 = Sha256::digest(sha256);
-	return hash256.to_vec()
+	hash256.to_vec()
+}
+
+pub fn sha256(bytes: &[u8]) -> Vec<u8> {
+	Sha256::digest(bytes).to_vec()
+}
+
+pub fn ripemd160(bytes: &[u8]) -> Vec<u8> {
+	Ripemd160::digest(bytes).to_vec()
+}
+
+pub fn sha1(bytes: &[u8]) -> Vec<u8> {
+	Sha1::digest(bytes).to_vec()
+}
+
+/// BIP340 tagged hash, i.e. SHA256(SHA256(tag) || SHA256(tag) || bytes).
+pub fn tagged_hash(tag: &str, bytes: &[u8]) -> Vec<u8> {
+	let tag_hash = Sha256::digest(tag.as_bytes());
+	let mut hasher = Sha256::new();
+	hasher.update(tag_hash);
+	hasher.update(tag_hash);
+	hasher.update(bytes);
+	hasher.finalize().to_vec()
+}
+
+pub fn hmac_sha512(key: &[u8], bytes: &[u8]) -> Vec<u8> {
+	let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes keys of any length");
+	mac.update(bytes);
+	mac.finalize().into_bytes().to_vec()
+}
+
+/// PBKDF2 with HMAC-SHA512, only the first 64 byte block of output which is all BIP39 needs.
+pub fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+	let mac = Hmac::<Sha512>::new_from_slice(password).expect("HMAC takes keys of any length");
+	let mut u = mac.clone().chain_update(salt).chain_update(1u32.to_be_bytes()).finalize().into_bytes();
+	let mut block = u;
+	for _ in 1..iterations {
+		u = mac.clone().chain_update(u).finalize().into_bytes();
+		block.iter_mut().zip(u).for_each(|(b, u)| *b ^= u);
+	}
+	block.to_vec()
 }
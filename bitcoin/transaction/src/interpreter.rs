@@ -0,0 +1,1192 @@
+// Ported from Bitcoin Core's src/script/interpreter.cpp
+
+use std::error::Error;
+use std::fmt;
+use crate::opcodes::{self, all};
+use crate::script::{Script, ScriptBuilder, ScriptSig, ScriptPubKey};
+use crate::txio::Encodable;
+use crate::hash;
+
+/// Maximum size of a script in bytes.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Maximum number of bytes pushable to the stack.
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Maximum number of non-push operations per script.
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Maximum number of public keys per multisig.
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+/// Maximum number of elements on the main and alt stack combined.
+pub const MAX_STACK_SIZE: usize = 1000;
+
+/// Locktimes below this are block heights, above it they are unix timestamps.
+pub const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+/// If this bit is set in a CSV argument (or an input's sequence) the relative locktime is disabled.
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+/// If this bit is set the relative locktime is in units of 512 seconds, otherwise in blocks.
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: i64 = 1 << 22;
+/// The bits of a sequence that hold the relative locktime.
+pub const SEQUENCE_LOCKTIME_MASK: i64 = 0x0000ffff;
+
+/// Everything that can go wrong while executing a script. The names follow `ScriptError_t` in
+/// Bitcoin Core so that failures can be compared against `bitcoin-cli` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptError {
+	/// The script ended with a false (or no) value on the stack.
+	EvalFalse,
+	/// OP_RETURN was executed.
+	OpReturn,
+	/// The script is longer than MAX_SCRIPT_SIZE.
+	ScriptSize,
+	/// A push is larger than MAX_SCRIPT_ELEMENT_SIZE.
+	PushSize,
+	/// More than MAX_OPS_PER_SCRIPT non-push operations.
+	OpCount,
+	/// More than MAX_STACK_SIZE elements on the stacks.
+	StackSize,
+	/// Signature count of a multisig is negative or larger than the key count.
+	SigCount,
+	/// Public key count of a multisig is negative or larger than MAX_PUBKEYS_PER_MULTISIG.
+	PubkeyCount,
+	/// OP_VERIFY failed.
+	Verify,
+	/// OP_EQUALVERIFY failed.
+	EqualVerify,
+	/// OP_NUMEQUALVERIFY failed.
+	NumEqualVerify,
+	/// OP_CHECKSIGVERIFY failed.
+	CheckSigVerify,
+	/// OP_CHECKMULTISIGVERIFY failed.
+	CheckMultiSigVerify,
+	/// An opcode that isn't defined, or a push that runs past the end of the script.
+	BadOpcode,
+	/// One of the opcodes disabled in 2010 (OP_CAT, OP_MUL, ...).
+	DisabledOpcode,
+	/// Not enough elements on the stack for the operation.
+	InvalidStackOperation,
+	/// Not enough elements on the alt stack for the operation.
+	InvalidAltstackOperation,
+	/// OP_IF/OP_NOTIF/OP_ELSE/OP_ENDIF do not match up.
+	UnbalancedConditional,
+	/// A number on the stack is larger than allowed for the operation.
+	NumOverflow,
+	/// OP_CHECKLOCKTIMEVERIFY or OP_CHECKSEQUENCEVERIFY got a negative argument.
+	NegativeLocktime,
+	/// The transaction does not satisfy OP_CHECKLOCKTIMEVERIFY or OP_CHECKSEQUENCEVERIFY.
+	UnsatisfiedLocktime,
+	/// The script sig of a P2SH spend contains something other than pushes.
+	SigPushOnly,
+	/// A witness script must leave exactly one element on the stack.
+	CleanStack,
+	/// A v0 witness program that is neither 20 (P2WPKH) nor 32 (P2WSH) bytes.
+	WitnessProgramWrongLength,
+	/// A P2WSH spend with an empty witness.
+	WitnessProgramWitnessEmpty,
+	/// The witness script doesn't hash to the program, or a P2WPKH witness isn't two elements.
+	WitnessProgramMismatch,
+	/// A native witness program spent with a non-empty script sig.
+	WitnessMalleated,
+	/// The script sig of a P2SH wrapped witness program must only push the program.
+	WitnessMalleatedP2SH,
+	/// A witness was provided for an input that doesn't spend a witness program.
+	WitnessUnexpected,
+}
+
+impl fmt::Display for ScriptError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let s = match *self {
+			ScriptError::EvalFalse => "Script evaluated without error but finished with a false/empty top stack element",
+			ScriptError::OpReturn => "OP_RETURN was encountered",
+			ScriptError::ScriptSize => "Script is too big",
+			ScriptError::PushSize => "Push value size limit exceeded",
+			ScriptError::OpCount => "Operation limit exceeded",
+			ScriptError::StackSize => "Stack size limit exceeded",
+			ScriptError::SigCount => "Signature count negative or greater than pubkey count",
+			ScriptError::PubkeyCount => "Pubkey count negative or limit exceeded",
+			ScriptError::Verify => "Script failed an OP_VERIFY operation",
+			ScriptError::EqualVerify => "Script failed an OP_EQUALVERIFY operation",
+			ScriptError::NumEqualVerify => "Script failed an OP_NUMEQUALVERIFY operation",
+			ScriptError::CheckSigVerify => "Script failed an OP_CHECKSIGVERIFY operation",
+			ScriptError::CheckMultiSigVerify => "Script failed an OP_CHECKMULTISIGVERIFY operation",
+			ScriptError::BadOpcode => "Opcode missing or not understood",
+			ScriptError::DisabledOpcode => "Attempted to use a disabled opcode",
+			ScriptError::InvalidStackOperation => "Operation not valid with the current stack size",
+			ScriptError::InvalidAltstackOperation => "Operation not valid with the current altstack size",
+			ScriptError::UnbalancedConditional => "Invalid OP_IF construction",
+			ScriptError::NumOverflow => "Script number overflow",
+			ScriptError::NegativeLocktime => "Negative locktime",
+			ScriptError::UnsatisfiedLocktime => "Locktime requirement not satisfied",
+			ScriptError::SigPushOnly => "Only push operators allowed in signatures",
+			ScriptError::CleanStack => "Stack size must be exactly one after execution",
+			ScriptError::WitnessProgramWrongLength => "Witness program has incorrect length",
+			ScriptError::WitnessProgramWitnessEmpty => "Witness program was passed an empty witness",
+			ScriptError::WitnessProgramMismatch => "Witness program hash mismatch",
+			ScriptError::WitnessMalleated => "Witness requires empty scriptSig",
+			ScriptError::WitnessMalleatedP2SH => "Witness requires only-redeemscript scriptSig",
+			ScriptError::WitnessUnexpected => "Witness provided for non-witness script",
+		};
+		f.write_str(s)
+	}
+}
+
+/// The script that was running when an error occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+	ScriptSig,
+	ScriptPubKey,
+	/// The serialized script pushed by the script sig of a P2SH spend.
+	RedeemScript,
+	/// The witness of a segwit spend, run against the P2WPKH script code or the witness script.
+	Witness,
+}
+
+impl fmt::Display for Stage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Stage::ScriptSig => write!(f, "script_sig"),
+			Stage::ScriptPubKey => write!(f, "script_pub_key"),
+			Stage::RedeemScript => write!(f, "redeem_script"),
+			Stage::Witness => write!(f, "witness"),
+		}
+	}
+}
+
+/// A failed script execution. Points at the script, the byte offset and the opcode that failed so
+/// that a spend can be debugged without re-running it by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecError {
+	pub stage: Stage,
+	/// Byte offset of the failing opcode inside the script. For failures which are not caused
+	/// by a single opcode (e.g. EvalFalse) this is the length of the script.
+	pub position: usize,
+	/// The failing opcode, if any.
+	pub opcode: Option<opcodes::All>,
+	pub error: ScriptError,
+}
+
+impl fmt::Display for ExecError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} failed at byte {}", self.stage, self.position)?;
+		if let Some(opcode) = self.opcode {
+			write!(f, " ({:?})", opcode)?;
+		}
+		write!(f, ": {}", self.error)
+	}
+}
+
+impl Error for ExecError {}
+
+/// Which signature hash algorithm a script is checked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigVersion {
+	/// Legacy scripts, including P2SH.
+	Base,
+	/// Scripts run from a v0 witness program (BIP143).
+	WitnessV0,
+}
+
+/// Signature and locktime checks depend on the transaction being spent, which the interpreter
+/// knows nothing about. Implementors provide that context. The defaults fail every check, same as
+/// Bitcoin Core's BaseSignatureChecker.
+pub trait SignatureChecker {
+	/// Check a DER signature (with the sighash byte appended) against a public key. `script_code`
+	/// is the part of the script that is committed to by the signature.
+	fn check_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &[u8], _sig_version: SigVersion) -> bool {
+		false
+	}
+	/// Check the argument of OP_CHECKLOCKTIMEVERIFY against the transaction.
+	fn check_lock_time(&self, _lock_time: i64) -> bool {
+		false
+	}
+	/// Check the argument of OP_CHECKSEQUENCEVERIFY against the input.
+	fn check_sequence(&self, _sequence: i64) -> bool {
+		false
+	}
+}
+
+/// A checker without a transaction. Every signature and locktime check fails.
+pub struct BaseSignatureChecker;
+
+impl SignatureChecker for BaseSignatureChecker {}
+
+/// Executes scripts on a main and alt stack. The main stack is kept after execution (or failure)
+/// so that it can be inspected.
+pub struct Interpreter<C: SignatureChecker> {
+	stack: Vec<Vec<u8>>,
+	alt_stack: Vec<Vec<u8>>,
+	checker: C,
+}
+
+impl<C: SignatureChecker> Interpreter<C> {
+	pub fn new(checker: C) -> Self {
+		Interpreter {
+			stack: Vec::new(),
+			alt_stack: Vec::new(),
+			checker,
+		}
+	}
+
+	/// The main stack, top element last.
+	#[cfg(test)]
+	pub fn stack(&self) -> &[Vec<u8>] {
+		&self.stack
+	}
+
+	/// Run a script sig followed by the script pub key it unlocks. If the script pub key is P2SH
+	/// the redeem script at the top of the script sig's stack is run as well.
+	pub fn verify(&mut self, script_sig: &ScriptSig, script_pub_key: &ScriptPubKey) -> Result<(), ExecError> {
+		self.verify_with_witness(script_sig, script_pub_key, &[])
+	}
+
+	/// Same as `verify` but segwit spends are supported as well. If the script pub key (or the
+	/// P2SH redeem script) is a v0 witness program, the witness is run against it. Witness
+	/// versions 1 and above are left for future soft forks and always succeed.
+	pub fn verify_with_witness(&mut self, script_sig: &ScriptSig, script_pub_key: &ScriptPubKey, witness: &[Vec<u8>]) -> Result<(), ExecError> {
+		let fail = |stage, error| ExecError { stage, position: 0, opcode: None, error };
+		self.stack.clear();
+
+		self.eval(&script_sig.script, Stage::ScriptSig)?;
+		let stack_copy = if script_pub_key.is_p2sh() { Some(self.stack.clone()) } else { None };
+
+		self.eval(&script_pub_key.script, Stage::ScriptPubKey)?;
+		self.check_top(Stage::ScriptPubKey, script_pub_key.script.len())?;
+
+		let mut had_witness = false;
+		if let Some((version, program)) = witness_program(&script_pub_key.script) {
+			had_witness = true;
+			if !script_sig.script.is_empty() {
+				return Err(fail(Stage::ScriptSig, ScriptError::WitnessMalleated));
+			}
+			self.verify_witness_program(witness, version, program)?;
+		}
+
+		if let Some(stack_copy) = stack_copy {
+			if !is_push_only(&script_sig.script) {
+				return Err(fail(Stage::ScriptSig, ScriptError::SigPushOnly));
+			}
+
+			// stack_copy can't be empty here, otherwise the script pub key would have failed
+			self.stack = stack_copy;
+			let redeem_script = self.stack.pop().expect("checked by script pub key");
+			self.eval(&redeem_script, Stage::RedeemScript)?;
+			self.check_top(Stage::RedeemScript, redeem_script.len())?;
+
+			if let Some((version, program)) = witness_program(&redeem_script) {
+				had_witness = true;
+				// The script sig has to be a single push of the redeem script, otherwise it could be
+				// changed without invalidating the witness.
+				let mut builder = ScriptBuilder::new();
+				builder.push_slice(&redeem_script);
+				if builder.into_script::<ScriptSig>().script != script_sig.script {
+					return Err(fail(Stage::ScriptSig, ScriptError::WitnessMalleatedP2SH));
+				}
+				self.verify_witness_program(witness, version, program)?;
+			}
+		}
+
+		if !had_witness && !witness.is_empty() {
+			return Err(fail(Stage::Witness, ScriptError::WitnessUnexpected));
+		}
+
+		Ok(())
+	}
+
+	fn verify_witness_program(&mut self, witness: &[Vec<u8>], version: u8, program: &[u8]) -> Result<(), ExecError> {
+		let fail = |error| ExecError { stage: Stage::Witness, position: 0, opcode: None, error };
+		if version != 0 {
+			// Taproot (and any later version) isn't checked here
+			self.stack = vec![vec![1]];
+			return Ok(());
+		}
+
+		let (script, stack) = if program.len() == 32 {
+			let (witness_script, stack) = witness.split_last().ok_or(fail(ScriptError::WitnessProgramWitnessEmpty))?;
+			if hash::sha256(witness_script) != program {
+				return Err(fail(ScriptError::WitnessProgramMismatch));
+			}
+			(witness_script.clone(), stack.to_vec())
+		} else if program.len() == 20 {
+			if witness.len() != 2 {
+				return Err(fail(ScriptError::WitnessProgramMismatch));
+			}
+			let mut builder = ScriptBuilder::new();
+			builder.push_opcode(all::OP_DUP);
+			builder.push_opcode(all::OP_HASH160);
+			builder.push_slice(program);
+			builder.push_opcode(all::OP_EQUALVERIFY);
+			builder.push_opcode(all::OP_CHECKSIG);
+			(builder.into_script::<ScriptSig>().script.to_vec(), witness.to_vec())
+		} else {
+			return Err(fail(ScriptError::WitnessProgramWrongLength));
+		};
+
+		if stack.iter().any(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+			return Err(fail(ScriptError::PushSize));
+		}
+		self.stack = stack;
+		self.execute(&script, Stage::Witness, SigVersion::WitnessV0)?;
+		// Witness scripts implicitly require a clean stack
+		if self.stack.len() != 1 {
+			return Err(ExecError { stage: Stage::Witness, position: script.len(), opcode: None, error: ScriptError::CleanStack });
+		}
+		self.check_top(Stage::Witness, script.len())
+	}
+
+	fn check_top(&self, stage: Stage, position: usize) -> Result<(), ExecError> {
+		match self.stack.last() {
+			Some(top) if cast_to_bool(top) => Ok(()),
+			_ => Err(ExecError { stage, position, opcode: None, error: ScriptError::EvalFalse }),
+		}
+	}
+
+	/// Execute a single legacy script on the current stack.
+	pub fn eval(&mut self, script: &[u8], stage: Stage) -> Result<(), ExecError> {
+		self.execute(script, stage, SigVersion::Base)
+	}
+
+	fn execute(&mut self, script: &[u8], stage: Stage, sig_version: SigVersion) -> Result<(), ExecError> {
+		if script.len() > MAX_SCRIPT_SIZE {
+			return Err(ExecError { stage, position: 0, opcode: None, error: ScriptError::ScriptSize });
+		}
+
+		let mut state = ExecState {
+			script,
+			exec: Vec::new(),
+			op_count: 0,
+			code_separator: 0,
+			sig_version,
+		};
+		self.alt_stack.clear();
+
+		let mut pc = 0;
+		while pc < script.len() {
+			let position = pc;
+			let opcode = opcodes::All::from(script[pc]);
+			let fail = |error| ExecError { stage, position, opcode: Some(opcode), error };
+
+			let (data, next) = read_instruction(script, pc).map_err(fail)?;
+			pc = next;
+			self.step(&mut state, opcode, data, pc).map_err(fail)?;
+
+			if self.stack.len() + self.alt_stack.len() > MAX_STACK_SIZE {
+				return Err(fail(ScriptError::StackSize));
+			}
+		}
+
+		if !state.exec.is_empty() {
+			return Err(ExecError { stage, position: script.len(), opcode: None, error: ScriptError::UnbalancedConditional });
+		}
+		Ok(())
+	}
+
+	fn step(&mut self, state: &mut ExecState, opcode: opcodes::All, data: Option<&[u8]>, pc: usize) -> Result<(), ScriptError> {
+		let executing = state.exec.iter().all(|b| *b);
+
+		if let Some(data) = data {
+			if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+				return Err(ScriptError::PushSize);
+			}
+		}
+
+		if opcode.code > all::OP_PUSHNUM_16.code {
+			state.op_count += 1;
+			if state.op_count > MAX_OPS_PER_SCRIPT {
+				return Err(ScriptError::OpCount);
+			}
+		}
+
+		if is_disabled(opcode) {
+			return Err(ScriptError::DisabledOpcode);
+		}
+
+		if let Some(data) = data {
+			if executing {
+				self.stack.push(data.to_vec());
+			}
+			return Ok(());
+		}
+
+		// Conditionals are processed even in an unexecuted branch so that nesting is tracked.
+		if !executing && !(all::OP_IF.code..=all::OP_ENDIF.code).contains(&opcode.code) {
+			return Ok(());
+		}
+
+		match opcode {
+			all::OP_PUSHNUM_NEG1 => self.stack.push(encode_num(-1)),
+			opcodes::All { code } if (all::OP_PUSHNUM_1.code..=all::OP_PUSHNUM_16.code).contains(&code) => {
+				self.stack.push(encode_num((code - all::OP_PUSHNUM_1.code + 1) as i64));
+			}
+
+			all::OP_NOP | all::OP_NOP1 | all::OP_NOP4 | all::OP_NOP5 | all::OP_NOP6 | all::OP_NOP7
+				| all::OP_NOP8 | all::OP_NOP9 | all::OP_NOP10 => {}
+
+			all::OP_CLTV => {
+				let lock_time = decode_num(self.top(1)?, 5)?;
+				if lock_time < 0 {
+					return Err(ScriptError::NegativeLocktime);
+				}
+				if !self.checker.check_lock_time(lock_time) {
+					return Err(ScriptError::UnsatisfiedLocktime);
+				}
+			}
+			all::OP_CSV => {
+				let sequence = decode_num(self.top(1)?, 5)?;
+				if sequence < 0 {
+					return Err(ScriptError::NegativeLocktime);
+				}
+				if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0 && !self.checker.check_sequence(sequence) {
+					return Err(ScriptError::UnsatisfiedLocktime);
+				}
+			}
+
+			all::OP_IF | all::OP_NOTIF => {
+				let mut value = false;
+				if executing {
+					let top = self.pop().map_err(|_| ScriptError::UnbalancedConditional)?;
+					value = cast_to_bool(&top);
+					if opcode == all::OP_NOTIF {
+						value = !value;
+					}
+				}
+				state.exec.push(value);
+			}
+			all::OP_ELSE => {
+				let last = state.exec.last_mut().ok_or(ScriptError::UnbalancedConditional)?;
+				*last = !*last;
+			}
+			all::OP_ENDIF => {
+				state.exec.pop().ok_or(ScriptError::UnbalancedConditional)?;
+			}
+			all::OP_VERIFY => {
+				if !cast_to_bool(&self.pop()?) {
+					return Err(ScriptError::Verify);
+				}
+			}
+			all::OP_RETURN => return Err(ScriptError::OpReturn),
+
+			// Stack operations
+			all::OP_TOALTSTACK => {
+				let top = self.pop()?;
+				self.alt_stack.push(top);
+			}
+			all::OP_FROMALTSTACK => {
+				let top = self.alt_stack.pop().ok_or(ScriptError::InvalidAltstackOperation)?;
+				self.stack.push(top);
+			}
+			all::OP_2DROP => {
+				self.pop()?;
+				self.pop()?;
+			}
+			all::OP_2DUP => {
+				let a = self.top(2)?.to_vec();
+				let b = self.top(1)?.to_vec();
+				self.stack.push(a);
+				self.stack.push(b);
+			}
+			all::OP_3DUP => {
+				let a = self.top(3)?.to_vec();
+				let b = self.top(2)?.to_vec();
+				let c = self.top(1)?.to_vec();
+				self.stack.push(a);
+				self.stack.push(b);
+				self.stack.push(c);
+			}
+			all::OP_2OVER => {
+				let a = self.top(4)?.to_vec();
+				let b = self.top(3)?.to_vec();
+				self.stack.push(a);
+				self.stack.push(b);
+			}
+			all::OP_2ROT => {
+				self.top(6)?;
+				let len = self.stack.len();
+				let moved: Vec<Vec<u8>> = self.stack.drain(len - 6..len - 4).collect();
+				self.stack.extend(moved);
+			}
+			all::OP_2SWAP => {
+				self.top(4)?;
+				let len = self.stack.len();
+				self.stack.swap(len - 4, len - 2);
+				self.stack.swap(len - 3, len - 1);
+			}
+			all::OP_IFDUP => {
+				let top = self.top(1)?.to_vec();
+				if cast_to_bool(&top) {
+					self.stack.push(top);
+				}
+			}
+			all::OP_DEPTH => self.stack.push(encode_num(self.stack.len() as i64)),
+			all::OP_DROP => {
+				self.pop()?;
+			}
+			all::OP_DUP => {
+				let top = self.top(1)?.to_vec();
+				self.stack.push(top);
+			}
+			all::OP_NIP => {
+				self.top(2)?;
+				let len = self.stack.len();
+				self.stack.remove(len - 2);
+			}
+			all::OP_OVER => {
+				let second = self.top(2)?.to_vec();
+				self.stack.push(second);
+			}
+			all::OP_PICK | all::OP_ROLL => {
+				let n = decode_num(self.top(1)?, 4)?;
+				self.pop()?;
+				if n < 0 || n as usize >= self.stack.len() {
+					return Err(ScriptError::InvalidStackOperation);
+				}
+				let index = self.stack.len() - 1 - n as usize;
+				let item = if opcode == all::OP_ROLL {
+					self.stack.remove(index)
+				} else {
+					self.stack[index].clone()
+				};
+				self.stack.push(item);
+			}
+			all::OP_ROT => {
+				self.top(3)?;
+				let len = self.stack.len();
+				let item = self.stack.remove(len - 3);
+				self.stack.push(item);
+			}
+			all::OP_SWAP => {
+				self.top(2)?;
+				let len = self.stack.len();
+				self.stack.swap(len - 2, len - 1);
+			}
+			all::OP_TUCK => {
+				let top = self.top(1)?.to_vec();
+				self.top(2)?;
+				let len = self.stack.len();
+				self.stack.insert(len - 2, top);
+			}
+			all::OP_SIZE => {
+				let size = self.top(1)?.len();
+				self.stack.push(encode_num(size as i64));
+			}
+
+			// Bitwise logic
+			all::OP_EQUAL | all::OP_EQUALVERIFY => {
+				let a = self.pop2()?;
+				let equal = a.0 == a.1;
+				if opcode == all::OP_EQUALVERIFY {
+					if !equal {
+						return Err(ScriptError::EqualVerify);
+					}
+				} else {
+					self.stack.push(encode_bool(equal));
+				}
+			}
+
+			// Arithmetic
+			all::OP_1ADD | all::OP_1SUB | all::OP_NEGATE | all::OP_ABS | all::OP_NOT | all::OP_0NOTEQUAL => {
+				let n = decode_num(self.top(1)?, 4)?;
+				self.pop()?;
+				let result = match opcode {
+					all::OP_1ADD => n + 1,
+					all::OP_1SUB => n - 1,
+					all::OP_NEGATE => -n,
+					all::OP_ABS => n.abs(),
+					all::OP_NOT => (n == 0) as i64,
+					_ => (n != 0) as i64,
+				};
+				self.stack.push(encode_num(result));
+			}
+			all::OP_ADD | all::OP_SUB | all::OP_BOOLAND | all::OP_BOOLOR | all::OP_NUMEQUAL
+				| all::OP_NUMEQUALVERIFY | all::OP_NUMNOTEQUAL | all::OP_LESSTHAN | all::OP_GREATERTHAN
+				| all::OP_LESSTHANOREQUAL | all::OP_GREATERTHANOREQUAL | all::OP_MIN | all::OP_MAX => {
+				let a = decode_num(self.top(2)?, 4)?;
+				let b = decode_num(self.top(1)?, 4)?;
+				self.pop2()?;
+				let result = match opcode {
+					all::OP_ADD => a + b,
+					all::OP_SUB => a - b,
+					all::OP_BOOLAND => (a != 0 && b != 0) as i64,
+					all::OP_BOOLOR => (a != 0 || b != 0) as i64,
+					all::OP_NUMEQUAL | all::OP_NUMEQUALVERIFY => (a == b) as i64,
+					all::OP_NUMNOTEQUAL => (a != b) as i64,
+					all::OP_LESSTHAN => (a < b) as i64,
+					all::OP_GREATERTHAN => (a > b) as i64,
+					all::OP_LESSTHANOREQUAL => (a <= b) as i64,
+					all::OP_GREATERTHANOREQUAL => (a >= b) as i64,
+					all::OP_MIN => a.min(b),
+					_ => a.max(b),
+				};
+				if opcode == all::OP_NUMEQUALVERIFY {
+					if result == 0 {
+						return Err(ScriptError::NumEqualVerify);
+					}
+				} else {
+					self.stack.push(encode_num(result));
+				}
+			}
+			all::OP_WITHIN => {
+				let x = decode_num(self.top(3)?, 4)?;
+				let min = decode_num(self.top(2)?, 4)?;
+				let max = decode_num(self.top(1)?, 4)?;
+				self.pop2()?;
+				self.pop()?;
+				self.stack.push(encode_bool(min <= x && x < max));
+			}
+
+			// Crypto
+			all::OP_RIPEMD160 | all::OP_SHA1 | all::OP_SHA256 | all::OP_HASH160 | all::OP_HASH256 => {
+				let top = self.pop()?;
+				let digest = match opcode {
+					all::OP_RIPEMD160 => hash::ripemd160(&top),
+					all::OP_SHA1 => hash::sha1(&top),
+					all::OP_SHA256 => hash::sha256(&top),
+					all::OP_HASH160 => hash::hash160(&top),
+					_ => hash::hash256(&top),
+				};
+				self.stack.push(digest);
+			}
+			all::OP_CODESEPARATOR => state.code_separator = pc,
+			all::OP_CHECKSIG | all::OP_CHECKSIGVERIFY => {
+				let (sig, pubkey) = self.pop2()?;
+				let mut script_code = state.script[state.code_separator..].to_vec();
+				// Signatures can't sign themselves. Segwit fixed this by not including the
+				// signature in the script code in the first place.
+				if state.sig_version == SigVersion::Base {
+					script_code = find_and_delete(&script_code, &sig);
+				}
+				let success = self.checker.check_sig(&sig, &pubkey, &script_code, state.sig_version);
+				if opcode == all::OP_CHECKSIGVERIFY {
+					if !success {
+						return Err(ScriptError::CheckSigVerify);
+					}
+				} else {
+					self.stack.push(encode_bool(success));
+				}
+			}
+			all::OP_CHECKMULTISIG | all::OP_CHECKMULTISIGVERIFY => {
+				let success = self.check_multisig(state)?;
+				if opcode == all::OP_CHECKMULTISIGVERIFY {
+					if !success {
+						return Err(ScriptError::CheckMultiSigVerify);
+					}
+				} else {
+					self.stack.push(encode_bool(success));
+				}
+			}
+
+			_ => return Err(ScriptError::BadOpcode),
+		}
+
+		Ok(())
+	}
+
+	/// Stack layout (top last): <dummy> <sig_1> ... <sig_m> <m> <pubkey_1> ... <pubkey_n> <n>
+	fn check_multisig(&mut self, state: &mut ExecState) -> Result<bool, ScriptError> {
+		let mut i = 1;
+		let mut keys_count = decode_num(self.top(i)?, 4)?;
+		if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&keys_count) {
+			return Err(ScriptError::PubkeyCount);
+		}
+		state.op_count += keys_count as usize;
+		if state.op_count > MAX_OPS_PER_SCRIPT {
+			return Err(ScriptError::OpCount);
+		}
+		i += 1;
+		let mut key_index = i;
+		i += keys_count as usize;
+
+		let mut sigs_count = decode_num(self.top(i)?, 4)?;
+		if sigs_count < 0 || sigs_count > keys_count {
+			return Err(ScriptError::SigCount);
+		}
+		i += 1;
+		let mut sig_index = i;
+		i += sigs_count as usize;
+		// the dummy element consumed because of the off-by-one bug in the original implementation
+		self.top(i)?;
+
+		let mut script_code = state.script[state.code_separator..].to_vec();
+		if state.sig_version == SigVersion::Base {
+			for k in 0..sigs_count as usize {
+				script_code = find_and_delete(&script_code, self.top(sig_index + k)?);
+			}
+		}
+
+		let mut success = true;
+		while success && sigs_count > 0 {
+			let sig = self.top(sig_index)?;
+			let pubkey = self.top(key_index)?;
+			if self.checker.check_sig(sig, pubkey, &script_code, state.sig_version) {
+				sig_index += 1;
+				sigs_count -= 1;
+			}
+			key_index += 1;
+			keys_count -= 1;
+
+			// There are more signatures left than keys, so it can't succeed
+			if sigs_count > keys_count {
+				success = false;
+			}
+		}
+
+		let len = self.stack.len();
+		self.stack.truncate(len - i);
+		Ok(success)
+	}
+
+	/// Get the n-th element from the top of the stack (1 is the top).
+	fn top(&self, n: usize) -> Result<&[u8], ScriptError> {
+		if n == 0 || n > self.stack.len() {
+			return Err(ScriptError::InvalidStackOperation);
+		}
+		Ok(&self.stack[self.stack.len() - n])
+	}
+
+	fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+		self.stack.pop().ok_or(ScriptError::InvalidStackOperation)
+	}
+
+	/// Pop the top two elements and return them in stack order i.e. (second, top).
+	fn pop2(&mut self) -> Result<(Vec<u8>, Vec<u8>), ScriptError> {
+		self.top(2)?;
+		let top = self.pop()?;
+		let second = self.pop()?;
+		Ok((second, top))
+	}
+}
+
+impl<C: SignatureChecker> fmt::Debug for Interpreter<C> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let stack: Vec<String> = self.stack.iter().map(|item| item.encode_hex_be()).collect();
+		f.debug_struct("Interpreter")
+			.field("stack", &stack)
+			.finish()
+	}
+}
+
+/// State of a single script execution.
+struct ExecState<'a> {
+	script: &'a [u8],
+	/// One entry per open OP_IF/OP_NOTIF, true if the branch is being executed.
+	exec: Vec<bool>,
+	op_count: usize,
+	/// Position right after the last executed OP_CODESEPARATOR.
+	code_separator: usize,
+	sig_version: SigVersion,
+}
+
+/// Read the instruction at `pc`. Returns the pushed data (None if the opcode doesn't push data)
+/// and the position of the next instruction.
+pub(crate) fn read_instruction(script: &[u8], pc: usize) -> Result<(Option<&[u8]>, usize), ScriptError> {
+	let code = script[pc];
+	let mut pc = pc + 1;
+
+	let len = if code <= all::OP_PUSHBYTES_75.code {
+		code as usize
+	} else if code == all::OP_PUSHDATA1.code || code == all::OP_PUSHDATA2.code || code == all::OP_PUSHDATA4.code {
+		let size = match code {
+			0x4c => 1,
+			0x4d => 2,
+			_ => 4,
+		};
+		if pc + size > script.len() {
+			return Err(ScriptError::BadOpcode);
+		}
+		let mut bytes = [0; 4];
+		bytes[..size].copy_from_slice(&script[pc..pc + size]);
+		pc += size;
+		u32::from_le_bytes(bytes) as usize
+	} else {
+		return Ok((None, pc));
+	};
+
+	if script.len() - pc < len {
+		return Err(ScriptError::BadOpcode);
+	}
+	Ok((Some(&script[pc..pc + len]), pc + len))
+}
+
+/// A witness program is a script pub key of a version opcode (OP_0 to OP_16) followed by a single
+/// push of 2 to 40 bytes. Returns the version and the pushed program.
+pub(crate) fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+	if script.len() < 4 || script.len() > 42 {
+		return None;
+	}
+	let version = match script[0] {
+		0 => 0,
+		code if (all::OP_PUSHNUM_1.code..=all::OP_PUSHNUM_16.code).contains(&code) => code - all::OP_PUSHNUM_1.code + 1,
+		_ => return None,
+	};
+	if script[1] as usize + 2 != script.len() {
+		return None;
+	}
+	Some((version, &script[2..]))
+}
+
+pub(crate) fn is_push_only(script: &[u8]) -> bool {
+	let mut pc = 0;
+	while pc < script.len() {
+		if script[pc] > all::OP_PUSHNUM_16.code {
+			return false;
+		}
+		match read_instruction(script, pc) {
+			Ok((_, next)) => pc = next,
+			Err(_) => return false,
+		}
+	}
+	true
+}
+
+fn is_disabled(opcode: opcodes::All) -> bool {
+	matches!(opcode,
+		all::OP_CAT | all::OP_SUBSTR | all::OP_LEFT | all::OP_RIGHT | all::OP_INVERT | all::OP_AND
+		| all::OP_OR | all::OP_XOR | all::OP_2MUL | all::OP_2DIV | all::OP_MUL | all::OP_DIV
+		| all::OP_MOD | all::OP_LSHIFT | all::OP_RSHIFT)
+}
+
+/// Remove every push of `sig` from the script. Pre-segwit signatures can't sign themselves so they
+/// are removed from the script code before hashing.
+pub fn find_and_delete(script: &[u8], sig: &[u8]) -> Vec<u8> {
+	let mut builder = ScriptBuilder::new();
+	builder.push_slice(sig);
+	let pattern = builder.into_script::<ScriptSig>().script;
+
+	let mut result = Vec::with_capacity(script.len());
+	let mut found = false;
+	let mut pc = 0;
+	let mut pc2 = 0;
+	loop {
+		result.extend_from_slice(&script[pc2..pc]);
+		while script.len() - pc >= pattern.len() && script[pc..].starts_with(&pattern) {
+			pc += pattern.len();
+			found = true;
+		}
+		pc2 = pc;
+
+		if pc >= script.len() {
+			break;
+		}
+		match read_instruction(script, pc) {
+			Ok((_, next)) => pc = next,
+			Err(_) => break,
+		}
+	}
+
+	if !found {
+		return script.to_vec();
+	}
+	result.extend_from_slice(&script[pc2..]);
+	result
+}
+
+/// Interpret a stack element as a boolean. Any non-zero value is true, except negative zero.
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+	for (i, b) in bytes.iter().enumerate() {
+		if *b != 0 {
+			// negative zero
+			return !(i == bytes.len() - 1 && *b == 0x80);
+		}
+	}
+	false
+}
+
+fn encode_bool(value: bool) -> Vec<u8> {
+	if value { vec![1] } else { vec![] }
+}
+
+/// Numbers on the stack are little-endian with the sign in the most significant bit of the last
+/// byte. Operands can be at most `max_len` bytes long but results may overflow that.
+pub fn decode_num(bytes: &[u8], max_len: usize) -> Result<i64, ScriptError> {
+	if bytes.len() > max_len {
+		return Err(ScriptError::NumOverflow);
+	}
+	if bytes.is_empty() {
+		return Ok(0);
+	}
+
+	let mut result: i64 = 0;
+	for (i, b) in bytes.iter().enumerate() {
+		result |= (*b as i64) << (8 * i);
+	}
+
+	let last = bytes[bytes.len() - 1];
+	if last & 0x80 != 0 {
+		return Ok(-(result & !(0x80 << (8 * (bytes.len() - 1)))));
+	}
+	Ok(result)
+}
+
+pub fn encode_num(n: i64) -> Vec<u8> {
+	let mut result = Vec::new();
+	if n == 0 {
+		return result;
+	}
+
+	let negative = n < 0;
+	let mut abs = n.unsigned_abs();
+	while abs > 0 {
+		result.push((abs & 0xff) as u8);
+		abs >>= 8;
+	}
+
+	// If the most significant byte is >= 0x80 and the value is positive, push a new zero byte to
+	// make sure the result is positive. Otherwise set the sign bit on the existing byte.
+	let last = result.len() - 1;
+	if result[last] & 0x80 != 0 {
+		result.push(if negative { 0x80 } else { 0 });
+	} else if negative {
+		result[last] |= 0x80;
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::hash;
+	use crate::opcodes::all;
+	use crate::script::{Script, ScriptBuilder, ScriptSig, ScriptPubKey};
+	use crate::txio::{Decodable, Encodable};
+	use super::{Interpreter, BaseSignatureChecker, SignatureChecker, SigVersion, ScriptError, Stage, decode_num, encode_num, find_and_delete};
+
+	/// Accepts any non-empty signature so that script logic can be tested without keys.
+	struct AcceptAll;
+
+	impl SignatureChecker for AcceptAll {
+		fn check_sig(&self, sig: &[u8], _pubkey: &[u8], _script_code: &[u8], _sig_version: SigVersion) -> bool {
+			!sig.is_empty()
+		}
+	}
+
+	fn asm<T: Script>(s: &str) -> T {
+		let mut builder = ScriptBuilder::new();
+		for token in s.split(' ').filter(|t| !t.is_empty()) {
+			builder.push(token).expect("valid token");
+		}
+		builder.into_script()
+	}
+
+	#[test]
+	fn script_num_round_trip() {
+		for n in [0, 1, -1, 127, 128, -128, 255, 256, 0x7fffffff, -0x7fffffff] {
+			assert_eq!(decode_num(&encode_num(n), 4).unwrap(), n);
+		}
+		assert_eq!(encode_num(128), vec![0x80, 0x00]);
+		assert_eq!(encode_num(-1), vec![0x81]);
+		assert_eq!(decode_num(&[0, 0, 0, 0, 1], 4), Err(ScriptError::NumOverflow));
+	}
+
+	#[test]
+	fn arithmetic() {
+		let script_sig: ScriptSig = asm("OP_2 OP_3");
+		let script_pub_key: ScriptPubKey = asm("OP_ADD OP_5 OP_EQUAL");
+		let mut interpreter = Interpreter::new(BaseSignatureChecker);
+		assert_eq!(interpreter.verify(&script_sig, &script_pub_key), Ok(()));
+		assert_eq!(interpreter.stack(), &[vec![1]]);
+	}
+
+	#[test]
+	fn hash_lock() {
+		let preimage = b"learning bitcoin".to_vec();
+		let mut builder = ScriptBuilder::new();
+		builder.push_opcode(all::OP_SHA256);
+		builder.push_slice(&hash::sha256(&preimage));
+		builder.push_opcode(all::OP_EQUAL);
+		let script_pub_key: ScriptPubKey = builder.into_script();
+
+		let mut builder = ScriptBuilder::new();
+		builder.push_slice(&preimage);
+		let script_sig: ScriptSig = builder.into_script();
+		assert_eq!(Interpreter::new(BaseSignatureChecker).verify(&script_sig, &script_pub_key), Ok(()));
+
+		let script_sig: ScriptSig = asm("deadbeef");
+		let err = Interpreter::new(BaseSignatureChecker).verify(&script_sig, &script_pub_key).unwrap_err();
+		assert_eq!(err.stage, Stage::ScriptPubKey);
+		assert_eq!(err.error, ScriptError::EvalFalse);
+		assert_eq!(err.opcode, None);
+	}
+
+	#[test]
+	fn failing_opcode_and_position() {
+		let script_sig: ScriptSig = asm("OP_1 OP_2");
+		let script_pub_key: ScriptPubKey = asm("OP_1 OP_EQUALVERIFY OP_1");
+		let mut interpreter = Interpreter::new(BaseSignatureChecker);
+		let err = interpreter.verify(&script_sig, &script_pub_key).unwrap_err();
+		assert_eq!(err.stage, Stage::ScriptPubKey);
+		assert_eq!(err.position, 1);
+		assert_eq!(err.opcode, Some(all::OP_EQUALVERIFY));
+		assert_eq!(err.error, ScriptError::EqualVerify);
+		assert_eq!(err.to_string(), "script_pub_key failed at byte 1 (OP_EQUALVERIFY): \
+		Script failed an OP_EQUALVERIFY operation");
+		// stack is kept for inspection
+		assert_eq!(interpreter.stack(), &[vec![1]]);
+	}
+
+	#[test]
+	fn conditionals() {
+		let script_pub_key: ScriptPubKey = asm("OP_IF OP_2 OP_ELSE OP_3 OP_ENDIF OP_3 OP_EQUAL");
+		let mut interpreter = Interpreter::new(BaseSignatureChecker);
+		assert_eq!(interpreter.verify(&asm("OP_0"), &script_pub_key), Ok(()));
+		assert_eq!(interpreter.verify(&asm("OP_1"), &script_pub_key).unwrap_err().error, ScriptError::EvalFalse);
+
+		let err = interpreter.verify(&asm("OP_1"), &asm("OP_IF OP_1")).unwrap_err();
+		assert_eq!(err.error, ScriptError::UnbalancedConditional);
+		let err = interpreter.verify(&asm("OP_1"), &asm("OP_ENDIF")).unwrap_err();
+		assert_eq!(err.error, ScriptError::UnbalancedConditional);
+	}
+
+	#[test]
+	fn disabled_and_reserved_opcodes() {
+		// disabled opcodes fail even in an unexecuted branch
+		let err = Interpreter::new(BaseSignatureChecker)
+			.verify(&asm("OP_0"), &asm("OP_IF OP_CAT OP_ENDIF OP_1")).unwrap_err();
+		assert_eq!(err.error, ScriptError::DisabledOpcode);
+		assert_eq!(err.position, 1);
+
+		// reserved opcodes only fail when executed
+		assert_eq!(Interpreter::new(BaseSignatureChecker)
+			.verify(&asm("OP_0"), &asm("OP_IF OP_RESERVED OP_ENDIF OP_1")), Ok(()));
+		let err = Interpreter::new(BaseSignatureChecker)
+			.verify(&asm("OP_1"), &asm("OP_IF OP_RESERVED OP_ENDIF OP_1")).unwrap_err();
+		assert_eq!(err.error, ScriptError::BadOpcode);
+
+		let err = Interpreter::new(BaseSignatureChecker).verify(&asm("OP_1"), &asm("OP_RETURN")).unwrap_err();
+		assert_eq!(err.error, ScriptError::OpReturn);
+	}
+
+	#[test]
+	fn stack_operations() {
+		let script_pub_key: ScriptPubKey = asm("OP_TOALTSTACK OP_DUP OP_FROMALTSTACK OP_ROT OP_2 \
+		OP_PICK OP_NIP OP_SWAP OP_DROP OP_DEPTH OP_2 OP_NUMEQUALVERIFY OP_EQUAL");
+		let mut interpreter = Interpreter::new(BaseSignatureChecker);
+		assert_eq!(interpreter.verify(&asm("OP_7 OP_8"), &script_pub_key), Ok(()));
+
+		let err = interpreter.verify(&asm("OP_1"), &asm("OP_FROMALTSTACK")).unwrap_err();
+		assert_eq!(err.error, ScriptError::InvalidAltstackOperation);
+		let err = interpreter.verify(&asm(""), &asm("OP_DROP")).unwrap_err();
+		assert_eq!(err.error, ScriptError::InvalidStackOperation);
+	}
+
+	#[test]
+	fn p2pkh() {
+		let pubkey = "03fd9bc1e9803e739720e0f1c63e580a94656c7d0cab6cd083f0c0dfb221b90662";
+		let script_pub_key = ScriptPubKey::new_p2pkh(ScriptSig::from_str(pubkey).unwrap().script);
+		let script_sig: ScriptSig = asm(&format!("3044022045c7199ffc8069a498135b7bb2678da16e8b5d49\
+		455b4a7ace755928c9339c7a022051cbf72024cf273444640f7b993b2bf3d329124b03e6744edaed5158a30e29b801 {}", pubkey));
+
+		assert_eq!(Interpreter::new(AcceptAll).verify(&script_sig, &script_pub_key), Ok(()));
+
+		// without a transaction the signature can't be valid
+		let err = Interpreter::new(BaseSignatureChecker).verify(&script_sig, &script_pub_key).unwrap_err();
+		assert_eq!(err.error, ScriptError::EvalFalse);
+
+		// wrong public key
+		let script_sig: ScriptSig = asm("3044 0211db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2");
+		let err = Interpreter::new(AcceptAll).verify(&script_sig, &script_pub_key).unwrap_err();
+		assert_eq!(err.opcode, Some(all::OP_EQUALVERIFY));
+		assert_eq!(err.position, 23);
+	}
+
+	#[test]
+	fn p2sh_multisig() {
+		let redeem_script: ScriptSig = asm("OP_2 022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e \
+		03a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c0 OP_2 OP_CHECKMULTISIG");
+		let script_pub_key = ScriptPubKey::new_p2sh(redeem_script.script.clone());
+
+		let script_sig: ScriptSig = asm(&format!("OP_0 3001 3002 {}", redeem_script.script.encode_hex_be()));
+		let mut interpreter = Interpreter::new(AcceptAll);
+		assert_eq!(interpreter.verify(&script_sig, &script_pub_key), Ok(()));
+		assert_eq!(interpreter.stack(), &[vec![1]]);
+
+		// one signature short
+		let script_sig: ScriptSig = asm(&format!("OP_0 3001 OP_0 {}", redeem_script.script.encode_hex_be()));
+		let err = Interpreter::new(AcceptAll).verify(&script_sig, &script_pub_key).unwrap_err();
+		assert_eq!(err.stage, Stage::RedeemScript);
+		assert_eq!(err.error, ScriptError::EvalFalse);
+
+		// the script sig of a P2SH spend has to be push only
+		let script_sig: ScriptSig = asm(&format!("OP_0 3001 3002 OP_NOP {}", redeem_script.script.encode_hex_be()));
+		let err = Interpreter::new(AcceptAll).verify(&script_sig, &script_pub_key).unwrap_err();
+		assert_eq!(err.stage, Stage::ScriptSig);
+		assert_eq!(err.error, ScriptError::SigPushOnly);
+	}
+
+	#[test]
+	fn p2wsh() {
+		let witness_script: ScriptSig = asm("OP_ADD OP_5 OP_EQUAL");
+		let program = hash::sha256(&witness_script.script);
+		let script_pub_key: ScriptPubKey = asm(&format!("OP_0 {}", program.encode_hex_be()));
+		let witness = vec![vec![2], vec![3], witness_script.script.to_vec()];
+
+		let mut interpreter = Interpreter::new(BaseSignatureChecker);
+		assert_eq!(interpreter.verify_with_witness(&asm(""), &script_pub_key, &witness), Ok(()));
+		assert_eq!(interpreter.stack(), &[vec![1]]);
+
+		// the witness script doesn't hash to the program
+		let witness = vec![vec![2], vec![3], asm::<ScriptSig>("OP_ADD OP_5 OP_EQUAL OP_NOP").script.to_vec()];
+		let err = Interpreter::new(BaseSignatureChecker).verify_with_witness(&asm(""), &script_pub_key, &witness).unwrap_err();
+		assert_eq!(err.stage, Stage::Witness);
+		assert_eq!(err.error, ScriptError::WitnessProgramMismatch);
+
+		// witness scripts need a clean stack
+		let witness = vec![vec![1], vec![2], vec![3], witness_script.script.to_vec()];
+		let err = Interpreter::new(BaseSignatureChecker).verify_with_witness(&asm(""), &script_pub_key, &witness).unwrap_err();
+		assert_eq!(err.error, ScriptError::CleanStack);
+
+		let err = Interpreter::new(BaseSignatureChecker).verify_with_witness(&asm(""), &script_pub_key, &[]).unwrap_err();
+		assert_eq!(err.error, ScriptError::WitnessProgramWitnessEmpty);
+
+		// native witness spends have an empty script sig
+		let err = Interpreter::new(BaseSignatureChecker).verify_with_witness(&asm("OP_1"), &script_pub_key, &witness).unwrap_err();
+		assert_eq!(err.stage, Stage::ScriptSig);
+		assert_eq!(err.error, ScriptError::WitnessMalleated);
+	}
+
+	#[test]
+	fn p2sh_p2wpkh() {
+		let pubkey = "03fd9bc1e9803e739720e0f1c63e580a94656c7d0cab6cd083f0c0dfb221b90662".decode_hex_be().unwrap();
+		let redeem_script: ScriptSig = asm(&format!("OP_0 {}", hash::hash160(&pubkey).encode_hex_be()));
+		let script_pub_key = ScriptPubKey::new_p2sh(redeem_script.script.clone());
+		let mut builder = ScriptBuilder::new();
+		builder.push_slice(&redeem_script.script);
+		let script_sig: ScriptSig = builder.into_script();
+
+		let witness = vec![vec![0x30, 0x01], pubkey.to_vec()];
+		assert_eq!(Interpreter::new(AcceptAll).verify_with_witness(&script_sig, &script_pub_key, &witness), Ok(()));
+
+		// the wrong key
+		let mut wrong_key = pubkey.to_vec();
+		wrong_key[1] ^= 1;
+		let err = Interpreter::new(AcceptAll).verify_with_witness(&script_sig, &script_pub_key, &[vec![0x30, 0x01], wrong_key]).unwrap_err();
+		assert_eq!(err.stage, Stage::Witness);
+		assert_eq!(err.error, ScriptError::EqualVerify);
+
+		// the redeem script has to be pushed on its own
+		let script_sig: ScriptSig = asm(&format!("OP_1 {}", redeem_script.script.encode_hex_be()));
+		let err = Interpreter::new(AcceptAll).verify_with_witness(&script_sig, &script_pub_key, &witness).unwrap_err();
+		assert_eq!(err.error, ScriptError::WitnessMalleatedP2SH);
+	}
+
+	#[test]
+	fn unexpected_witness() {
+		let script_pub_key: ScriptPubKey = asm("OP_1");
+		let err = Interpreter::new(BaseSignatureChecker).verify_with_witness(&asm(""), &script_pub_key, &[vec![1]]).unwrap_err();
+		assert_eq!(err.stage, Stage::Witness);
+		assert_eq!(err.error, ScriptError::WitnessUnexpected);
+	}
+
+	#[test]
+	fn truncated_push() {
+		let script_pub_key = ScriptPubKey::from_str("4c05aabb").unwrap();
+		let err = Interpreter::new(BaseSignatureChecker).verify(&asm(""), &script_pub_key).unwrap_err();
+		assert_eq!(err.error, ScriptError::BadOpcode);
+		assert_eq!(err.opcode, Some(all::OP_PUSHDATA1));
+		assert_eq!(err.position, 0);
+	}
+
+	#[test]
+	fn find_and_delete_signature() {
+		let script = ScriptSig::from_str("02aabb5102aabbac").unwrap().script;
+		assert_eq!(find_and_delete(&script, &[0xaa, 0xbb]), vec![0x51, 0xac]);
+		// only removed at instruction boundaries
+		let script = ScriptSig::from_str("0302aabb").unwrap().script;
+		assert_eq!(find_and_delete(&script, &[0xaa, 0xbb]), script.to_vec());
+	}
+}
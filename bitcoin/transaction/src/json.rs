@@ -0,0 +1,441 @@
+// The JSON bitcoind returns for decoderawtransaction, decodescript and validateaddress, so the
+// output of the cli can be handled by the same tools. Amounts are in BTC like bitcoind's.
+
+use serde_json::{json, Map, Value};
+use crate::descriptor;
+use crate::interpreter::{self, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE};
+use crate::opcodes::all;
+use crate::script::{Script, ScriptPubKey, ScriptType};
+use crate::sighash::{SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_NONE, SIGHASH_SINGLE};
+use crate::transaction::Transaction;
+use crate::txio::{Decodable, Encodable};
+
+/// Names of the opcodes from OP_PUSHDATA1 to OP_CHECKSIGADD as Core writes them.
+const OP_NAMES: [&str; 111] = [
+	"OP_PUSHDATA1", "OP_PUSHDATA2", "OP_PUSHDATA4", "-1", "OP_RESERVED",
+	"1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16",
+	"OP_NOP", "OP_VER", "OP_IF", "OP_NOTIF", "OP_VERIF", "OP_VERNOTIF", "OP_ELSE", "OP_ENDIF",
+	"OP_VERIFY", "OP_RETURN", "OP_TOALTSTACK", "OP_FROMALTSTACK", "OP_2DROP", "OP_2DUP", "OP_3DUP",
+	"OP_2OVER", "OP_2ROT", "OP_2SWAP", "OP_IFDUP", "OP_DEPTH", "OP_DROP", "OP_DUP", "OP_NIP",
+	"OP_OVER", "OP_PICK", "OP_ROLL", "OP_ROT", "OP_SWAP", "OP_TUCK", "OP_CAT", "OP_SUBSTR",
+	"OP_LEFT", "OP_RIGHT", "OP_SIZE", "OP_INVERT", "OP_AND", "OP_OR", "OP_XOR", "OP_EQUAL",
+	"OP_EQUALVERIFY", "OP_RESERVED1", "OP_RESERVED2", "OP_1ADD", "OP_1SUB", "OP_2MUL", "OP_2DIV",
+	"OP_NEGATE", "OP_ABS", "OP_NOT", "OP_0NOTEQUAL", "OP_ADD", "OP_SUB", "OP_MUL", "OP_DIV",
+	"OP_MOD", "OP_LSHIFT", "OP_RSHIFT", "OP_BOOLAND", "OP_BOOLOR", "OP_NUMEQUAL",
+	"OP_NUMEQUALVERIFY", "OP_NUMNOTEQUAL", "OP_LESSTHAN", "OP_GREATERTHAN", "OP_LESSTHANOREQUAL",
+	"OP_GREATERTHANOREQUAL", "OP_MIN", "OP_MAX", "OP_WITHIN", "OP_RIPEMD160", "OP_SHA1",
+	"OP_SHA256", "OP_HASH160", "OP_HASH256", "OP_CODESEPARATOR", "OP_CHECKSIG",
+	"OP_CHECKSIGVERIFY", "OP_CHECKMULTISIG", "OP_CHECKMULTISIGVERIFY", "OP_NOP1",
+	"OP_CHECKLOCKTIMEVERIFY", "OP_CHECKSEQUENCEVERIFY", "OP_NOP4", "OP_NOP5", "OP_NOP6", "OP_NOP7",
+	"OP_NOP8", "OP_NOP9", "OP_NOP10", "OP_CHECKSIGADD",
+];
+
+fn op_name(code: u8) -> &'static str {
+	match code {
+		0xff => "OP_INVALIDOPCODE",
+		code if code >= all::OP_PUSHDATA1.into_u8() => OP_NAMES.get((code - all::OP_PUSHDATA1.into_u8()) as usize).copied().unwrap_or("OP_UNKNOWN"),
+		_ => "OP_UNKNOWN",
+	}
+}
+
+/// Core's asm: pushes of up to 4 bytes are shown as numbers and longer ones as hex. With
+/// `decode_sighash` a push that looks like a signature has its sighash type shown as [ALL] etc.,
+/// which bitcoind does for script sigs.
+pub fn script_to_asm(script: &[u8], decode_sighash: bool) -> String {
+	let unspendable = script.first() == Some(&all::OP_RETURN.into_u8()) || script.len() > MAX_SCRIPT_SIZE;
+	let mut asm = Vec::new();
+	let mut pc = 0;
+	while pc < script.len() {
+		let Ok((data, next)) = interpreter::read_instruction(script, pc) else {
+			asm.push("[error]".to_string());
+			break;
+		};
+		asm.push(match data {
+			Some(data) if data.len() <= 4 => {
+				interpreter::decode_num(data, 4).expect("at most 4 bytes").to_string()
+			}
+			Some(data) if decode_sighash && !unspendable && is_signature(data) => {
+				let (sighash_type, signature) = data.split_last().expect("signatures aren't empty");
+				format!("{}[{}]", signature.encode_hex_be(), sighash_name(*sighash_type))
+			}
+			Some(data) => data.encode_hex_be(),
+			None => op_name(script[pc]).to_string(),
+		});
+		pc = next;
+	}
+	asm.join(" ")
+}
+
+fn sighash_name(sighash_type: u8) -> &'static str {
+	let anyone_can_pay = sighash_type as u32 & SIGHASH_ANYONECANPAY != 0;
+	match (sighash_type as u32 & !SIGHASH_ANYONECANPAY, anyone_can_pay) {
+		(SIGHASH_ALL, false) => "ALL",
+		(SIGHASH_ALL, true) => "ALL|ANYONECANPAY",
+		(SIGHASH_NONE, false) => "NONE",
+		(SIGHASH_NONE, true) => "NONE|ANYONECANPAY",
+		(SIGHASH_SINGLE, false) => "SINGLE",
+		_ => "SINGLE|ANYONECANPAY",
+	}
+}
+
+/// Same as Core's CScript::HasValidOps: the script parses, has no opcode above OP_NOP10 and no push
+/// larger than a stack element.
+fn has_valid_ops(script: &[u8]) -> bool {
+	let mut pc = 0;
+	while pc < script.len() {
+		match interpreter::read_instruction(script, pc) {
+			Ok((Some(data), _)) if data.len() > MAX_SCRIPT_ELEMENT_SIZE => return false,
+			Ok((None, _)) if script[pc] > all::OP_NOP10.into_u8() => return false,
+			Ok((_, next)) => pc = next,
+			Err(_) => return false,
+		}
+	}
+	true
+}
+
+/// Whether the script has an opcode that makes tapscripts succeed right away.
+fn has_op_success(script: &[u8]) -> bool {
+	let mut pc = 0;
+	while pc < script.len() {
+		match interpreter::read_instruction(script, pc) {
+			Ok((None, _)) if is_op_success(script[pc]) => return true,
+			Ok((_, next)) => pc = next,
+			Err(_) => return false,
+		}
+	}
+	false
+}
+
+/// Opcodes that make a tapscript succeed right away (BIP342), same as Core's IsOpSuccess.
+fn is_op_success(code: u8) -> bool {
+	matches!(code, 80 | 98 | 126..=129 | 131..=134 | 137..=138 | 141..=142 | 149..=153 | 187..=254)
+}
+
+/// A strict DER signature (BIP66) followed by a defined sighash type.
+fn is_signature(sig: &[u8]) -> bool {
+	let len = sig.len();
+	if !(9..=73).contains(&len) || sig[0] != 0x30 || sig[1] as usize != len - 3 {
+		return false;
+	}
+	let len_r = sig[3] as usize;
+	if 5 + len_r >= len {
+		return false;
+	}
+	let len_s = sig[5 + len_r] as usize;
+	if len_r + len_s + 7 != len {
+		return false;
+	}
+	// R and S are positive integers without padding
+	let valid_integer = |tag: u8, int: &[u8]| {
+		tag == 0x02 && !int.is_empty() && int[0] & 0x80 == 0 && !(int.len() > 1 && int[0] == 0 && int[1] & 0x80 == 0)
+	};
+	if !valid_integer(sig[2], &sig[4..4 + len_r]) || !valid_integer(sig[4 + len_r], &sig[6 + len_r..6 + len_r + len_s]) {
+		return false;
+	}
+	let sighash_type = sig[len - 1] as u32 & !SIGHASH_ANYONECANPAY;
+	(SIGHASH_ALL..=SIGHASH_SINGLE).contains(&sighash_type)
+}
+
+fn btc(sats: u64) -> Value {
+	json!(sats as f64 / 100_000_000.0)
+}
+
+/// The scriptPubKey object of outputs: asm, desc, hex, address and type.
+pub fn script_pub_key_to_json(script_pub_key: &ScriptPubKey) -> Value {
+	let mut json = Map::new();
+	json.insert("asm".to_string(), json!(script_to_asm(&script_pub_key.script, false)));
+	json.insert("desc".to_string(), json!(descriptor::infer(script_pub_key)));
+	json.insert("hex".to_string(), json!(script_pub_key.script.encode_hex_be()));
+	if let Some(address) = script_pub_key.get_address() {
+		json.insert("address".to_string(), json!(address));
+	}
+	json.insert("type".to_string(), json!(script_pub_key.get_type().to_string()));
+	Value::Object(json)
+}
+
+/// Same as bitcoind's decoderawtransaction. The fee is added when the outputs spent by the inputs
+/// are known, like getrawtransaction does.
+pub fn transaction_to_json(tx: &Transaction) -> Value {
+	let witness_data = tx.witness_data.as_deref().unwrap_or_default();
+	let is_coinbase = tx.inputs.len() == 1
+		&& tx.inputs[0].previous_tx.chars().all(|c| c == '0')
+		&& tx.inputs[0].tx_index == u32::MAX;
+
+	let vin: Vec<Value> = tx.inputs.iter().enumerate().map(|(i, input)| {
+		let mut json = Map::new();
+		if is_coinbase {
+			json.insert("coinbase".to_string(), json!(input.script_sig.script.encode_hex_be()));
+		} else {
+			json.insert("txid".to_string(), json!(input.previous_tx));
+			json.insert("vout".to_string(), json!(input.tx_index));
+			json.insert("scriptSig".to_string(), json!({
+				"asm": script_to_asm(&input.script_sig.script, true),
+				"hex": input.script_sig.script.encode_hex_be(),
+			}));
+		}
+		if let Some(stack) = witness_data.get(i).filter(|stack| !stack.0.is_empty()) {
+			json.insert("txinwitness".to_string(), json!(stack.0));
+		}
+		let sequence = input.sequence.decode_hex_be().map(|bytes| {
+			bytes.iter().fold(0u64, |acc, b| acc << 8 | *b as u64)
+		}).unwrap_or_default();
+		json.insert("sequence".to_string(), json!(sequence));
+		Value::Object(json)
+	}).collect();
+
+	let vout: Vec<Value> = tx.outputs.iter().enumerate().map(|(n, output)| {
+		let script_pub_key = &output.script_pub_key;
+		json!({
+			"value": btc(output.amount),
+			"n": n,
+			"scriptPubKey": script_pub_key_to_json(script_pub_key),
+		})
+	}).collect();
+
+	let mut json = json!({
+		"txid": tx.txid(),
+		"hash": tx.wtxid(),
+		"version": tx.version,
+		"size": tx.size(),
+		"vsize": tx.vsize(),
+		"weight": tx.weight(),
+		"locktime": tx.lock_time,
+		"vin": vin,
+		"vout": vout,
+	});
+	if let Some(extra_info) = &tx.extra_info {
+		json["fee"] = btc(extra_info.miner_fee);
+	}
+	json
+}
+
+/// Same as bitcoind's decodescript: the script, the P2SH address it can be wrapped in and the
+/// segwit script it can be wrapped in (P2WPKH for single keys, P2WSH otherwise).
+pub fn decode_script_to_json(script: &ScriptPubKey) -> Value {
+	let network = script.network();
+	let mut json = Map::new();
+	json.insert("asm".to_string(), json!(script_to_asm(&script.script, false)));
+	json.insert("desc".to_string(), json!(descriptor::infer(script)));
+	let script_type = script.get_type();
+	if let Some(address) = script.get_address() {
+		json.insert("address".to_string(), json!(address));
+	}
+	json.insert("type".to_string(), json!(script_type.to_string()));
+
+	// Scripts with invalid or OP_SUCCESSx opcodes can't be spent from P2SH, and nothing can be
+	// nested twice. Only a type of scripts that are complete without a witness can be wrapped.
+	let can_wrap = matches!(script_type, ScriptType::Multisig | ScriptType::Custom | ScriptType::P2PK
+		| ScriptType::P2PKH | ScriptType::P2WPKH | ScriptType::P2WSH)
+		&& has_valid_ops(&script.script)
+		&& !has_op_success(&script.script)
+		&& script.script.first() != Some(&all::OP_RETURN.into_u8())
+		&& script.script.len() <= MAX_SCRIPT_SIZE;
+	if !can_wrap {
+		return Value::Object(json);
+	}
+
+	let mut p2sh = ScriptPubKey::new_p2sh(script.script.clone());
+	p2sh.set_network(network);
+	json.insert("p2sh".to_string(), json!(p2sh.get_address()));
+
+	// segwit only allows compressed keys
+	let segwit = match script_type {
+		ScriptType::P2PK => {
+			let key = &script.script[1..script.script.len() - 1];
+			(key.len() == 33).then(|| ScriptPubKey::new_p2wpkh(key.into()))
+		}
+		ScriptType::P2PKH => ScriptPubKey::new_witness_program(0, &script.script[3..23]).ok(),
+		ScriptType::Multisig => {
+			let (_, pubkeys) = crate::script::parse_multisig(&script.script).expect("multisig scripts can be parsed");
+			pubkeys.iter().all(|key| key.len() == 33).then(|| ScriptPubKey::new_p2wsh(script.script.clone()))
+		}
+		ScriptType::Custom => Some(ScriptPubKey::new_p2wsh(script.script.clone())),
+		_ => None,
+	};
+	if let Some(mut segwit) = segwit {
+		segwit.set_network(network);
+		let mut segwit_json = script_pub_key_to_json(&segwit);
+		// bitcoind knows the witness script of the P2WSH it just made
+		if script_type == ScriptType::Multisig {
+			let inner = descriptor::infer(script);
+			let inner = inner.split('#').next().expect("split returns at least one item");
+			let desc = format!("wsh({})", inner);
+			let checksum = descriptor::checksum(&desc).expect("inferred descriptors only contain valid characters");
+			segwit_json["desc"] = json!(format!("{}#{}", desc, checksum));
+		}
+		let mut p2sh_segwit = ScriptPubKey::new_p2sh(segwit.script.clone());
+		p2sh_segwit.set_network(network);
+		segwit_json["p2sh-segwit"] = json!(p2sh_segwit.get_address());
+		json.insert("segwit".to_string(), segwit_json);
+	}
+	Value::Object(json)
+}
+
+/// Same as bitcoind's validateaddress for a valid address.
+pub fn address_to_json(address: &str, script_pub_key: &ScriptPubKey) -> Value {
+	let mut json = Map::new();
+	json.insert("isvalid".to_string(), json!(true));
+	json.insert("address".to_string(), json!(address));
+	json.insert("scriptPubKey".to_string(), json!(script_pub_key.script.encode_hex_be()));
+	match interpreter::witness_program(&script_pub_key.script) {
+		Some((version, program)) => {
+			if script_pub_key.get_type() != ScriptType::WitnessUnknown {
+				json.insert("isscript".to_string(), json!(version != 0 || program.len() == 32));
+			}
+			json.insert("iswitness".to_string(), json!(true));
+			json.insert("witness_version".to_string(), json!(version));
+			json.insert("witness_program".to_string(), json!(program.encode_hex_be()));
+		}
+		None => {
+			json.insert("isscript".to_string(), json!(script_pub_key.is_p2sh()));
+			json.insert("iswitness".to_string(), json!(false));
+		}
+	}
+	Value::Object(json)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::txio::Decodable;
+	use crate::transaction::Transaction;
+	use crate::Deserialize;
+	use super::{address_to_json, decode_script_to_json, script_to_asm, transaction_to_json};
+
+	#[test]
+	fn asm_like_core() {
+		let asm = |hex: &str, decode_sighash: bool| script_to_asm(&hex.decode_hex_be().unwrap(), decode_sighash);
+
+		assert_eq!(asm("76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac", false),
+			"OP_DUP OP_HASH160 88fed7b8154069b5d2ace12fa4b7f96ab73d59df OP_EQUALVERIFY OP_CHECKSIG");
+		// small pushes are numbers
+		assert_eq!(asm("00514f60020001037f000004ffffff7f", false), "0 1 -1 16 256 127 2147483647");
+		assert_eq!(asm("b1b2ba50ff", false), "OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_CHECKSIGADD OP_RESERVED OP_INVALIDOPCODE");
+		assert_eq!(asm("bb", false), "OP_UNKNOWN");
+		assert_eq!(asm("6a05abcd", false), "OP_RETURN [error]");
+
+		let script_sig = "47304402201cea9a1b2a09b6c5e1a3b4b0b8dbd5b1e1b3e8fb0d6c6ab48b10e0f8ff39eb9702204e2f1d2a2c8a2e4b7d0bb8e9bd8dbd52de05b7a1d73fbbbe6ae7d8ad6cbbe40c81";
+		assert!(asm(script_sig, true).ends_with("e40c[ALL|ANYONECANPAY]"));
+		assert!(asm(script_sig, false).ends_with("e40c81"));
+	}
+
+	#[test]
+	fn decode_raw_transaction_json() {
+		// decode_transaction_pre_segwit_1
+		let tx = Transaction::decode_raw("01000000015dcb2625cc55a00079d49f38a7da8806b18cb60f8afa45279b174c89c5a86a65000000001976a91488fed7b8\
+		154069b5d2ace12fa4b7f96ab73d59df88acffffffff01e80300000000000003abcdef00000000".decode_hex_be().unwrap()).unwrap();
+		let expected = json!({
+			"txid": "a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694",
+			"hash": "a3e835a83b4b96565a65677509402bb46a3a81bc65aadb39b39b57afd2608694",
+			"version": 1,
+			"size": 88,
+			"vsize": 88,
+			"weight": 352,
+			"locktime": 0,
+			"vin": [{
+				"txid": "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d",
+				"vout": 0,
+				"scriptSig": {
+					"asm": "OP_DUP OP_HASH160 88fed7b8154069b5d2ace12fa4b7f96ab73d59df OP_EQUALVERIFY OP_CHECKSIG",
+					"hex": "76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac",
+				},
+				"sequence": 4294967295u32,
+			}],
+			"vout": [{
+				"value": 0.00001,
+				"n": 0,
+				"scriptPubKey": {
+					"asm": "OP_CODESEPARATOR OP_UNKNOWN OP_UNKNOWN",
+					"desc": "raw(abcdef)#74d3zdr6",
+					"hex": "abcdef",
+					"type": "nonstandard",
+				},
+			}],
+		});
+		assert_eq!(transaction_to_json(&tx), expected);
+	}
+
+	#[test]
+	fn decode_script_json() {
+		let decode = |hex: &str| decode_script_to_json(&ScriptPubKey::from_str(hex).unwrap());
+
+		let p2pkh = decode("76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac");
+		assert_eq!(p2pkh["type"], "pubkeyhash");
+		assert_eq!(p2pkh["address"], "1DVNBsH8KkW4r8voRwChVj68Nnyy1JXY5F");
+		assert!(p2pkh["p2sh"].as_str().unwrap().starts_with('3'));
+		assert_eq!(p2pkh["segwit"]["hex"], "001488fed7b8154069b5d2ace12fa4b7f96ab73d59df");
+		assert_eq!(p2pkh["segwit"]["type"], "witness_v0_keyhash");
+		assert!(p2pkh["segwit"]["p2sh-segwit"].as_str().unwrap().starts_with('3'));
+
+		let multisig = decode("5121022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a48010e2103a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae");
+		assert_eq!(multisig["type"], "multisig");
+		assert!(multisig.get("address").is_none());
+		assert_eq!(multisig["segwit"]["type"], "witness_v0_scripthash");
+		assert!(multisig["segwit"]["desc"].as_str().unwrap().starts_with("wsh(multi(1,022afc20"));
+
+		// nothing to wrap
+		for hex in ["a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87", "6a0568656c6c6f", "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"] {
+			let json = decode(hex);
+			assert!(json.get("p2sh").is_none() && json.get("segwit").is_none(), "{}", hex);
+		}
+		// a P2WPKH can be put in P2SH but not in another witness program
+		let p2wpkh = decode("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+		assert!(p2wpkh.get("p2sh").is_some() && p2wpkh.get("segwit").is_none());
+		let keys: Vec<&str> = p2wpkh.as_object().unwrap().keys().map(String::as_str).collect();
+		assert_eq!(keys, ["asm", "desc", "address", "type", "p2sh"]);
+
+		// OP_SUCCESSx opcodes (OP_RESERVED, OP_VER, OP_CAT, OP_MUL and unassigned ones) can't be wrapped
+		for hex in ["50", "5162", "517e", "5195", "51bb", "51fe"] {
+			let json = decode(hex);
+			assert!(json.get("p2sh").is_none() && json.get("segwit").is_none(), "{}", hex);
+		}
+		assert!(decode("5193").get("p2sh").is_some());
+
+		// like Core's HasValidOps, OP_INVALIDOPCODE and anything above OP_NOP10 is invalid
+		for hex in ["51ff", "51ba", "ff"] {
+			let json = decode(hex);
+			assert!(json.get("p2sh").is_none() && json.get("segwit").is_none(), "{}", hex);
+		}
+		assert!(decode("51b9").get("segwit").is_some());
+		// so is a push larger than a stack element
+		let json = decode(&format!("4d0902{}", "00".repeat(521)));
+		assert!(json.get("p2sh").is_none() && json.get("segwit").is_none());
+		assert!(decode(&format!("4d0802{}", "00".repeat(520))).get("segwit").is_some());
+
+		// segwit only takes compressed keys
+		let compressed = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+		let uncompressed = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+		let p2pk = decode(&format!("21{}ac", compressed));
+		assert_eq!(p2pk["type"], "pubkey");
+		assert_eq!(p2pk["segwit"]["hex"], "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+		let p2pk = decode(&format!("41{}ac", uncompressed));
+		assert_eq!(p2pk["type"], "pubkey");
+		assert!(p2pk.get("p2sh").is_some() && p2pk.get("segwit").is_none());
+		let multisig = decode(&format!("5121{}41{}52ae", compressed, uncompressed));
+		assert_eq!(multisig["type"], "multisig");
+		assert!(multisig.get("p2sh").is_some() && multisig.get("segwit").is_none());
+	}
+
+	#[test]
+	fn validate_address_json() {
+		let validate = |address: &str| address_to_json(address, &ScriptPubKey::from_address(address).unwrap());
+
+		assert_eq!(validate("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"), json!({
+			"isvalid": true,
+			"address": "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+			"scriptPubKey": "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+			"isscript": false,
+			"iswitness": false,
+		}));
+		assert_eq!(validate("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"), json!({
+			"isvalid": true,
+			"address": "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+			"scriptPubKey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+			"isscript": false,
+			"iswitness": true,
+			"witness_version": 0,
+			"witness_program": "751e76e8199196d454941c45d1b3a323f1433bd6",
+		}));
+	}
+}
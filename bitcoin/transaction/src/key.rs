@@ -0,0 +1,676 @@
+// secp256k1 private and public keys. The elliptic curve maths is done by the secp256k1 crate, which
+// wraps Bitcoin Core's libsecp256k1.
+//
+// Extended keys (BIP32) are keys with a chain code, from which child keys are derived. A wallet
+// only has to back up the master key, every key it uses is derived from it along a path such as
+// m/84'/0'/0'/0/5. Non-hardened children can be derived from the parent's public key alone, so
+// an xpub is enough to generate a wallet's addresses. Hardened children need the private key.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use secp256k1::{Scalar, SecretKey, SECP256K1};
+use crate::base58::{self, Base58Error};
+use crate::hash;
+use crate::network::Network;
+use crate::txio::{Decodable, Encodable, HexBytes};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyError {
+	/// Private keys are 32 bytes, public keys are 33 (compressed) or 65 (uncompressed) bytes.
+	/// Extended keys are 78 bytes and seeds 16 to 64 bytes.
+	InvalidLength(usize),
+	/// The number is zero or isn't smaller than the order of the curve.
+	InvalidPrivateKey,
+	/// The bytes aren't a point on the curve.
+	InvalidPublicKey,
+	/// Not a valid hex string.
+	InvalidHex,
+	/// Not a valid Base58 string.
+	InvalidBase58,
+	/// The Base58Check checksum doesn't match.
+	InvalidChecksum,
+	/// Unknown version byte of a WIF private key.
+	InvalidWifPrefix(u8),
+	/// Unknown version bytes of an extended key, or those of a public key where a private key is
+	/// expected and vice versa.
+	InvalidExtendedKeyPrefix([u8; 4]),
+	/// A master key (depth 0) can't have a parent fingerprint or a child number.
+	InvalidDepth,
+	/// Hardened children can only be derived from a private key.
+	HardenedFromPublic,
+	/// The child key is invalid, which happens with a probability lower than 1 in 2^127. BIP32
+	/// says to use the next index instead.
+	InvalidChild(u32),
+	InvalidDerivationPath(String),
+}
+
+impl fmt::Display for KeyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			KeyError::InvalidLength(len) => write!(f, "invalid key length of {} bytes", len),
+			KeyError::InvalidPrivateKey => write!(f, "private key is not in the range of the curve order"),
+			KeyError::InvalidPublicKey => write!(f, "public key is not a point on the curve"),
+			KeyError::InvalidHex => write!(f, "key is not valid hex"),
+			KeyError::InvalidBase58 => write!(f, "key is not valid base58"),
+			KeyError::InvalidChecksum => write!(f, "base58 checksum mismatch"),
+			KeyError::InvalidWifPrefix(prefix) => write!(f, "unknown WIF prefix {:#04x}", prefix),
+			KeyError::InvalidExtendedKeyPrefix(prefix) => write!(f, "unknown extended key prefix {}", prefix.encode_hex_be()),
+			KeyError::InvalidDepth => write!(f, "master key with a parent"),
+			KeyError::HardenedFromPublic => write!(f, "hardened children can't be derived from a public key"),
+			KeyError::InvalidChild(index) => write!(f, "child {} is an invalid key, use the next index", index),
+			KeyError::InvalidDerivationPath(ref path) => write!(f, "invalid derivation path \"{}\"", path),
+		}
+	}
+}
+
+impl Error for KeyError {}
+
+impl From<Base58Error> for KeyError {
+	fn from(e: Base58Error) -> Self {
+		match e {
+			Base58Error::InvalidBase58 => KeyError::InvalidBase58,
+			Base58Error::InvalidChecksum => KeyError::InvalidChecksum,
+		}
+	}
+}
+
+/// A secp256k1 private key. `compressed` decides the serialization of its public key, which
+/// changes the addresses derived from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrivateKey {
+	pub compressed: bool,
+	/// Only changes the WIF prefix. Testnet, signet and regtest share theirs so a parsed WIF key is
+	/// either mainnet or testnet.
+	pub network: Network,
+	inner: SecretKey,
+}
+
+impl PrivateKey {
+	/// Generate a new private key using the OS random number generator.
+	#[cfg(test)]
+	pub fn new_random(compressed: bool, network: Network) -> Self {
+		let inner = SecretKey::new(&mut secp256k1::rand::thread_rng());
+		PrivateKey { compressed, network, inner }
+	}
+
+	/// Parse a 32 byte private key. The key has to be in the range [1, n-1] where n is the order
+	/// of the curve.
+	pub fn from_bytes(bytes: &[u8], compressed: bool, network: Network) -> Result<Self, KeyError> {
+		if bytes.len() != 32 {
+			return Err(KeyError::InvalidLength(bytes.len()))
+		}
+		let inner = SecretKey::from_slice(bytes).map_err(|_| KeyError::InvalidPrivateKey)?;
+		Ok(PrivateKey { compressed, network, inner })
+	}
+
+	/// Parse a private key in Wallet Import Format, i.e. Base58Check of
+	/// <prefix> <32 byte key> [0x01 if the public key is compressed].
+	pub fn from_wif(wif: &str) -> Result<Self, KeyError> {
+		let payload = base58::decode_check(wif)?;
+		let compressed = match payload.len() {
+			33 => false,
+			34 if payload[33] == 0x01 => true,
+			_ => return Err(KeyError::InvalidLength(payload.len())),
+		};
+		let network = match payload[0] {
+			prefix if prefix == Network::Mainnet.wif_prefix() => Network::Mainnet,
+			prefix if prefix == Network::Testnet.wif_prefix() => Network::Testnet,
+			prefix => return Err(KeyError::InvalidWifPrefix(prefix)),
+		};
+		PrivateKey::from_bytes(&payload[1..33], compressed, network)
+	}
+
+	/// Encode the private key in Wallet Import Format.
+	pub fn to_wif(self) -> String {
+		let mut bytes = vec![self.network.wif_prefix()];
+		bytes.extend_from_slice(&self.inner.secret_bytes());
+		if self.compressed { bytes.push(0x01); }
+		base58::encode_check(&bytes)
+	}
+
+	/// The raw 32 byte private key.
+	#[cfg(test)]
+	pub fn as_bytes(&self) -> HexBytes {
+		Box::new(self.inner.secret_bytes())
+	}
+
+	pub(crate) fn secret_key(&self) -> &SecretKey {
+		&self.inner
+	}
+
+	/// Multiply the generator point by the private key to get the public key.
+	pub fn public_key(&self) -> PublicKey {
+		PublicKey {
+			compressed: self.compressed,
+			inner: self.inner.public_key(SECP256K1),
+		}
+	}
+}
+
+/// A secp256k1 public key, i.e. a point on the curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey {
+	/// Compressed keys are serialized as 33 bytes: 0x02 or 0x03 depending on whether y is even
+	/// or odd, followed by x. Uncompressed keys are 65 bytes: 0x04 followed by x and y.
+	pub compressed: bool,
+	inner: secp256k1::PublicKey,
+}
+
+impl PublicKey {
+	/// Parse a serialized public key, checking that it's a valid point on the curve.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyError> {
+		let compressed = match bytes.len() {
+			33 => true,
+			65 => false,
+			len => return Err(KeyError::InvalidLength(len)),
+		};
+		let inner = secp256k1::PublicKey::from_slice(bytes).map_err(|_| KeyError::InvalidPublicKey)?;
+		Ok(PublicKey { compressed, inner })
+	}
+
+	/// Serialize the public key. This is what goes into scripts, e.g. `Script::new_p2pkh`.
+	pub fn as_bytes(&self) -> HexBytes {
+		if self.compressed {
+			Box::new(self.inner.serialize())
+		} else {
+			Box::new(self.inner.serialize_uncompressed())
+		}
+	}
+
+	pub(crate) fn inner(&self) -> &secp256k1::PublicKey {
+		&self.inner
+	}
+
+	/// hash160 of the serialized public key, as used in P2PKH and P2WPKH scripts.
+	pub fn pubkey_hash(&self) -> HexBytes {
+		hash::hash160(&self.as_bytes()).into_boxed_slice()
+	}
+}
+
+impl FromStr for PublicKey {
+	type Err = KeyError;
+
+	/// Parse a hex encoded public key.
+	fn from_str(hex: &str) -> Result<Self, Self::Err> {
+		let bytes = hex.decode_hex_be().map_err(|_| KeyError::InvalidHex)?;
+		PublicKey::from_bytes(&bytes)
+	}
+}
+
+impl fmt::Display for PublicKey {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.as_bytes().encode_hex_be())
+	}
+}
+
+/// Child numbers from this one up are hardened, written with a ' in derivation paths.
+pub const HARDENED: u32 = 1 << 31;
+
+/// The child numbers leading from a master key to one of its descendants, e.g. m/84'/0'/0'/0/5.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DerivationPath(pub Vec<u32>);
+
+impl FromStr for DerivationPath {
+	type Err = KeyError;
+
+	/// Hardened child numbers end with ' or h. The leading "m/" is optional.
+	fn from_str(path: &str) -> Result<Self, Self::Err> {
+		let invalid = || KeyError::InvalidDerivationPath(path.to_string());
+		let children = match path {
+			"m" | "" => return Ok(DerivationPath::default()),
+			_ => path.strip_prefix("m/").unwrap_or(path),
+		};
+		children.split('/').map(|child| {
+			let (index, hardened) = match child.strip_suffix(['\'', 'h']) {
+				Some(index) => (index, HARDENED),
+				None => (child, 0),
+			};
+			// no sign, no leading zeros
+			if index.starts_with('+') || (index.len() > 1 && index.starts_with('0')) {
+				return Err(invalid())
+			}
+			match index.parse::<u32>() {
+				Ok(index) if index < HARDENED => Ok(index | hardened),
+				_ => Err(invalid()),
+			}
+		}).collect::<Result<_, _>>().map(DerivationPath)
+	}
+}
+
+impl fmt::Display for DerivationPath {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("m")?;
+		for child in &self.0 {
+			match child & HARDENED {
+				0 => write!(f, "/{}", child)?,
+				_ => write!(f, "/{}'", child & !HARDENED)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+/// What both kinds of extended keys have besides the key itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedKeyInfo {
+	pub network: Network,
+	/// 0 for the master key, 1 for its children and so on.
+	pub depth: u8,
+	/// The first 4 bytes of the parent's key identifier, 0 for the master key.
+	pub parent_fingerprint: [u8; 4],
+	pub child_number: u32,
+	pub chain_code: [u8; 32],
+}
+
+impl ExtendedKeyInfo {
+	/// The info of a child derived with I = HMAC-SHA512(chain code, data): the right half of I is
+	/// its chain code, the left half is returned to tweak the parent key with.
+	fn child(&self, parent_fingerprint: [u8; 4], index: u32, data: &[u8]) -> Result<(Self, Scalar), KeyError> {
+		let i = hash::hmac_sha512(&self.chain_code, data);
+		// the left half has to be smaller than the order of the curve
+		let tweak = Scalar::from_be_bytes(i[..32].try_into().expect("32 bytes")).map_err(|_| KeyError::InvalidChild(index))?;
+		let info = ExtendedKeyInfo {
+			network: self.network,
+			depth: self.depth.checked_add(1).ok_or(KeyError::InvalidChild(index))?,
+			parent_fingerprint,
+			child_number: index,
+			chain_code: i[32..].try_into().expect("32 bytes"),
+		};
+		Ok((info, tweak))
+	}
+
+	/// <version> <depth> <parent fingerprint> <child number> <chain code> <33 byte key>
+	fn serialize(&self, prefix: [u8; 4], key: &[u8]) -> String {
+		let mut bytes = prefix.to_vec();
+		bytes.push(self.depth);
+		bytes.extend_from_slice(&self.parent_fingerprint);
+		bytes.extend_from_slice(&self.child_number.to_be_bytes());
+		bytes.extend_from_slice(&self.chain_code);
+		bytes.extend_from_slice(key);
+		base58::encode_check(&bytes)
+	}
+
+	/// Parse a serialized extended key, the network is found with `network_of` from the version
+	/// bytes. Returns the 33 byte key.
+	fn parse(s: &str, network_of: fn([u8; 4]) -> Option<Network>) -> Result<(Self, Vec<u8>), KeyError> {
+		let bytes = base58::decode_check(s)?;
+		if bytes.len() != 78 {
+			return Err(KeyError::InvalidLength(bytes.len()))
+		}
+		let prefix = bytes[..4].try_into().expect("4 bytes");
+		let network = network_of(prefix).ok_or(KeyError::InvalidExtendedKeyPrefix(prefix))?;
+		let info = ExtendedKeyInfo {
+			network,
+			depth: bytes[4],
+			parent_fingerprint: bytes[5..9].try_into().expect("4 bytes"),
+			child_number: u32::from_be_bytes(bytes[9..13].try_into().expect("4 bytes")),
+			chain_code: bytes[13..45].try_into().expect("32 bytes"),
+		};
+		if info.depth == 0 && (info.parent_fingerprint != [0; 4] || info.child_number != 0) {
+			return Err(KeyError::InvalidDepth)
+		}
+		Ok((info, bytes[45..].to_vec()))
+	}
+}
+
+/// The first 4 bytes of hash160 of a compressed public key, which identifies an extended key.
+fn fingerprint(public_key: &secp256k1::PublicKey) -> [u8; 4] {
+	hash::hash160(&public_key.serialize())[..4].try_into().expect("4 bytes")
+}
+
+/// A BIP32 extended private key, xprv or tprv. Its public key is always compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedPrivateKey {
+	pub info: ExtendedKeyInfo,
+	pub private_key: PrivateKey,
+}
+
+impl ExtendedPrivateKey {
+	/// The master key of a seed, e.g. one generated from a mnemonic. BIP32 recommends 32 bytes.
+	pub fn new_master(seed: &[u8], network: Network) -> Result<Self, KeyError> {
+		if !(16..=64).contains(&seed.len()) {
+			return Err(KeyError::InvalidLength(seed.len()))
+		}
+		let i = hash::hmac_sha512(b"Bitcoin seed", seed);
+		let info = ExtendedKeyInfo {
+			network,
+			depth: 0,
+			parent_fingerprint: [0; 4],
+			child_number: 0,
+			chain_code: i[32..].try_into().expect("32 bytes"),
+		};
+		let private_key = PrivateKey::from_bytes(&i[..32], true, network)?;
+		Ok(ExtendedPrivateKey { info, private_key })
+	}
+
+	/// Derive a child: hardened children are derived from the private key, others from the
+	/// public key. The child's private key is the parent's plus the left half of the HMAC.
+	pub fn derive_child(&self, index: u32) -> Result<Self, KeyError> {
+		let mut data = if index & HARDENED != 0 {
+			let mut data = vec![0];
+			data.extend_from_slice(&self.private_key.inner.secret_bytes());
+			data
+		} else {
+			self.public_key().inner.serialize().to_vec()
+		};
+		data.extend_from_slice(&index.to_be_bytes());
+
+		let (info, tweak) = self.info.child(self.fingerprint(), index, &data)?;
+		let inner = self.private_key.inner.add_tweak(&tweak).map_err(|_| KeyError::InvalidChild(index))?;
+		let private_key = PrivateKey { compressed: true, network: self.info.network, inner };
+		Ok(ExtendedPrivateKey { info, private_key })
+	}
+
+	pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, KeyError> {
+		path.0.iter().try_fold(*self, |key, &index| key.derive_child(index))
+	}
+
+	pub fn public_key(&self) -> PublicKey {
+		self.private_key.public_key()
+	}
+
+	/// The extended public key with the same chain code, from which the non-hardened children's
+	/// public keys can be derived.
+	pub fn extended_public_key(&self) -> ExtendedPublicKey {
+		ExtendedPublicKey { info: self.info, public_key: self.public_key() }
+	}
+
+	/// Identifies this key as the parent of its children, and as the master key in key origins.
+	pub fn fingerprint(&self) -> [u8; 4] {
+		fingerprint(&self.public_key().inner)
+	}
+}
+
+impl FromStr for ExtendedPrivateKey {
+	type Err = KeyError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let network_of = |prefix| match prefix {
+			prefix if prefix == Network::Mainnet.xprv_prefix() => Some(Network::Mainnet),
+			prefix if prefix == Network::Testnet.xprv_prefix() => Some(Network::Testnet),
+			_ => None,
+		};
+		let (info, key) = ExtendedKeyInfo::parse(s, network_of)?;
+		// private keys are prefixed with 0 to be as long as public keys
+		if key[0] != 0 {
+			return Err(KeyError::InvalidPrivateKey)
+		}
+		let private_key = PrivateKey::from_bytes(&key[1..], true, info.network)?;
+		Ok(ExtendedPrivateKey { info, private_key })
+	}
+}
+
+impl fmt::Display for ExtendedPrivateKey {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut key = vec![0];
+		key.extend_from_slice(&self.private_key.inner.secret_bytes());
+		f.write_str(&self.info.serialize(self.info.network.xprv_prefix(), &key))
+	}
+}
+
+/// A BIP32 extended public key, xpub or tpub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+	pub info: ExtendedKeyInfo,
+	pub public_key: PublicKey,
+}
+
+impl ExtendedPublicKey {
+	/// Derive a non-hardened child: the parent's public key plus the left half of the HMAC times
+	/// the generator, the same public key as the child of the extended private key.
+	pub fn derive_child(&self, index: u32) -> Result<Self, KeyError> {
+		if index & HARDENED != 0 {
+			return Err(KeyError::HardenedFromPublic)
+		}
+		let mut data = self.public_key.inner.serialize().to_vec();
+		data.extend_from_slice(&index.to_be_bytes());
+
+		let (info, tweak) = self.info.child(self.fingerprint(), index, &data)?;
+		let inner = self.public_key.inner.add_exp_tweak(SECP256K1, &tweak).map_err(|_| KeyError::InvalidChild(index))?;
+		Ok(ExtendedPublicKey { info, public_key: PublicKey { compressed: true, inner } })
+	}
+
+	pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, KeyError> {
+		path.0.iter().try_fold(*self, |key, &index| key.derive_child(index))
+	}
+
+	pub fn fingerprint(&self) -> [u8; 4] {
+		fingerprint(&self.public_key.inner)
+	}
+}
+
+impl FromStr for ExtendedPublicKey {
+	type Err = KeyError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let network_of = |prefix| match prefix {
+			prefix if prefix == Network::Mainnet.xpub_prefix() => Some(Network::Mainnet),
+			prefix if prefix == Network::Testnet.xpub_prefix() => Some(Network::Testnet),
+			_ => None,
+		};
+		let (info, key) = ExtendedKeyInfo::parse(s, network_of)?;
+		let public_key = PublicKey::from_bytes(&key)?;
+		Ok(ExtendedPublicKey { info, public_key })
+	}
+}
+
+impl fmt::Display for ExtendedPublicKey {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.info.serialize(self.info.network.xpub_prefix(), &self.public_key.inner.serialize()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+	use crate::network::Network;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::txio::{Decodable, Encodable};
+	use crate::base58;
+	use super::{DerivationPath, ExtendedPrivateKey, ExtendedPublicKey, KeyError, PrivateKey, PublicKey, HARDENED};
+
+	const G_COMPRESSED: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+	const G_UNCOMPRESSED: &str = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817984\
+	83ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+
+	#[test]
+	fn private_key_to_public_key() {
+		// 1 * G = G
+		let mut one = [0; 32];
+		one[31] = 1;
+		let private_key = PrivateKey::from_bytes(&one, true, Network::Mainnet).unwrap();
+		assert_eq!(private_key.public_key().as_bytes().encode_hex_be(), G_COMPRESSED);
+
+		let private_key = PrivateKey::from_bytes(&one, false, Network::Mainnet).unwrap();
+		assert_eq!(private_key.public_key().as_bytes().encode_hex_be(), G_UNCOMPRESSED);
+	}
+
+	#[test]
+	fn private_key_validation() {
+		assert_eq!(PrivateKey::from_bytes(&[0; 32], true, Network::Mainnet), Err(KeyError::InvalidPrivateKey));
+		assert_eq!(PrivateKey::from_bytes(&[1; 31], true, Network::Mainnet), Err(KeyError::InvalidLength(31)));
+		// the order of the curve
+		let n = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141".decode_hex_be().unwrap();
+		assert_eq!(PrivateKey::from_bytes(&n, true, Network::Mainnet), Err(KeyError::InvalidPrivateKey));
+
+		let key = PrivateKey::new_random(true, Network::Testnet);
+		assert_eq!(PrivateKey::from_bytes(&key.as_bytes(), true, Network::Testnet), Ok(key));
+	}
+
+	#[test]
+	fn wif() {
+		// https://en.bitcoin.it/wiki/Wallet_import_format
+		let key = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d".decode_hex_be().unwrap();
+
+		let private_key = PrivateKey::from_bytes(&key, false, Network::Mainnet).unwrap();
+		assert_eq!(private_key.to_wif(), "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ");
+		assert_eq!(PrivateKey::from_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ"), Ok(private_key));
+
+		let private_key = PrivateKey::from_bytes(&key, true, Network::Mainnet).unwrap();
+		assert_eq!(private_key.to_wif(), "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617");
+		assert_eq!(PrivateKey::from_wif("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617"), Ok(private_key));
+
+		let private_key = PrivateKey::from_bytes(&key, true, Network::Testnet).unwrap();
+		assert_eq!(PrivateKey::from_wif(&private_key.to_wif()), Ok(private_key));
+
+		assert_eq!(PrivateKey::from_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTj"), Err(KeyError::InvalidChecksum));
+		assert_eq!(PrivateKey::from_wif("0OIl"), Err(KeyError::InvalidBase58));
+	}
+
+	#[test]
+	fn public_key_validation() {
+		let public_key = PublicKey::from_str(G_COMPRESSED).unwrap();
+		assert!(public_key.compressed);
+		assert_eq!(public_key.to_string(), G_COMPRESSED);
+
+		let public_key = PublicKey::from_str(G_UNCOMPRESSED).unwrap();
+		assert!(!public_key.compressed);
+		assert_eq!(public_key.to_string(), G_UNCOMPRESSED);
+
+		// x isn't on the curve
+		let invalid = "02".to_string() + &"00".repeat(32);
+		assert_eq!(PublicKey::from_str(&invalid), Err(KeyError::InvalidPublicKey));
+		assert_eq!(PublicKey::from_str(&G_COMPRESSED[..64]), Err(KeyError::InvalidLength(32)));
+		assert_eq!(PublicKey::from_str("zz"), Err(KeyError::InvalidHex));
+	}
+
+	#[test]
+	fn public_key_scripts() {
+		let public_key = PublicKey::from_str(G_COMPRESSED).unwrap();
+		assert_eq!(public_key.pubkey_hash().encode_hex_be(), "751e76e8199196d454941c45d1b3a323f1433bd6");
+
+		let p2pkh = ScriptPubKey::new_p2pkh(public_key.as_bytes());
+		assert_eq!(p2pkh.as_asm(), "OP_DUP OP_HASH160 751e76e8199196d454941c45d1b3a323f1433bd6 OP_EQUALVERIFY OP_CHECKSIG");
+
+		let p2pk = ScriptPubKey::new_p2pk(public_key.as_bytes());
+		assert!(p2pk.is_p2pk());
+		assert_eq!(p2pk.script[1..34], *public_key.as_bytes());
+	}
+
+	/// Derive every path from the seed's master key and compare the xprv and xpub. The xpubs of
+	/// non-hardened children are also derived from their parent's xpub.
+	fn check_bip32_vector(seed: &str, vector: &[(&str, &str, &str)]) {
+		let master = ExtendedPrivateKey::new_master(&seed.decode_hex_be().unwrap(), Network::Mainnet).unwrap();
+		let mut parent_xpub = None;
+		for (path, xprv, xpub) in vector {
+			let path = DerivationPath::from_str(path).unwrap();
+			let key = master.derive_path(&path).unwrap();
+			assert_eq!(key.to_string(), *xprv);
+			assert_eq!(key.extended_public_key().to_string(), *xpub);
+			assert_eq!(ExtendedPrivateKey::from_str(xprv), Ok(key));
+			assert_eq!(ExtendedPublicKey::from_str(xpub), Ok(key.extended_public_key()));
+
+			let last = path.0.last().copied().unwrap_or(HARDENED);
+			if let (Some(parent), 0) = (parent_xpub, last & HARDENED) {
+				assert_eq!(ExtendedPublicKey::derive_child(&parent, last), Ok(key.extended_public_key()));
+			}
+			parent_xpub = Some(key.extended_public_key());
+		}
+	}
+
+	#[test]
+	fn bip32_vectors() {
+		// https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vectors
+		check_bip32_vector("000102030405060708090a0b0c0d0e0f", &[
+			("m", "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+				"xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+			("m/0'", "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+				"xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw"),
+			("m/0'/1", "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+				"xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"),
+			("m/0'/1/2'", "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+				"xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5"),
+			("m/0'/1/2'/2", "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+				"xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV"),
+			("m/0'/1/2'/2/1000000000", "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+				"xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy"),
+		]);
+		check_bip32_vector("fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b78\
+			75726f6c696663605d5a5754514e4b484542", &[
+			("m", "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U",
+				"xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB"),
+			("m/0", "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt",
+				"xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH"),
+			("m/0/2147483647'", "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9",
+				"xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a"),
+			("m/0/2147483647'/1", "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef",
+				"xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon"),
+			("m/0/2147483647'/1/2147483646'", "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc",
+				"xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL"),
+			("m/0/2147483647'/1/2147483646'/2", "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j",
+				"xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt"),
+		]);
+		// leading zeros of private keys are kept
+		check_bip32_vector("4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c6\
+			4d2e8a1e7d1457df2e5a3c51c73235be", &[
+			("m", "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6",
+				"xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13"),
+			("m/0'", "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L",
+				"xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y"),
+		]);
+	}
+
+	#[test]
+	fn extended_keys() {
+		let seed = "000102030405060708090a0b0c0d0e0f".decode_hex_be().unwrap();
+		let master = ExtendedPrivateKey::new_master(&seed, Network::Mainnet).unwrap();
+		assert_eq!(master.fingerprint().encode_hex_be(), "3442193e");
+		let child = master.derive_child(HARDENED).unwrap();
+		assert_eq!(child.info.parent_fingerprint, master.fingerprint());
+		assert_eq!(child.info.depth, 1);
+		assert_eq!(child.extended_public_key().fingerprint(), child.fingerprint());
+		assert_eq!(master.extended_public_key().derive_child(HARDENED), Err(KeyError::HardenedFromPublic));
+		assert_eq!(ExtendedPrivateKey::new_master(&[0; 15], Network::Mainnet), Err(KeyError::InvalidLength(15)));
+
+		let testnet = ExtendedPrivateKey::new_master(&seed, Network::Testnet).unwrap();
+		assert!(testnet.to_string().starts_with("tprv8ZgxMBicQKsP"));
+		assert!(testnet.extended_public_key().to_string().starts_with("tpubD6NzVbkrYhZ4"));
+		assert_eq!(ExtendedPrivateKey::from_str(&testnet.to_string()), Ok(testnet));
+		assert_eq!(testnet.derive_child(0).unwrap().private_key.network, Network::Testnet);
+
+		// an xpub isn't an xprv
+		let xpub = master.extended_public_key().to_string();
+		assert_eq!(ExtendedPrivateKey::from_str(&xpub), Err(KeyError::InvalidExtendedKeyPrefix([0x04, 0x88, 0xb2, 0x1e])));
+		assert_eq!(ExtendedPublicKey::from_str(&master.to_string()), Err(KeyError::InvalidExtendedKeyPrefix([0x04, 0x88, 0xad, 0xe4])));
+		let mut wrong_checksum = xpub.clone();
+		wrong_checksum.replace_range(xpub.len() - 1.., if xpub.ends_with('8') { "9" } else { "8" });
+		assert_eq!(ExtendedPublicKey::from_str(&wrong_checksum), Err(KeyError::InvalidChecksum));
+
+		// serialize modified payloads
+		let base58_check = |bytes: Vec<u8>| base58::encode_check(&bytes);
+		let payload = |key: &str| base58::decode_check(key).unwrap();
+		let mut bytes = payload(&master.to_string());
+		bytes[8] = 1;
+		assert_eq!(ExtendedPrivateKey::from_str(&base58_check(bytes)), Err(KeyError::InvalidDepth));
+		let mut bytes = payload(&master.to_string());
+		bytes[12] = 1;
+		assert_eq!(ExtendedPrivateKey::from_str(&base58_check(bytes)), Err(KeyError::InvalidDepth));
+		let mut bytes = payload(&master.to_string());
+		bytes[45] = 4;
+		assert_eq!(ExtendedPrivateKey::from_str(&base58_check(bytes)), Err(KeyError::InvalidPrivateKey));
+		let mut bytes = payload(&master.to_string());
+		bytes[46..].copy_from_slice(&[0; 32]);
+		assert_eq!(ExtendedPrivateKey::from_str(&base58_check(bytes)), Err(KeyError::InvalidPrivateKey));
+		let mut bytes = payload(&xpub);
+		bytes[45] = 4;
+		assert_eq!(ExtendedPublicKey::from_str(&base58_check(bytes)), Err(KeyError::InvalidPublicKey));
+		let mut bytes = payload(&xpub);
+		bytes.pop();
+		assert_eq!(ExtendedPublicKey::from_str(&base58_check(bytes)), Err(KeyError::InvalidLength(77)));
+	}
+
+	#[test]
+	fn derivation_paths() {
+		let path = DerivationPath::from_str("m/84'/0'/0'/0/5").unwrap();
+		assert_eq!(path, DerivationPath(vec![84 | HARDENED, HARDENED, HARDENED, 0, 5]));
+		assert_eq!(path.to_string(), "m/84'/0'/0'/0/5");
+		assert_eq!(DerivationPath::from_str("84h/0h/0h/0/5"), Ok(path));
+		assert_eq!(DerivationPath::from_str("m"), Ok(DerivationPath(Vec::new())));
+		assert_eq!(DerivationPath::from_str("m/2147483647'").unwrap().0, [u32::MAX]);
+
+		for invalid in ["m/", "m/2147483648", "m/-1", "m/+1", "m/01", "m/1''", "n/1", "m/1/"] {
+			assert_eq!(DerivationPath::from_str(invalid), Err(KeyError::InvalidDerivationPath(invalid.to_string())));
+		}
+	}
+}
@@ -1,8 +1,15 @@
 use std::error::Error;
 use std::io::{BufRead, self};
+use std::path::Path;
+use std::str::FromStr;
+use block::Block;
 use transaction::Transaction;
 use txio::HexBytes;
-use crate::script::ScriptPubKey;
+use crate::interpreter::{Interpreter, BaseSignatureChecker};
+use crate::network::Network;
+use crate::client::{BitcoindClient, EsploraClient};
+use crate::prevout::{MemoryPrevouts, NoPrevouts, PrevoutProvider};
+use crate::script::{ScriptPubKey, ScriptSig};
 use crate::txio::UserReadExt;
 
 mod txio;
@@ -10,7 +17,26 @@ mod opcodes;
 mod transaction;
 mod script;
 mod hash;
+mod interpreter;
+mod sighash;
 mod key;
+mod sign;
+mod block;
+mod merkle;
+mod address;
+mod base58;
+mod network;
+mod error;
+mod prevout;
+mod psbt;
+mod mnemonic;
+mod wordlist;
+mod client;
+mod cli;
+mod descriptor;
+mod json;
+#[cfg(test)]
+mod mock_server;
 
 /// Bitcoin transactions follow a specific encoding so that each node in the network can
 /// communicate in a shared language. Serialize is a trait which different components of a
@@ -35,36 +61,186 @@ pub trait Deserialize {
 }
 
 fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	if args.is_empty() {
+		return interactive();
+	}
+	match cli::run(&args) {
+		Ok(output) => println!("{}", output),
+		Err(e) => {
+			eprintln!("{}", e);
+			std::process::exit(if matches!(e, cli::CliError::Usage(_)) { 2 } else { 1 });
+		}
+	}
+}
+
+/// The menu shown when no command is given.
+fn interactive() {
+	let network = read_network(io::stdin().lock());
+
 	println!("What would you like to do?");
 	println!("1. Create  new transaction");
 	println!("2. Create new script");
 	println!("3. Decode raw transaction");
 	println!("4. Decode raw script");
+	println!("5. Run a script sig against a script pub key");
+	println!("6. Decode raw block");
 
 	let option = io::stdin().lock().user_read_u32();
 
 	if option == 1 {
-		let transaction = Transaction::encode_raw(io::stdin().lock());
+		let mut transaction = Transaction::encode_raw(io::stdin().lock());
+		println!("Sign the transaction? (enter true or false)");
+		if io::stdin().lock().user_read_bool() {
+			if let Err(e) = sign::sign_interactive(&mut transaction, io::stdin().lock()) {
+				println!("Couldn't sign the transaction: {}", e);
+			}
+		}
+		transaction.set_network(network);
 		println!();
 		println!("{:#?}", transaction);
 		println!("Raw transaction {:#?}", transaction.as_hex());
 	} else if option == 2 {
-		let script = ScriptPubKey::encode_raw(io::stdin().lock());
+		let mut script = ScriptPubKey::encode_raw(io::stdin().lock());
+		script.set_network(network);
 		println!();
 		println!("script_pub_key: {:#?}", script);
 	} else if option == 3 {
 		println!("Enter a raw transaction hex");
 		let hexbytes = io::stdin().lock().user_read_hex_var();
-		let transaction = Transaction::decode_raw(hexbytes);
+		let mut transaction = Transaction::decode_raw(hexbytes);
+		if let Ok(tx) = &mut transaction {
+			let provider = read_prevout_provider(io::stdin().lock());
+			if let Err(e) = tx.fetch_prevouts(provider.as_ref()) {
+				println!("Couldn't look up the spent outputs: {}", e);
+			}
+			tx.set_network(network);
+		}
 		println!();
+		// The same summary as the top of bitcoind's decoderawtransaction
+		if let Ok(tx) = &transaction {
+			println!("txid: {}", tx.txid());
+			println!("hash: {}", tx.wtxid());
+			println!("size: {}", tx.size());
+			println!("vsize: {}", tx.vsize());
+			println!("weight: {}", tx.weight());
+			// the signatures can only be checked once the spent outputs are known
+			match tx.verify() {
+				Ok(()) => println!("valid signatures: true"),
+				Err(sign::VerifyError::MissingPrevout(_)) => {}
+				Err(e) => println!("valid signatures: false ({})", e),
+			}
+		}
 		println!("{:#?}", transaction);
 	} else if option == 4 {
 		println!("Enter a raw script hex");
 		let hexbytes = io::stdin().lock().user_read_hex_var();
-		let script = ScriptPubKey::decode_raw(hexbytes);
+		let mut script = ScriptPubKey::decode_raw(hexbytes);
+		if let Ok(script) = &mut script {
+			script.set_network(network);
+		}
 		println!();
 		println!("{:#?}", script);
+	} else if option == 5 {
+		println!("Enter the script sig hex");
+		let script_sig = ScriptSig::decode_raw(io::stdin().lock().user_read_hex_var());
+		println!("Enter the script pub key hex");
+		let script_pub_key = ScriptPubKey::decode_raw(io::stdin().lock().user_read_hex_var());
+		let (script_sig, script_pub_key) = match (script_sig, script_pub_key) {
+			(Ok(sig), Ok(pub_key)) => (sig, pub_key),
+			(Err(e), _) | (_, Err(e)) => {
+				println!("Couldn't parse the scripts: {}", e);
+				return;
+			}
+		};
+		// Signatures can't be checked without the spending transaction
+		let mut interpreter = Interpreter::new(BaseSignatureChecker);
+		let result = interpreter.verify(&script_sig, &script_pub_key);
+		println!();
+		match result {
+			Ok(()) => println!("Script executed successfully"),
+			Err(e) => println!("Script failed: {}", e),
+		}
+		println!("{:#?}", interpreter);
+	} else if option == 6 {
+		println!("Enter a raw block hex");
+		let hexbytes = io::stdin().lock().user_read_hex_var();
+		let mut block = Block::decode_raw(hexbytes);
+		if let Ok(block) = &mut block {
+			block.transactions.iter_mut().for_each(|tx| tx.set_network(network));
+		}
+		println!();
+		if let Ok(block) = &block {
+			println!("hash: {}", block.block_hash());
+			println!("valid proof of work: {}", block.header.check_pow());
+			println!("valid merkle root: {}", block.check_merkle_root());
+		}
+		println!("{:#?}", block);
 	} else {
-		todo!()
+		println!("Unknown option {}", option);
+	}
+}
+
+/// Addresses depend on the network, ask for it until a known one is entered.
+fn read_network<R: BufRead>(mut reader: R) -> Network {
+	println!("Which network? (enter main, test, signet or regtest)");
+	loop {
+		let mut line = String::new();
+		match reader.read_line(&mut line) {
+			Ok(_) => match Network::from_str(line.trim()) {
+				Ok(network) => return network,
+				Err(e) => println!("{}. Try again.", e),
+			},
+			Err(e) => println!("{}! Try again.", e),
+		}
+	}
+}
+
+/// The fee is only known with the outputs spent by the inputs. Ask where to find them, nothing is
+/// looked up unless a file or server is given.
+fn read_prevout_provider<R: BufRead>(mut reader: R) -> Box<dyn PrevoutProvider> {
+	println!("Look up the spent outputs?");
+	println!("1. No");
+	println!("2. From a UTXO file (.json or .csv)");
+	println!("3. From an Esplora server");
+	println!("4. From a bitcoind node (needs -txindex)");
+	loop {
+		let option = reader.user_read_u32();
+		if option == 1 {
+			return Box::new(NoPrevouts);
+		} else if option == 2 {
+			println!("Enter the path of the file");
+			let mut line = String::new();
+			if let Err(e) = reader.read_line(&mut line) {
+				println!("{}! Try again.", e);
+				continue;
+			}
+			match MemoryPrevouts::from_file(Path::new(line.trim())) {
+				Ok(prevouts) => return Box::new(prevouts),
+				Err(e) => println!("{}. Try again.", e),
+			}
+		} else if option == 3 {
+			println!("Enter the url of the server (e.g. http://localhost:3002)");
+			let mut line = String::new();
+			match reader.read_line(&mut line) {
+				Ok(_) => return Box::new(EsploraClient::new(line.trim())),
+				Err(e) => println!("{}! Try again.", e),
+			}
+		} else if option == 4 {
+			println!("Enter the RPC url, user and password separated by spaces (e.g. http://localhost:8332 user password)");
+			let mut line = String::new();
+			if let Err(e) = reader.read_line(&mut line) {
+				println!("{}! Try again.", e);
+				continue;
+			}
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			match fields[..] {
+				[url] => return Box::new(BitcoindClient::new(url, None)),
+				[url, user, password] => return Box::new(BitcoindClient::new(url, Some((user, password)))),
+				_ => println!("Expected a url, optionally followed by a user and password. Try again."),
+			}
+		} else {
+			println!("Unknown option {}. Try again.", option);
+		}
 	}
 }
@@ -0,0 +1,535 @@
+// Merkle trees commit to the transactions of a block. Hashes here are in internal byte order,
+// i.e. reversed compared to how txids are displayed.
+//
+// A partial merkle tree (BIP37) proves that some transactions are in a block without the whole
+// block: it only has the hashes needed to recompute the merkle root. bitcoind's `gettxoutproof`
+// returns one together with the block header, which is a `MerkleBlock` here.
+
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+use crate::block::{Block, BlockHeader};
+use crate::error::DecodeError;
+use crate::hash;
+use crate::txio::{check_consumed, Decodable, Encodable, HexBytes, ReadExt, WriteExt};
+use crate::Deserialize;
+
+/// A block can't contain more transactions than fit in its weight, 4000000 / 240 (the weight of
+/// the smallest transaction).
+const MAX_TRANSACTIONS: u32 = 16666;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+	NoTransactions,
+	TooManyTransactions(u32),
+	/// Building a tree needs a match flag for every txid.
+	MatchCountMismatch { txids: usize, matches: usize },
+	/// More hashes than transactions.
+	TooManyHashes,
+	NotEnoughHashes,
+	NotEnoughBits,
+	NotAllHashesConsumed,
+	NotAllBitsConsumed,
+	/// Both children of a node are the same, see CVE-2012-2459.
+	IdenticalHashesFound,
+	/// The tree doesn't hash to the merkle root of the block header.
+	MerkleRootMismatch,
+}
+
+impl fmt::Display for MerkleError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MerkleError::NoTransactions => write!(f, "the tree has no transactions"),
+			MerkleError::TooManyTransactions(n) => write!(f, "{} transactions don't fit in a block", n),
+			MerkleError::MatchCountMismatch { txids, matches } => write!(f, "{} match flags for {} txids", matches, txids),
+			MerkleError::TooManyHashes => write!(f, "the tree has more hashes than transactions"),
+			MerkleError::NotEnoughHashes => write!(f, "ran out of hashes while traversing the tree"),
+			MerkleError::NotEnoughBits => write!(f, "ran out of flag bits while traversing the tree"),
+			MerkleError::NotAllHashesConsumed => write!(f, "not all hashes were used"),
+			MerkleError::NotAllBitsConsumed => write!(f, "not all flag bits were used"),
+			MerkleError::IdenticalHashesFound => write!(f, "found identical left and right hashes"),
+			MerkleError::MerkleRootMismatch => write!(f, "the merkle root doesn't match the block header"),
+		}
+	}
+}
+
+impl Error for MerkleError {}
+
+/// Compute the merkle root of a list of hashes. Each level hashes pairs of nodes with hash256,
+/// duplicating the last node when a level has an odd number of them.
+#[cfg(test)]
+pub fn merkle_root(hashes: &[HexBytes]) -> Option<HexBytes> {
+	merkle_root_mutated(hashes).map(|(root, _)| root)
+}
+
+/// Same as `merkle_root`, also telling if two identical hashes were paired. Duplicating the
+/// trailing hashes of a level gives the same root, so such a list can't be trusted to be the one
+/// the root commits to (CVE-2012-2459).
+fn merkle_root_mutated(hashes: &[HexBytes]) -> Option<(HexBytes, bool)> {
+	if hashes.is_empty() {
+		return None;
+	}
+	let mut mutated = false;
+	let mut level = hashes.to_vec();
+	while level.len() > 1 {
+		level = level.chunks(2)
+			.map(|pair| {
+				if pair.len() == 2 && pair[0] == pair[1] {
+					mutated = true;
+				}
+				hash_pair(&pair[0], pair.last().expect("chunks aren't empty"))
+			})
+			.collect();
+	}
+	level.pop().map(|root| (root, mutated))
+}
+
+fn hash_pair(left: &[u8], right: &[u8]) -> HexBytes {
+	let mut bytes = left.to_vec();
+	bytes.extend_from_slice(right);
+	hash::hash256(&bytes).into_boxed_slice()
+}
+
+impl Block {
+	/// Merkle root of the txids of the block, in display order.
+	#[cfg(test)]
+	pub fn compute_merkle_root(&self) -> Option<String> {
+		merkle_root(&self.txids()).map(|root| root.encode_hex_le())
+	}
+
+	/// Check that the header commits to the transactions of the block. Like bitcoind, a block
+	/// with duplicated transactions that still hash to the root is rejected as mutated.
+	pub fn check_merkle_root(&self) -> bool {
+		match merkle_root_mutated(&self.txids()) {
+			Some((root, mutated)) => !mutated && *root == self.header.merkle_root,
+			None => false,
+		}
+	}
+
+	fn txids(&self) -> Vec<HexBytes> {
+		self.transactions.iter()
+			.map(|tx| tx.txid().decode_hex_le().expect("txid is valid hex"))
+			.collect()
+	}
+}
+
+/// The tree is walked depth first. For every node visited there's a bit saying whether a matched
+/// transaction is below it. Nodes without a match below them, and matched leaves, are not
+/// descended into and their hash is included instead.
+#[derive(Debug, PartialEq)]
+pub struct PartialMerkleTree {
+	/// Number of transactions in the block.
+	pub tx_count: u32,
+	pub hashes: Vec<HexBytes>,
+	pub bits: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+	/// Build a tree proving the txids (internal byte order) whose entry in `matches` is true.
+	pub fn from_txids(txids: &[HexBytes], matches: &[bool]) -> Result<Self, MerkleError> {
+		if txids.len() != matches.len() {
+			return Err(MerkleError::MatchCountMismatch { txids: txids.len(), matches: matches.len() });
+		}
+		if txids.is_empty() {
+			return Err(MerkleError::NoTransactions);
+		}
+		let tx_count = u32::try_from(txids.len()).unwrap_or(u32::MAX);
+		if tx_count > MAX_TRANSACTIONS {
+			return Err(MerkleError::TooManyTransactions(tx_count));
+		}
+		let mut tree = PartialMerkleTree {
+			tx_count,
+			hashes: Vec::new(),
+			bits: Vec::new(),
+		};
+		let height = tree.height();
+		tree.traverse_and_build(height, 0, txids, matches);
+		Ok(tree)
+	}
+
+	/// Recompute the merkle root, collecting the matched txids and their position in the block.
+	pub fn extract_matches(&self, matches: &mut Vec<HexBytes>, indexes: &mut Vec<u32>) -> Result<HexBytes, MerkleError> {
+		matches.clear();
+		indexes.clear();
+		if self.tx_count == 0 {
+			return Err(MerkleError::NoTransactions);
+		}
+		if self.tx_count > MAX_TRANSACTIONS {
+			return Err(MerkleError::TooManyTransactions(self.tx_count));
+		}
+		if self.hashes.len() > self.tx_count as usize {
+			return Err(MerkleError::TooManyHashes);
+		}
+		// there's at least one bit per hash
+		if self.bits.len() < self.hashes.len() {
+			return Err(MerkleError::NotEnoughBits);
+		}
+
+		let mut bits_used = 0;
+		let mut hashes_used = 0;
+		let root = self.traverse_and_extract(self.height(), 0, &mut bits_used, &mut hashes_used, matches, indexes)?;
+		// only the padding of the last byte can be left
+		if bits_used.div_ceil(8) != self.bits.len().div_ceil(8) {
+			return Err(MerkleError::NotAllBitsConsumed);
+		}
+		if hashes_used != self.hashes.len() {
+			return Err(MerkleError::NotAllHashesConsumed);
+		}
+		Ok(root)
+	}
+
+	fn height(&self) -> u32 {
+		let mut height = 0;
+		while self.tree_width(height) > 1 {
+			height += 1;
+		}
+		height
+	}
+
+	/// Number of nodes at a height, leaves being at height 0.
+	fn tree_width(&self, height: u32) -> u32 {
+		(self.tx_count + (1 << height) - 1) >> height
+	}
+
+	fn calc_hash(&self, height: u32, pos: u32, txids: &[HexBytes]) -> HexBytes {
+		if height == 0 {
+			return txids[pos as usize].clone();
+		}
+		let left = self.calc_hash(height - 1, pos * 2, txids);
+		let right = if pos * 2 + 1 < self.tree_width(height - 1) {
+			self.calc_hash(height - 1, pos * 2 + 1, txids)
+		} else {
+			left.clone()
+		};
+		hash_pair(&left, &right)
+	}
+
+	fn traverse_and_build(&mut self, height: u32, pos: u32, txids: &[HexBytes], matches: &[bool]) {
+		let start = (pos << height) as usize;
+		let end = (((pos + 1) << height) as usize).min(txids.len());
+		let parent_of_match = matches[start..end].iter().any(|m| *m);
+		self.bits.push(parent_of_match);
+
+		if height == 0 || !parent_of_match {
+			let hash = self.calc_hash(height, pos, txids);
+			self.hashes.push(hash);
+		} else {
+			self.traverse_and_build(height - 1, pos * 2, txids, matches);
+			if pos * 2 + 1 < self.tree_width(height - 1) {
+				self.traverse_and_build(height - 1, pos * 2 + 1, txids, matches);
+			}
+		}
+	}
+
+	fn traverse_and_extract(&self, height: u32, pos: u32, bits_used: &mut usize, hashes_used: &mut usize, matches: &mut Vec<HexBytes>, indexes: &mut Vec<u32>) -> Result<HexBytes, MerkleError> {
+		let parent_of_match = *self.bits.get(*bits_used).ok_or(MerkleError::NotEnoughBits)?;
+		*bits_used += 1;
+
+		if height == 0 || !parent_of_match {
+			let hash = self.hashes.get(*hashes_used).ok_or(MerkleError::NotEnoughHashes)?.clone();
+			*hashes_used += 1;
+			if height == 0 && parent_of_match {
+				matches.push(hash.clone());
+				indexes.push(pos);
+			}
+			return Ok(hash);
+		}
+
+		let left = self.traverse_and_extract(height - 1, pos * 2, bits_used, hashes_used, matches, indexes)?;
+		let right = if pos * 2 + 1 < self.tree_width(height - 1) {
+			let right = self.traverse_and_extract(height - 1, pos * 2 + 1, bits_used, hashes_used, matches, indexes)?;
+			// Otherwise a tree with a duplicated transaction would have the same root
+			if right == left {
+				return Err(MerkleError::IdenticalHashesFound);
+			}
+			right
+		} else {
+			left.clone()
+		};
+		Ok(hash_pair(&left, &right))
+	}
+
+	fn read_from(stream: &mut Cursor<HexBytes>) -> Result<Self, DecodeError> {
+		let tx_count = stream.read_u32_le()?;
+		let hash_count = stream.read_compact_size()?;
+		let mut hashes = Vec::new();
+		for _ in 0..hash_count {
+			hashes.push(stream.read_hex256()?);
+		}
+		// bits are packed 8 per byte, least significant bit first
+		let byte_count = stream.read_compact_size()?;
+		let bytes = stream.read_hex_var(byte_count)?;
+		let bits = (0..bytes.len() * 8).map(|i| bytes[i / 8] & (1 << (i % 8)) != 0).collect();
+		Ok(PartialMerkleTree { tx_count, hashes, bits })
+	}
+
+	fn write_to(&self, stream: &mut Vec<u8>) {
+		stream.write_u32_le(self.tx_count);
+		stream.write_varint(self.hashes.len() as u64);
+		for hash in &self.hashes {
+			stream.write_hex(hash.clone(), false);
+		}
+		let mut bytes = vec![0u8; self.bits.len().div_ceil(8)];
+		for (i, bit) in self.bits.iter().enumerate() {
+			bytes[i / 8] |= (*bit as u8) << (i % 8);
+		}
+		stream.write_hex(bytes.into_boxed_slice(), true);
+	}
+}
+
+/// A block header and a partial merkle tree of some of its transactions, the `merkleblock`
+/// message and the result of `gettxoutproof`.
+#[derive(Debug, PartialEq)]
+pub struct MerkleBlock {
+	pub header: BlockHeader,
+	pub txn: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+	/// Prove that the given txids (display order) are in the block. Txids that aren't in the
+	/// block are ignored.
+	pub fn from_block(block: &Block, txids: &[String]) -> Result<Self, MerkleError> {
+		let block_txids: Vec<String> = block.transactions.iter().map(|tx| tx.txid()).collect();
+		let matches: Vec<bool> = block_txids.iter().map(|txid| txids.contains(txid)).collect();
+		let block_txids: Vec<HexBytes> = block_txids.iter()
+			.map(|txid| txid.decode_hex_le().expect("txid is valid hex"))
+			.collect();
+
+		Ok(MerkleBlock {
+			header: block.header.clone(),
+			txn: PartialMerkleTree::from_txids(&block_txids, &matches)?,
+		})
+	}
+
+	/// Check the proof against the header and return the txids it proves, in display order.
+	pub fn verify(&self) -> Result<Vec<String>, MerkleError> {
+		let mut matches = Vec::new();
+		let mut indexes = Vec::new();
+		let root = self.txn.extract_matches(&mut matches, &mut indexes)?;
+		if *root != self.header.merkle_root {
+			return Err(MerkleError::MerkleRootMismatch);
+		}
+		Ok(matches.iter().map(|txid| txid.encode_hex_le()).collect())
+	}
+}
+
+impl Deserialize for MerkleBlock {
+	fn decode_raw(bytes: HexBytes) -> Result<Self, Box<dyn Error>> {
+		let mut stream = Cursor::new(bytes);
+		let header = BlockHeader::read_from(&mut stream)?;
+		let txn = PartialMerkleTree::read_from(&mut stream)?;
+		check_consumed(&stream)?;
+		Ok(MerkleBlock { header, txn })
+	}
+
+	fn as_bytes(&self) -> HexBytes {
+		let mut stream = self.header.as_bytes().to_vec();
+		self.txn.write_to(&mut stream);
+		stream.into_boxed_slice()
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use crate::block::Block;
+	use crate::transaction::Transaction;
+	use crate::Deserialize;
+	use crate::txio::{Decodable, Encodable, HexBytes};
+	use super::{merkle_root, MerkleBlock, MerkleError, PartialMerkleTree};
+
+	// Block 0000000000013b8ab2cd513b0261a14096412195a72a0c4827d229dcc7e0f7af with 9 transactions.
+	pub(crate) const BLOCK: &str = "0100000090f0a9f110702f808219ebea1173056042a714bad51b916cb6800000000000005275289558f51c9966699404ae2294730c\
+	3c9f9bda53523ce50e9b95e558da2fdb261b4d4c86041b1ab1bf9309010000000100000000000000000000000000000000000000000000\
+	00000000000000000000ffffffff07044c86041b0146ffffffff0100f2052a01000000434104e18f7afbe4721580e81e8414fc8c24d7cf\
+	acf254bb5c7b949450c3e997c2dc1242487a8169507b631eb3771f2b425483fb13102c4eb5d858eef260fe70fbfae0ac00000000010000\
+	000196608ccbafa16abada902780da4dc35dafd7af05fa0da08cf833575f8cf9e836000000004a493046022100dab24889213caf43ae6a\
+	dc41cf1c9396c08240c199f5225acf45416330fd7dbd022100fe37900e0644bf574493a07fc5edba06dbc07c311b947520c2d514bc5725\
+	dcb401ffffffff0100f2052a010000001976a914f15d1921f52e4007b146dfa60f369ed2fc393ce288ac000000000100000001fb766c12\
+	88458c2bafcfec81e48b24d98ec706de6b8af7c4e3c29419bfacb56d000000008c493046022100f268ba165ce0ad2e6d93f089cfcd3785\
+	de5c963bb5ea6b8c1b23f1ce3e517b9f022100da7c0f21adc6c401887f2bfd1922f11d76159cbc597fbd756a23dcbb00f4d7290141042b\
+	4e8625a96127826915a5b109852636ad0da753c9e1d5606a50480cd0c40f1f8b8d898235e571fe9357d9ec842bc4bba1827daaf4de06d7\
+	1844d0057707966affffffff0280969800000000001976a9146963907531db72d0ed1a0cfb471ccb63923446f388ac80d6e34c00000000\
+	1976a914f0688ba1c0d1ce182c7af6741e02658c7d4dfcd388ac000000000100000002c40297f730dd7b5a99567eb8d27b78758f607507\
+	c52292d02d4031895b52f2ff010000008b483045022100f7edfd4b0aac404e5bab4fd3889e0c6c41aa8d0e6fa122316f68eddd0a650139\
+	02205b09cc8b2d56e1cd1f7f2fafd60a129ed94504c4ac7bdc67b56fe67512658b3e014104732012cb962afa90d31b25d8fb0e32c94e51\
+	3ab7a17805c14ca4c3423e18b4fb5d0e676841733cb83abaf975845c9f6f2a8097b7d04f4908b18368d6fc2d68ecffffffffca5065ff96\
+	17cbcba45eb23726df6498a9b9cafed4f54cbab9d227b0035ddefb000000008a473044022068010362a13c7f9919fa832b2dee4e788f61\
+	f6f5d344a7c2a0da6ae740605658022006d1af525b9a14a35c003b78b72bd59738cd676f845d1ff3fc25049e01003614014104732012cb\
+	962afa90d31b25d8fb0e32c94e513ab7a17805c14ca4c3423e18b4fb5d0e676841733cb83abaf975845c9f6f2a8097b7d04f4908b18368\
+	d6fc2d68ecffffffff01001ec4110200000043410469ab4181eceb28985b9b4e895c13fa5e68d85761b7eee311db5addef76fa86218651\
+	34a221bd01f28ec9999ee3e021e60766e9d1f3458c115fb28650605f11c9ac000000000100000001cdaf2f758e91c514655e2dc50633d1\
+	e4c84989f8aa90a0dbc883f0d23ed5c2fa010000008b48304502207ab51be6f12a1962ba0aaaf24a20e0b69b27a94fac5adf45aa7d2d18\
+	ffd9236102210086ae728b370e5329eead9accd880d0cb070aea0c96255fae6c4f1ddcce1fd56e014104462e76fd4067b3a0aa42070082\
+	dcb0bf2f388b6495cf33d789904f07d0f55c40fbd4b82963c69b3dc31895d0c772c812b1d5fbcade15312ef1c0e8ebbb12dcd4ffffffff\
+	02404b4c00000000001976a9142b6ba7c9d796b75eef7942fc9288edd37c32f5c388ac002d3101000000001976a9141befba0cdc1ad565\
+	29371864d9f6cb042faa06b588ac000000000100000001b4a47603e71b61bc3326efd90111bf02d2f549b067f4c4a8fa183b57a0f800cb\
+	010000008a4730440220177c37f9a505c3f1a1f0ce2da777c339bd8339ffa02c7cb41f0a5804f473c9230220585b25a2ee80eb59292e52\
+	b987dad92acb0c64eced92ed9ee105ad153cdb12d001410443bd44f683467e549dae7d20d1d79cbdb6df985c6e9c029c8d0c6cb46cc1a4\
+	d3cf7923c5021b27f7a0b562ada113bc85d5fda5a1b41e87fe6e8802817cf69996ffffffff0280651406000000001976a9145505614859\
+	643ab7b547cd7f1f5e7e2a12322d3788ac00aa0271000000001976a914ea4720a7a52fc166c55ff2298e07baf70ae67e1b88ac00000000\
+	010000000586c62cd602d219bb60edb14a3e204de0705176f9022fe49a538054fb14abb49e010000008c493046022100f2bc2aba2534be\
+	cbdf062eb993853a42bbbc282083d0daf9b4b585bd401aa8c9022100b1d7fd7ee0b95600db8535bbf331b19eed8d961f7a8e54159c5367\
+	5d5f69df8c014104462e76fd4067b3a0aa42070082dcb0bf2f388b6495cf33d789904f07d0f55c40fbd4b82963c69b3dc31895d0c772c8\
+	12b1d5fbcade15312ef1c0e8ebbb12dcd4ffffffff03ad0e58ccdac3df9dc28a218bcf6f1997b0a93306faaa4b3a28ae83447b21790100\
+	00008b483045022100be12b2937179da88599e27bb31c3525097a07cdb52422d165b3ca2f2020ffcf702200971b51f853a53d644ebae9e\
+	c8f3512e442b1bcb6c315a5b491d119d10624c83014104462e76fd4067b3a0aa42070082dcb0bf2f388b6495cf33d789904f07d0f55c40\
+	fbd4b82963c69b3dc31895d0c772c812b1d5fbcade15312ef1c0e8ebbb12dcd4ffffffff2acfcab629bbc8685792603762c921580030ba\
+	144af553d271716a95089e107b010000008b483045022100fa579a840ac258871365dd48cd7552f96c8eea69bd00d84f05b283a0dab311\
+	e102207e3c0ee9234814cfbb1b659b83671618f45abc1326b9edcc77d552a4f2a805c0014104462e76fd4067b3a0aa42070082dcb0bf2f\
+	388b6495cf33d789904f07d0f55c40fbd4b82963c69b3dc31895d0c772c812b1d5fbcade15312ef1c0e8ebbb12dcd4ffffffffdcdc6023\
+	bbc9944a658ddc588e61eacb737ddf0a3cd24f113b5a8634c517fcd2000000008b4830450221008d6df731df5d32267954bd7d2dda2302\
+	b74c6c2a6aa5c0ca64ecbabc1af03c75022010e55c571d65da7701ae2da1956c442df81bbf076cdbac25133f99d98a9ed34c014104462e\
+	76fd4067b3a0aa42070082dcb0bf2f388b6495cf33d789904f07d0f55c40fbd4b82963c69b3dc31895d0c772c812b1d5fbcade15312ef1\
+	c0e8ebbb12dcd4ffffffffe15557cd5ce258f479dfd6dc6514edf6d7ed5b21fcfa4a038fd69f06b83ac76e010000008b483045022023b3\
+	e0ab071eb11de2eb1cc3a67261b866f86bf6867d4558165f7c8c8aca2d86022100dc6e1f53a91de3efe8f63512850811f26284b62f850c\
+	70ca73ed5de8771fb451014104462e76fd4067b3a0aa42070082dcb0bf2f388b6495cf33d789904f07d0f55c40fbd4b82963c69b3dc318\
+	95d0c772c812b1d5fbcade15312ef1c0e8ebbb12dcd4ffffffff01404b4c00000000001976a9142b6ba7c9d796b75eef7942fc9288edd3\
+	7c32f5c388ac00000000010000000166d7577163c932b4f9690ca6a80b6e4eb001f0a2fa9023df5595602aae96ed8d000000008a473044\
+	0220262b42546302dfb654a229cefc86432b89628ff259dc87edd1154535b16a67e102207b4634c020a97c3e7bbd0d4d19da6aa2269ad9\
+	dded4026e896b213d73ca4b63f014104979b82d02226b3a4597523845754d44f13639e3bf2df5e82c6aab2bdc79687368b01b1ab8b1987\
+	5ae3c90d661a3d0a33161dab29934edeb36aa01976be3baf8affffffff02404b4c00000000001976a9144854e695a02af0aeacb823ccbc\
+	272134561e0a1688ac40420f00000000001976a914abee93376d6b37b5c2940655a6fcaf1c8e74237988ac0000000001000000014e3f8e\
+	f2e91349a9059cb4f01e54ab2597c1387161d3da89919f7ea6acdbb371010000008c49304602210081f3183471a5ca22307c0800226f3e\
+	f9c353069e0773ac76bb580654d56aa523022100d4c56465bdc069060846f4fbf2f6b20520b2a80b08b168b31e66ddb9c694e240014104\
+	976c79848e18251612f8940875b2b08d06e6dc73b9840e8860c066b7e87432c477e9a59a453e71e6d76d5fe34058b800a098fc1740ce30\
+	12e8fc8a00c96af966ffffffff02c0e1e400000000001976a9144134e75a6fcb6042034aab5e18570cf1f844f54788ac404b4c00000000\
+	001976a9142b6ba7c9d796b75eef7942fc9288edd37c32f5c388ac00000000";
+
+	fn txid(n: u8) -> HexBytes {
+		let mut hash = [0u8; 32];
+		hash[0] = n;
+		Box::new(hash)
+	}
+
+	#[test]
+	fn block_merkle_root() {
+		let mut block = Block::decode_raw(BLOCK.decode_hex_be().unwrap()).unwrap();
+		assert_eq!(block.compute_merkle_root().unwrap(), block.header.merkle_root.encode_hex_le());
+		assert!(block.check_merkle_root());
+
+		block.transactions.swap(1, 2);
+		assert!(!block.check_merkle_root());
+	}
+
+	#[test]
+	fn mutated_block_merkle_root() {
+		// CVE-2012-2459: the 9th transaction is paired with itself, adding a copy of it gives the
+		// same merkle root
+		let mut block = Block::decode_raw(BLOCK.decode_hex_be().unwrap()).unwrap();
+		let last = Transaction::decode_raw(block.transactions[8].as_bytes()).unwrap();
+		block.transactions.push(last);
+		assert_eq!(block.compute_merkle_root().unwrap(), block.header.merkle_root.encode_hex_le());
+		assert!(!block.check_merkle_root());
+	}
+
+	#[test]
+	fn merkle_root_odd_levels() {
+		assert_eq!(merkle_root(&[]), None);
+		assert_eq!(merkle_root(&[txid(1)]), Some(txid(1)));
+
+		// the last hash of a level with an odd number of hashes is paired with itself
+		let ab = super::hash_pair(&txid(1), &txid(2));
+		let cc = super::hash_pair(&txid(3), &txid(3));
+		assert_eq!(merkle_root(&[txid(1), txid(2), txid(3)]), Some(super::hash_pair(&ab, &cc)));
+	}
+
+	#[test]
+	fn partial_merkle_tree_round_trip() {
+		for tx_count in [1u8, 2, 3, 4, 7, 9, 16, 17] {
+			let txids: Vec<HexBytes> = (0..tx_count).map(txid).collect();
+			let root = merkle_root(&txids).unwrap();
+
+			for step in 1..=tx_count {
+				let matches: Vec<bool> = (0..tx_count).map(|i| i % step == 0).collect();
+				let tree = PartialMerkleTree::from_txids(&txids, &matches).unwrap();
+
+				let mut found = Vec::new();
+				let mut indexes = Vec::new();
+				assert_eq!(tree.extract_matches(&mut found, &mut indexes).unwrap(), root);
+				let expected: Vec<u32> = (0..tx_count as u32).filter(|i| i % step as u32 == 0).collect();
+				assert_eq!(indexes, expected);
+				assert_eq!(found, expected.iter().map(|i| txids[*i as usize].clone()).collect::<Vec<_>>());
+			}
+		}
+	}
+
+	#[test]
+	fn partial_merkle_tree_malleability() {
+		// the last two txids repeat, which gives the same merkle root as 10 transactions
+		let txids: Vec<HexBytes> = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 9, 10].iter().map(|i| txid(*i)).collect();
+		let matches = [false, false, false, false, false, false, false, false, false, true, true, false];
+		let tree = PartialMerkleTree::from_txids(&txids, &matches).unwrap();
+		assert_eq!(tree.extract_matches(&mut vec![], &mut vec![]), Err(MerkleError::IdenticalHashesFound));
+	}
+
+	#[test]
+	fn partial_merkle_tree_errors() {
+		let txids: Vec<HexBytes> = (0..5).map(txid).collect();
+		assert_eq!(PartialMerkleTree::from_txids(&txids, &[true]), Err(MerkleError::MatchCountMismatch { txids: 5, matches: 1 }));
+		assert_eq!(PartialMerkleTree::from_txids(&[], &[]), Err(MerkleError::NoTransactions));
+		let mut tree = PartialMerkleTree::from_txids(&txids, &[false, true, false, false, false]).unwrap();
+
+		tree.hashes.push(txid(9));
+		assert_eq!(tree.extract_matches(&mut vec![], &mut vec![]), Err(MerkleError::NotAllHashesConsumed));
+		tree.hashes.truncate(1);
+		assert_eq!(tree.extract_matches(&mut vec![], &mut vec![]), Err(MerkleError::NotEnoughHashes));
+
+		let tree = PartialMerkleTree { tx_count: 0, hashes: vec![], bits: vec![] };
+		assert_eq!(tree.extract_matches(&mut vec![], &mut vec![]), Err(MerkleError::NoTransactions));
+	}
+
+	#[test]
+	fn gettxoutproof() {
+		// gettxoutproof '["220ebc64e21abece964927322cba69180ed853bb187fbc6923bac7d010b9d87a"]'
+		let proof = "0100000090f0a9f110702f808219ebea1173056042a714bad51b916cb6800000000000005275289558f51c\
+		9966699404ae2294730c3c9f9bda53523ce50e9b95e558da2fdb261b4d4c86041b1ab1bf930900000005fac\
+		7708a6e81b2a986dea60db2663840ed141130848162eb1bd1dee54f309a1b2ee1e12587e497ada70d9bd10d\
+		31e83f0a924825b96cb8d04e8936d793fb60db7ad8b910d0c7ba2369bc7f18bb53d80e1869ba2c32274996c\
+		ebe1ae264bc0e2289189ff0316cdc10511da71da757e553cada9f3b5b1434f3923673adb57d83caac392c38\
+		af156d6fc30b55fad4112df2b95531e68114e9ad10011e72f7b7cfdb025700";
+		let bytes = proof.decode_hex_be().unwrap();
+		let merkle_block = MerkleBlock::decode_raw(bytes.clone()).unwrap();
+
+		assert_eq!(merkle_block.header.block_hash(), "0000000000013b8ab2cd513b0261a14096412195a72a0c4827d229dcc7e0f7af");
+		assert_eq!(merkle_block.txn.tx_count, 9);
+		assert_eq!(merkle_block.verify().unwrap(), vec!["220ebc64e21abece964927322cba69180ed853bb187fbc6923bac7d010b9d87a"]);
+		assert_eq!(merkle_block.as_bytes(), bytes);
+
+		// the same proof built from the block, decoded bits include the padding of the last byte
+		let block = Block::decode_raw(BLOCK.decode_hex_be().unwrap()).unwrap();
+		let txids = vec!["220ebc64e21abece964927322cba69180ed853bb187fbc6923bac7d010b9d87a".to_string()];
+		assert_eq!(MerkleBlock::from_block(&block, &txids).unwrap().as_bytes(), bytes);
+
+		let mut merkle_block = merkle_block;
+		merkle_block.txn.hashes[0][0] ^= 1;
+		assert_eq!(merkle_block.verify(), Err(MerkleError::MerkleRootMismatch));
+	}
+
+	#[test]
+	fn merkle_block_from_block() {
+		let block = Block::decode_raw(BLOCK.decode_hex_be().unwrap()).unwrap();
+		let txids = vec![
+			"74d681e0e03bafa802c8aa084379aa98d9fcd632ddc2ed9782b586ec87451f20".to_string(),
+			"f9fc751cb7dc372406a9f8d738d5e6f8f63bab71986a39cf36ee70ee17036d07".to_string(),
+			"c0ffee00003bafa802c8aa084379aa98d9fcd632ddc2ed9782b586ec87451f20".to_string(),
+		];
+		let merkle_block = MerkleBlock::from_block(&block, &txids).unwrap();
+		assert_eq!(merkle_block.header.block_hash(), block.block_hash());
+
+		// in block order, the txid that isn't in the block is left out
+		assert_eq!(merkle_block.verify().unwrap(), vec![txids[1].clone(), txids[0].clone()]);
+
+		let mut indexes = Vec::new();
+		merkle_block.txn.extract_matches(&mut vec![], &mut indexes).unwrap();
+		assert_eq!(indexes, vec![1, 8]);
+
+		let merkle_block = MerkleBlock::from_block(&block, &[]).unwrap();
+		assert_eq!(merkle_block.verify().unwrap(), Vec::<String>::new());
+		assert_eq!(merkle_block.txn.hashes.len(), 1);
+
+		// nothing can be proven about a block without transactions
+		let mut empty = Block::decode_raw(BLOCK.decode_hex_be().unwrap()).unwrap();
+		empty.transactions.clear();
+		assert_eq!(MerkleBlock::from_block(&empty, &txids), Err(MerkleError::NoTransactions));
+	}
+}
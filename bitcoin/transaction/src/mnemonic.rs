@@ -0,0 +1,304 @@
+// Mnemonic sentences (BIP39). A wallet's seed is encoded as words so that it can be written down
+// and typed back in. The entropy is followed by a checksum of its first bits, every 11 bits are
+// then the index of a word in the wordlist. The words and an optional passphrase are stretched
+// into a 64 byte seed with PBKDF2, which is what the BIP32 master key is created from.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use secp256k1::rand::RngCore;
+use unicode_normalization::UnicodeNormalization;
+use crate::hash;
+use crate::key::{ExtendedPrivateKey, KeyError};
+use crate::network::Network;
+use crate::wordlist::ENGLISH;
+
+/// Iterations of PBKDF2, to make brute forcing a passphrase slow.
+const PBKDF2_ROUNDS: u32 = 2048;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MnemonicError {
+	/// Entropy is 16 to 32 bytes, a multiple of 4.
+	InvalidEntropyLength(usize),
+	/// A mnemonic is 12, 15, 18, 21 or 24 words.
+	InvalidWordCount(usize),
+	UnknownWord(String),
+	InvalidChecksum,
+}
+
+impl fmt::Display for MnemonicError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MnemonicError::InvalidEntropyLength(len) => write!(f, "invalid entropy length of {} bytes", len),
+			MnemonicError::InvalidWordCount(count) => write!(f, "invalid mnemonic of {} words", count),
+			MnemonicError::UnknownWord(word) => write!(f, "\"{}\" is not in the wordlist", word),
+			MnemonicError::InvalidChecksum => write!(f, "mnemonic checksum mismatch"),
+		}
+	}
+}
+
+impl Error for MnemonicError {}
+
+/// A mnemonic with the English wordlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+	entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+	/// 16 bytes of entropy give 12 words, every 4 more bytes 3 more words up to 24.
+	pub fn from_entropy(entropy: &[u8]) -> Result<Self, MnemonicError> {
+		if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+			return Err(MnemonicError::InvalidEntropyLength(entropy.len()))
+		}
+		Ok(Mnemonic { entropy: entropy.to_vec() })
+	}
+
+	/// Generate a new mnemonic of 12 to 24 words using the OS random number generator.
+	pub fn generate(word_count: usize) -> Result<Self, MnemonicError> {
+		if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+			return Err(MnemonicError::InvalidWordCount(word_count))
+		}
+		let mut entropy = vec![0; word_count / 3 * 4];
+		secp256k1::rand::thread_rng().fill_bytes(&mut entropy);
+		Mnemonic::from_entropy(&entropy)
+	}
+
+	pub fn entropy(&self) -> &[u8] {
+		&self.entropy
+	}
+
+	/// The entropy followed by the first entropy length / 32 bits of its sha256, split into 11 bit
+	/// word indexes.
+	pub fn words(&self) -> Vec<&'static str> {
+		let mut bits = self.entropy.clone();
+		bits.push(hash::sha256(&self.entropy)[0]);
+		let word_count = self.entropy.len() * 8 * 33 / 32 / 11;
+		(0..word_count).map(|i| {
+			let index = (i * 11..(i + 1) * 11).fold(0, |index, bit| index << 1 | bit_at(&bits, bit));
+			ENGLISH[index]
+		}).collect()
+	}
+
+	/// The BIP39 seed: PBKDF2 of the sentence with "mnemonic" followed by the passphrase as salt.
+	/// Both are NFKD normalized first. Any passphrase gives a valid seed, of a different wallet.
+	pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+		let sentence = self.to_string().nfkd().collect::<String>();
+		let salt = format!("mnemonic{}", passphrase.nfkd());
+		let seed = hash::pbkdf2_hmac_sha512(sentence.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS);
+		seed.try_into().expect("64 bytes")
+	}
+
+	/// The BIP32 master key of the seed.
+	pub fn to_master_key(&self, passphrase: &str, network: Network) -> Result<ExtendedPrivateKey, KeyError> {
+		ExtendedPrivateKey::new_master(&self.to_seed(passphrase), network)
+	}
+}
+
+/// Index of a word in the wordlist. The first 4 letters of a word are enough to find it.
+fn word_index(word: &str) -> Option<usize> {
+	// a prefix sorts right before the words it starts
+	let index = ENGLISH.partition_point(|candidate| *candidate < word);
+	let candidate = ENGLISH.get(index)?;
+	(*candidate == word || (word.len() >= 4 && candidate.starts_with(word))).then_some(index)
+}
+
+fn bit_at(bytes: &[u8], bit: usize) -> usize {
+	(bytes[bit / 8] >> (7 - bit % 8) & 1) as usize
+}
+
+impl FromStr for Mnemonic {
+	type Err = MnemonicError;
+
+	/// Parse the words of a mnemonic separated by whitespace, checking the checksum. Words can be
+	/// shortened to their first 4 letters.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let sentence = s.nfkd().collect::<String>();
+		let words: Vec<&str> = sentence.split_whitespace().collect();
+		if !(12..=24).contains(&words.len()) || !words.len().is_multiple_of(3) {
+			return Err(MnemonicError::InvalidWordCount(words.len()))
+		}
+
+		let indexes = words.iter()
+			.map(|word| word_index(word).ok_or_else(|| MnemonicError::UnknownWord(word.to_string())))
+			.collect::<Result<Vec<_>, _>>()?;
+		let mut bits = vec![0u8; (words.len() * 11).div_ceil(8)];
+		for (i, index) in indexes.iter().enumerate() {
+			for bit in 0..11 {
+				let position = i * 11 + bit;
+				bits[position / 8] |= ((index >> (10 - bit) & 1) as u8) << (7 - position % 8);
+			}
+		}
+
+		let entropy_len = words.len() * 11 * 32 / 33 / 8;
+		let mnemonic = Mnemonic::from_entropy(&bits[..entropy_len])?;
+		if mnemonic.words() != indexes.iter().map(|index| ENGLISH[*index]).collect::<Vec<_>>() {
+			return Err(MnemonicError::InvalidChecksum)
+		}
+		Ok(mnemonic)
+	}
+}
+
+impl fmt::Display for Mnemonic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.words().join(" "))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+	use crate::key::DerivationPath;
+	use crate::network::Network;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::txio::{Decodable, Encodable};
+	use super::{Mnemonic, MnemonicError};
+
+	// https://github.com/trezor/python-mnemonic/blob/master/vectors.json, with the passphrase "TREZOR"
+	const VECTORS: [(&str, &str, &str); 24] = [
+		("00000000000000000000000000000000",
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+			"c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"),
+		("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+			"legal winner thank year wave sausage worth useful legal winner thank yellow",
+			"2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607"),
+		("80808080808080808080808080808080",
+			"letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+			"d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8"),
+		("ffffffffffffffffffffffffffffffff",
+			"zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+			"ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069"),
+		("000000000000000000000000000000000000000000000000",
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+			"035895f2f481b1b0f01fcf8c289c794660b289981a78f8106447707fdd9666ca06da5a9a565181599b79f53b844d8a71dd9f439c52a3d7b3e8a79c906ac845fa"),
+		("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+			"legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+			"f2b94508732bcbacbcc020faefecfc89feafa6649a5491b8c952cede496c214a0c7b3c392d168748f2d4a612bada0753b52a1c7ac53c1e93abd5c6320b9e95dd"),
+		("808080808080808080808080808080808080808080808080",
+			"letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+			"107d7c02a5aa6f38c58083ff74f04c607c2d2c0ecc55501dadd72d025b751bc27fe913ffb796f841c49b1d33b610cf0e91d3aa239027f5e99fe4ce9e5088cd65"),
+		("ffffffffffffffffffffffffffffffffffffffffffffffff",
+			"zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+			"0cd6e5d827bb62eb8fc1e262254223817fd068a74b5b449cc2f667c3f1f985a76379b43348d952e2265b4cd129090758b3e3c2c49103b5051aac2eaeb890a528"),
+		("0000000000000000000000000000000000000000000000000000000000000000",
+			"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+			"bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd3097170af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8"),
+		("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+			"legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+			"bc09fca1804f7e69da93c2f2028eb238c227f2e9dda30cd63699232578480a4021b146ad717fbb7e451ce9eb835f43620bf5c514db0f8add49f5d121449d3e87"),
+		("8080808080808080808080808080808080808080808080808080808080808080",
+			"letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+			"c0c519bd0e91a2ed54357d9d1ebef6f5af218a153624cf4f2da911a0ed8f7a09e2ef61af0aca007096df430022f7a2b6fb91661a9589097069720d015e4e982f"),
+		("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+			"zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+			"dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad"),
+		("9e885d952ad362caeb4efe34a8e91bd2",
+			"ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+			"274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028"),
+		("6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+			"gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+			"628c3827a8823298ee685db84f55caa34b5cc195a778e52d45f59bcf75aba68e4d7590e101dc414bc1bbd5737666fbbef35d1f1903953b66624f910feef245ac"),
+		("68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+			"hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+			"64c87cde7e12ecf6704ab95bb1408bef047c22db4cc7491c4271d170a1b213d20b385bc1588d9c7b38f1b39d415665b8a9030c9ec653d75e65f847d8fc1fc440"),
+		("c0ba5a8e914111210f2bd131f3d5e08d",
+			"scheme spot photo card baby mountain device kick cradle pact join borrow",
+			"ea725895aaae8d4c1cf682c1bfd2d358d52ed9f0f0591131b559e2724bb234fca05aa9c02c57407e04ee9dc3b454aa63fbff483a8b11de949624b9f1831a9612"),
+		("6d9be1ee6ebd27a258115aad99b7317b9c8d28b6d76431c3",
+			"horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+			"fd579828af3da1d32544ce4db5c73d53fc8acc4ddb1e3b251a31179cdb71e853c56d2fcb11aed39898ce6c34b10b5382772db8796e52837b54468aeb312cfc3d"),
+		("9f6a2878b2520799a44ef18bc7df394e7061a224d2c33cd015b157d746869863",
+			"panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+			"72be8e052fc4919d2adf28d5306b5474b0069df35b02303de8c1729c9538dbb6fc2d731d5f832193cd9fb6aeecbc469594a70e3dd50811b5067f3b88b28c3e8d"),
+		("23db8160a31d3e0dca3688ed941adbf3",
+			"cat swing flag economy stadium alone churn speed unique patch report train",
+			"deb5f45449e615feff5640f2e49f933ff51895de3b4381832b3139941c57b59205a42480c52175b6efcffaa58a2503887c1e8b363a707256bdd2b587b46541f5"),
+		("8197a4a47f0425faeaa69deebc05ca29c0a5b5cc76ceacc0",
+			"light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+			"4cbdff1ca2db800fd61cae72a57475fdc6bab03e441fd63f96dabd1f183ef5b782925f00105f318309a7e9c3ea6967c7801e46c8a58082674c860a37b93eda02"),
+		("066dca1a2bb7e8a1db2832148ce9933eea0f3ac9548d793112d9a95c9407efad",
+			"all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+			"26e975ec644423f4a4c4f4215ef09b4bd7ef924e85d1d17c4cf3f136c2863cf6df0a475045652c57eb5fb41513ca2a2d67722b77e954b4b3fc11f7590449191d"),
+		("f30f8c1da665478f49b001d94c5fc452",
+			"vessel ladder alter error federal sibling chat ability sun glass valve picture",
+			"2aaa9242daafcee6aa9d7269f17d4efe271e1b9a529178d7dc139cd18747090bf9d60295d0ce74309a78852a9caadf0af48aae1c6253839624076224374bc63f"),
+		("c10ec20dc3cd9f652c7fac2f1230f7a3c828389a14392f05",
+			"scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+			"7b4a10be9d98e6cba265566db7f136718e1398c71cb581e1b2f464cac1ceedf4f3e274dc270003c670ad8d02c4558b2f8e39edea2775c9e232c7cb798b069e88"),
+		("f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+			"void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+			"01f5bced59dec48e362f2c45b5de68b9fd6c92c6634f44d6d40aab69056506f0e35524a518034ddc1192e1dacd32c1ed3eaa3c3b131c88ed8e7e54c49a5d0998"),
+	];
+
+	#[test]
+	fn bip39_vectors() {
+		for (entropy, sentence, seed) in VECTORS {
+			let mnemonic = Mnemonic::from_entropy(&entropy.decode_hex_be().unwrap()).unwrap();
+			assert_eq!(mnemonic.to_string(), sentence);
+			assert_eq!(Mnemonic::from_str(sentence), Ok(mnemonic.clone()));
+			assert_eq!(mnemonic.entropy().encode_hex_be(), entropy);
+			assert_eq!(mnemonic.to_seed("TREZOR").encode_hex_be(), seed);
+		}
+
+		let mnemonic = Mnemonic::from_str(VECTORS[0].1).unwrap();
+		let master = mnemonic.to_master_key("TREZOR", Network::Mainnet).unwrap();
+		assert_eq!(master.to_string(), "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF");
+	}
+
+	#[test]
+	fn seed_words_to_address() {
+		// https://github.com/bitcoin/bips/blob/master/bip-0084.mediawiki#test-vectors
+		let mnemonic = Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+		let master = mnemonic.to_master_key("", Network::Mainnet).unwrap();
+		let key = master.derive_path(&DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap()).unwrap();
+		assert_eq!(key.public_key().to_string(), "0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c");
+		let script_pub_key = ScriptPubKey::new_p2wpkh(key.public_key().as_bytes());
+		assert_eq!(script_pub_key.get_address().as_deref(), Some("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"));
+	}
+
+	#[test]
+	fn invalid_mnemonics() {
+		assert_eq!(Mnemonic::from_entropy(&[0; 15]), Err(MnemonicError::InvalidEntropyLength(15)));
+		assert_eq!(Mnemonic::from_entropy(&[0; 18]), Err(MnemonicError::InvalidEntropyLength(18)));
+		assert_eq!(Mnemonic::from_entropy(&[0; 36]), Err(MnemonicError::InvalidEntropyLength(36)));
+
+		let abandon = "abandon ".repeat(11);
+		assert_eq!(Mnemonic::from_str(&abandon), Err(MnemonicError::InvalidWordCount(11)));
+		assert_eq!(Mnemonic::from_str(&(abandon.clone() + "abandon")), Err(MnemonicError::InvalidChecksum));
+		assert_eq!(Mnemonic::from_str(&(abandon.clone() + "abo")), Err(MnemonicError::UnknownWord("abo".to_string())));
+		assert_eq!(Mnemonic::from_str(&(abandon.clone() + "abouts")), Err(MnemonicError::UnknownWord("abouts".to_string())));
+		assert_eq!(Mnemonic::from_str(&(abandon.clone() + "About")), Err(MnemonicError::UnknownWord("About".to_string())));
+		// any whitespace separates words
+		assert!(Mnemonic::from_str(&format!("  {}\n about\t", abandon)).is_ok());
+	}
+
+	#[test]
+	fn shortened_words() {
+		// the first 4 letters of every word are unique
+		let mnemonic = Mnemonic::from_str(VECTORS[0].1).unwrap();
+		assert_eq!(Mnemonic::from_str(&format!("{}abou", "aban ".repeat(11))), Ok(mnemonic.clone()));
+		assert_eq!(Mnemonic::from_str(&format!("{}abou", "abando ".repeat(11))), Ok(mnemonic));
+		let sentence = VECTORS[17].1;
+		let shortened: Vec<String> = sentence.split(' ').map(|word| word.chars().take(4).collect()).collect();
+		assert_eq!(Mnemonic::from_str(&shortened.join(" ")).unwrap().to_string(), sentence);
+		// words shorter than 4 letters have to be complete
+		assert_eq!(Mnemonic::from_str(&format!("{}ar", "aban ".repeat(23))), Err(MnemonicError::UnknownWord("ar".to_string())));
+		assert!(Mnemonic::from_str(&format!("{}art", "aban ".repeat(23))).is_ok());
+	}
+
+	#[test]
+	fn generate_and_passphrase() {
+		for word_count in [12, 15, 18, 21, 24] {
+			let mnemonic = Mnemonic::generate(word_count).unwrap();
+			assert_eq!(mnemonic.words().len(), word_count);
+			assert_eq!(Mnemonic::from_str(&mnemonic.to_string()), Ok(mnemonic));
+		}
+		assert_eq!(Mnemonic::generate(13), Err(MnemonicError::InvalidWordCount(13)));
+		assert_ne!(Mnemonic::generate(12), Mnemonic::generate(12));
+
+		// passphrases are normalized: é composed and decomposed are the same
+		let mnemonic = Mnemonic::from_str(VECTORS[0].1).unwrap();
+		assert_eq!(mnemonic.to_seed("caf\u{e9}"), mnemonic.to_seed("cafe\u{301}"));
+		assert_ne!(mnemonic.to_seed(""), mnemonic.to_seed("TREZOR"));
+	}
+}
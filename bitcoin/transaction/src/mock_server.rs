@@ -0,0 +1,99 @@
+// A minimal HTTP server running in a thread of the test process, so the clients can be tested
+// without a network. It answers every request with a handler and keeps the requests it got.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+	pub method: String,
+	pub path: String,
+	/// Header names are lowercase.
+	pub headers: Vec<(String, String)>,
+	pub body: String,
+}
+
+impl Request {
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+	}
+}
+
+pub struct MockServer {
+	/// http://127.0.0.1:<port>, the port is picked by the OS.
+	pub url: String,
+	requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+	/// Answer every request with the status and body returned by `handler`. The server runs until
+	/// the test process exits.
+	pub fn start<F>(handler: F) -> Self
+	where F: Fn(&Request) -> (u16, String) + Send + 'static {
+		let listener = TcpListener::bind("127.0.0.1:0").expect("couldn't bind the mock server");
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		let requests = Arc::new(Mutex::new(Vec::new()));
+
+		let received = Arc::clone(&requests);
+		thread::spawn(move || {
+			for stream in listener.incoming().flatten() {
+				// a connection that can't be read is dropped, the client sees the error
+				if let Some(request) = read_request(&stream) {
+					let (status, body) = handler(&request);
+					received.lock().unwrap().push(request);
+					let _ = write_response(stream, status, &body);
+				}
+			}
+		});
+
+		MockServer { url, requests }
+	}
+
+	/// The requests received so far, oldest first.
+	pub fn requests(&self) -> Vec<Request> {
+		self.requests.lock().unwrap().clone()
+	}
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+	let mut reader = BufReader::new(stream);
+	let mut line = String::new();
+	reader.read_line(&mut line).ok()?;
+	let mut parts = line.split_whitespace();
+	let method = parts.next()?.to_string();
+	let path = parts.next()?.to_string();
+
+	let mut headers = Vec::new();
+	loop {
+		let mut line = String::new();
+		reader.read_line(&mut line).ok()?;
+		let line = line.trim_end();
+		if line.is_empty() { break }
+		let (name, value) = line.split_once(':')?;
+		headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+	}
+
+	let length = headers.iter()
+		.find(|(name, _)| name == "content-length")
+		.and_then(|(_, value)| value.parse().ok())
+		.unwrap_or(0);
+	let mut body = vec![0; length];
+	reader.read_exact(&mut body).ok()?;
+
+	Some(Request { method, path, headers, body: String::from_utf8(body).ok()? })
+}
+
+fn write_response(mut stream: TcpStream, status: u16, body: &str) -> std::io::Result<()> {
+	let reason = match status {
+		200 => "OK",
+		400 => "Bad Request",
+		401 => "Unauthorized",
+		404 => "Not Found",
+		500 => "Internal Server Error",
+		_ => "Unknown",
+	};
+	write!(stream, "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, reason, body.len(), body)?;
+	stream.flush()
+}
@@ -0,0 +1,121 @@
+// The same software runs several networks that don't share coins. Addresses and WIF keys start
+// with a network specific prefix so that they can't be used on the wrong one by mistake.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Network {
+	#[default]
+	Mainnet,
+	Testnet,
+	Signet,
+	Regtest,
+}
+
+impl Network {
+	/// Version byte of Base58 P2PKH addresses.
+	pub fn p2pkh_prefix(self) -> u8 {
+		match self {
+			Network::Mainnet => 0,
+			Network::Testnet | Network::Signet | Network::Regtest => 111,
+		}
+	}
+
+	/// Version byte of Base58 P2SH addresses.
+	pub fn p2sh_prefix(self) -> u8 {
+		match self {
+			Network::Mainnet => 5,
+			Network::Testnet | Network::Signet | Network::Regtest => 196,
+		}
+	}
+
+	/// Version byte of private keys in Wallet Import Format.
+	pub fn wif_prefix(self) -> u8 {
+		match self {
+			Network::Mainnet => 0x80,
+			Network::Testnet | Network::Signet | Network::Regtest => 0xef,
+		}
+	}
+
+	/// Version bytes of BIP32 extended private keys: xprv on mainnet, tprv on the test networks.
+	pub fn xprv_prefix(self) -> [u8; 4] {
+		match self {
+			Network::Mainnet => [0x04, 0x88, 0xad, 0xe4],
+			Network::Testnet | Network::Signet | Network::Regtest => [0x04, 0x35, 0x83, 0x94],
+		}
+	}
+
+	/// Version bytes of BIP32 extended public keys: xpub on mainnet, tpub on the test networks.
+	pub fn xpub_prefix(self) -> [u8; 4] {
+		match self {
+			Network::Mainnet => [0x04, 0x88, 0xb2, 0x1e],
+			Network::Testnet | Network::Signet | Network::Regtest => [0x04, 0x35, 0x87, 0xcf],
+		}
+	}
+
+	/// Human readable part of bech32 segwit addresses.
+	pub fn bech32_hrp(self) -> &'static str {
+		match self {
+			Network::Mainnet => "bc",
+			Network::Testnet | Network::Signet => "tb",
+			Network::Regtest => "bcrt",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNetworkError(pub String);
+
+impl fmt::Display for ParseNetworkError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "unknown network \"{}\", expected main, test, signet or regtest", self.0)
+	}
+}
+
+impl Error for ParseNetworkError {}
+
+impl FromStr for Network {
+	type Err = ParseNetworkError;
+
+	/// Accepts bitcoind's `-chain` names as well as the longer names.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"main" | "mainnet" | "bitcoin" => Ok(Network::Mainnet),
+			"test" | "testnet" => Ok(Network::Testnet),
+			"signet" => Ok(Network::Signet),
+			"regtest" => Ok(Network::Regtest),
+			_ => Err(ParseNetworkError(s.to_string())),
+		}
+	}
+}
+
+impl fmt::Display for Network {
+	/// Same as bitcoind's `-chain` names.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = match self {
+			Network::Mainnet => "main",
+			Network::Testnet => "test",
+			Network::Signet => "signet",
+			Network::Regtest => "regtest",
+		};
+		f.write_str(name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+	use super::Network;
+
+	#[test]
+	fn network_names() {
+		for network in [Network::Mainnet, Network::Testnet, Network::Signet, Network::Regtest] {
+			assert_eq!(Network::from_str(&network.to_string()), Ok(network));
+		}
+		assert_eq!(Network::from_str("bitcoin"), Ok(Network::Mainnet));
+		assert!(Network::from_str("testnet4x").is_err());
+		assert_eq!(Network::default(), Network::Mainnet);
+	}
+}
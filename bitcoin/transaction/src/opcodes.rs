@@ -1,4 +1,4 @@
-/// Code picked up from https://github.com/rust-bitcoin/rust-bitcoin/blob/master/src/blockdata/opcodes.rs
+// Code picked up from https://github.com/rust-bitcoin/rust-bitcoin/blob/master/src/blockdata/opcodes.rs
 
 use std::fmt;
 
@@ -541,7 +541,7 @@ impl fmt::Debug for All {
 			all::OP_PUSHDATA4 => write!(f, "PUSHDATA4"),
 			all::OP_PUSHNUM_NEG1 => write!(f, "PUSHNUM_NEG1"),
 			all::OP_RESERVED => write!(f, "RESERVED"),
-			All {code: x} if x >= all::OP_PUSHNUM_1.code && x <= all::OP_PUSHNUM_16.code => write!(f, "PUSHNUM_{}", x - all::OP_PUSHNUM_1.code + 1),
+			All {code: x} if (all::OP_PUSHNUM_1.code..=all::OP_PUSHNUM_16.code).contains(&x) => write!(f, "PUSHNUM_{}", x - all::OP_PUSHNUM_1.code + 1),
 			all::OP_NOP => write!(f, "NOP"),
 			all::OP_VER => write!(f, "VER"),
 			all::OP_IF => write!(f, "IF"),
@@ -623,7 +623,7 @@ impl fmt::Debug for All {
 			all::OP_CHECKMULTISIGVERIFY => write!(f, "CHECKMULTISIGVERIFY"),
 			all::OP_CLTV => write!(f, "CLTV"),
 			all::OP_CSV => write!(f, "CSV"),
-			All {code: x} if x >= all::OP_NOP1.code && x <= all::OP_NOP10.code => write!(f, "NOP{}", x - all::OP_NOP1.code + 1),
+			All {code: x} if (all::OP_NOP1.code..=all::OP_NOP10.code).contains(&x) => write!(f, "NOP{}", x - all::OP_NOP1.code + 1),
 			all::OP_INVALIDOPCODE => write!(f, "INVALIDOPCODE"),
 			all::OP_CHECKSIGADD => write!(f, "CHECKSIGADD"),
 			All {code: x} => write!(f, "RETURN_{}", x),
@@ -651,6 +651,9 @@ impl From<u8> for All {
 impl From<&str> for All {
 	fn from(s: &str) -> All {
 		match s {
+			"OP_0" => all::OP_PUSHBYTES_0,
+			"OP_FALSE" => all::OP_PUSHBYTES_0,
+			"OP_TRUE" => all::OP_PUSHNUM_1,
 			"OP_PUSHDATA1" => all::OP_PUSHDATA1,
 			"OP_PUSHDATA2" => all::OP_PUSHDATA2,
 			"OP_PUSHDATA4" => all::OP_PUSHDATA4,
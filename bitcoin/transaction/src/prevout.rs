@@ -0,0 +1,228 @@
+// The outputs spent by a transaction aren't part of it, only their outpoints are. Amounts and
+// script pub keys have to come from somewhere else to compute the fee or to sign and verify
+// inputs. The caller picks where: nowhere, a map filled by hand or from a UTXO file, or a server
+// (see client.rs).
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use crate::client::ClientError;
+use crate::script::{Script, ScriptPubKey};
+use crate::transaction::Output;
+use crate::txio::Decodable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrevoutError {
+	/// The UTXO file couldn't be read.
+	Io(String),
+	/// A UTXO in a file is missing a field or has an invalid one. Lines are counted from 1 in CSV
+	/// files, entries from 0 in JSON files.
+	InvalidUtxo { entry: usize, reason: String },
+	/// The server couldn't be asked for the previous transaction.
+	Client(ClientError),
+	/// The previous transaction exists but doesn't have this many outputs.
+	OutputNotFound { txid: String, index: u32 },
+}
+
+impl fmt::Display for PrevoutError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PrevoutError::Io(e) => write!(f, "couldn't read the UTXO file: {}", e),
+			PrevoutError::InvalidUtxo { entry, reason } => write!(f, "invalid UTXO {}: {}", entry, reason),
+			PrevoutError::Client(e) => write!(f, "{}", e),
+			PrevoutError::OutputNotFound { txid, index } => write!(f, "output {}:{} not found", txid, index),
+		}
+	}
+}
+
+impl Error for PrevoutError {}
+
+/// Where the outputs spent by inputs are looked up.
+pub trait PrevoutProvider {
+	/// Output `index` of the transaction `txid` (in the usual reversed byte order). Ok(None) if the
+	/// provider doesn't know the transaction, errors are for when it couldn't tell.
+	fn get_prevout(&self, txid: &str, index: u32) -> Result<Option<Output>, PrevoutError>;
+}
+
+/// Don't look up anything. Decoding stays offline and the fee is unknown.
+pub struct NoPrevouts;
+
+impl PrevoutProvider for NoPrevouts {
+	fn get_prevout(&self, _txid: &str, _index: u32) -> Result<Option<Output>, PrevoutError> {
+		Ok(None)
+	}
+}
+
+/// Outputs kept in memory, added one by one or loaded from a UTXO file.
+#[derive(Debug, Default)]
+pub struct MemoryPrevouts(HashMap<(String, u32), Output>);
+
+impl MemoryPrevouts {
+	pub fn new() -> Self {
+		MemoryPrevouts(HashMap::new())
+	}
+
+	pub fn insert(&mut self, txid: &str, index: u32, output: Output) {
+		self.0.insert((txid.to_lowercase(), index), output);
+	}
+
+	#[cfg(test)]
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	/// Load a `.json` or `.csv` UTXO file, see `from_json` and `from_csv` for the formats.
+	pub fn from_file(path: &Path) -> Result<Self, PrevoutError> {
+		let content = fs::read_to_string(path).map_err(|e| PrevoutError::Io(e.to_string()))?;
+		match path.extension().and_then(|extension| extension.to_str()) {
+			Some("csv") => MemoryPrevouts::from_csv(&content),
+			_ => MemoryPrevouts::from_json(&content),
+		}
+	}
+
+	/// A JSON array of UTXOs with the same fields as Esplora's outputs, plus the outpoint:
+	///
+	/// `[{"txid": "...", "vout": 0, "value": 1000, "scriptpubkey": "0014..."}]`
+	pub fn from_json(content: &str) -> Result<Self, PrevoutError> {
+		let utxos: serde_json::Value = serde_json::from_str(content)
+			.map_err(|e| PrevoutError::InvalidUtxo { entry: 0, reason: e.to_string() })?;
+		let utxos = utxos.as_array()
+			.ok_or(PrevoutError::InvalidUtxo { entry: 0, reason: "expected an array".to_string() })?;
+
+		let mut prevouts = MemoryPrevouts::new();
+		for (entry, utxo) in utxos.iter().enumerate() {
+			let invalid = |reason: &str| PrevoutError::InvalidUtxo { entry, reason: reason.to_string() };
+			let txid = utxo["txid"].as_str().ok_or_else(|| invalid("missing txid"))?;
+			let index = utxo["vout"].as_u64()
+				.and_then(|index| u32::try_from(index).ok())
+				.ok_or_else(|| invalid("missing vout"))?;
+			let output = parse_output(&utxo["value"], &utxo["scriptpubkey"]).map_err(invalid)?;
+			check_txid(txid).map_err(invalid)?;
+			prevouts.insert(txid, index, output);
+		}
+		Ok(prevouts)
+	}
+
+	/// One UTXO per line as `txid,vout,value,scriptpubkey`. Empty lines and a header line starting
+	/// with "txid" are skipped.
+	pub fn from_csv(content: &str) -> Result<Self, PrevoutError> {
+		let mut prevouts = MemoryPrevouts::new();
+		for (i, line) in content.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with("txid") { continue }
+			let invalid = |reason: &str| PrevoutError::InvalidUtxo { entry: i + 1, reason: reason.to_string() };
+
+			let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+			let [txid, index, amount, script] = fields[..] else {
+				return Err(invalid("expected txid,vout,value,scriptpubkey"));
+			};
+			check_txid(txid).map_err(invalid)?;
+			let index = index.parse().map_err(|_| invalid("invalid vout"))?;
+			let amount = amount.parse().map_err(|_| invalid("invalid value"))?;
+			let script = script.decode_hex_be().map_err(|_| invalid("invalid scriptpubkey"))?;
+			prevouts.insert(txid, index, Output { amount, script_pub_key: ScriptPubKey::from_bytes(script) });
+		}
+		Ok(prevouts)
+	}
+}
+
+impl PrevoutProvider for MemoryPrevouts {
+	fn get_prevout(&self, txid: &str, index: u32) -> Result<Option<Output>, PrevoutError> {
+		// script pub keys can't be cloned, build a new output from the same bytes
+		let output = self.0.get(&(txid.to_lowercase(), index)).map(|output| Output {
+			amount: output.amount,
+			script_pub_key: ScriptPubKey::from_bytes(output.script_pub_key.script.clone()),
+		});
+		Ok(output)
+	}
+}
+
+/// An output from Esplora style `value` (sats) and `scriptpubkey` (hex) fields.
+fn parse_output(value: &serde_json::Value, script: &serde_json::Value) -> Result<Output, &'static str> {
+	let amount = value.as_u64().ok_or("missing value")?;
+	let script = script.as_str().ok_or("missing scriptpubkey")?
+		.decode_hex_be().map_err(|_| "invalid scriptpubkey")?;
+	Ok(Output { amount, script_pub_key: ScriptPubKey::from_bytes(script) })
+}
+
+/// Txids are 32 bytes of hex.
+fn check_txid(txid: &str) -> Result<(), &'static str> {
+	match txid.decode_hex_be() {
+		Ok(bytes) if bytes.len() == 32 => Ok(()),
+		_ => Err("invalid txid"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::transaction::Output;
+	use super::{MemoryPrevouts, NoPrevouts, PrevoutError, PrevoutProvider};
+
+	const TXID: &str = "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d";
+
+	#[test]
+	fn memory_prevouts() {
+		let mut prevouts = MemoryPrevouts::new();
+		let script_pub_key = ScriptPubKey::from_str("76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac").unwrap();
+		prevouts.insert(TXID, 1, Output { amount: 5000, script_pub_key });
+
+		let output = prevouts.get_prevout(&TXID.to_uppercase(), 1).unwrap().unwrap();
+		assert_eq!(output.amount, 5000);
+		assert!(output.script_pub_key.is_p2pkh());
+		assert_eq!(prevouts.get_prevout(TXID, 0), Ok(None));
+		assert_eq!(NoPrevouts.get_prevout(TXID, 1), Ok(None));
+	}
+
+	#[test]
+	fn load_utxo_files() {
+		let json = format!(r#"[
+			{{"txid": "{}", "vout": 0, "value": 1000, "scriptpubkey": "0014751e76e8199196d454941c45d1b3a323f1433bd6"}},
+			{{"txid": "{}", "vout": 3, "value": 2500, "scriptpubkey": "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87"}}
+		]"#, TXID, TXID);
+		let csv = format!("txid,vout,value,scriptpubkey\n\
+			{},0,1000,0014751e76e8199196d454941c45d1b3a323f1433bd6\n\
+			\n\
+			{},3,2500,a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87\n", TXID, TXID);
+
+		for prevouts in [MemoryPrevouts::from_json(&json).unwrap(), MemoryPrevouts::from_csv(&csv).unwrap()] {
+			assert_eq!(prevouts.len(), 2);
+			let output = prevouts.get_prevout(TXID, 0).unwrap().unwrap();
+			assert_eq!(output.amount, 1000);
+			assert!(output.script_pub_key.is_p2wpkh());
+			let output = prevouts.get_prevout(TXID, 3).unwrap().unwrap();
+			assert_eq!(output.amount, 2500);
+			assert!(output.script_pub_key.is_p2sh());
+		}
+
+		let path = std::env::temp_dir().join(format!("prevouts-{}.csv", std::process::id()));
+		fs::write(&path, &csv).unwrap();
+		assert_eq!(MemoryPrevouts::from_file(&path).unwrap().len(), 2);
+		fs::remove_file(&path).unwrap();
+		assert!(matches!(MemoryPrevouts::from_file(&path), Err(PrevoutError::Io(_))));
+	}
+
+	#[test]
+	fn invalid_utxo_files() {
+		let invalid = |result: Result<MemoryPrevouts, PrevoutError>| match result {
+			Err(PrevoutError::InvalidUtxo { entry, reason }) => (entry, reason),
+			other => panic!("expected an invalid UTXO, got {:?}", other),
+		};
+
+		assert_eq!(invalid(MemoryPrevouts::from_json("{}")).1, "expected an array");
+		let json = format!(r#"[{{"txid": "{}", "vout": 0, "value": 1, "scriptpubkey": "51"}}, {{"txid": "{}", "vout": 0}}]"#, TXID, TXID);
+		assert_eq!(invalid(MemoryPrevouts::from_json(&json)), (1, "missing value".to_string()));
+		let json = r#"[{"txid": "abcd", "vout": 0, "value": 1, "scriptpubkey": "51"}]"#;
+		assert_eq!(invalid(MemoryPrevouts::from_json(json)), (0, "invalid txid".to_string()));
+
+		let csv = format!("{},0,1,51\n{},0,1", TXID, TXID);
+		assert_eq!(invalid(MemoryPrevouts::from_csv(&csv)).0, 2);
+		let csv = format!("{},0,-1,51", TXID);
+		assert_eq!(invalid(MemoryPrevouts::from_csv(&csv)), (1, "invalid value".to_string()));
+		let csv = format!("{},0,1,5", TXID);
+		assert_eq!(invalid(MemoryPrevouts::from_csv(&csv)), (1, "invalid scriptpubkey".to_string()));
+	}
+}
@@ -0,0 +1,2021 @@
+// Partially signed bitcoin transactions (BIP174). A PSBT carries an unsigned transaction along with
+// everything needed to sign it, so that creating, signing and broadcasting a transaction can happen
+// in different programs on different devices. Each role adds to it:
+// - Creator: wraps an unsigned transaction.
+// - Updater: adds the outputs being spent, redeem/witness scripts and key origins.
+// - Signer: adds partial signatures.
+// - Combiner: merges the PSBTs returned by the signers.
+// - Finalizer: turns the signatures into final script sigs and witnesses.
+// - Extractor: builds the signed transaction.
+//
+// The format is a magic followed by key-value maps: one global map, then one map per input and
+// one per output. A key starts with its type, the rest of the key is key data. Keys we don't know
+// are kept as they are so they survive a round trip through this tool.
+//
+// Version 2 (BIP370) has no unsigned transaction in the global map. The transaction's fields are
+// spread over the maps instead: the version, fallback lock time and input and output counts are
+// global, outpoints and sequences are in the input maps and amounts and scripts in the output
+// maps. This lets a Constructor add inputs and outputs after the PSBT is created, e.g. when two
+// parties fund a transaction together, as long as the PSBT says they can still be added. The lock
+// time isn't a field either, it's the one required by the inputs or else the fallback.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+use secp256k1::{Message, SECP256K1};
+use crate::error::DecodeError;
+use crate::hash;
+use crate::interpreter;
+use crate::key::{PrivateKey, PublicKey};
+use crate::prevout::{PrevoutError, PrevoutProvider};
+use crate::script::{parse_multisig, Script, ScriptBuilder, ScriptPubKey, ScriptSig};
+use crate::sighash::{self, SighashError, SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_NONE, SIGHASH_SINGLE};
+use crate::transaction::{Input, Output, Transaction, WitnessStack};
+use crate::txio::{check_consumed, compact_size, Decodable, Encodable, HexBytes, ReadExt};
+use crate::Deserialize;
+
+/// "psbt" followed by 0xff.
+pub const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_XPUB: u8 = 0x01;
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
+const PSBT_IN_TAP_SCRIPT_SIG: u8 = 0x14;
+const PSBT_IN_TAP_LEAF_SCRIPT: u8 = 0x15;
+const PSBT_IN_TAP_BIP32_DERIVATION: u8 = 0x16;
+const PSBT_IN_TAP_INTERNAL_KEY: u8 = 0x17;
+const PSBT_IN_TAP_MERKLE_ROOT: u8 = 0x18;
+
+const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+const PSBT_OUT_TAP_INTERNAL_KEY: u8 = 0x05;
+const PSBT_OUT_TAP_TREE: u8 = 0x06;
+const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
+
+/// Bits of a version 2 PSBT's modifiable flags: whether inputs and outputs can still be added, and
+/// whether an input is signed with SIGHASH_SINGLE so inputs and outputs have to be added in pairs.
+pub const TX_MODIFIABLE_INPUTS: u8 = 0x01;
+pub const TX_MODIFIABLE_OUTPUTS: u8 = 0x02;
+pub const TX_MODIFIABLE_SIGHASH_SINGLE: u8 = 0x04;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PsbtError {
+	/// The data doesn't start with "psbt" 0xff.
+	InvalidMagic,
+	InvalidBase64,
+	/// The data ended in the middle of a map, or there is data after the last one.
+	Decode(DecodeError),
+	/// Keys have to be unique within a map. The key is in hex.
+	DuplicateKey(String),
+	/// A key of a known type with key data it can't have, or that the PSBT's version doesn't have.
+	/// The key is in hex.
+	InvalidKey(String),
+	/// A value that isn't valid for the type of its key. The key is in hex.
+	InvalidValue { key: String, reason: String },
+	/// The global map has no unsigned transaction.
+	MissingUnsignedTx,
+	/// The unsigned transaction has a script sig or a witness.
+	UnsignedTxHasScripts,
+	/// A field a version 2 PSBT has to have, by name.
+	MissingField(&'static str),
+	UnsupportedVersion(u32),
+	/// Some inputs require a lock time in blocks, others in seconds.
+	IncompatibleLockTimes,
+	/// Inputs or outputs can't be added: the PSBT is version 0, or it's signed in a way that
+	/// commits to them.
+	NotModifiable,
+	/// With an input signed with SIGHASH_SINGLE, inputs and outputs are added in pairs at the
+	/// same index.
+	UnpairedSighashSingle,
+	InputIndexOutOfRange(usize),
+	/// A previous transaction given for an input isn't the one the input spends from.
+	PrevoutMismatch(usize),
+	/// Only PSBTs of the same transaction can be combined.
+	DifferentTransactions,
+	/// The output spent by the input isn't in the PSBT.
+	MissingUtxo(usize),
+	/// The input doesn't have the signatures or scripts needed to finalize it.
+	CannotFinalize(usize),
+	/// A transaction can only be extracted once every input is finalized.
+	NotFinalized(usize),
+	Prevout(PrevoutError),
+	Sighash(SighashError),
+}
+
+impl fmt::Display for PsbtError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PsbtError::InvalidMagic => write!(f, "not a PSBT, invalid magic bytes"),
+			PsbtError::InvalidBase64 => write!(f, "invalid base64"),
+			PsbtError::Decode(e) => write!(f, "{}", e),
+			PsbtError::DuplicateKey(key) => write!(f, "duplicate key {}", key),
+			PsbtError::InvalidKey(key) => write!(f, "invalid key {}", key),
+			PsbtError::InvalidValue { key, reason } => write!(f, "invalid value for key {}: {}", key, reason),
+			PsbtError::MissingUnsignedTx => write!(f, "the PSBT has no unsigned transaction"),
+			PsbtError::UnsignedTxHasScripts => write!(f, "the unsigned transaction has script sigs or witnesses"),
+			PsbtError::MissingField(field) => write!(f, "the PSBT has no {}", field),
+			PsbtError::UnsupportedVersion(version) => write!(f, "PSBT version {} is not supported", version),
+			PsbtError::IncompatibleLockTimes => write!(f, "the inputs require lock times in both blocks and seconds"),
+			PsbtError::NotModifiable => write!(f, "inputs and outputs can't be added to the PSBT"),
+			PsbtError::UnpairedSighashSingle => write!(f, "inputs and outputs have to be added in pairs of the same index"),
+			PsbtError::InputIndexOutOfRange(i) => write!(f, "input {} does not exist", i),
+			PsbtError::PrevoutMismatch(i) => write!(f, "the previous transaction of input {} doesn't match its outpoint", i),
+			PsbtError::DifferentTransactions => write!(f, "the PSBTs are for different transactions"),
+			PsbtError::MissingUtxo(i) => write!(f, "the output spent by input {} is unknown", i),
+			PsbtError::CannotFinalize(i) => write!(f, "input {} can't be finalized", i),
+			PsbtError::NotFinalized(i) => write!(f, "input {} is not finalized", i),
+			PsbtError::Prevout(e) => write!(f, "{}", e),
+			PsbtError::Sighash(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl Error for PsbtError {}
+
+impl From<DecodeError> for PsbtError {
+	fn from(e: DecodeError) -> Self {
+		PsbtError::Decode(e)
+	}
+}
+
+impl From<SighashError> for PsbtError {
+	fn from(e: SighashError) -> Self {
+		PsbtError::Sighash(e)
+	}
+}
+
+/// Where a key comes from: the fingerprint of the master key and the BIP32 derivation path.
+/// Hardened indexes have the top bit set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySource {
+	pub fingerprint: [u8; 4],
+	pub path: Vec<u32>,
+}
+
+impl KeySource {
+	fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		if bytes.len() < 4 || !bytes.len().is_multiple_of(4) {
+			return None;
+		}
+		let fingerprint = bytes[..4].try_into().expect("4 bytes");
+		let path = bytes[4..].chunks(4).map(|index| u32::from_le_bytes(index.try_into().expect("4 bytes"))).collect();
+		Some(KeySource { fingerprint, path })
+	}
+
+	fn as_bytes(&self) -> Vec<u8> {
+		let mut bytes = self.fingerprint.to_vec();
+		for index in &self.path {
+			bytes.extend_from_slice(&index.to_le_bytes());
+		}
+		bytes
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Psbt {
+	/// The transaction being signed, without script sigs and witnesses. In version 2 it's built
+	/// from the maps, with its lock time determined by the inputs.
+	pub unsigned_tx: Transaction,
+	/// Extended public keys (78 bytes serialized) and their origin.
+	pub xpubs: BTreeMap<HexBytes, KeySource>,
+	pub version: u32,
+	/// Version 2: the lock time if no input requires one.
+	pub fallback_lock_time: Option<u32>,
+	/// Version 2: the TX_MODIFIABLE_* bits.
+	pub tx_modifiable: Option<u8>,
+	/// Keys of unknown and proprietary types, with their type byte.
+	pub unknown: BTreeMap<HexBytes, HexBytes>,
+	/// One per input of the unsigned transaction.
+	pub inputs: Vec<PsbtInput>,
+	/// One per output of the unsigned transaction.
+	pub outputs: Vec<PsbtOutput>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PsbtInput {
+	/// The whole transaction the input spends from. Needed for inputs that aren't segwit.
+	pub non_witness_utxo: Option<Transaction>,
+	/// Only the output being spent, enough for segwit inputs as the amount is signed.
+	pub witness_utxo: Option<Output>,
+	/// Signatures by public key.
+	pub partial_sigs: BTreeMap<HexBytes, HexBytes>,
+	pub sighash_type: Option<u32>,
+	pub redeem_script: Option<HexBytes>,
+	pub witness_script: Option<HexBytes>,
+	pub bip32_derivation: BTreeMap<HexBytes, KeySource>,
+	pub final_script_sig: Option<HexBytes>,
+	pub final_script_witness: Option<WitnessStack>,
+	/// Version 2: the lock time the input needs, in seconds or in blocks. An input with both
+	/// can be spent with either kind of lock time.
+	pub required_time_lock_time: Option<u32>,
+	pub required_height_lock_time: Option<u32>,
+	/// Signature of the taproot output key, 64 or 65 bytes.
+	pub tap_key_sig: Option<HexBytes>,
+	/// Signatures by x-only public key followed by the hash of the leaf they sign for.
+	pub tap_script_sigs: BTreeMap<HexBytes, HexBytes>,
+	/// Leaf scripts followed by their leaf version, by control block.
+	pub tap_leaf_scripts: BTreeMap<HexBytes, HexBytes>,
+	/// Origin of x-only public keys and the hashes of the leaves they are used in.
+	pub tap_bip32_derivation: BTreeMap<HexBytes, (Vec<HexBytes>, KeySource)>,
+	pub tap_internal_key: Option<HexBytes>,
+	pub tap_merkle_root: Option<HexBytes>,
+	pub unknown: BTreeMap<HexBytes, HexBytes>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PsbtOutput {
+	pub redeem_script: Option<HexBytes>,
+	pub witness_script: Option<HexBytes>,
+	pub bip32_derivation: BTreeMap<HexBytes, KeySource>,
+	pub tap_internal_key: Option<HexBytes>,
+	/// The script tree as (depth, leaf version, script) tuples, serialized.
+	pub tap_tree: Option<HexBytes>,
+	pub tap_bip32_derivation: BTreeMap<HexBytes, (Vec<HexBytes>, KeySource)>,
+	pub unknown: BTreeMap<HexBytes, HexBytes>,
+}
+
+/// Read the key-value pairs of a map up to its separator.
+fn read_map(stream: &mut Cursor<HexBytes>) -> Result<Vec<(HexBytes, HexBytes)>, PsbtError> {
+	let mut pairs: Vec<(HexBytes, HexBytes)> = Vec::new();
+	loop {
+		let key_len = stream.read_compact_size()?;
+		if key_len == 0 {
+			return Ok(pairs);
+		}
+		let key = stream.read_hex_var(key_len)?;
+		let value_len = stream.read_compact_size()?;
+		let value = stream.read_hex_var(value_len)?;
+		if pairs.iter().any(|(k, _)| *k == key) {
+			return Err(PsbtError::DuplicateKey(key.encode_hex_be()));
+		}
+		pairs.push((key, value));
+	}
+}
+
+fn write_pair(stream: &mut Vec<u8>, key_type: u8, key_data: &[u8], value: &[u8]) {
+	stream.extend(compact_size(key_data.len() as u64 + 1));
+	stream.push(key_type);
+	stream.extend_from_slice(key_data);
+	stream.extend(compact_size(value.len() as u64));
+	stream.extend_from_slice(value);
+}
+
+fn invalid_value(key: &[u8], reason: &str) -> PsbtError {
+	PsbtError::InvalidValue { key: key.encode_hex_be(), reason: reason.to_string() }
+}
+
+/// Most keys are only their type, the data is in the value.
+fn check_no_key_data(key: &[u8]) -> Result<(), PsbtError> {
+	if key.len() != 1 {
+		return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+	}
+	Ok(())
+}
+
+fn check_pubkey(key: &[u8]) -> Result<HexBytes, PsbtError> {
+	PublicKey::from_bytes(&key[1..]).map_err(|_| PsbtError::InvalidKey(key.encode_hex_be()))?;
+	Ok(key[1..].into())
+}
+
+fn check_x_only_key(key: &[u8], data: &[u8]) -> Result<(), PsbtError> {
+	if secp256k1::XOnlyPublicKey::from_slice(data).is_err() {
+		return Err(invalid_value(key, "invalid x-only public key"));
+	}
+	Ok(())
+}
+
+/// 64 byte schnorr signature, followed by the sighash type unless it's SIGHASH_DEFAULT.
+fn check_schnorr_sig(key: &[u8], sig: &[u8]) -> Result<(), PsbtError> {
+	match sig.len() {
+		64 => Ok(()),
+		65 if sig[64] != 0 => Ok(()),
+		_ => Err(invalid_value(key, "invalid schnorr signature")),
+	}
+}
+
+fn parse_key_source(key: &[u8], value: &[u8]) -> Result<KeySource, PsbtError> {
+	KeySource::from_bytes(value).ok_or_else(|| invalid_value(key, "invalid key origin"))
+}
+
+/// <number of leaf hashes> <leaf hashes> <key source>
+fn parse_tap_key_source(key: &[u8], value: &[u8]) -> Result<(Vec<HexBytes>, KeySource), PsbtError> {
+	let invalid = || invalid_value(key, "invalid taproot key origin");
+	let mut stream = Cursor::new(HexBytes::from(value));
+	let count = stream.read_compact_size().map_err(|_| invalid())?;
+	let leaf_hashes = (0..count).map(|_| stream.read_hex256()).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+	let source = parse_key_source(key, &value[stream.position() as usize..])?;
+	Ok((leaf_hashes, source))
+}
+
+fn tap_key_source_bytes((leaf_hashes, source): &(Vec<HexBytes>, KeySource)) -> Vec<u8> {
+	let mut bytes = compact_size(leaf_hashes.len() as u64);
+	for leaf_hash in leaf_hashes {
+		bytes.extend_from_slice(leaf_hash);
+	}
+	bytes.extend(source.as_bytes());
+	bytes
+}
+
+/// <depth> <leaf version> <script> for every leaf, in depth-first order.
+fn check_tap_tree(key: &[u8], value: &[u8]) -> Result<(), PsbtError> {
+	let invalid = || invalid_value(key, "invalid taproot tree");
+	if value.is_empty() {
+		return Err(invalid());
+	}
+	let mut stream = Cursor::new(HexBytes::from(value));
+	while (stream.position() as usize) < value.len() {
+		let depth = stream.read_u8_le().map_err(|_| invalid())?;
+		let leaf_version = stream.read_u8_le().map_err(|_| invalid())?;
+		let len = stream.read_compact_size().map_err(|_| invalid())?;
+		stream.read_hex_var(len).map_err(|_| invalid())?;
+		if depth > 128 || leaf_version & 1 != 0 {
+			return Err(invalid());
+		}
+	}
+	Ok(())
+}
+
+fn parse_witness_utxo(key: &[u8], value: &[u8]) -> Result<Output, PsbtError> {
+	let invalid = |_| invalid_value(key, "invalid output");
+	let mut stream = Cursor::new(HexBytes::from(value));
+	let amount = stream.read_u64_le().map_err(invalid)?;
+	let len = stream.read_compact_size().map_err(invalid)?;
+	let script_pub_key = ScriptPubKey::from_bytes(stream.read_hex_var(len).map_err(invalid)?);
+	check_consumed(&stream).map_err(invalid)?;
+	Ok(Output { amount, script_pub_key })
+}
+
+fn witness_utxo_bytes(output: &Output) -> Vec<u8> {
+	let mut bytes = output.amount.to_le_bytes().to_vec();
+	bytes.extend(compact_size(output.script_pub_key.script.len() as u64));
+	bytes.extend_from_slice(&output.script_pub_key.script);
+	bytes
+}
+
+fn parse_witness(key: &[u8], value: &[u8]) -> Result<WitnessStack, PsbtError> {
+	let invalid = |_| invalid_value(key, "invalid witness");
+	let mut stream = Cursor::new(HexBytes::from(value));
+	let count = stream.read_compact_size().map_err(invalid)?;
+	let mut stack = WitnessStack(Vec::new());
+	for _ in 0..count {
+		let len = stream.read_compact_size().map_err(invalid)?;
+		stack.0.push(stream.read_hex_var(len).map_err(invalid)?.encode_hex_be());
+	}
+	check_consumed(&stream).map_err(invalid)?;
+	Ok(stack)
+}
+
+fn witness_bytes(stack: &WitnessStack) -> Vec<u8> {
+	let mut bytes = compact_size(stack.0.len() as u64);
+	for item in &stack.0 {
+		let item = item.decode_hex_be().expect("witness items are valid hex");
+		bytes.extend(compact_size(item.len() as u64));
+		bytes.extend_from_slice(&item);
+	}
+	bytes
+}
+
+fn parse_u32(key: &[u8], value: &[u8]) -> Result<u32, PsbtError> {
+	let bytes = value.try_into().map_err(|_| invalid_value(key, "expected 4 bytes"))?;
+	Ok(u32::from_le_bytes(bytes))
+}
+
+fn parse_count(key: &[u8], value: &[u8]) -> Result<u64, PsbtError> {
+	let invalid = |_| invalid_value(key, "invalid compact size");
+	let mut stream = Cursor::new(HexBytes::from(value));
+	let count = stream.read_compact_size().map_err(invalid)?;
+	check_consumed(&stream).map_err(invalid)?;
+	Ok(count)
+}
+
+/// Lock times below the threshold are block heights, a height of 0 wouldn't require anything.
+fn parse_lock_time(key: &[u8], value: &[u8], in_blocks: bool) -> Result<u32, PsbtError> {
+	let lock_time = parse_u32(key, value)?;
+	if in_blocks != ((lock_time as i64) < interpreter::LOCKTIME_THRESHOLD) || lock_time == 0 {
+		return Err(invalid_value(key, "invalid lock time"));
+	}
+	Ok(lock_time)
+}
+
+impl PsbtInput {
+	/// In version 2 the input's outpoint and sequence are in its map too.
+	fn read_from(stream: &mut Cursor<HexBytes>, version: u32) -> Result<(Self, Option<Input>), PsbtError> {
+		let mut input = PsbtInput::default();
+		let mut previous_tx = None;
+		let mut tx_index = None;
+		let mut sequence = None;
+		for (key, value) in read_map(stream)? {
+			match key[0] {
+				PSBT_IN_NON_WITNESS_UTXO => {
+					check_no_key_data(&key)?;
+					let tx = Transaction::decode_raw(value).map_err(|e| invalid_value(&key, &e.to_string()))?;
+					input.non_witness_utxo = Some(tx);
+				}
+				PSBT_IN_WITNESS_UTXO => {
+					check_no_key_data(&key)?;
+					input.witness_utxo = Some(parse_witness_utxo(&key, &value)?);
+				}
+				PSBT_IN_PARTIAL_SIG => {
+					input.partial_sigs.insert(check_pubkey(&key)?, value);
+				}
+				PSBT_IN_SIGHASH_TYPE => {
+					check_no_key_data(&key)?;
+					input.sighash_type = Some(parse_u32(&key, &value)?);
+				}
+				PSBT_IN_REDEEM_SCRIPT => {
+					check_no_key_data(&key)?;
+					input.redeem_script = Some(value);
+				}
+				PSBT_IN_WITNESS_SCRIPT => {
+					check_no_key_data(&key)?;
+					input.witness_script = Some(value);
+				}
+				PSBT_IN_BIP32_DERIVATION => {
+					input.bip32_derivation.insert(check_pubkey(&key)?, parse_key_source(&key, &value)?);
+				}
+				PSBT_IN_FINAL_SCRIPTSIG => {
+					check_no_key_data(&key)?;
+					input.final_script_sig = Some(value);
+				}
+				PSBT_IN_FINAL_SCRIPTWITNESS => {
+					check_no_key_data(&key)?;
+					input.final_script_witness = Some(parse_witness(&key, &value)?);
+				}
+				PSBT_IN_PREVIOUS_TXID if version == 2 => {
+					check_no_key_data(&key)?;
+					if value.len() != 32 {
+						return Err(invalid_value(&key, "expected 32 bytes"));
+					}
+					previous_tx = Some(value.encode_hex_le());
+				}
+				PSBT_IN_OUTPUT_INDEX if version == 2 => {
+					check_no_key_data(&key)?;
+					tx_index = Some(parse_u32(&key, &value)?);
+				}
+				PSBT_IN_SEQUENCE if version == 2 => {
+					check_no_key_data(&key)?;
+					sequence = Some(parse_u32(&key, &value)?);
+				}
+				PSBT_IN_REQUIRED_TIME_LOCKTIME if version == 2 => {
+					check_no_key_data(&key)?;
+					input.required_time_lock_time = Some(parse_lock_time(&key, &value, false)?);
+				}
+				PSBT_IN_REQUIRED_HEIGHT_LOCKTIME if version == 2 => {
+					check_no_key_data(&key)?;
+					input.required_height_lock_time = Some(parse_lock_time(&key, &value, true)?);
+				}
+				PSBT_IN_PREVIOUS_TXID | PSBT_IN_OUTPUT_INDEX | PSBT_IN_SEQUENCE
+					| PSBT_IN_REQUIRED_TIME_LOCKTIME | PSBT_IN_REQUIRED_HEIGHT_LOCKTIME if key.len() == 1 => {
+					// a field of the other version, with key data it's an unknown key
+					return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+				}
+				PSBT_IN_TAP_KEY_SIG => {
+					check_no_key_data(&key)?;
+					check_schnorr_sig(&key, &value)?;
+					input.tap_key_sig = Some(value);
+				}
+				PSBT_IN_TAP_SCRIPT_SIG => {
+					// x-only key and leaf hash
+					if key.len() != 65 {
+						return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+					}
+					check_x_only_key(&key, &key[1..33])?;
+					check_schnorr_sig(&key, &value)?;
+					input.tap_script_sigs.insert(key[1..].into(), value);
+				}
+				PSBT_IN_TAP_LEAF_SCRIPT => {
+					// the control block is the leaf version, the internal key and the merkle path
+					let control_block = &key[1..];
+					if control_block.len() < 33 || !(control_block.len() - 33).is_multiple_of(32) || control_block.len() > 33 + 128 * 32 {
+						return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+					}
+					if value.last() != Some(&(control_block[0] & 0xfe)) {
+						return Err(invalid_value(&key, "leaf version doesn't match the control block"));
+					}
+					input.tap_leaf_scripts.insert(control_block.into(), value);
+				}
+				PSBT_IN_TAP_BIP32_DERIVATION => {
+					if key.len() != 33 {
+						return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+					}
+					input.tap_bip32_derivation.insert(key[1..].into(), parse_tap_key_source(&key, &value)?);
+				}
+				PSBT_IN_TAP_INTERNAL_KEY => {
+					check_no_key_data(&key)?;
+					check_x_only_key(&key, &value)?;
+					input.tap_internal_key = Some(value);
+				}
+				PSBT_IN_TAP_MERKLE_ROOT => {
+					check_no_key_data(&key)?;
+					if value.len() != 32 {
+						return Err(invalid_value(&key, "expected 32 bytes"));
+					}
+					input.tap_merkle_root = Some(value);
+				}
+				_ => {
+					input.unknown.insert(key, value);
+				}
+			}
+		}
+		if version < 2 {
+			return Ok((input, None));
+		}
+		let tx_input = Input {
+			previous_tx: previous_tx.ok_or(PsbtError::MissingField("PSBT_IN_PREVIOUS_TXID"))?,
+			tx_index: tx_index.ok_or(PsbtError::MissingField("PSBT_IN_OUTPUT_INDEX"))?,
+			script_sig: ScriptSig::new(Box::new([])),
+			sequence: format!("{:08x}", sequence.unwrap_or(0xffffffff)),
+			prevout: None,
+		};
+		Ok((input, Some(tx_input)))
+	}
+
+	/// Version 2 PSBTs are written with the input's outpoint and sequence.
+	fn write_to(&self, stream: &mut Vec<u8>, tx_input: Option<&Input>) {
+		if let Some(tx) = &self.non_witness_utxo {
+			write_pair(stream, PSBT_IN_NON_WITNESS_UTXO, &[], &tx.serialize(true));
+		}
+		if let Some(output) = &self.witness_utxo {
+			write_pair(stream, PSBT_IN_WITNESS_UTXO, &[], &witness_utxo_bytes(output));
+		}
+		for (pubkey, sig) in &self.partial_sigs {
+			write_pair(stream, PSBT_IN_PARTIAL_SIG, pubkey, sig);
+		}
+		if let Some(sighash_type) = self.sighash_type {
+			write_pair(stream, PSBT_IN_SIGHASH_TYPE, &[], &sighash_type.to_le_bytes());
+		}
+		if let Some(script) = &self.redeem_script {
+			write_pair(stream, PSBT_IN_REDEEM_SCRIPT, &[], script);
+		}
+		if let Some(script) = &self.witness_script {
+			write_pair(stream, PSBT_IN_WITNESS_SCRIPT, &[], script);
+		}
+		for (pubkey, source) in &self.bip32_derivation {
+			write_pair(stream, PSBT_IN_BIP32_DERIVATION, pubkey, &source.as_bytes());
+		}
+		if let Some(script_sig) = &self.final_script_sig {
+			write_pair(stream, PSBT_IN_FINAL_SCRIPTSIG, &[], script_sig);
+		}
+		if let Some(witness) = &self.final_script_witness {
+			write_pair(stream, PSBT_IN_FINAL_SCRIPTWITNESS, &[], &witness_bytes(witness));
+		}
+		if let Some(tx_input) = tx_input {
+			write_pair(stream, PSBT_IN_PREVIOUS_TXID, &[], &tx_input.previous_tx.decode_hex_le().expect("txids are valid hex"));
+			write_pair(stream, PSBT_IN_OUTPUT_INDEX, &[], &tx_input.tx_index.to_le_bytes());
+			// a missing sequence is a final one
+			if tx_input.sequence != "ffffffff" {
+				write_pair(stream, PSBT_IN_SEQUENCE, &[], &tx_input.sequence.decode_hex_le().expect("sequences are valid hex"));
+			}
+		}
+		if let Some(lock_time) = self.required_time_lock_time {
+			write_pair(stream, PSBT_IN_REQUIRED_TIME_LOCKTIME, &[], &lock_time.to_le_bytes());
+		}
+		if let Some(lock_time) = self.required_height_lock_time {
+			write_pair(stream, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, &[], &lock_time.to_le_bytes());
+		}
+		if let Some(sig) = &self.tap_key_sig {
+			write_pair(stream, PSBT_IN_TAP_KEY_SIG, &[], sig);
+		}
+		for (key, sig) in &self.tap_script_sigs {
+			write_pair(stream, PSBT_IN_TAP_SCRIPT_SIG, key, sig);
+		}
+		for (control_block, script) in &self.tap_leaf_scripts {
+			write_pair(stream, PSBT_IN_TAP_LEAF_SCRIPT, control_block, script);
+		}
+		for (key, source) in &self.tap_bip32_derivation {
+			write_pair(stream, PSBT_IN_TAP_BIP32_DERIVATION, key, &tap_key_source_bytes(source));
+		}
+		if let Some(key) = &self.tap_internal_key {
+			write_pair(stream, PSBT_IN_TAP_INTERNAL_KEY, &[], key);
+		}
+		if let Some(root) = &self.tap_merkle_root {
+			write_pair(stream, PSBT_IN_TAP_MERKLE_ROOT, &[], root);
+		}
+		for (key, value) in &self.unknown {
+			write_pair(stream, key[0], &key[1..], value);
+		}
+		stream.push(0x00);
+	}
+
+	pub fn is_finalized(&self) -> bool {
+		self.final_script_sig.is_some() || self.final_script_witness.is_some()
+	}
+
+	fn is_signed(&self) -> bool {
+		self.is_finalized() || !self.partial_sigs.is_empty() || self.tap_key_sig.is_some() || !self.tap_script_sigs.is_empty()
+	}
+
+	fn requires_lock_time(&self) -> bool {
+		self.required_time_lock_time.is_some() || self.required_height_lock_time.is_some()
+	}
+
+	/// Keep what is missing from self, signatures and other map entries are merged.
+	fn combine(&mut self, other: PsbtInput) {
+		merge_option(&mut self.non_witness_utxo, other.non_witness_utxo);
+		merge_option(&mut self.witness_utxo, other.witness_utxo);
+		merge_map(&mut self.partial_sigs, other.partial_sigs);
+		merge_option(&mut self.sighash_type, other.sighash_type);
+		merge_option(&mut self.redeem_script, other.redeem_script);
+		merge_option(&mut self.witness_script, other.witness_script);
+		merge_map(&mut self.bip32_derivation, other.bip32_derivation);
+		merge_option(&mut self.final_script_sig, other.final_script_sig);
+		merge_option(&mut self.final_script_witness, other.final_script_witness);
+		merge_option(&mut self.required_time_lock_time, other.required_time_lock_time);
+		merge_option(&mut self.required_height_lock_time, other.required_height_lock_time);
+		merge_option(&mut self.tap_key_sig, other.tap_key_sig);
+		merge_map(&mut self.tap_script_sigs, other.tap_script_sigs);
+		merge_map(&mut self.tap_leaf_scripts, other.tap_leaf_scripts);
+		merge_map(&mut self.tap_bip32_derivation, other.tap_bip32_derivation);
+		merge_option(&mut self.tap_internal_key, other.tap_internal_key);
+		merge_option(&mut self.tap_merkle_root, other.tap_merkle_root);
+		merge_map(&mut self.unknown, other.unknown);
+	}
+}
+
+impl PsbtOutput {
+	/// In version 2 the output's amount and script are in its map too.
+	fn read_from(stream: &mut Cursor<HexBytes>, version: u32) -> Result<(Self, Option<Output>), PsbtError> {
+		let mut output = PsbtOutput::default();
+		let mut amount = None;
+		let mut script = None;
+		for (key, value) in read_map(stream)? {
+			match key[0] {
+				PSBT_OUT_REDEEM_SCRIPT => {
+					check_no_key_data(&key)?;
+					output.redeem_script = Some(value);
+				}
+				PSBT_OUT_WITNESS_SCRIPT => {
+					check_no_key_data(&key)?;
+					output.witness_script = Some(value);
+				}
+				PSBT_OUT_BIP32_DERIVATION => {
+					output.bip32_derivation.insert(check_pubkey(&key)?, parse_key_source(&key, &value)?);
+				}
+				PSBT_OUT_AMOUNT if version == 2 => {
+					check_no_key_data(&key)?;
+					let bytes = value.as_ref().try_into().map_err(|_| invalid_value(&key, "expected 8 bytes"))?;
+					let value = u64::try_from(i64::from_le_bytes(bytes)).map_err(|_| invalid_value(&key, "negative amount"))?;
+					amount = Some(value);
+				}
+				PSBT_OUT_SCRIPT if version == 2 => {
+					check_no_key_data(&key)?;
+					script = Some(value);
+				}
+				PSBT_OUT_AMOUNT | PSBT_OUT_SCRIPT if key.len() == 1 => {
+					return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+				}
+				PSBT_OUT_TAP_INTERNAL_KEY => {
+					check_no_key_data(&key)?;
+					check_x_only_key(&key, &value)?;
+					output.tap_internal_key = Some(value);
+				}
+				PSBT_OUT_TAP_TREE => {
+					check_no_key_data(&key)?;
+					check_tap_tree(&key, &value)?;
+					output.tap_tree = Some(value);
+				}
+				PSBT_OUT_TAP_BIP32_DERIVATION => {
+					if key.len() != 33 {
+						return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+					}
+					output.tap_bip32_derivation.insert(key[1..].into(), parse_tap_key_source(&key, &value)?);
+				}
+				_ => {
+					output.unknown.insert(key, value);
+				}
+			}
+		}
+		if version < 2 {
+			return Ok((output, None));
+		}
+		let tx_output = Output {
+			amount: amount.ok_or(PsbtError::MissingField("PSBT_OUT_AMOUNT"))?,
+			script_pub_key: ScriptPubKey::from_bytes(script.ok_or(PsbtError::MissingField("PSBT_OUT_SCRIPT"))?),
+		};
+		Ok((output, Some(tx_output)))
+	}
+
+	/// Version 2 PSBTs are written with the output's amount and script.
+	fn write_to(&self, stream: &mut Vec<u8>, tx_output: Option<&Output>) {
+		if let Some(script) = &self.redeem_script {
+			write_pair(stream, PSBT_OUT_REDEEM_SCRIPT, &[], script);
+		}
+		if let Some(script) = &self.witness_script {
+			write_pair(stream, PSBT_OUT_WITNESS_SCRIPT, &[], script);
+		}
+		for (pubkey, source) in &self.bip32_derivation {
+			write_pair(stream, PSBT_OUT_BIP32_DERIVATION, pubkey, &source.as_bytes());
+		}
+		if let Some(tx_output) = tx_output {
+			write_pair(stream, PSBT_OUT_AMOUNT, &[], &tx_output.amount.to_le_bytes());
+			write_pair(stream, PSBT_OUT_SCRIPT, &[], &tx_output.script_pub_key.script);
+		}
+		if let Some(key) = &self.tap_internal_key {
+			write_pair(stream, PSBT_OUT_TAP_INTERNAL_KEY, &[], key);
+		}
+		if let Some(tree) = &self.tap_tree {
+			write_pair(stream, PSBT_OUT_TAP_TREE, &[], tree);
+		}
+		for (key, source) in &self.tap_bip32_derivation {
+			write_pair(stream, PSBT_OUT_TAP_BIP32_DERIVATION, key, &tap_key_source_bytes(source));
+		}
+		for (key, value) in &self.unknown {
+			write_pair(stream, key[0], &key[1..], value);
+		}
+		stream.push(0x00);
+	}
+
+	fn combine(&mut self, other: PsbtOutput) {
+		merge_option(&mut self.redeem_script, other.redeem_script);
+		merge_option(&mut self.witness_script, other.witness_script);
+		merge_map(&mut self.bip32_derivation, other.bip32_derivation);
+		merge_option(&mut self.tap_internal_key, other.tap_internal_key);
+		merge_option(&mut self.tap_tree, other.tap_tree);
+		merge_map(&mut self.tap_bip32_derivation, other.tap_bip32_derivation);
+		merge_map(&mut self.unknown, other.unknown);
+	}
+}
+
+fn merge_option<T>(field: &mut Option<T>, other: Option<T>) {
+	if field.is_none() {
+		*field = other;
+	}
+}
+
+fn merge_map<V>(map: &mut BTreeMap<HexBytes, V>, other: BTreeMap<HexBytes, V>) {
+	for (key, value) in other {
+		map.entry(key).or_insert(value);
+	}
+}
+
+impl Psbt {
+	/// Creator: wrap a transaction whose inputs have no script sig and no witness.
+	pub fn from_unsigned_tx(unsigned_tx: Transaction) -> Result<Self, PsbtError> {
+		check_unsigned(&unsigned_tx)?;
+		Ok(Psbt {
+			inputs: unsigned_tx.inputs.iter().map(|_| PsbtInput::default()).collect(),
+			outputs: unsigned_tx.outputs.iter().map(|_| PsbtOutput::default()).collect(),
+			unsigned_tx,
+			xpubs: BTreeMap::new(),
+			version: 0,
+			fallback_lock_time: None,
+			tx_modifiable: None,
+			unknown: BTreeMap::new(),
+		})
+	}
+
+	/// Creator of a version 2 PSBT: no inputs and outputs yet, Constructors add them.
+	pub fn new_v2(tx_version: u32, fallback_lock_time: Option<u32>) -> Self {
+		let unsigned_tx = Transaction {
+			version: tx_version,
+			flag: None,
+			in_counter: 0,
+			inputs: Vec::new(),
+			out_counter: 0,
+			outputs: Vec::new(),
+			witness_data: None,
+			lock_time: fallback_lock_time.unwrap_or(0),
+			extra_info: None,
+		};
+		Psbt {
+			unsigned_tx,
+			xpubs: BTreeMap::new(),
+			version: 2,
+			fallback_lock_time,
+			tx_modifiable: Some(TX_MODIFIABLE_INPUTS | TX_MODIFIABLE_OUTPUTS),
+			unknown: BTreeMap::new(),
+			inputs: Vec::new(),
+			outputs: Vec::new(),
+		}
+	}
+
+	/// Parse a binary PSBT.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, PsbtError> {
+		if !bytes.starts_with(&PSBT_MAGIC) {
+			return Err(PsbtError::InvalidMagic);
+		}
+		let mut stream = Cursor::new(HexBytes::from(bytes));
+		stream.set_position(PSBT_MAGIC.len() as u64);
+
+		let global = read_map(&mut stream)?;
+		// which keys are allowed depends on the version
+		let version = match global.iter().find(|(key, _)| key[0] == PSBT_GLOBAL_VERSION) {
+			Some((key, value)) => {
+				check_no_key_data(key)?;
+				parse_u32(key, value)?
+			}
+			None => 0,
+		};
+		if version != 0 && version != 2 {
+			return Err(PsbtError::UnsupportedVersion(version));
+		}
+
+		let mut unsigned_tx = None;
+		let mut xpubs = BTreeMap::new();
+		let mut tx_version = None;
+		let mut fallback_lock_time = None;
+		let mut input_count = None;
+		let mut output_count = None;
+		let mut tx_modifiable = None;
+		let mut unknown = BTreeMap::new();
+		for (key, value) in global {
+			match key[0] {
+				PSBT_GLOBAL_UNSIGNED_TX if version == 0 => {
+					check_no_key_data(&key)?;
+					// the witness serialization is never used, an unsigned transaction has no witness
+					let tx = Transaction::decode_raw(value).map_err(|e| invalid_value(&key, &e.to_string()))?;
+					check_unsigned(&tx)?;
+					unsigned_tx = Some(tx);
+				}
+				PSBT_GLOBAL_XPUB => {
+					if key.len() != 79 {
+						return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+					}
+					xpubs.insert(key[1..].into(), parse_key_source(&key, &value)?);
+				}
+				PSBT_GLOBAL_TX_VERSION if version == 2 => {
+					check_no_key_data(&key)?;
+					tx_version = Some(parse_u32(&key, &value)?);
+				}
+				PSBT_GLOBAL_FALLBACK_LOCKTIME if version == 2 => {
+					check_no_key_data(&key)?;
+					fallback_lock_time = Some(parse_u32(&key, &value)?);
+				}
+				PSBT_GLOBAL_INPUT_COUNT if version == 2 => {
+					check_no_key_data(&key)?;
+					input_count = Some(parse_count(&key, &value)?);
+				}
+				PSBT_GLOBAL_OUTPUT_COUNT if version == 2 => {
+					check_no_key_data(&key)?;
+					output_count = Some(parse_count(&key, &value)?);
+				}
+				PSBT_GLOBAL_TX_MODIFIABLE if version == 2 => {
+					check_no_key_data(&key)?;
+					if value.len() != 1 {
+						return Err(invalid_value(&key, "expected 1 byte"));
+					}
+					tx_modifiable = Some(value[0]);
+				}
+				PSBT_GLOBAL_VERSION => {}
+				PSBT_GLOBAL_UNSIGNED_TX | PSBT_GLOBAL_TX_VERSION | PSBT_GLOBAL_FALLBACK_LOCKTIME
+					| PSBT_GLOBAL_INPUT_COUNT | PSBT_GLOBAL_OUTPUT_COUNT | PSBT_GLOBAL_TX_MODIFIABLE if key.len() == 1 => {
+					return Err(PsbtError::InvalidKey(key.encode_hex_be()));
+				}
+				_ => {
+					unknown.insert(key, value);
+				}
+			}
+		}
+
+		let psbt = if version == 0 {
+			let unsigned_tx = unsigned_tx.ok_or(PsbtError::MissingUnsignedTx)?;
+			let inputs = unsigned_tx.inputs.iter()
+				.map(|_| PsbtInput::read_from(&mut stream, version).map(|(input, _)| input))
+				.collect::<Result<Vec<_>, _>>()?;
+			let outputs = unsigned_tx.outputs.iter()
+				.map(|_| PsbtOutput::read_from(&mut stream, version).map(|(output, _)| output))
+				.collect::<Result<Vec<_>, _>>()?;
+			Psbt { unsigned_tx, xpubs, version, fallback_lock_time, tx_modifiable, unknown, inputs, outputs }
+		} else {
+			let tx_version = tx_version.ok_or(PsbtError::MissingField("PSBT_GLOBAL_TX_VERSION"))?;
+			let input_count = input_count.ok_or(PsbtError::MissingField("PSBT_GLOBAL_INPUT_COUNT"))?;
+			let output_count = output_count.ok_or(PsbtError::MissingField("PSBT_GLOBAL_OUTPUT_COUNT"))?;
+			let mut psbt = Psbt::new_v2(tx_version, fallback_lock_time);
+			psbt.xpubs = xpubs;
+			psbt.tx_modifiable = tx_modifiable;
+			psbt.unknown = unknown;
+			for _ in 0..input_count {
+				let (input, tx_input) = PsbtInput::read_from(&mut stream, version)?;
+				psbt.unsigned_tx.inputs.push(tx_input.expect("version 2 inputs have an outpoint"));
+				psbt.inputs.push(input);
+			}
+			for _ in 0..output_count {
+				let (output, tx_output) = PsbtOutput::read_from(&mut stream, version)?;
+				psbt.unsigned_tx.outputs.push(tx_output.expect("version 2 outputs have an amount"));
+				psbt.outputs.push(output);
+			}
+			psbt.unsigned_tx.in_counter = input_count;
+			psbt.unsigned_tx.out_counter = output_count;
+			psbt.unsigned_tx.lock_time = lock_time(psbt.fallback_lock_time, &psbt.inputs)?;
+			psbt
+		};
+		check_consumed(&stream)?;
+
+		for (i, (input, tx_input)) in psbt.inputs.iter().zip(&psbt.unsigned_tx.inputs).enumerate() {
+			if let Some(tx) = &input.non_witness_utxo {
+				check_non_witness_utxo(i, tx_input, tx)?;
+			}
+		}
+		Ok(psbt)
+	}
+
+	/// Serialize into the binary format.
+	pub fn serialize(&self) -> Vec<u8> {
+		let v2 = self.version == 2;
+		let tx = &self.unsigned_tx;
+		let mut stream = PSBT_MAGIC.to_vec();
+		if !v2 {
+			write_pair(&mut stream, PSBT_GLOBAL_UNSIGNED_TX, &[], &tx.serialize(false));
+		}
+		for (xpub, source) in &self.xpubs {
+			write_pair(&mut stream, PSBT_GLOBAL_XPUB, xpub, &source.as_bytes());
+		}
+		if v2 {
+			write_pair(&mut stream, PSBT_GLOBAL_TX_VERSION, &[], &tx.version.to_le_bytes());
+			if let Some(lock_time) = self.fallback_lock_time {
+				write_pair(&mut stream, PSBT_GLOBAL_FALLBACK_LOCKTIME, &[], &lock_time.to_le_bytes());
+			}
+			write_pair(&mut stream, PSBT_GLOBAL_INPUT_COUNT, &[], &compact_size(self.inputs.len() as u64));
+			write_pair(&mut stream, PSBT_GLOBAL_OUTPUT_COUNT, &[], &compact_size(self.outputs.len() as u64));
+			if let Some(flags) = self.tx_modifiable {
+				write_pair(&mut stream, PSBT_GLOBAL_TX_MODIFIABLE, &[], &[flags]);
+			}
+		}
+		if self.version != 0 {
+			write_pair(&mut stream, PSBT_GLOBAL_VERSION, &[], &self.version.to_le_bytes());
+		}
+		for (key, value) in &self.unknown {
+			write_pair(&mut stream, key[0], &key[1..], value);
+		}
+		stream.push(0x00);
+
+		for (input, tx_input) in self.inputs.iter().zip(&tx.inputs) {
+			input.write_to(&mut stream, Some(tx_input).filter(|_| v2));
+		}
+		for (output, tx_output) in self.outputs.iter().zip(&tx.outputs) {
+			output.write_to(&mut stream, Some(tx_output).filter(|_| v2));
+		}
+		stream
+	}
+
+	pub fn from_base64(psbt: &str) -> Result<Self, PsbtError> {
+		let bytes = base64::decode(psbt.trim()).map_err(|_| PsbtError::InvalidBase64)?;
+		Psbt::from_bytes(&bytes)
+	}
+
+	pub fn to_base64(&self) -> String {
+		base64::encode(self.serialize())
+	}
+
+	/// Constructor: add an input to a version 2 PSBT whose inputs are modifiable. The PSBT's map
+	/// for it can already have the spent output or a required lock time, but an input that would
+	/// change the lock time of a transaction that is already signed is refused. Returns the
+	/// input's index.
+	pub fn add_input(&mut self, tx_input: Input, input: PsbtInput) -> Result<usize, PsbtError> {
+		if self.version != 2 || self.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_INPUTS == 0 {
+			return Err(PsbtError::NotModifiable);
+		}
+		self.check_pair(self.inputs.len(), self.outputs.len())?;
+		if !tx_input.script_sig.script.is_empty() {
+			return Err(PsbtError::UnsignedTxHasScripts);
+		}
+		let input_index = self.inputs.len();
+		if let Some(tx) = &input.non_witness_utxo {
+			check_non_witness_utxo(input_index, &tx_input, tx)?;
+		}
+
+		self.inputs.push(input);
+		let lock_time = match lock_time(self.fallback_lock_time, &self.inputs) {
+			Ok(lock_time) if lock_time != self.unsigned_tx.lock_time && self.inputs.iter().any(PsbtInput::is_signed) => {
+				Err(PsbtError::NotModifiable)
+			}
+			result => result,
+		};
+		if lock_time.is_err() {
+			self.inputs.pop();
+		}
+		let lock_time = lock_time?;
+
+		let tx = &mut self.unsigned_tx;
+		tx.inputs.push(tx_input);
+		tx.in_counter = tx.inputs.len() as u64;
+		tx.lock_time = lock_time;
+		Ok(input_index)
+	}
+
+	/// Constructor: add an output to a version 2 PSBT whose outputs are modifiable. Outputs are
+	/// added at the end, so the outputs of inputs signed with SIGHASH_SINGLE stay where they are.
+	/// Returns the output's index.
+	pub fn add_output(&mut self, tx_output: Output, output: PsbtOutput) -> Result<usize, PsbtError> {
+		if self.version != 2 || self.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_OUTPUTS == 0 {
+			return Err(PsbtError::NotModifiable);
+		}
+		self.check_pair(self.outputs.len(), self.inputs.len())?;
+		let tx = &mut self.unsigned_tx;
+		tx.outputs.push(tx_output);
+		tx.out_counter = tx.outputs.len() as u64;
+		self.outputs.push(output);
+		Ok(self.outputs.len() - 1)
+	}
+
+	/// Once an input is signed with SIGHASH_SINGLE an input or output can only be added at the
+	/// index of the last one of the other kind, or to be paired by the next one.
+	fn check_pair(&self, index: usize, others: usize) -> Result<(), PsbtError> {
+		let sighash_single = self.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_SIGHASH_SINGLE != 0;
+		if sighash_single && index != others && index + 1 != others {
+			return Err(PsbtError::UnpairedSighashSingle);
+		}
+		Ok(())
+	}
+
+	/// The output spent by an input, from its witness utxo or its previous transaction.
+	pub fn spent_output(&self, input_index: usize) -> Result<&Output, PsbtError> {
+		let input = self.inputs.get(input_index).ok_or(PsbtError::InputIndexOutOfRange(input_index))?;
+		let tx_index = self.unsigned_tx.inputs[input_index].tx_index as usize;
+		input.witness_utxo.as_ref()
+			.or_else(|| input.non_witness_utxo.as_ref().and_then(|tx| tx.outputs.get(tx_index)))
+			.ok_or(PsbtError::MissingUtxo(input_index))
+	}
+
+	/// Updater: add the transaction an input spends from. Signers need it for inputs that aren't
+	/// segwit, to be sure of the amount being spent.
+	pub fn set_non_witness_utxo(&mut self, input_index: usize, tx: Transaction) -> Result<(), PsbtError> {
+		if input_index >= self.inputs.len() {
+			return Err(PsbtError::InputIndexOutOfRange(input_index));
+		}
+		check_non_witness_utxo(input_index, &self.unsigned_tx.inputs[input_index], &tx)?;
+		self.inputs[input_index].non_witness_utxo = Some(tx);
+		Ok(())
+	}
+
+	/// Updater: add the outputs spent by segwit inputs from a provider. A P2SH output is only
+	/// added once the input's redeem script is set and is a witness program. Other inputs need
+	/// their whole previous transaction, see `set_non_witness_utxo`.
+	pub fn add_witness_utxos<P: PrevoutProvider + ?Sized>(&mut self, provider: &P) -> Result<(), PsbtError> {
+		for (input, psbt_input) in self.unsigned_tx.inputs.iter().zip(self.inputs.iter_mut()) {
+			let prevout = provider.get_prevout(&input.previous_tx, input.tx_index).map_err(PsbtError::Prevout)?;
+			if let Some(prevout) = prevout {
+				let script = &prevout.script_pub_key;
+				let wraps_segwit = script.is_p2sh() && psbt_input.redeem_script.as_ref().is_some_and(|redeem| {
+					hash::hash160(redeem) == script.script[2..22] && interpreter::witness_program(redeem).is_some()
+				});
+				if script.is_witness_program() || wraps_segwit {
+					psbt_input.witness_utxo = Some(prevout);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Updater: add redeem scripts and witness scripts to the inputs that spend them. The spent
+	/// outputs have to be known.
+	pub fn add_scripts(&mut self, scripts: &[HexBytes]) {
+		for i in 0..self.inputs.len() {
+			let Ok(spent) = self.spent_output(i) else { continue };
+			let mut script = spent.script_pub_key.script.clone();
+			let mut redeem_script = None;
+			if spent.script_pub_key.is_p2sh() {
+				redeem_script = scripts.iter().find(|s| hash::hash160(s) == script[2..22]).cloned();
+				match &redeem_script {
+					Some(redeem) => script = redeem.clone(),
+					None => continue,
+				}
+			}
+			let witness_script = match interpreter::witness_program(&script) {
+				Some((0, program)) if program.len() == 32 => scripts.iter().find(|s| hash::sha256(s) == program).cloned(),
+				_ => None,
+			};
+
+			let input = &mut self.inputs[i];
+			merge_option(&mut input.redeem_script, redeem_script);
+			merge_option(&mut input.witness_script, witness_script);
+		}
+	}
+
+	/// Signer: sign every input that one of the keys can sign, with the input's sighash type or
+	/// SIGHASH_ALL. Returns the number of signatures added. Like `Signer`, P2PK, P2PKH and
+	/// multisig scripts are supported, directly or wrapped in P2SH and/or P2WSH, as well as P2WPKH.
+	pub fn sign(&mut self, keys: &[PrivateKey]) -> Result<usize, PsbtError> {
+		let mut count = 0;
+		for i in 0..self.inputs.len() {
+			if self.inputs[i].is_finalized() {
+				continue;
+			}
+			let Some((script_code, amount, segwit)) = self.script_code(i)? else { continue };
+			let sighash_type = self.inputs[i].sighash_type.unwrap_or(SIGHASH_ALL);
+
+			for key in keys {
+				let pubkey = key.public_key().as_bytes();
+				if !script_uses_key(&script_code, &pubkey) {
+					continue;
+				}
+				let sighash = if segwit {
+					self.unsigned_tx.segwit_v0_signature_hash(i, &script_code, Some(amount), sighash_type)?
+				} else {
+					self.unsigned_tx.legacy_signature_hash(i, &script_code, sighash_type)?
+				};
+				let message = Message::from_slice(&sighash).expect("sighash is 32 bytes");
+				let mut sig = SECP256K1.sign_ecdsa(&message, key.secret_key()).serialize_der().to_vec();
+				sig.push(sighash_type as u8);
+				self.inputs[i].partial_sigs.insert(pubkey, sig.into_boxed_slice());
+				self.signed_with(sighash_type);
+				count += 1;
+			}
+		}
+		Ok(count)
+	}
+
+	/// A signature commits to the inputs unless it's SIGHASH_ANYONECANPAY, and to the outputs
+	/// unless it's SIGHASH_NONE, so those can't be added anymore.
+	fn signed_with(&mut self, sighash_type: u32) {
+		let Some(flags) = &mut self.tx_modifiable else { return };
+		let base_type = sighash_type & 0x1f;
+		if sighash_type & SIGHASH_ANYONECANPAY == 0 {
+			*flags &= !TX_MODIFIABLE_INPUTS;
+		}
+		if base_type != SIGHASH_NONE {
+			*flags &= !TX_MODIFIABLE_OUTPUTS;
+		}
+		if base_type == SIGHASH_SINGLE {
+			*flags |= TX_MODIFIABLE_SIGHASH_SINGLE;
+		}
+	}
+
+	/// The script signatures of an input commit to, the amount spent and whether it's segwit.
+	/// None if the scripts needed aren't in the PSBT or the input is taproot. Legacy signatures
+	/// don't commit to the amount, so they need the previous transaction (BIP174).
+	fn script_code(&self, input_index: usize) -> Result<Option<(HexBytes, u64, bool)>, PsbtError> {
+		let spent = self.spent_output(input_index)?;
+		let input = &self.inputs[input_index];
+		let mut script = spent.script_pub_key.script.clone();
+		if spent.script_pub_key.is_p2sh() {
+			match &input.redeem_script {
+				Some(redeem) if hash::hash160(redeem) == script[2..22] => script = redeem.clone(),
+				_ => return Ok(None),
+			}
+		}
+		let script_code = match interpreter::witness_program(&script) {
+			Some((0, program)) if program.len() == 20 => (sighash::p2wpkh_script_code(program), spent.amount, true),
+			Some((0, program)) if program.len() == 32 => match &input.witness_script {
+				Some(witness_script) if hash::sha256(witness_script) == program => (witness_script.clone(), spent.amount, true),
+				_ => return Ok(None),
+			},
+			Some(_) => return Ok(None),
+			None => {
+				let tx_input = &self.unsigned_tx.inputs[input_index];
+				let verified = input.non_witness_utxo.as_ref()
+					.filter(|tx| check_non_witness_utxo(input_index, tx_input, tx).is_ok())
+					.map(|tx| &tx.outputs[tx_input.tx_index as usize])
+					.filter(|output| output.script_pub_key.script == spent.script_pub_key.script);
+				match verified {
+					Some(output) => (script, output.amount, false),
+					None => return Ok(None),
+				}
+			}
+		};
+		Ok(Some(script_code))
+	}
+
+	/// Identifies the transaction of a PSBT: its txid. For version 2 the sequences are set to 0 as
+	/// they can still be changed by Updaters.
+	pub fn unique_id(&self) -> String {
+		if self.version != 2 {
+			return self.unsigned_tx.txid();
+		}
+		let tx = &self.unsigned_tx;
+		let inputs = tx.inputs.iter().map(|input| Input {
+			previous_tx: input.previous_tx.clone(),
+			tx_index: input.tx_index,
+			script_sig: ScriptSig::new(Box::new([])),
+			sequence: "00000000".to_string(),
+			prevout: None,
+		});
+		let outputs = tx.outputs.iter().map(|output| Output {
+			amount: output.amount,
+			script_pub_key: ScriptPubKey::from_bytes(output.script_pub_key.script.clone()),
+		});
+		let id_tx = Transaction {
+			version: tx.version,
+			flag: None,
+			in_counter: tx.in_counter,
+			inputs: inputs.collect(),
+			out_counter: tx.out_counter,
+			outputs: outputs.collect(),
+			witness_data: None,
+			lock_time: tx.lock_time,
+			extra_info: None,
+		};
+		id_tx.txid()
+	}
+
+	/// Combiner: merge the maps of a PSBT of the same transaction, e.g. one signed by another
+	/// signer. Values already in self are kept. Inputs or outputs can only be added if both PSBTs
+	/// allow it.
+	pub fn combine(&mut self, other: Psbt) -> Result<(), PsbtError> {
+		if self.version != other.version || self.unique_id() != other.unique_id() {
+			return Err(PsbtError::DifferentTransactions);
+		}
+		if let (Some(flags), Some(other)) = (&mut self.tx_modifiable, other.tx_modifiable) {
+			let modifiable = TX_MODIFIABLE_INPUTS | TX_MODIFIABLE_OUTPUTS;
+			*flags = (*flags & other & modifiable) | ((*flags | other) & TX_MODIFIABLE_SIGHASH_SINGLE);
+		}
+		merge_option(&mut self.fallback_lock_time, other.fallback_lock_time);
+		merge_option(&mut self.tx_modifiable, other.tx_modifiable);
+		merge_map(&mut self.xpubs, other.xpubs);
+		merge_map(&mut self.unknown, other.unknown);
+		for (input, other) in self.inputs.iter_mut().zip(other.inputs) {
+			input.combine(other);
+		}
+		for (output, other) in self.outputs.iter_mut().zip(other.outputs) {
+			output.combine(other);
+		}
+		Ok(())
+	}
+
+	/// Finalizer: build the final script sig and witness of every input that has enough
+	/// signatures, and clear the data that was only needed to sign it. Fails with the first input
+	/// that couldn't be finalized, the others are still finalized.
+	pub fn finalize(&mut self) -> Result<(), PsbtError> {
+		let mut result = Ok(());
+		for i in 0..self.inputs.len() {
+			if let Err(e) = self.finalize_input(i) {
+				result = result.and(Err(e));
+			}
+		}
+		result
+	}
+
+	pub fn finalize_input(&mut self, input_index: usize) -> Result<(), PsbtError> {
+		let spent = self.spent_output(input_index)?;
+		let input = &self.inputs[input_index];
+		if input.is_finalized() {
+			return Ok(());
+		}
+		let cannot_finalize = PsbtError::CannotFinalize(input_index);
+
+		let mut script = spent.script_pub_key.script.clone();
+		let mut redeem_script = None;
+		if spent.script_pub_key.is_p2sh() {
+			let redeem = input.redeem_script.as_ref()
+				.filter(|redeem| hash::hash160(redeem) == script[2..22])
+				.ok_or(cannot_finalize.clone())?;
+			script = redeem.clone();
+			redeem_script = Some(redeem.clone());
+		}
+
+		let (mut script_sig, witness) = match interpreter::witness_program(&script) {
+			Some((0, program)) if program.len() == 20 => {
+				let (pubkey, sig) = input.partial_sigs.iter()
+					.find(|(pubkey, _)| hash::hash160(pubkey) == program)
+					.ok_or(cannot_finalize)?;
+				(Vec::new(), Some(vec![sig.to_vec(), pubkey.to_vec()]))
+			}
+			Some((0, program)) if program.len() == 32 => {
+				let witness_script = input.witness_script.as_ref()
+					.filter(|witness_script| hash::sha256(witness_script) == program)
+					.ok_or(cannot_finalize.clone())?;
+				let mut witness = satisfy(witness_script, &input.partial_sigs).ok_or(cannot_finalize)?;
+				witness.push(witness_script.to_vec());
+				(Vec::new(), Some(witness))
+			}
+			Some((1, program)) if program.len() == 32 => {
+				let sig = input.tap_key_sig.as_ref().ok_or(cannot_finalize)?;
+				(Vec::new(), Some(vec![sig.to_vec()]))
+			}
+			Some(_) => return Err(cannot_finalize),
+			None => (satisfy(&script, &input.partial_sigs).ok_or(cannot_finalize)?, None),
+		};
+
+		if let Some(redeem_script) = redeem_script {
+			script_sig.push(redeem_script.to_vec());
+		}
+		let mut builder = ScriptBuilder::new();
+		for item in &script_sig {
+			builder.push_slice(item);
+		}
+		let script_sig: ScriptSig = builder.into_script();
+
+		// only the spent output, the final scripts and unknown keys are kept
+		let input = &mut self.inputs[input_index];
+		*input = PsbtInput {
+			non_witness_utxo: input.non_witness_utxo.take(),
+			witness_utxo: input.witness_utxo.take(),
+			final_script_sig: Some(script_sig.script).filter(|script| !script.is_empty()),
+			final_script_witness: witness.map(|items| WitnessStack(items.iter().map(|item| item.encode_hex_be()).collect())),
+			unknown: std::mem::take(&mut input.unknown),
+			..PsbtInput::default()
+		};
+		Ok(())
+	}
+
+	/// Extractor: the signed transaction, once every input is finalized. The outputs spent by the
+	/// inputs are set as their prevouts so it can be verified right away.
+	pub fn extract_tx(self) -> Result<Transaction, PsbtError> {
+		let Psbt { mut unsigned_tx, inputs, .. } = self;
+		if let Some(i) = inputs.iter().position(|input| !input.is_finalized()) {
+			return Err(PsbtError::NotFinalized(i));
+		}
+
+		let has_witness = inputs.iter().any(|input| input.final_script_witness.is_some());
+		let mut witness_data = Vec::new();
+		for (tx_input, input) in unsigned_tx.inputs.iter_mut().zip(inputs) {
+			let tx_index = tx_input.tx_index as usize;
+			let spent = input.witness_utxo
+				.or_else(|| input.non_witness_utxo.and_then(|mut tx| (tx_index < tx.outputs.len()).then(|| tx.outputs.swap_remove(tx_index))));
+			tx_input.prevout = spent;
+			tx_input.script_sig = ScriptSig::new(input.final_script_sig.unwrap_or_default());
+			witness_data.push(input.final_script_witness.unwrap_or(WitnessStack(Vec::new())));
+		}
+		if has_witness {
+			unsigned_tx.flag = Some(1);
+			unsigned_tx.witness_data = Some(witness_data);
+		}
+		Ok(unsigned_tx)
+	}
+}
+
+fn check_non_witness_utxo(input_index: usize, input: &Input, tx: &Transaction) -> Result<(), PsbtError> {
+	if tx.txid() != input.previous_tx || tx.outputs.len() <= input.tx_index as usize {
+		return Err(PsbtError::PrevoutMismatch(input_index));
+	}
+	Ok(())
+}
+
+/// The lock time of a version 2 PSBT. Inputs that require one all have to accept the same kind,
+/// blocks if they can, and the latest is used. Inputs without requirements accept both.
+fn lock_time(fallback_lock_time: Option<u32>, inputs: &[PsbtInput]) -> Result<u32, PsbtError> {
+	let requiring: Vec<&PsbtInput> = inputs.iter().filter(|input| input.requires_lock_time()).collect();
+	if requiring.is_empty() {
+		return Ok(fallback_lock_time.unwrap_or(0));
+	}
+	let heights: Option<Vec<u32>> = requiring.iter().map(|input| input.required_height_lock_time).collect();
+	let times: Option<Vec<u32>> = requiring.iter().map(|input| input.required_time_lock_time).collect();
+	heights.or(times)
+		.and_then(|lock_times| lock_times.into_iter().max())
+		.ok_or(PsbtError::IncompatibleLockTimes)
+}
+
+/// A transaction can only be wrapped in a PSBT before it's signed.
+fn check_unsigned(tx: &Transaction) -> Result<(), PsbtError> {
+	let has_witness = tx.witness_data.as_ref().is_some_and(|stacks| stacks.iter().any(|stack| !stack.0.is_empty()));
+	if has_witness || tx.inputs.iter().any(|input| !input.script_sig.script.is_empty()) {
+		return Err(PsbtError::UnsignedTxHasScripts);
+	}
+	Ok(())
+}
+
+/// Whether signing for the public key is useful to satisfy a script code.
+fn script_uses_key(script_code: &[u8], pubkey: &[u8]) -> bool {
+	let script = ScriptPubKey::from_bytes(script_code.into());
+	if script.is_p2pkh() {
+		hash::hash160(pubkey) == script_code[3..23]
+	} else if script.is_p2pk() {
+		script_code[1..script_code.len() - 1] == *pubkey
+	} else if let Some((_, pubkeys)) = parse_multisig(script_code) {
+		pubkeys.contains(&pubkey)
+	} else {
+		false
+	}
+}
+
+/// The stack items that satisfy a P2PK, P2PKH or multisig script with the signatures available.
+fn satisfy(script: &[u8], sigs: &BTreeMap<HexBytes, HexBytes>) -> Option<Vec<Vec<u8>>> {
+	let script_pub_key = ScriptPubKey::from_bytes(script.into());
+	if script_pub_key.is_p2pk() {
+		let sig = sigs.get(&script[1..script.len() - 1])?;
+		Some(vec![sig.to_vec()])
+	} else if script_pub_key.is_p2pkh() {
+		let (pubkey, sig) = sigs.iter().find(|(pubkey, _)| hash::hash160(pubkey) == script[3..23])?;
+		Some(vec![sig.to_vec(), pubkey.to_vec()])
+	} else if let Some((required, pubkeys)) = parse_multisig(script) {
+		// the dummy element consumed by OP_CHECKMULTISIG, then signatures in the order of the keys
+		let mut stack = vec![Vec::new()];
+		stack.extend(pubkeys.iter().filter_map(|pubkey| sigs.get(*pubkey)).take(required).map(|sig| sig.to_vec()));
+		(stack.len() > required).then_some(stack)
+	} else {
+		None
+	}
+}
+
+impl Deserialize for Psbt {
+	fn decode_raw(bytes: HexBytes) -> Result<Self, Box<dyn Error>> {
+		Ok(Psbt::from_bytes(&bytes)?)
+	}
+
+	fn as_bytes(&self) -> HexBytes {
+		self.serialize().into_boxed_slice()
+	}
+}
+
+/// PSBTs are passed around as base64.
+impl FromStr for Psbt {
+	type Err = PsbtError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Psbt::from_base64(s)
+	}
+}
+
+impl fmt::Display for Psbt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.to_base64())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::key::PrivateKey;
+	use crate::network::Network;
+	use crate::prevout::MemoryPrevouts;
+	use crate::script::{Script, ScriptPubKey, ScriptSig};
+	use crate::sighash::{SIGHASH_ALL, SIGHASH_ANYONECANPAY};
+	use crate::transaction::{Input, Output, Transaction};
+	use crate::txio::{Decodable, Encodable};
+	use super::{KeySource, Psbt, PsbtError, PsbtInput, PsbtOutput, TX_MODIFIABLE_INPUTS, TX_MODIFIABLE_OUTPUTS, TX_MODIFIABLE_SIGHASH_SINGLE};
+
+	// BIP174: one P2PKH input with its previous transaction, base64
+	const VALID_1: &str = "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKG\
+	h2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7uk\
+	zGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0t\
+	AXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiI\
+	rHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGL\
+	ONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuF\
+	LYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+\
+	B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA";
+
+	// BIP174: sighash type
+	const VALID_3: &str = "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000\
+	feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a914\
+	3545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4\
+	cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffff\
+	ffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974\
+	d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888\
+	ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f5683\
+	11dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2\
+	f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b85\
+	2d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e425\
+	5b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000\
+	000001030401000000000000";
+
+	// BIP174: P2SH-P2WSH input with a witness script and key origins
+	const VALID_5: &str = "70736274ff0100550200000001279a2323a5dfb51fc45f220fa58b0fc13e1e3342792a85d7e36cd6333b5cbc390000000000\
+	ffffffff01a05aea0b000000001976a914ffe9c0061097cc3b636f2cb0460fa4fc427d2b4588ac0000000000010120955eea\
+	0b0000000017a9146345200f68d189e1adc0df1c4d16ea8f14c0dbeb87220203b1341ccba7683b6af4f1238cd6e97e7167d5\
+	69fac47f1e48d47541844355bd4646304302200424b58effaaa694e1559ea5c93bbfd4a89064224055cdf070b6771469442d\
+	07021f5c8eb0fea6516d60b8acb33ad64ede60e8785bfb3aa94b99bdf86151db9a9a010104220020771fd18ad459666dd49f\
+	3d564e3dbc42f4c84774e360ada16816a8ed488d5681010547522103b1341ccba7683b6af4f1238cd6e97e7167d569fac47f\
+	1e48d47541844355bd462103de55d1e1dac805e3f8a58c1fbf9b94c02f3dbaafe127fefca4995f26f82083bd52ae220603b1\
+	341ccba7683b6af4f1238cd6e97e7167d569fac47f1e48d47541844355bd4610b4a6ba670000008000000080040000802206\
+	03de55d1e1dac805e3f8a58c1fbf9b94c02f3dbaafe127fefca4995f26f82083bd10b4a6ba67000000800000008005000080\
+	0000";
+
+	// BIP174: unknown key types
+	const VALID_6: &str = "70736274ff01003f0200000001ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff0000000000\
+	ffffffff010000000000000000036a010000000000000a0f0102030405060708090f0102030405060708090a0b0c0d0e0f00\
+	00";
+
+	// BIP174: a network transaction, not a PSBT
+	const NO_MAGIC: &str = "0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf6000000006a473044022070b224\
+	5123e6bf474d60c5b50c043d4c691a5d2435f09a34a7662a9dc251790a022001329ca9dacf280bdf30740ec0390422422c81\
+	cb45839457aeb76fc12edd95b3012102657d118d3357b8e0f4c2cd46db7b39f6d9c38d9a70abcb9b2de5dc8dbfe4ce31feff\
+	ffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545\
+	e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300";
+
+	// BIP174: the output maps are missing
+	const MISSING_OUTPUTS: &str = "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000\
+	feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a914\
+	3545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4\
+	cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffff\
+	ffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974\
+	d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888\
+	ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f5683\
+	11dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2\
+	f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b85\
+	2d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e425\
+	5b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000\
+	000000";
+
+	// BIP174: the unsigned transaction has a script sig
+	const SIGNED_UNSIGNED_TX: &str = "70736274ff0100fd0a010200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be4000000\
+	006a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891\
+	ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682\
+	dc86e2d73fa88292feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000fe\
+	ffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a914\
+	6f4620b553fa095e721b9ee0efe9fa039cca459788ac00000000000001012000e1f5050000000017a9143545e6e33b832c47\
+	050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000";
+
+	// BIP174: no unsigned transaction in the global map
+	const NO_UNSIGNED_TX: &str = "70736274ff000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e5\
+	0100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b46\
+	08bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb\
+	0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62a\
+	c753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a\
+	240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb\
+	87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33\
+	ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72bee\
+	f0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000000000";
+
+	// BIP371: taproot input key origin and output internal key
+	const TAPROOT_VALID: &str = "70736274ff01005e020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000\
+	ffffffff0148e6052a0100000022512083698e458c6664e1595d75da2597de1e22ee97d798e706c4c0a4b5a9823cd7430000\
+	00000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a075721\
+	16fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000\
+	800100000000000000011720fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa232000105201124\
+	da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e67121071124da7aec92ccd06c954562647f437b138b\
+	95721a84be2bf2276bbddab3e6711900772b2da7560000800100008000000080000000000500000000";
+
+	// BIP371: 33 byte internal key
+	const TAPROOT_BAD_INTERNAL_KEY: &str = "70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000\
+	ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369\
+	df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63f\
+	f03183ecd8f609c7510ae8a48e03910a075701172102fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee239304\
+	6d3fa232000000";
+
+	// BIP371: key path signature of the wrong size
+	const TAPROOT_BAD_KEY_SIG: &str = "70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000\
+	ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369\
+	df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63f\
+	f03183ecd8f609c7510ae8a48e03910a0757011342173bb3d36c074afb716fec6307a069a2e450b995f3c82785945ab8df0e\
+	24260dcd703b0cbf34de399184a9481ac2b3586db6601f026a77f7e4938481bc34751701aa000000";
+
+	// BIP371: control block of the wrong size
+	const TAPROOT_BAD_CONTROL_BLOCK: &str = "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000\
+	ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca630000\
+	00000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b69261\
+	15c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da\
+	60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e123202c\
+	b13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc00000";
+
+	// BIP174 combiner: P2SH multisig and P2SH-P2WSH multisig inputs, one signature each
+	const PSBT_1: &str = "70736274ff01009a020000000258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd750000000000\
+	ffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d0100000000ffffffff0270aaf008\
+	00000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df55\
+	46e8742d1d87008f00000000000100bb0200000001aad73931018bd25f84ae400b68848be09db706eac2ac18298babee71ab\
+	656f8b0000000048473044022058f6fc7c6a33e1b31548d481c826c015bd30135aad42cd67790dab66d2ad243b02204a1ced\
+	2604c6735b6393e5b41691dd78b00f0c5942fb9f751856faa938157dba01feffffff0280f0fa020000000017a9140fb94634\
+	21696b82c833af241c78c17ddbde493487d0f20a270100000017a91429ca74f8a08f81999428185c97b5d852e4063f618765\
+	0000002202029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f473044022074018ad4180097\
+	b873323c0015720b3684cc8123891048e7dbcd9b55ad679c99022073d369b740e3eb53dcefa33823c8070514ca55a7dd9544\
+	f157c167913261118c01010304010000000104475221029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183\
+	f1ab96e07f2102dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d752ae2206029583bf39ae0a\
+	609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f10d90c6a4f000000800000008000000080220602dab61ff4\
+	9a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d710d90c6a4f0000008000000080010000800001012000\
+	c2eb0b0000000017a914b7f5faf40e3d40a5a459b1db3535f2b72fa921e887220203089dc10c7ac6db54f91329af617333db\
+	388cead0c231f723379d1b99030b02dc473044022062eb7a556107a7c73f45ac4ab5a1dddf6f7075fb1275969a7f383efff7\
+	84bcb202200c05dbb7470dbf2f08557dd356c7325c1ed30913e996cd3840945db12228da5f01010304010000000104220020\
+	8c2353173743b595dfb4a07b72ba8e42e3797da74e87fe7d9d7497e3b2028903010547522103089dc10c7ac6db54f91329af\
+	617333db388cead0c231f723379d1b99030b02dc21023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860\
+	221f0e7352ae2206023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e7310d90c6a4f00000080\
+	0000008003000080220603089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc10d90c6a4f0000\
+	0080000000800200008000220203a9a4c37f5996d3aa25dbac6b570af0650394492942460b354753ed9eeca5877110d90c6a\
+	4f000000800000008004000080002202027f6399757d2eff55a136ad02c684b1838b6556e5f1b6b34282a94b6b5005109610\
+	d90c6a4f00000080000000800500008000";
+
+	// BIP174 combiner: the same with the other signatures
+	const PSBT_2: &str = "70736274ff01009a020000000258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd750000000000\
+	ffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d0100000000ffffffff0270aaf008\
+	00000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df55\
+	46e8742d1d87008f00000000000100bb0200000001aad73931018bd25f84ae400b68848be09db706eac2ac18298babee71ab\
+	656f8b0000000048473044022058f6fc7c6a33e1b31548d481c826c015bd30135aad42cd67790dab66d2ad243b02204a1ced\
+	2604c6735b6393e5b41691dd78b00f0c5942fb9f751856faa938157dba01feffffff0280f0fa020000000017a9140fb94634\
+	21696b82c833af241c78c17ddbde493487d0f20a270100000017a91429ca74f8a08f81999428185c97b5d852e4063f618765\
+	0000002202029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f473044022074018ad4180097\
+	b873323c0015720b3684cc8123891048e7dbcd9b55ad679c99022073d369b740e3eb53dcefa33823c8070514ca55a7dd9544\
+	f157c167913261118c01220202dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d74830450221\
+	00f61038b308dc1da865a34852746f015772934208c6d24454393cd99bdf2217770220056e675a675a6d0a02b85b14e5e290\
+	74d8a25a9b5760bea2816f661910a006ea01010304010000000104475221029583bf39ae0a609747ad199addd634fa610855\
+	9d6c5cd39b4c2183f1ab96e07f2102dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d752ae22\
+	06029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f10d90c6a4f0000008000000080000000\
+	80220602dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d710d90c6a4f000000800000008001\
+	0000800001012000c2eb0b0000000017a914b7f5faf40e3d40a5a459b1db3535f2b72fa921e887220203089dc10c7ac6db54\
+	f91329af617333db388cead0c231f723379d1b99030b02dc473044022062eb7a556107a7c73f45ac4ab5a1dddf6f7075fb12\
+	75969a7f383efff784bcb202200c05dbb7470dbf2f08557dd356c7325c1ed30913e996cd3840945db12228da5f012202023a\
+	dd904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e73473044022065f45ba5998b59a27ffe1a7bed01\
+	6af1f1f90d54b3aa8f7450aa5f56a25103bd02207f724703ad1edb96680b284b56d4ffcb88f7fb759eabbe08aa30f29b8513\
+	83d2010103040100000001042200208c2353173743b595dfb4a07b72ba8e42e3797da74e87fe7d9d7497e3b2028903010547\
+	522103089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc21023add904f3d6dcf59ddb906b0de\
+	e23529b7ffb9ed50e5e86151926860221f0e7352ae2206023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e8615192\
+	6860221f0e7310d90c6a4f000000800000008003000080220603089dc10c7ac6db54f91329af617333db388cead0c231f723\
+	379d1b99030b02dc10d90c6a4f00000080000000800200008000220203a9a4c37f5996d3aa25dbac6b570af0650394492942\
+	460b354753ed9eeca5877110d90c6a4f000000800000008004000080002202027f6399757d2eff55a136ad02c684b1838b65\
+	56e5f1b6b34282a94b6b5005109610d90c6a4f00000080000000800500008000";
+
+	fn from_hex(hex: &str) -> Result<Psbt, PsbtError> {
+		Psbt::from_bytes(&hex.decode_hex_be().unwrap())
+	}
+
+	#[test]
+	fn bip174_valid_vectors() {
+		let psbt: Psbt = VALID_1.parse().unwrap();
+		assert_eq!(psbt.unsigned_tx.lock_time, 1257139);
+		assert_eq!(psbt.unsigned_tx.inputs[0].previous_tx, "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126");
+		let previous_tx = psbt.inputs[0].non_witness_utxo.as_ref().unwrap();
+		assert_eq!(previous_tx.txid(), "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126");
+		assert_eq!(psbt.spent_output(0).unwrap().amount, 200000000);
+		assert_eq!(psbt.outputs.len(), 2);
+		assert_eq!(psbt.to_string(), VALID_1);
+
+		let psbt = from_hex(VALID_3).unwrap();
+		assert_eq!(psbt.inputs[0].sighash_type, Some(SIGHASH_ALL));
+
+		let psbt = from_hex(VALID_5).unwrap();
+		let input = &psbt.inputs[0];
+		assert_eq!(input.witness_utxo.as_ref().unwrap().amount, 199909013);
+		assert_eq!(input.partial_sigs.len(), 1);
+		assert_eq!(input.redeem_script.as_ref().unwrap().encode_hex_be(), "0020771fd18ad459666dd49f3d564e3dbc42f4c84774e360ada16816a8ed488d5681");
+		assert_eq!(input.witness_script.as_ref().unwrap().len(), 71);
+		let pubkey = "03b1341ccba7683b6af4f1238cd6e97e7167d569fac47f1e48d47541844355bd46".decode_hex_be().unwrap();
+		let source = KeySource { fingerprint: [0xb4, 0xa6, 0xba, 0x67], path: vec![0x80000000, 0x80000000, 0x80000004] };
+		assert_eq!(input.bip32_derivation.get(&pubkey), Some(&source));
+
+		// unknown keys are kept
+		let psbt = from_hex(VALID_6).unwrap();
+		assert_eq!(psbt.inputs[0].unknown.len(), 1);
+		let (key, value) = psbt.inputs[0].unknown.iter().next().unwrap();
+		assert_eq!(key.encode_hex_be(), "0f010203040506070809");
+		assert_eq!(value.encode_hex_be(), "0102030405060708090a0b0c0d0e0f");
+
+		for hex in [VALID_3, VALID_5, VALID_6, PSBT_1] {
+			assert_eq!(from_hex(hex).unwrap().serialize().encode_hex_be(), hex);
+		}
+	}
+
+	#[test]
+	fn bip174_invalid_vectors() {
+		assert_eq!(from_hex(NO_MAGIC), Err(PsbtError::InvalidMagic));
+		assert!(matches!(from_hex(MISSING_OUTPUTS), Err(PsbtError::Decode(_))));
+		assert_eq!(from_hex(SIGNED_UNSIGNED_TX), Err(PsbtError::UnsignedTxHasScripts));
+		assert_eq!(from_hex(NO_UNSIGNED_TX), Err(PsbtError::MissingUnsignedTx));
+		assert_eq!(Psbt::from_base64("not base64!"), Err(PsbtError::InvalidBase64));
+
+		// every key of a map is unique
+		let mut duplicate = VALID_6.decode_hex_be().unwrap().to_vec();
+		let input_map = VALID_6.find("0a0f").unwrap() / 2;
+		let pair = duplicate[input_map..input_map + 27].to_vec();
+		duplicate.splice(input_map..input_map, pair);
+		assert_eq!(Psbt::from_bytes(&duplicate), Err(PsbtError::DuplicateKey("0f010203040506070809".to_string())));
+
+		// each cut ends in the middle of a map
+		let bytes = VALID_5.decode_hex_be().unwrap();
+		for len in 5..bytes.len() {
+			assert!(Psbt::from_bytes(&bytes[..len]).is_err(), "{}", len);
+		}
+		let mut padded = bytes.to_vec();
+		padded.push(0);
+		assert!(matches!(Psbt::from_bytes(&padded), Err(PsbtError::Decode(_))));
+	}
+
+	#[test]
+	fn bip371_vectors() {
+		let psbt = from_hex(TAPROOT_VALID).unwrap();
+		assert_eq!(psbt.inputs[0].tap_internal_key.as_ref().unwrap().len(), 32);
+		assert_eq!(psbt.inputs[0].tap_bip32_derivation.len(), 1);
+		assert_eq!(psbt.outputs[0].tap_internal_key.as_ref().unwrap().len(), 32);
+		assert_eq!(psbt.serialize().encode_hex_be(), TAPROOT_VALID);
+
+		assert!(matches!(from_hex(TAPROOT_BAD_INTERNAL_KEY), Err(PsbtError::InvalidValue { .. })));
+		assert!(matches!(from_hex(TAPROOT_BAD_KEY_SIG), Err(PsbtError::InvalidValue { .. })));
+		assert!(matches!(from_hex(TAPROOT_BAD_CONTROL_BLOCK), Err(PsbtError::InvalidKey(_))));
+	}
+
+	#[test]
+	fn combine_psbts() {
+		let mut psbt = from_hex(PSBT_1).unwrap();
+		psbt.combine(from_hex(PSBT_2).unwrap()).unwrap();
+		assert_eq!(psbt, from_hex(PSBT_2).unwrap());
+
+		let mut psbt = from_hex(PSBT_2).unwrap();
+		psbt.combine(from_hex(PSBT_1).unwrap()).unwrap();
+		assert_eq!(psbt, from_hex(PSBT_2).unwrap());
+
+		let other = from_hex(VALID_5).unwrap();
+		assert_eq!(psbt.combine(other), Err(PsbtError::DifferentTransactions));
+	}
+
+	#[test]
+	fn finalize_and_extract() {
+		let mut psbt = from_hex(PSBT_1).unwrap();
+		assert_eq!(psbt.finalize(), Err(PsbtError::CannotFinalize(0)));
+		assert_eq!(from_hex(PSBT_1).unwrap().extract_tx(), Err(PsbtError::NotFinalized(0)));
+
+		let mut psbt = from_hex(PSBT_2).unwrap();
+		psbt.finalize().unwrap();
+		for input in &psbt.inputs {
+			assert!(input.is_finalized());
+			assert!(input.partial_sigs.is_empty() && input.redeem_script.is_none() && input.bip32_derivation.is_empty());
+		}
+		assert!(psbt.inputs[0].final_script_witness.is_none());
+		assert_eq!(psbt.inputs[1].final_script_witness.as_ref().unwrap().0.len(), 4);
+		assert_eq!(from_hex(&psbt.serialize().encode_hex_be()).unwrap(), psbt);
+
+		// the signatures of the test vectors are valid
+		let tx = psbt.extract_tx().unwrap();
+		tx.verify().unwrap();
+		assert_eq!(Psbt::from_unsigned_tx(tx).unwrap_err(), PsbtError::UnsignedTxHasScripts);
+	}
+
+	#[test]
+	fn sign_on_two_devices() {
+		let alice = PrivateKey::from_bytes(&[1; 32], true, Network::Testnet).unwrap();
+		let bob = PrivateKey::from_bytes(&[2; 32], true, Network::Testnet).unwrap();
+		let multisig = ScriptPubKey::new_multisig(2, &[alice.public_key().as_bytes(), bob.public_key().as_bytes()]).unwrap().script;
+
+		let input = |previous_tx: &str, tx_index| Input {
+			previous_tx: previous_tx.to_string(),
+			tx_index,
+			script_sig: ScriptSig::new(Box::new([])),
+			sequence: "fffffffd".to_string(),
+			prevout: None,
+		};
+		let transaction = |inputs: Vec<Input>, outputs: Vec<Output>| Transaction {
+			version: 2,
+			flag: None,
+			in_counter: inputs.len() as u64,
+			inputs,
+			out_counter: outputs.len() as u64,
+			outputs,
+			witness_data: None,
+			lock_time: 0,
+			extra_info: None,
+		};
+		// pays to alice and to a 2-of-2 multisig of alice and bob
+		let previous_tx = || transaction(vec![input(&"11".repeat(32), 0)], vec![
+			Output { amount: 50_000, script_pub_key: ScriptPubKey::new_p2pkh(alice.public_key().as_bytes()) },
+			Output { amount: 100_000, script_pub_key: ScriptPubKey::new_p2wsh(multisig.clone()) },
+		]);
+		let txid = previous_tx().txid();
+
+		// Creator
+		let unsigned_tx = transaction(vec![input(&txid, 0), input(&txid, 1)], vec![
+			Output { amount: 140_000, script_pub_key: ScriptPubKey::new_p2wpkh(bob.public_key().as_bytes()) },
+		]);
+		let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+
+		// Updater
+		assert_eq!(psbt.set_non_witness_utxo(0, transaction(vec![input(&txid, 0)], Vec::new())), Err(PsbtError::PrevoutMismatch(0)));
+		psbt.set_non_witness_utxo(0, previous_tx()).unwrap();
+		let mut prevouts = MemoryPrevouts::new();
+		prevouts.insert(&txid, 0, Output { amount: 50_000, script_pub_key: ScriptPubKey::new_p2pkh(alice.public_key().as_bytes()) });
+		prevouts.insert(&txid, 1, Output { amount: 100_000, script_pub_key: ScriptPubKey::new_p2wsh(multisig.clone()) });
+		psbt.add_witness_utxos(&prevouts).unwrap();
+		psbt.add_scripts(std::slice::from_ref(&multisig));
+		assert!(psbt.inputs[0].witness_utxo.is_none());
+		assert_eq!(psbt.inputs[1].witness_script, Some(multisig.clone()));
+		let shared = psbt.to_string();
+
+		// Signers, each with its own copy
+		let mut alice_psbt: Psbt = shared.parse().unwrap();
+		assert_eq!(alice_psbt.sign(&[alice]).unwrap(), 2);
+		let mut bob_psbt: Psbt = shared.parse().unwrap();
+		assert_eq!(bob_psbt.sign(&[bob]).unwrap(), 1);
+
+		// alice alone can only finalize her own input
+		assert_eq!(alice_psbt.finalize(), Err(PsbtError::CannotFinalize(1)));
+		assert!(alice_psbt.inputs[0].is_finalized());
+
+		// Combiner, Finalizer and Extractor
+		let mut psbt: Psbt = alice_psbt.to_string().parse().unwrap();
+		psbt.combine(bob_psbt.to_string().parse().unwrap()).unwrap();
+		assert_eq!(psbt.inputs[1].partial_sigs.len(), 2);
+		psbt.finalize().unwrap();
+		let tx = psbt.extract_tx().unwrap();
+		tx.verify().unwrap();
+		assert!(tx.inputs[1].script_sig.script.is_empty());
+		assert_eq!(tx.witness_data.as_ref().unwrap()[0].0.len(), 0);
+	}
+
+	#[test]
+	fn sign_p2sh_multisig_needs_previous_tx() {
+		let alice = PrivateKey::from_bytes(&[1; 32], true, Network::Testnet).unwrap();
+		let bob = PrivateKey::from_bytes(&[2; 32], true, Network::Testnet).unwrap();
+		let multisig = ScriptPubKey::new_multisig(1, &[alice.public_key().as_bytes(), bob.public_key().as_bytes()]).unwrap().script;
+		let spent = || Output { amount: 100_000, script_pub_key: ScriptPubKey::new_p2sh(multisig.clone()) };
+		let previous_tx = Transaction {
+			version: 2,
+			flag: None,
+			in_counter: 1,
+			inputs: vec![unsigned_input(&"11".repeat(32), 0)],
+			out_counter: 1,
+			outputs: vec![spent()],
+			witness_data: None,
+			lock_time: 0,
+			extra_info: None,
+		};
+		let txid = previous_tx.txid();
+		let unsigned_tx = Transaction {
+			version: 2,
+			flag: None,
+			in_counter: 1,
+			inputs: vec![unsigned_input(&txid, 0)],
+			out_counter: 1,
+			outputs: vec![Output { amount: 90_000, script_pub_key: ScriptPubKey::new_p2wpkh(bob.public_key().as_bytes()) }],
+			witness_data: None,
+			lock_time: 0,
+			extra_info: None,
+		};
+		let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+
+		// the redeem script isn't a witness program, so the output isn't added as a witness utxo
+		let mut prevouts = MemoryPrevouts::new();
+		prevouts.insert(&txid, 0, spent());
+		psbt.inputs[0].redeem_script = Some(multisig.clone());
+		psbt.add_witness_utxos(&prevouts).unwrap();
+		assert!(psbt.inputs[0].witness_utxo.is_none());
+
+		// nor signed with only the spent output, a legacy signature doesn't commit to its amount
+		psbt.inputs[0].witness_utxo = Some(spent());
+		assert_eq!(psbt.sign(&[alice]).unwrap(), 0);
+
+		psbt.inputs[0].witness_utxo = None;
+		psbt.set_non_witness_utxo(0, previous_tx).unwrap();
+		assert_eq!(psbt.sign(&[alice]).unwrap(), 1);
+
+		// a redeem script that doesn't hash to the spent script can't finalize the input
+		let mut wrong_redeem: Psbt = psbt.to_string().parse().unwrap();
+		wrong_redeem.inputs[0].redeem_script = Some(ScriptPubKey::new_multisig(1, &[bob.public_key().as_bytes()]).unwrap().script);
+		assert_eq!(wrong_redeem.finalize(), Err(PsbtError::CannotFinalize(0)));
+		psbt.finalize().unwrap();
+		psbt.extract_tx().unwrap().verify().unwrap();
+	}
+
+	fn unsigned_input(previous_tx: &str, tx_index: u32) -> Input {
+		Input {
+			previous_tx: previous_tx.to_string(),
+			tx_index,
+			script_sig: ScriptSig::new(Box::new([])),
+			sequence: "fffffffd".to_string(),
+			prevout: None,
+		}
+	}
+
+	fn lock_time_input(time: Option<u32>, height: Option<u32>) -> PsbtInput {
+		PsbtInput { required_time_lock_time: time, required_height_lock_time: height, ..PsbtInput::default() }
+	}
+
+	#[test]
+	fn bip370_serialization() {
+		let mut psbt = Psbt::new_v2(2, Some(100));
+		let witness_utxo = Output { amount: 10_000, script_pub_key: ScriptPubKey::from_bytes([0x51].into()) };
+		let input = PsbtInput { witness_utxo: Some(witness_utxo), ..PsbtInput::default() };
+		assert_eq!(psbt.add_input(unsigned_input(&"11".repeat(32), 1), input), Ok(0));
+		let output = Output { amount: 9_000, script_pub_key: ScriptPubKey::from_bytes([0x6a].into()) };
+		assert_eq!(psbt.add_output(output, PsbtOutput::default()), Ok(0));
+		assert_eq!(psbt.unsigned_tx.lock_time, 100);
+
+		// the transaction is in the maps: version, fallback lock time, counts and flags, then the
+		// outpoint and sequence of the input and the amount and script of the output
+		let hex = psbt.serialize().encode_hex_be();
+		assert_eq!(hex, "70736274ff010204020000000103046400000001040101010501010106010301fb040200000000\
+			01010a10270000000000000151010e201111111111111111111111111111111111111111111111111111111111111111\
+			010f0401000000011004fdffffff0001030828230000000000000104016a00");
+		let parsed = from_hex(&hex).unwrap();
+		assert_eq!(parsed, psbt);
+		assert_eq!(parsed.unsigned_tx.inputs[0].sequence, "fffffffd");
+		assert_eq!(parsed.unsigned_tx.outputs[0].amount, 9_000);
+		assert_eq!(parsed.unique_id(), psbt.unique_id());
+		assert_ne!(parsed.unique_id(), psbt.unsigned_tx.txid());
+	}
+
+	#[test]
+	fn bip370_invalid() {
+		// version 1 doesn't exist
+		assert_eq!(from_hex("70736274ff01fb040100000000"), Err(PsbtError::UnsupportedVersion(1)));
+		assert_eq!(from_hex("70736274ff010204020000000105010001fb040200000000"), Err(PsbtError::MissingField("PSBT_GLOBAL_INPUT_COUNT")));
+		assert_eq!(from_hex("70736274ff01fb040200000000"), Err(PsbtError::MissingField("PSBT_GLOBAL_TX_VERSION")));
+		// an input without its output index
+		let no_output_index = "70736274ff01020402000000010401010105010001fb040200000000\
+			010e2011111111111111111111111111111111111111111111111111111111111111110000";
+		assert_eq!(from_hex(no_output_index), Err(PsbtError::MissingField("PSBT_IN_OUTPUT_INDEX")));
+		// a negative amount
+		let negative_amount = "70736274ff01020402000000010401000105010101fb040200000000010308ffffffffffffffff0104016a00";
+		assert!(matches!(from_hex(negative_amount), Err(PsbtError::InvalidValue { .. })));
+		// a required time lock time that is a height
+		let bad_lock_time = "70736274ff01020402000000010401010105010001fb040200000000\
+			010e201111111111111111111111111111111111111111111111111111111111111111\
+			010f04000000000111046400000000";
+		assert!(matches!(from_hex(bad_lock_time), Err(PsbtError::InvalidValue { .. })));
+
+		// version 0 has an unsigned transaction, version 2 doesn't
+		let mut bytes = VALID_1.parse::<Psbt>().unwrap().serialize();
+		bytes.splice(5..5, "01fb0402000000".decode_hex_be().unwrap().to_vec());
+		assert_eq!(Psbt::from_bytes(&bytes), Err(PsbtError::InvalidKey("00".to_string())));
+		let mut psbt = Psbt::new_v2(2, None);
+		psbt.add_output(Output { amount: 1, script_pub_key: ScriptPubKey::from_bytes([0x6a].into()) }, PsbtOutput::default()).unwrap();
+		let mut bytes = psbt.serialize();
+		let version = psbt.serialize().encode_hex_be().find("01fb0402000000").unwrap() / 2;
+		bytes[version + 3] = 0;
+		assert_eq!(Psbt::from_bytes(&bytes), Err(PsbtError::InvalidKey("02".to_string())));
+
+		// a version 0 PSBT can't be added to
+		let mut psbt = from_hex(PSBT_1).unwrap();
+		assert_eq!(psbt.add_input(unsigned_input(&"11".repeat(32), 0), PsbtInput::default()), Err(PsbtError::NotModifiable));
+		let output = Output { amount: 1, script_pub_key: ScriptPubKey::from_bytes([0x6a].into()) };
+		assert_eq!(psbt.add_output(output, PsbtOutput::default()), Err(PsbtError::NotModifiable));
+	}
+
+	#[test]
+	fn bip370_lock_time() {
+		let txid = "11".repeat(32);
+		let mut psbt = Psbt::new_v2(2, Some(10));
+		psbt.add_input(unsigned_input(&txid, 0), PsbtInput::default()).unwrap();
+		assert_eq!(psbt.unsigned_tx.lock_time, 10);
+
+		// blocks are used when every input accepts them, the latest lock time wins
+		psbt.add_input(unsigned_input(&txid, 1), lock_time_input(Some(1_700_000_000), Some(800_000))).unwrap();
+		assert_eq!(psbt.unsigned_tx.lock_time, 800_000);
+		psbt.add_input(unsigned_input(&txid, 2), lock_time_input(None, Some(700_000))).unwrap();
+		assert_eq!(psbt.unsigned_tx.lock_time, 800_000);
+		assert_eq!(psbt.add_input(unsigned_input(&txid, 3), lock_time_input(Some(1_600_000_000), None)), Err(PsbtError::IncompatibleLockTimes));
+		assert_eq!(psbt.inputs.len(), 3);
+		assert_eq!(psbt.unsigned_tx.inputs.len(), 3);
+		assert_eq!(from_hex(&psbt.serialize().encode_hex_be()).unwrap(), psbt);
+
+		let mut psbt = Psbt::new_v2(2, None);
+		psbt.add_input(unsigned_input(&txid, 0), lock_time_input(Some(1_700_000_000), Some(800_000))).unwrap();
+		psbt.add_input(unsigned_input(&txid, 1), lock_time_input(Some(1_600_000_000), None)).unwrap();
+		assert_eq!(psbt.unsigned_tx.lock_time, 1_700_000_000);
+	}
+
+	#[test]
+	fn bip370_construct_together() {
+		let alice = PrivateKey::from_bytes(&[1; 32], true, Network::Testnet).unwrap();
+		let bob = PrivateKey::from_bytes(&[2; 32], true, Network::Testnet).unwrap();
+		let alice_script = || ScriptPubKey::new_p2wpkh(alice.public_key().as_bytes());
+		let bob_script = || ScriptPubKey::new_p2wpkh(bob.public_key().as_bytes());
+		let funding = || ScriptPubKey::new_p2wsh(ScriptPubKey::new_multisig(2, &[alice.public_key().as_bytes(), bob.public_key().as_bytes()]).unwrap().script);
+		let spending = |amount, script_pub_key| PsbtInput { witness_utxo: Some(Output { amount, script_pub_key }), ..PsbtInput::default() };
+
+		// alice creates the funding transaction with her input and the shared output, bob adds his
+		// input and his change
+		let mut psbt = Psbt::new_v2(2, Some(800_000));
+		psbt.add_input(unsigned_input(&"aa".repeat(32), 0), spending(60_000, alice_script())).unwrap();
+		psbt.add_output(Output { amount: 100_000, script_pub_key: funding() }, PsbtOutput::default()).unwrap();
+		let mut bob_psbt: Psbt = psbt.to_string().parse().unwrap();
+		bob_psbt.add_input(unsigned_input(&"bb".repeat(32), 3), spending(50_000, bob_script())).unwrap();
+		bob_psbt.add_output(Output { amount: 9_000, script_pub_key: bob_script() }, PsbtOutput::default()).unwrap();
+		let shared = bob_psbt.to_string();
+
+		// once signed, the inputs and outputs are committed to
+		let mut alice_psbt: Psbt = shared.parse().unwrap();
+		assert_eq!(alice_psbt.sign(&[alice]).unwrap(), 1);
+		assert_eq!(alice_psbt.tx_modifiable, Some(0));
+		let output = Output { amount: 1_000, script_pub_key: alice_script() };
+		assert_eq!(alice_psbt.add_output(output, PsbtOutput::default()), Err(PsbtError::NotModifiable));
+
+		// bob signs with SIGHASH_ANYONECANPAY, which still lets inputs be added as long as the lock
+		// time doesn't change
+		let mut bob_psbt: Psbt = shared.parse().unwrap();
+		bob_psbt.inputs[1].sighash_type = Some(SIGHASH_ALL | SIGHASH_ANYONECANPAY);
+		assert_eq!(bob_psbt.sign(&[bob]).unwrap(), 1);
+		assert_eq!(bob_psbt.tx_modifiable, Some(TX_MODIFIABLE_INPUTS));
+		let mut other_psbt: Psbt = bob_psbt.to_string().parse().unwrap();
+		let later = lock_time_input(None, Some(900_000));
+		assert_eq!(other_psbt.add_input(unsigned_input(&"cc".repeat(32), 0), later), Err(PsbtError::NotModifiable));
+		assert_eq!(other_psbt.add_input(unsigned_input(&"cc".repeat(32), 0), PsbtInput::default()), Ok(2));
+		assert_eq!(other_psbt.unsigned_tx.lock_time, 800_000);
+		assert_eq!(bob_psbt.combine(other_psbt), Err(PsbtError::DifferentTransactions));
+
+		// an updater changing a sequence doesn't make it another transaction
+		bob_psbt.unsigned_tx.inputs[0].sequence = "fffffffe".to_string();
+		let mut psbt: Psbt = alice_psbt.to_string().parse().unwrap();
+		psbt.combine(bob_psbt).unwrap();
+		assert_eq!(psbt.tx_modifiable, Some(0));
+		assert_eq!(psbt.unsigned_tx.inputs[0].sequence, "fffffffd");
+		psbt.finalize().unwrap();
+		let tx = psbt.extract_tx().unwrap();
+		assert_eq!(tx.lock_time, 800_000);
+		tx.verify().unwrap();
+	}
+
+	#[test]
+	fn bip370_sighash_single_pairs() {
+		let output = || Output { amount: 1_000, script_pub_key: ScriptPubKey::from_bytes([0x51].into()) };
+		let mut psbt = Psbt::new_v2(2, None);
+		psbt.tx_modifiable = Some(TX_MODIFIABLE_INPUTS | TX_MODIFIABLE_OUTPUTS | TX_MODIFIABLE_SIGHASH_SINGLE);
+		assert_eq!(psbt.add_input(unsigned_input(&"11".repeat(32), 0), PsbtInput::default()), Ok(0));
+		assert_eq!(psbt.add_input(unsigned_input(&"22".repeat(32), 0), PsbtInput::default()), Err(PsbtError::UnpairedSighashSingle));
+		assert_eq!(psbt.add_output(output(), PsbtOutput::default()), Ok(0));
+
+		// the output can come first too
+		assert_eq!(psbt.add_output(output(), PsbtOutput::default()), Ok(1));
+		assert_eq!(psbt.add_output(output(), PsbtOutput::default()), Err(PsbtError::UnpairedSighashSingle));
+		assert_eq!(psbt.add_input(unsigned_input(&"22".repeat(32), 0), PsbtInput::default()), Ok(1));
+		assert_eq!((psbt.inputs.len(), psbt.outputs.len()), (2, 2));
+	}
+}
@@ -1,9 +1,13 @@
-/// Code ideas from https://github.com/rust-bitcoin/rust-bitcoin/blob/master/src/blockdata/script.rs
+// Code ideas from https://github.com/rust-bitcoin/rust-bitcoin/blob/master/src/blockdata/script.rs
 
 use std::error::Error;
 use std::io::{BufRead, Cursor};
 use crate::txio::{Encodable, Decodable, HexBytes, ReadExt, UserReadExt};
-use crate::{Serialize, opcodes, Deserialize, hash};
+use crate::{Serialize, opcodes, Deserialize, hash, address, base58, interpreter};
+#[cfg(test)]
+use crate::error::DecodeError;
+use crate::key::PublicKey;
+use crate::network::Network;
 use std::fmt;
 
 // TODO: Q. How do I organize the code so that I can have a ScriptPubKey and ScriptSig type with
@@ -14,13 +18,13 @@ use std::fmt;
 // macro. It's effectively writing the code twice though. Is there a better way to do this?
 
 /// Script is the programming language used in Bitcoin to construct a ScriptSig and ScriptPubKey.
-/// While the semantics of the language haven't been handled yet, parsing to and from has been
-/// implemented.
+/// This trait handles parsing to and from scripts. Executing them is done by the interpreter.
 pub trait Script {
 	/// Convert hex-formatted bytes into a Script type.
 	fn from_bytes(bytes: HexBytes) -> Self;
 	/// Convert a hex string into a Script type.
-	fn from_str(hex: &str) -> Self;
+	#[cfg(test)]
+	fn from_str(hex: &str) -> Result<Self, DecodeError> where Self: Sized;
 
 	/// Create a new Pay-to-Script-Hash (P2SH) type script given an original script. This function does a hash160 of
 	/// the provided raw script before generating the P2SH script.
@@ -29,6 +33,23 @@ pub trait Script {
 	fn new_p2pkh(public_key: HexBytes) -> Self;
 	/// Create a new Pay-to-Public-Key (P2PK) type script given a public key.
 	fn new_p2pk(public_key: HexBytes) -> Self;
+	/// Create a new Pay-to-Witness-Public-Key-Hash (P2WPKH) type script given a compressed public key.
+	fn new_p2wpkh(public_key: HexBytes) -> Self;
+	/// Create a new Pay-to-Witness-Script-Hash (P2WSH) type script given a witness script. This
+	/// function does a sha256 of the provided script.
+	fn new_p2wsh(witness_script: HexBytes) -> Self;
+	/// Create a new Pay-to-Taproot (P2TR) type script given the 32 byte x-only output key. The
+	/// output key is the internal key already tweaked with the script tree.
+	#[cfg(test)]
+	fn new_p2tr(output_key: HexBytes) -> Self;
+	/// Create a new segwit output script of any version given the witness program. The version
+	/// goes from 0 to 16 and the program is 2 to 40 bytes, or 20 or 32 bytes for version 0.
+	fn new_witness_program(version: u8, program: &[u8]) -> Result<Self, BuildError> where Self: Sized;
+	/// Create a new bare multisig script where `required` of the public keys have to sign. Fails
+	/// for more than 16 keys and unless 1 to all of the keys are required.
+	fn new_multisig(required: usize, public_keys: &[HexBytes]) -> Result<Self, BuildError> where Self: Sized;
+	/// Create a new provably unspendable OP_RETURN script carrying some data.
+	fn new_op_return(data: &[u8]) -> Self;
 
 	/// Checks whether a script pubkey is a P2SH output.
 	fn is_p2sh(&self) -> bool;
@@ -36,10 +57,19 @@ pub trait Script {
 	fn is_p2pkh(&self) -> bool;
 	/// Checks whether a script pubkey is a P2PK output.
 	fn is_p2pk(&self) -> bool;
+	/// Checks whether a script pubkey is a P2WPKH output.
+	fn is_p2wpkh(&self) -> bool;
+	/// Checks whether a script pubkey is a P2WSH output.
+	fn is_p2wsh(&self) -> bool;
+	/// Checks whether a script pubkey is a P2TR output.
+	fn is_p2tr(&self) -> bool;
+	/// Checks whether a script pubkey is a segwit output of any version.
+	fn is_witness_program(&self) -> bool;
+	/// Checks whether a script pubkey is a bare multisig output.
+	fn is_multisig(&self) -> bool;
+	/// Checks whether a script pubkey is an OP_RETURN output followed only by pushes.
+	fn is_op_return(&self) -> bool;
 
-	/// Generate an Base58 encoded address of a given script. 
-	/// Note: at the moment, this only generates an address for P2SH and P2PKH script types.
-	fn get_address(&self) -> Option<String>;
 	/// Determine the script type.
 	fn get_type(&self) -> ScriptType;
 
@@ -48,19 +78,24 @@ pub trait Script {
 	fn as_asm(&self) -> String;
 }
 
+/// A segwit output script: the witness version opcode followed by a push of the program.
+fn witness_program_script<T: Script>(version: opcodes::All, program: &[u8]) -> T {
+	let mut script_builder = ScriptBuilder::new();
+	script_builder.push_opcode(version);
+	script_builder.push_slice(program);
+	script_builder.into_script()
+}
+
 macro_rules! impl_script_for {
-	($ty: ty) => {
+	($ty: ty, $new: expr) => {
 		impl Script for $ty {
 			fn from_bytes(bytes: HexBytes) -> Self {
-				<$ty>::new(bytes)
+				($new)(bytes)
 			}
 
-			fn from_str(hex: &str) -> Self {
-				let bytes = match hex.decode_hex_be() {
-					Ok(b) => b,
-					Err(e) => panic!("{}", e)
-				};
-				<$ty>::from_bytes(bytes)
+			#[cfg(test)]
+			fn from_str(hex: &str) -> Result<Self, DecodeError> {
+				Ok(<$ty>::from_bytes(hex.decode_hex_be()?))
 			}
 
 			fn new_p2sh(original_script: HexBytes) -> Self {
@@ -85,11 +120,62 @@ macro_rules! impl_script_for {
 
 			fn new_p2pk(public_key: HexBytes) -> Self {
 				let mut script_builder = ScriptBuilder::new();
-				script_builder.push_slice(&public_key);
+				script_builder.push_key(public_key);
 				script_builder.push_opcode(opcodes::all::OP_CHECKSIG);
 				script_builder.into_script()
 			}
 
+			fn new_p2wpkh(public_key: HexBytes) -> Self {
+				witness_program_script(opcodes::all::OP_PUSHBYTES_0, &hash::hash160(&public_key))
+			}
+
+			fn new_p2wsh(witness_script: HexBytes) -> Self {
+				witness_program_script(opcodes::all::OP_PUSHBYTES_0, &hash::sha256(&witness_script))
+			}
+
+			#[cfg(test)]
+			fn new_p2tr(output_key: HexBytes) -> Self {
+				witness_program_script(opcodes::all::OP_PUSHNUM_1, &output_key)
+			}
+
+			fn new_witness_program(version: u8, program: &[u8]) -> Result<Self, BuildError> {
+				if version > 16 {
+					return Err(BuildError::InvalidWitnessVersion(version));
+				}
+				if !(2..=40).contains(&program.len()) || (version == 0 && program.len() != 20 && program.len() != 32) {
+					return Err(BuildError::InvalidWitnessProgramLength(program.len()));
+				}
+				let mut script_builder = ScriptBuilder::new();
+				script_builder.push_int(version)?;
+				script_builder.push_slice(program);
+				Ok(script_builder.into_script())
+			}
+
+			fn new_multisig(required: usize, public_keys: &[HexBytes]) -> Result<Self, BuildError> {
+				let key_count = u8::try_from(public_keys.len()).map_err(|_| BuildError::TooManyKeys(public_keys.len()))?;
+				if key_count > 16 {
+					return Err(BuildError::TooManyKeys(public_keys.len()));
+				}
+				if required == 0 || required > public_keys.len() {
+					return Err(BuildError::InvalidRequiredSignatures(required));
+				}
+				let mut script_builder = ScriptBuilder::new();
+				script_builder.push_int(required as u8)?;
+				for key in public_keys {
+					script_builder.push_key(key.clone());
+				}
+				script_builder.push_int(key_count)?;
+				script_builder.push_opcode(opcodes::all::OP_CHECKMULTISIG);
+				Ok(script_builder.into_script())
+			}
+
+			fn new_op_return(data: &[u8]) -> Self {
+				let mut script_builder = ScriptBuilder::new();
+				script_builder.push_opcode(opcodes::all::OP_RETURN);
+				script_builder.push_slice(data);
+				script_builder.into_script()
+			}
+
 			#[inline]
 			fn is_p2sh(&self) -> bool {
 				self.script.len() == 23
@@ -124,35 +210,61 @@ macro_rules! impl_script_for {
 				}
 			}
 
-			fn get_address(&self) -> Option<String> {
-				if self.is_p2pkh() {
-					let pubkey_hash = &self.script[3..23];
-					let mut bytes = vec![111]; // using testnet prefix
-					bytes.extend_from_slice(pubkey_hash);
-					let checksum = &hash::hash256(&bytes.clone())[..4];
-					bytes.extend_from_slice(&checksum);
-					assert_eq!(bytes.len(), 25);
-					Some(bs58::encode(bytes).into_string())
-				} else if self.is_p2sh() {
-					let pubkey_hash = &self.script[2..22];
-					let mut bytes = vec![196]; // using testnet prefix
-					bytes.extend_from_slice(pubkey_hash);
-					let checksum = &hash::hash256(&bytes.clone())[..4];
-					bytes.extend_from_slice(&checksum);
-					assert_eq!(bytes.len(), 25);
-					Some(bs58::encode(bytes).into_string())
-				} else {
-					None
+			#[inline]
+			fn is_p2wpkh(&self) -> bool {
+				matches!(interpreter::witness_program(&self.script), Some((0, program)) if program.len() == 20)
+			}
+
+			#[inline]
+			fn is_p2wsh(&self) -> bool {
+				matches!(interpreter::witness_program(&self.script), Some((0, program)) if program.len() == 32)
+			}
+
+			#[inline]
+			fn is_p2tr(&self) -> bool {
+				matches!(interpreter::witness_program(&self.script), Some((1, program)) if program.len() == 32)
+			}
+
+			#[inline]
+			fn is_witness_program(&self) -> bool {
+				interpreter::witness_program(&self.script).is_some()
+			}
+
+			fn is_multisig(&self) -> bool {
+				// Core only counts keys of a valid public key size as standard
+				match parse_multisig(&self.script) {
+					Some((required, pubkeys)) => required > 0 && pubkeys.iter().all(|key| key.len() == 33 || key.len() == 65),
+					None => false,
+				}
+			}
+
+			fn is_op_return(&self) -> bool {
+				match self.script.split_first() {
+					Some((first, rest)) => *first == opcodes::all::OP_RETURN.into_u8() && interpreter::is_push_only(rest),
+					None => false,
 				}
 			}
 
+			// Same order as Solver in Core's script/solver.cpp
 			fn get_type(&self) -> ScriptType {
 				if self.is_p2sh() {
 					ScriptType::P2SH
-				} else if self.is_p2pkh() {
-					ScriptType::P2PKH
+				} else if let Some((version, program)) = interpreter::witness_program(&self.script) {
+					match (version, program.len()) {
+						(0, 20) => ScriptType::P2WPKH,
+						(0, 32) => ScriptType::P2WSH,
+						(0, _) => ScriptType::Custom,
+						(1, 32) => ScriptType::P2TR,
+						_ => ScriptType::WitnessUnknown,
+					}
+				} else if self.is_op_return() {
+					ScriptType::NullData
 				} else if self.is_p2pk() {
 					ScriptType::P2PK
+				} else if self.is_p2pkh() {
+					ScriptType::P2PKH
+				} else if self.is_multisig() {
+					ScriptType::Multisig
 				} else {
 					ScriptType::Custom
 				}
@@ -171,12 +283,19 @@ macro_rules! impl_script_for {
 
 					// not sure if this is the correct condition
 					if opcode.code == opcodes::all::OP_PUSHBYTES_1.into_u8() {
-						let size = stream.read_u8_le().expect("shouldn't fail i think");
+						// a push running past the end is shown like Core does
+						let Ok(size) = stream.read_u8_le() else {
+							parsed.push_str("[error]");
+							break;
+						};
 						parsed.push_str(&format!("{}", size));
 						parsed.push_str(" ");
 					} else if opcode.code > opcodes::all::OP_PUSHBYTES_1.into_u8() && opcode.code <= opcodes::all::OP_PUSHBYTES_75.into_u8() {
 						let len = opcode.code;
-						let script = stream.read_hex_var(len as u64).expect("shouldn't fail i think");
+						let Ok(script) = stream.read_hex_var(len as u64) else {
+							parsed.push_str("[error]");
+							break;
+						};
 						parsed.push_str(&(*script).encode_hex_be());
 						parsed.push_str(" ");
 					} else if opcode.code >= opcodes::all::OP_PUSHNUM_1.into_u8() && 
@@ -194,8 +313,9 @@ macro_rules! impl_script_for {
 	};
 }
 
-impl_script_for!(ScriptSig);
-impl_script_for!(ScriptPubKey);
+impl_script_for!(ScriptSig, ScriptSig::new);
+// Scripts created without a network get mainnet addresses, see `ScriptPubKey::set_network`.
+impl_script_for!(ScriptPubKey, |bytes| ScriptPubKey::new(bytes, Network::default()));
 
 /// Script Signatures are the unlocking script provided with an input that satisfies the conditions
 /// placed by the ScriptPubKey.
@@ -207,14 +327,55 @@ pub struct ScriptSig {
 /// it.
 pub struct ScriptPubKey {
 	pub script: HexBytes,
+	/// Only used for the address.
+	network: Network,
 	address: Option<String>,
 	script_type: Option<ScriptType>
 }
 
+/// Reasons a script can't be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+	/// Only 0 to 16 can be pushed with OP_0 and OP_1 to OP_16.
+	NumOutOfRange(u8),
+	/// Witness versions go from 0 to 16.
+	InvalidWitnessVersion(u8),
+	/// Witness programs are 2 to 40 bytes, version 0 programs are 20 or 32 bytes.
+	InvalidWitnessProgramLength(usize),
+	/// A bare multisig has at most 16 keys.
+	TooManyKeys(usize),
+	/// A multisig has to require at least one signature and at most one per key.
+	InvalidRequiredSignatures(usize),
+}
+
+impl fmt::Display for BuildError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			BuildError::NumOutOfRange(n) => write!(f, "{} can't be pushed as a small integer", n),
+			BuildError::InvalidWitnessVersion(v) => write!(f, "invalid witness version {}", v),
+			BuildError::InvalidWitnessProgramLength(len) => write!(f, "invalid witness program length of {} bytes", len),
+			BuildError::TooManyKeys(count) => write!(f, "a multisig can have at most 16 keys, not {}", count),
+			BuildError::InvalidRequiredSignatures(required) => write!(f, "a multisig can't require {} signatures", required),
+		}
+	}
+}
+
+impl Error for BuildError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ScriptType {
 	P2SH,
 	P2PKH,
 	P2PK,
+	P2WPKH,
+	P2WSH,
+	P2TR,
+	/// Segwit output of a version or program length without rules yet, spendable by anyone.
+	WitnessUnknown,
+	/// Bare multisig, `<m> <pubkeys...> <n> OP_CHECKMULTISIG`.
+	Multisig,
+	/// OP_RETURN output, used to put data in the chain.
+	NullData,
 	Custom
 }
 
@@ -229,21 +390,57 @@ impl ScriptSig {
 impl ScriptPubKey {
 	// FIXME: This is a hack because of a poor architectural choice. I have to instantiate a script 
 	// with None address and script_type first. What is the best way to fix this?
-	pub fn new(bytes: HexBytes) -> Self {
+	pub fn new(bytes: HexBytes, network: Network) -> Self {
 		let mut script = ScriptPubKey { 
 			script: bytes,
+			network,
 			address: None,
 			script_type: None
 		};
 
-		script.address = script.get_address();
+		script.address = script.address_on(network);
 		script.script_type = Some(script.get_type());
 		script
 	}
+
+	/// The network the address is for. Signet and regtest use the Base58 prefixes of testnet, and
+	/// signet the bech32 prefix of testnet too, so a script parsed from one of those addresses is
+	/// on Network::Testnet until `set_network` says otherwise.
+	pub fn network(&self) -> Network {
+		self.network
+	}
+
+	/// Change the network the address is shown for.
+	pub fn set_network(&mut self, network: Network) {
+		self.network = network;
+		self.address = self.address_on(network);
+	}
+
+	/// The address of the script on its network: Base58 for P2SH and P2PKH, bech32/bech32m for
+	/// witness programs. Other script types don't have an address.
+	pub fn get_address(&self) -> Option<String> {
+		self.address.clone()
+	}
+
+	fn address_on(&self, network: Network) -> Option<String> {
+		if self.is_p2pkh() {
+			let mut payload = vec![network.p2pkh_prefix()];
+			payload.extend_from_slice(&self.script[3..23]);
+			Some(base58::encode_check(&payload))
+		} else if self.is_p2sh() {
+			let mut payload = vec![network.p2sh_prefix()];
+			payload.extend_from_slice(&self.script[2..22]);
+			Some(base58::encode_check(&payload))
+		} else if let Some((version, program)) = interpreter::witness_program(&self.script) {
+			address::encode_segwit(network.bech32_hrp(), version, program)
+		} else {
+			None
+		}
+	}
 }
 
 macro_rules!  impl_serialize_for {
-	($ty: ty) => {
+	($ty: ty, $from_address: expr) => {
 		impl Serialize for $ty {
 			fn encode_raw<R: BufRead>(mut reader: R) -> Self {
 				println!("What type of script do you want to create?");
@@ -252,6 +449,7 @@ macro_rules!  impl_serialize_for {
 				println!("3. P2PK");
 				println!("4. Leave empty");
 				println!("5. Custom (be careful)");
+				println!("6. Address");
 				println!("Enter option:");
 				let option = reader.user_read_u32();
 
@@ -272,16 +470,19 @@ macro_rules!  impl_serialize_for {
 					<$ty>::new_p2sh(script.as_bytes())
 				} else if option == 2 { // p2pkh script
 					println!("Enter the public key:");
-					let key = <$ty>::from_bytes(reader.user_read_hex_var());
+					let key = read_public_key(&mut reader);
 					Self::new_p2pkh(key.as_bytes())
 				} else if option == 3 { // p2pk script
 					println!("Enter the public key:");
-					let key = <$ty>::from_bytes(reader.user_read_hex_var());
+					let key = read_public_key(&mut reader);
 					Self::new_p2pk(key.as_bytes())
 				}  else if option == 4 { // empty, useful for signrawtransactionwithwallet
-					<$ty>::from_str("")
+					<$ty>::from_bytes(Box::new([]))
 				} else if option == 5 { // custom script
 					<$ty>::from_bytes(reader.user_read_hex_var())
+				} else if option == 6 { // script the address pays to
+					println!("Enter the address:");
+					($from_address)(read_address(&mut reader))
 				} else {
 					todo!()
 				}
@@ -294,31 +495,85 @@ macro_rules!  impl_serialize_for {
 	};
 }
 
-impl_serialize_for!(ScriptSig);
-impl_serialize_for!(ScriptPubKey);
+impl_serialize_for!(ScriptSig, |script: ScriptPubKey| ScriptSig::from_bytes(script.script));
+// keeps the network of the address
+impl_serialize_for!(ScriptPubKey, |script: ScriptPubKey| script);
+
+/// Keep asking for a public key until a valid one is entered.
+fn read_public_key<R: BufRead>(reader: &mut R) -> PublicKey {
+	loop {
+		match PublicKey::from_bytes(&reader.user_read_hex_var()) {
+			Ok(key) => return key,
+			Err(e) => println!("Invalid public key ({}). Try again:", e),
+		}
+	}
+}
+
+/// Keep asking for an address until a valid one is entered.
+fn read_address<R: BufRead>(reader: &mut R) -> ScriptPubKey {
+	loop {
+		let mut line = String::new();
+		if let Err(e) = reader.read_line(&mut line) {
+			println!("{}! Try again:", e);
+			continue;
+		}
+		match ScriptPubKey::from_address(line.trim()) {
+			Ok(script) => return script,
+			Err(e) => println!("Invalid address ({}). Try again:", e),
+		}
+	}
+}
+
+/// Parse <m> <pubkey_1> ... <pubkey_n> <n> OP_CHECKMULTISIG into m and the public keys.
+pub(crate) fn parse_multisig(script: &[u8]) -> Option<(usize, Vec<&[u8]>)> {
+	let (last, rest) = script.split_last()?;
+	if *last != opcodes::all::OP_CHECKMULTISIG.into_u8() {
+		return None;
+	}
+	let small_int = |code: u8| {
+		if (opcodes::all::OP_PUSHNUM_1.into_u8()..=opcodes::all::OP_PUSHNUM_16.into_u8()).contains(&code) {
+			Some((code - opcodes::all::OP_PUSHNUM_1.into_u8() + 1) as usize)
+		} else {
+			None
+		}
+	};
+	let required = small_int(*rest.first()?)?;
+	let (total, rest) = rest[1..].split_last()?;
+	let total = small_int(*total)?;
+	let mut pubkeys = Vec::new();
+	let mut pc = 0;
+	while pc < rest.len() {
+		let (data, next) = interpreter::read_instruction(rest, pc).ok()?;
+		pubkeys.push(data?);
+		pc = next;
+	}
+	if pubkeys.len() != total || required > total {
+		return None;
+	}
+	Some((required, pubkeys))
+}
 
 
 macro_rules! impl_deserialize_for {
 	($ty: ty) => {
 		impl Deserialize for $ty {
 			fn decode_raw(bytes: HexBytes) -> Result<Self, Box<dyn Error>> {
-				println!("{:02x?}", bytes);
 				let len = bytes.len();
 				let mut stream = Cursor::new(bytes);
 
 				let mut script_builder = ScriptBuilder::new();
 
 				while (stream.position() as usize) < len {
-					let b = stream.read_u8_le().expect("shouldn't fail i think");
+					let b = stream.read_u8_le()?;
 					let opcode = opcodes::All::from(b);
 
 					// not sure if this is the correct condition
 					if opcode.code == opcodes::all::OP_PUSHBYTES_1.into_u8() {
-						let size = stream.read_u8_le().expect("shouldn't fail i think");
+						let size = stream.read_u8_le()?;
 						script_builder.push_size(size);
 					} else if opcode.code > opcodes::all::OP_PUSHBYTES_1.into_u8() && opcode.code <= opcodes::all::OP_PUSHBYTES_75.into_u8() {
 						let len = opcode.code;
-						let script = stream.read_hex_var(len as u64).expect("shouldn't fail i think");
+						let script = stream.read_hex_var(len as u64)?;
 						script_builder.push_slice(&script)
 					} else if opcode.code >= opcodes::all::OP_PUSHNUM_1.into_u8() && 
 						opcode.code <= opcodes::all::OP_PUSHNUM_15.into_u8() {
@@ -375,23 +630,17 @@ impl fmt::Debug for ScriptPubKey {
 		f.write_str("\n")?;
 		f.write_str("\thex: ")?;
 		write!(f, "\"{}\"", self.as_hex())?;
-		match &self.address {
-			Some(s) => {
-				f.write_str("\n")?;
-				f.write_str("\taddress: ")?;
-				write!(f, "\"{}\"", s)?;
-			},
-			None => {}
+		if let Some(s) = &self.address {
+			f.write_str("\n")?;
+			f.write_str("\taddress: ")?;
+			write!(f, "\"{}\"", s)?;
 		}
 		f.write_str("\n")?;
 		f.write_str("\ttype: ")?;
-		match &self.script_type {
-			Some(t) => {
-				f.write_str("\n")?;
-				f.write_str("\taddress: ")?;
-				write!(f, "\"{}\"", t)?;
-			},
-			None => {}
+		if let Some(t) = &self.script_type {
+			f.write_str("\n")?;
+			f.write_str("\taddress: ")?;
+			write!(f, "\"{}\"", t)?;
 		}
 		f.write_str("\n}")
 	}
@@ -405,8 +654,8 @@ impl ScriptBuilder {
 		ScriptBuilder(vec![])	
 	}
 
-	pub fn into_script<T: Script>(&self) -> T {
-		let hexbytes = self.0.clone().into_boxed_slice();
+	pub fn into_script<T: Script>(self) -> T {
+		let hexbytes = self.0.into_boxed_slice();
 		T::from_bytes(hexbytes)
 	}
 
@@ -424,15 +673,26 @@ impl ScriptBuilder {
 		Ok(())
 	}
 
-	fn push_key(&mut self, key: HexBytes) {
+	/// Push a serialized public key, see `key::PublicKey::as_bytes`.
+	pub fn push_key(&mut self, key: HexBytes) {
+		self.push_slice(&key);
+	}
 
+	/// Push a number from 0 to 16 with OP_0 and OP_1 to OP_16, larger numbers are a NumOutOfRange.
+	pub fn push_int(&mut self, n: u8) -> Result<(), BuildError> {
+		match n {
+			0 => self.push_opcode(opcodes::all::OP_PUSHBYTES_0),
+			1..=16 => self.push_opcode(opcodes::All::from(opcodes::all::OP_PUSHNUM_1.into_u8() + n - 1)),
+			_ => return Err(BuildError::NumOutOfRange(n)),
+		}
+		Ok(())
 	}
 
-	fn push_opcode(&mut self, opcode: opcodes::All) {
+	pub fn push_opcode(&mut self, opcode: opcodes::All) {
 		self.0.push(opcode.into_u8());
 	}
 
-	fn push_slice(&mut self, script_hash: &[u8]) {
+	pub fn push_slice(&mut self, script_hash: &[u8]) {
 		self.push_var_int(script_hash.len() as u64);
 		self.0.extend(script_hash.iter().cloned());
 	}
@@ -443,6 +703,7 @@ impl ScriptBuilder {
 		self.0.push(size);
 	}
 
+	#[allow(clippy::tabs_in_doc_comments)]
 	/**
 	 * Compact Size
 	 * https://en.bitcoin.it/wiki/Protocol_documentation#Variable_length_integer
@@ -483,14 +744,22 @@ impl fmt::Display for ScriptType {
 			ScriptType::P2SH => write!(f, "scripthash"),
 			ScriptType::P2PKH => write!(f, "pubkeyhash"),
 			ScriptType::P2PK => write!(f, "pubkey"),
-			ScriptType::Custom => write!(f, "non-standard"),
+			ScriptType::P2WPKH => write!(f, "witness_v0_keyhash"),
+			ScriptType::P2WSH => write!(f, "witness_v0_scripthash"),
+			ScriptType::P2TR => write!(f, "witness_v1_taproot"),
+			ScriptType::WitnessUnknown => write!(f, "witness_unknown"),
+			ScriptType::Multisig => write!(f, "multisig"),
+			ScriptType::NullData => write!(f, "nulldata"),
+			ScriptType::Custom => write!(f, "nonstandard"),
 		}
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::script::ScriptPubKey;
+    use crate::error::DecodeError;
+    use crate::network::Network;
+    use crate::script::{BuildError, ScriptBuilder, ScriptPubKey, ScriptType};
     use crate::txio::Decodable;
     use crate::{Deserialize, Serialize};
     use super::Script;
@@ -545,4 +814,97 @@ mod tests {
 		assert_eq!(script.as_asm(), "1 022afc20bf379bc96a2f4e9e63ffceb8652b2b6a097f63fbee6ecec2a49a\
 		48010e 03a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c0 2 OP_CHECKMULTISIG".to_string())
 	}
+
+    #[test]
+	fn encode_script_from_address() {
+		// an invalid address is asked again
+		let input = "6\nbc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5\nbc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4\n";
+		let script = ScriptPubKey::encode_raw(input.as_bytes());
+		assert_eq!(script.as_hex(), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+
+		// the script keeps the network of the address
+		let script = ScriptPubKey::encode_raw("6\ntb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx\n".as_bytes());
+		assert_eq!(script.network(), Network::Testnet);
+	}
+
+    #[test]
+	fn script_templates() {
+		let pubkey = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798".decode_hex_be().unwrap();
+		let other_pubkey = "03a767c7221e9f15f870f1ad9311f5ab937d79fcaeee15bb2c722bca515581b4c0".decode_hex_be().unwrap();
+
+		let p2wpkh = ScriptPubKey::new_p2wpkh(pubkey.clone());
+		assert_eq!(p2wpkh.as_hex(), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+		assert!(p2wpkh.is_p2wpkh() && p2wpkh.is_witness_program() && !p2wpkh.is_p2wsh());
+
+		let p2wsh = ScriptPubKey::new_p2wsh(ScriptPubKey::new_p2pk(pubkey.clone()).script);
+		assert_eq!(p2wsh.as_hex(), "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+		assert!(p2wsh.is_p2wsh() && !p2wsh.is_p2tr());
+
+		let p2tr = ScriptPubKey::new_p2tr(pubkey[1..].into());
+		assert_eq!(p2tr.as_hex(), "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+		assert!(p2tr.is_p2tr() && !p2tr.is_p2wsh());
+
+		let multisig = ScriptPubKey::new_multisig(1, &[pubkey.clone(), other_pubkey]).unwrap();
+		assert_eq!(multisig.as_hex(), "51210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817982103a767c7221e9f15f870f1\
+		ad9311f5ab937d79fcaeee15bb2c722bca515581b4c052ae");
+		assert!(multisig.is_multisig());
+		assert_eq!(ScriptPubKey::new_multisig(2, std::slice::from_ref(&pubkey)).unwrap_err(), BuildError::InvalidRequiredSignatures(2));
+		assert_eq!(ScriptPubKey::new_multisig(0, std::slice::from_ref(&pubkey)).unwrap_err(), BuildError::InvalidRequiredSignatures(0));
+		assert_eq!(ScriptPubKey::new_multisig(1, &vec![pubkey.clone(); 17]).unwrap_err(), BuildError::TooManyKeys(17));
+		assert_eq!(ScriptPubKey::new_witness_program(16, &[0; 2]).unwrap().as_hex(), "60020000");
+		assert_eq!(ScriptPubKey::new_witness_program(17, &[0; 2]).unwrap_err(), BuildError::InvalidWitnessVersion(17));
+		assert_eq!(ScriptPubKey::new_witness_program(1, &[0; 41]).unwrap_err(), BuildError::InvalidWitnessProgramLength(41));
+		assert_eq!(ScriptPubKey::new_witness_program(1, &[0; 1]).unwrap_err(), BuildError::InvalidWitnessProgramLength(1));
+		assert_eq!(ScriptPubKey::new_witness_program(0, &[0; 21]).unwrap_err(), BuildError::InvalidWitnessProgramLength(21));
+		assert!(ScriptPubKey::new_witness_program(0, &[0; 32]).is_ok());
+		let mut builder = ScriptBuilder::new();
+		assert_eq!(builder.push_int(17), Err(BuildError::NumOutOfRange(17)));
+
+		let op_return = ScriptPubKey::new_op_return(b"hello world");
+		assert_eq!(op_return.as_hex(), "6a0b68656c6c6f20776f726c64");
+		assert!(op_return.is_op_return());
+		assert!(ScriptPubKey::from_str("6a").unwrap().is_op_return());
+		assert!(!ScriptPubKey::from_str("6aac").unwrap().is_op_return());
+	}
+
+    #[test]
+	fn script_types() {
+		let vectors = [
+			("a914751e76e8199196d454941c45d1b3a323f1433bd687", ScriptType::P2SH, "scripthash"),
+			("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", ScriptType::P2PKH, "pubkeyhash"),
+			("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac", ScriptType::P2PK, "pubkey"),
+			("0014751e76e8199196d454941c45d1b3a323f1433bd6", ScriptType::P2WPKH, "witness_v0_keyhash"),
+			("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262", ScriptType::P2WSH, "witness_v0_scripthash"),
+			("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798", ScriptType::P2TR, "witness_v1_taproot"),
+			("6002751e", ScriptType::WitnessUnknown, "witness_unknown"),
+			("5210751e76e8199196d454941c45d1b3a323", ScriptType::WitnessUnknown, "witness_unknown"),
+			("51210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f8179851ae", ScriptType::Multisig, "multisig"),
+			("6a0b68656c6c6f20776f726c64", ScriptType::NullData, "nulldata"),
+			// version 0 programs have to be 20 or 32 bytes
+			("0010751e76e8199196d454941c45d1b3a323", ScriptType::Custom, "nonstandard"),
+			// the key of a multisig has to look like a public key
+			("5102abcd51ae", ScriptType::Custom, "nonstandard"),
+			("a820affb7035b385c7e8608d209498cd85c60eddadf4e2e50356f601289198219e7387", ScriptType::Custom, "nonstandard"),
+		];
+		for (hex, script_type, name) in vectors {
+			let script = ScriptPubKey::from_str(hex).unwrap();
+			assert_eq!(script.get_type(), script_type, "{}", hex);
+			assert_eq!(script_type.to_string(), name);
+		}
+	}
+
+    #[test]
+	fn decode_malformed_scripts() {
+		// the push runs past the end of the script
+		let error = ScriptPubKey::decode_raw("05abcd".decode_hex_be().unwrap()).unwrap_err();
+		assert_eq!(error.downcast_ref::<DecodeError>(), Some(&DecodeError::UnexpectedEof));
+		// scripts over MAX_SCRIPT_SIZE can't be executed but still decode, like in bitcoind
+		let script = ScriptPubKey::decode_raw(vec![0x6a; 10_001].into_boxed_slice()).unwrap();
+		assert_eq!(script.script.len(), 10_001);
+		assert_eq!(ScriptPubKey::from_str("abc").err(), Some(DecodeError::BadHex));
+
+		// scripts in transactions aren't parsed when decoding, showing them can't fail either
+		assert_eq!(ScriptPubKey::from_str("6a05abcd").unwrap().as_asm(), "OP_RETURN [error]");
+		assert_eq!(ScriptPubKey::from_str("01").unwrap().as_asm(), "[error]");
+	}
 }
@@ -0,0 +1,708 @@
+// Signature hashes. Each input signs a hash of a modified copy of the transaction, and the sighash
+// flag appended to the signature decides which parts of the transaction are committed to.
+// https://en.bitcoin.it/wiki/OP_CHECKSIG
+
+use std::error::Error;
+use std::fmt;
+use crate::hash;
+use crate::interpreter;
+use crate::opcodes::all;
+use crate::script::{ScriptBuilder, ScriptSig};
+use crate::transaction::{Input, Output, Transaction};
+use crate::txio::{Decodable, HexBytes, WriteExt};
+
+/// Sign all inputs and outputs.
+pub const SIGHASH_ALL: u32 = 0x01;
+/// Sign all inputs but none of the outputs.
+pub const SIGHASH_NONE: u32 = 0x02;
+/// Sign all inputs and only the output with the same index as the input being signed.
+pub const SIGHASH_SINGLE: u32 = 0x03;
+/// Combined with one of the above, only sign the input being signed.
+pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+/// Taproot only. Same as SIGHASH_ALL but the signature is 64 bytes as no flag is appended.
+pub const SIGHASH_DEFAULT: u32 = 0x00;
+
+/// Leaf version of tapscript (BIP342).
+pub const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+
+/// Returned instead of a hash when signing with SIGHASH_SINGLE but there's no output with the same
+/// index as the input. This is a bug in the original implementation that is now part of consensus.
+const UINT256_ONE: [u8; 32] = [
+	1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Reasons a signature hash can't be computed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SighashError {
+	/// The transaction doesn't have an input at this index.
+	InputIndexOutOfRange(usize),
+	/// The amount (or script pub key) spent by an input is needed but the input has no prevout.
+	MissingPrevout(usize),
+	/// The script code of the input couldn't be determined from its prevout, script sig or witness.
+	UnknownScriptCode(usize),
+	/// Taproot signs all spent outputs so there must be exactly one per input.
+	SpentOutputsMismatch { inputs: usize, spent_outputs: usize },
+	/// The sighash type isn't one of the types allowed by BIP341.
+	InvalidSighashType(u32),
+	/// Taproot doesn't have the SIGHASH_SINGLE bug. Signing an input without a matching output
+	/// is an error.
+	SingleWithoutOutput(usize),
+	/// The previous transaction hash of an input isn't 32 bytes of hex.
+	InvalidOutpoint(usize),
+	/// The sequence of an input isn't 4 bytes of hex.
+	InvalidSequence(usize),
+}
+
+impl fmt::Display for SighashError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SighashError::InputIndexOutOfRange(i) => write!(f, "input {} does not exist", i),
+			SighashError::MissingPrevout(i) => write!(f, "the output spent by input {} is unknown", i),
+			SighashError::UnknownScriptCode(i) => write!(f, "couldn't determine the script code of input {}", i),
+			SighashError::SpentOutputsMismatch { inputs, spent_outputs } => {
+				write!(f, "transaction has {} inputs but {} spent outputs were given", inputs, spent_outputs)
+			},
+			SighashError::InvalidSighashType(t) => write!(f, "invalid taproot sighash type {:#04x}", t),
+			SighashError::SingleWithoutOutput(i) => write!(f, "SIGHASH_SINGLE used on input {} without a matching output", i),
+			SighashError::InvalidOutpoint(i) => write!(f, "the previous transaction hash of input {} is not valid", i),
+			SighashError::InvalidSequence(i) => write!(f, "the sequence of input {} is not valid", i),
+		}
+	}
+}
+
+impl Error for SighashError {}
+
+/// A leaf of a taproot script tree that is being spent, along with the position of the last
+/// executed OP_CODESEPARATOR.
+pub struct TapLeaf<'a> {
+	pub leaf_hash: &'a [u8],
+	/// Opcode position of the last OP_CODESEPARATOR, or 0xffffffff if there's none.
+	pub code_separator_position: u32,
+}
+
+impl Transaction {
+	/// Compute the legacy (pre-segwit) signature hash of an input. `script_code` is the script
+	/// pub key being spent (or the redeem script for P2SH). The hash is returned in the byte order
+	/// that is signed, which is the reverse of how hashes are usually displayed.
+	///
+	/// Like Bitcoin Core, any `sighash_type` is accepted. Only the lower 5 bits and the
+	/// ANYONECANPAY bit change what is hashed but the full value is committed to.
+	pub fn legacy_signature_hash(&self, input_index: usize, script_code: &[u8], sighash_type: u32) -> Result<HexBytes, SighashError> {
+		self.check_inputs()?;
+		if input_index >= self.inputs.len() {
+			return Ok(Box::new(UINT256_ONE));
+		}
+
+		let base_type = sighash_type & 0x1f;
+		let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+
+		if base_type == SIGHASH_SINGLE && input_index >= self.outputs.len() {
+			return Ok(Box::new(UINT256_ONE));
+		}
+
+		let script_code = remove_code_separators(script_code);
+
+		let mut stream: Vec<u8> = Vec::new();
+		stream.write_u32_le(self.version);
+
+		let input_indexes: Vec<usize> = if anyone_can_pay {
+			vec![input_index]
+		} else {
+			(0..self.inputs.len()).collect()
+		};
+
+		stream.write_varint(input_indexes.len() as u64);
+		for i in input_indexes {
+			let input = &self.inputs[i];
+			write_outpoint(&mut stream, input, i)?;
+			// Only the input being signed gets a script, the others are emptied.
+			if i == input_index {
+				stream.write_hex(script_code.clone().into_boxed_slice(), true);
+			} else {
+				stream.write_varint(0);
+			}
+			// With NONE and SINGLE the other inputs are free to update their sequence.
+			if i != input_index && (base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE) {
+				stream.write_u32_le(0);
+			} else {
+				write_sequence(&mut stream, input, i)?;
+			}
+		}
+
+		if base_type == SIGHASH_NONE {
+			stream.write_varint(0);
+		} else if base_type == SIGHASH_SINGLE {
+			// Outputs before the signed one are replaced by "null" outputs (amount of -1 and an
+			// empty script) and outputs after it are removed.
+			stream.write_varint(input_index as u64 + 1);
+			for _ in 0..input_index {
+				stream.write_u64_le(u64::MAX);
+				stream.write_varint(0);
+			}
+			write_output(&mut stream, &self.outputs[input_index]);
+		} else {
+			stream.write_varint(self.outputs.len() as u64);
+			for output in &self.outputs {
+				write_output(&mut stream, output);
+			}
+		}
+
+		stream.write_u32_le(self.lock_time);
+		stream.write_u32_le(sighash_type);
+
+		Ok(hash::hash256(&stream).into_boxed_slice())
+	}
+
+	/// Check that the outpoint and sequence of every input can be serialized. They are kept as hex
+	/// so a transaction built by hand can hold anything.
+	pub fn check_inputs(&self) -> Result<(), SighashError> {
+		self.serialize_outpoints()?;
+		self.serialize_sequences()?;
+		Ok(())
+	}
+
+	/// Double SHA256 of all outpoints spent by the transaction (BIP143 hashPrevouts).
+	pub fn hash_prevouts(&self) -> Result<HexBytes, SighashError> {
+		Ok(hash::hash256(&self.serialize_outpoints()?).into_boxed_slice())
+	}
+
+	/// Double SHA256 of the sequence of all inputs (BIP143 hashSequence).
+	pub fn hash_sequence(&self) -> Result<HexBytes, SighashError> {
+		Ok(hash::hash256(&self.serialize_sequences()?).into_boxed_slice())
+	}
+
+	/// Double SHA256 of all outputs (BIP143 hashOutputs).
+	pub fn hash_outputs(&self) -> HexBytes {
+		hash::hash256(&self.serialize_outputs()).into_boxed_slice()
+	}
+
+	fn serialize_outpoints(&self) -> Result<Vec<u8>, SighashError> {
+		let mut stream: Vec<u8> = Vec::new();
+		for (i, input) in self.inputs.iter().enumerate() {
+			write_outpoint(&mut stream, input, i)?;
+		}
+		Ok(stream)
+	}
+
+	fn serialize_sequences(&self) -> Result<Vec<u8>, SighashError> {
+		let mut stream: Vec<u8> = Vec::new();
+		for (i, input) in self.inputs.iter().enumerate() {
+			write_sequence(&mut stream, input, i)?;
+		}
+		Ok(stream)
+	}
+
+	fn serialize_outputs(&self) -> Vec<u8> {
+		let mut stream: Vec<u8> = Vec::new();
+		for output in &self.outputs {
+			write_output(&mut stream, output);
+		}
+		stream
+	}
+
+	/// Compute the BIP143 signature hash of a segwit v0 input. Unlike the legacy algorithm the
+	/// amount being spent is signed as well. If `amount` is None, it is taken from the input's
+	/// prevout.
+	///
+	/// `script_code` is the P2PKH script of the public key hash for P2WPKH, or the witness script
+	/// for P2WSH. See `segwit_v0_script_code` to get it from the input itself.
+	pub fn segwit_v0_signature_hash(&self, input_index: usize, script_code: &[u8], amount: Option<u64>, sighash_type: u32) -> Result<HexBytes, SighashError> {
+		let input = self.inputs.get(input_index).ok_or(SighashError::InputIndexOutOfRange(input_index))?;
+		self.check_inputs()?;
+		let amount = match amount {
+			Some(amount) => amount,
+			None => input.prevout.as_ref().ok_or(SighashError::MissingPrevout(input_index))?.amount,
+		};
+
+		let base_type = sighash_type & 0x1f;
+		let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+		let zero: HexBytes = Box::new([0; 32]);
+
+		let hash_prevouts = if anyone_can_pay { zero.clone() } else { self.hash_prevouts()? };
+		let hash_sequence = if anyone_can_pay || base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE {
+			zero.clone()
+		} else {
+			self.hash_sequence()?
+		};
+		let hash_outputs = if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+			self.hash_outputs()
+		} else if base_type == SIGHASH_SINGLE && input_index < self.outputs.len() {
+			let mut stream: Vec<u8> = Vec::new();
+			write_output(&mut stream, &self.outputs[input_index]);
+			hash::hash256(&stream).into_boxed_slice()
+		} else {
+			zero
+		};
+
+		let mut stream: Vec<u8> = Vec::new();
+		stream.write_u32_le(self.version);
+		stream.write_hex(hash_prevouts, false);
+		stream.write_hex(hash_sequence, false);
+		write_outpoint(&mut stream, input, input_index)?;
+		stream.write_hex(script_code.to_vec().into_boxed_slice(), true);
+		stream.write_u64_le(amount);
+		write_sequence(&mut stream, input, input_index)?;
+		stream.write_hex(hash_outputs, false);
+		stream.write_u32_le(self.lock_time);
+		stream.write_u32_le(sighash_type);
+
+		Ok(hash::hash256(&stream).into_boxed_slice())
+	}
+
+	/// Work out the BIP143 script code of a segwit v0 input. The witness program is taken from the
+	/// prevout's script pub key, or from the script sig for P2SH wrapped inputs. A P2WPKH program
+	/// gives a P2PKH script of the key hash, a P2WSH program gives the witness script, which is the
+	/// last item on the witness stack.
+	pub fn segwit_v0_script_code(&self, input_index: usize) -> Result<HexBytes, SighashError> {
+		let input = self.inputs.get(input_index).ok_or(SighashError::InputIndexOutOfRange(input_index))?;
+
+		// For P2SH-P2WPKH/P2WSH the script sig is a single push of the witness program
+		let script_sig = &input.script_sig.script;
+		let program = if script_sig.len() > 1 && script_sig[0] as usize == script_sig.len() - 1 {
+			script_sig[1..].to_vec()
+		} else {
+			let prevout = input.prevout.as_ref().ok_or(SighashError::MissingPrevout(input_index))?;
+			prevout.script_pub_key.script.to_vec()
+		};
+
+		if program.len() == 22 && program[0] == all::OP_PUSHBYTES_0.into_u8() && program[1] == all::OP_PUSHBYTES_20.into_u8() {
+			Ok(p2wpkh_script_code(&program[2..]))
+		} else if program.len() == 34 && program[0] == all::OP_PUSHBYTES_0.into_u8() && program[1] == all::OP_PUSHBYTES_32.into_u8() {
+			let witness_script = self.witness_data.as_ref()
+				.and_then(|witnesses| witnesses.get(input_index))
+				.and_then(|stack| stack.0.last())
+				.and_then(|item| item.decode_hex_be().ok())
+				.ok_or(SighashError::UnknownScriptCode(input_index))?;
+			Ok(witness_script)
+		} else {
+			Err(SighashError::UnknownScriptCode(input_index))
+		}
+	}
+
+	/// The outputs spent by every input, taken from their prevouts. Taproot signatures commit to
+	/// all of them.
+	pub fn spent_outputs(&self) -> Result<Vec<&Output>, SighashError> {
+		self.inputs.iter().enumerate()
+			.map(|(i, input)| input.prevout.as_ref().ok_or(SighashError::MissingPrevout(i)))
+			.collect()
+	}
+
+	/// Compute the BIP341 signature hash of a taproot input. Both the amounts and script pub keys
+	/// of `spent_outputs` (one per input, in order) are signed. Pass the annex of the input's
+	/// witness if it has one (see `WitnessStack::annex`), and the leaf being executed for a
+	/// script path spend. Key path spends pass None.
+	pub fn taproot_signature_hash(
+		&self,
+		input_index: usize,
+		spent_outputs: &[&Output],
+		annex: Option<&[u8]>,
+		leaf: Option<TapLeaf>,
+		sighash_type: u32,
+	) -> Result<HexBytes, SighashError> {
+		let input = self.inputs.get(input_index).ok_or(SighashError::InputIndexOutOfRange(input_index))?;
+		if spent_outputs.len() != self.inputs.len() {
+			return Err(SighashError::SpentOutputsMismatch { inputs: self.inputs.len(), spent_outputs: spent_outputs.len() })
+		}
+		if !matches!(sighash_type, 0x00..=0x03 | 0x81..=0x83) {
+			return Err(SighashError::InvalidSighashType(sighash_type))
+		}
+		self.check_inputs()?;
+
+		let output_type = if sighash_type == SIGHASH_DEFAULT { SIGHASH_ALL } else { sighash_type & 0x03 };
+		let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+
+		let mut stream: Vec<u8> = Vec::new();
+		// sighash epoch
+		stream.write_u8_le(0);
+
+		// control
+		stream.write_u8_le(sighash_type as u8);
+
+		// transaction data
+		stream.write_u32_le(self.version);
+		stream.write_u32_le(self.lock_time);
+		if !anyone_can_pay {
+			let mut amounts: Vec<u8> = Vec::new();
+			let mut script_pub_keys: Vec<u8> = Vec::new();
+			for output in spent_outputs {
+				amounts.write_u64_le(output.amount);
+				script_pub_keys.write_hex(output.script_pub_key.script.clone(), true);
+			}
+			stream.write_hex(hash::sha256(&self.serialize_outpoints()?).into_boxed_slice(), false);
+			stream.write_hex(hash::sha256(&amounts).into_boxed_slice(), false);
+			stream.write_hex(hash::sha256(&script_pub_keys).into_boxed_slice(), false);
+			stream.write_hex(hash::sha256(&self.serialize_sequences()?).into_boxed_slice(), false);
+		}
+		if output_type == SIGHASH_ALL {
+			stream.write_hex(hash::sha256(&self.serialize_outputs()).into_boxed_slice(), false);
+		}
+
+		// data about this input
+		let spend_type = if leaf.is_some() { 2 } else { 0 } + if annex.is_some() { 1 } else { 0 };
+		stream.write_u8_le(spend_type);
+		if anyone_can_pay {
+			let spent_output = spent_outputs[input_index];
+			write_outpoint(&mut stream, input, input_index)?;
+			write_output(&mut stream, spent_output);
+			write_sequence(&mut stream, input, input_index)?;
+		} else {
+			stream.write_u32_le(input_index as u32);
+		}
+		if let Some(annex) = annex {
+			let mut annex_stream: Vec<u8> = Vec::new();
+			annex_stream.write_hex(annex.to_vec().into_boxed_slice(), true);
+			stream.write_hex(hash::sha256(&annex_stream).into_boxed_slice(), false);
+		}
+
+		// data about this output
+		if output_type == SIGHASH_SINGLE {
+			let output = self.outputs.get(input_index).ok_or(SighashError::SingleWithoutOutput(input_index))?;
+			let mut output_stream: Vec<u8> = Vec::new();
+			write_output(&mut output_stream, output);
+			stream.write_hex(hash::sha256(&output_stream).into_boxed_slice(), false);
+		}
+
+		// BIP342 extension for script path spends
+		if let Some(leaf) = leaf {
+			stream.write_hex(leaf.leaf_hash.to_vec().into_boxed_slice(), false);
+			// key version
+			stream.write_u8_le(0);
+			stream.write_u32_le(leaf.code_separator_position);
+		}
+
+		Ok(hash::tagged_hash("TapSighash", &stream).into_boxed_slice())
+	}
+}
+
+/// Hash of a leaf in a taproot script tree. Tapscript leaves use TAPROOT_LEAF_TAPSCRIPT as the
+/// leaf version.
+pub fn tap_leaf_hash(leaf_version: u8, script: &[u8]) -> HexBytes {
+	let mut stream: Vec<u8> = Vec::new();
+	stream.write_u8_le(leaf_version);
+	stream.write_hex(script.to_vec().into_boxed_slice(), true);
+	hash::tagged_hash("TapLeaf", &stream).into_boxed_slice()
+}
+
+/// The script code of a P2WPKH input is the P2PKH script of the same public key hash.
+pub fn p2wpkh_script_code(pubkey_hash: &[u8]) -> HexBytes {
+	let mut script_builder = ScriptBuilder::new();
+	script_builder.push_opcode(all::OP_DUP);
+	script_builder.push_opcode(all::OP_HASH160);
+	script_builder.push_slice(pubkey_hash);
+	script_builder.push_opcode(all::OP_EQUALVERIFY);
+	script_builder.push_opcode(all::OP_CHECKSIG);
+	script_builder.into_script::<ScriptSig>().script
+}
+
+fn write_outpoint(stream: &mut Vec<u8>, input: &Input, input_index: usize) -> Result<(), SighashError> {
+	let txid = input.previous_tx.decode_hex_le().ok()
+		.filter(|txid| txid.len() == 32)
+		.ok_or(SighashError::InvalidOutpoint(input_index))?;
+	stream.write_hex(txid, false);
+	stream.write_u32_le(input.tx_index);
+	Ok(())
+}
+
+fn write_sequence(stream: &mut Vec<u8>, input: &Input, input_index: usize) -> Result<(), SighashError> {
+	let sequence = input.sequence.decode_hex_le().ok()
+		.filter(|sequence| sequence.len() == 4)
+		.ok_or(SighashError::InvalidSequence(input_index))?;
+	stream.write_hex(sequence, false);
+	Ok(())
+}
+
+fn write_output(stream: &mut Vec<u8>, output: &Output) {
+	stream.write_u64_le(output.amount);
+	stream.write_hex(output.script_pub_key.script.clone(), true);
+}
+
+/// Legacy signatures don't commit to OP_CODESEPARATORs in the script code.
+fn remove_code_separators(script: &[u8]) -> Vec<u8> {
+	let mut result = Vec::with_capacity(script.len());
+	let mut pc = 0;
+	while pc < script.len() {
+		let next = match interpreter::read_instruction(script, pc) {
+			Ok((_, next)) => next,
+			// A malformed push is hashed as is
+			Err(_) => script.len(),
+		};
+		if script[pc] != all::OP_CODESEPARATOR.into_u8() {
+			result.extend_from_slice(&script[pc..next]);
+		}
+		pc = next;
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use crate::script::{Script, ScriptPubKey};
+	use crate::transaction::{Output, Transaction, WitnessStack};
+	use crate::txio::{Decodable, Encodable, ReadExt};
+	use crate::Deserialize;
+	use super::*;
+
+	// Test vectors are from Bitcoin Core's src/test/data/sighash.json. The expected hashes are in
+	// display order.
+	fn run_test_sighash(tx: &str, script: &str, input_index: usize, sighash_type: i64, expected: &str) {
+		let tx = Transaction::decode_raw(tx.decode_hex_be().unwrap()).unwrap();
+		let script = script.decode_hex_be().unwrap();
+		let sighash = tx.legacy_signature_hash(input_index, &script, sighash_type as u32).unwrap();
+		assert_eq!(sighash.encode_hex_le(), expected);
+	}
+
+	#[test]
+	fn legacy_sighash_all() {
+		run_test_sighash("4db591ab018adcef5f4f3f2060e41f7829ce3a07ea41d681e8cb70a0e37685561e4767ac3b00\
+		00000005000052acabd280e63601ae6ef20000000000036a636326c908f7", "ac6a51526300630052", 0, 862877446,
+		"355ccaf30697c9c5b966e619a554d3323d7494c3ea280a9b0dfb73f953f5c1cb");
+	}
+
+	#[test]
+	fn legacy_sighash_all_anyonecanpay() {
+		run_test_sighash("8b96d7a30132f6005b5bd33ea82aa325e2bcb441f46f63b5fca159ac7094499f380f6b7e2e00\
+		000000076aacabac6300acffffffff0158056700000000000465005100c319e6d0", "52006a", 0, -1100733473,
+		"fb4bd26a91b5cf225dd3f170eb09bad0eac314bc1e74503cc2a3f376833f183e");
+	}
+
+	#[test]
+	fn legacy_sighash_none_anyonecanpay() {
+		run_test_sighash("32fa0b0804e6ea101e137665a041cc2350b794e59bf42d9b09088b01cde806ec1bbea077df02\
+		00000008515153650000006506a11c55904258fa418e57b88b12724b81153260d3f4c9f080439789a391ab147aab\
+		b0fa0000000007000052ac51ab510986f2a15c0d5e05d20dc876dd2dafa435276d53da7b47c393f20900e55f163b\
+		97ce0b800000000008ab526a520065636a8087df7d4d9c985fb42308fb09dce704650719140aa6050e8955fa5d2e\
+		a46b464a333f870000000009636300636a6565006affffffff01994a0d040000000002536500000000",
+		"516563530065", 2, -163068286, "f58637277d2bc42e18358dc55f7e87e7043f5e33f4ce1fc974e715ef0d3d1c2a");
+	}
+
+	#[test]
+	fn legacy_sighash_single() {
+		run_test_sighash("6f62138301436f33a00b84a26a0457ccbfc0f82403288b9cbae39986b34357cb2ff9b889b302\
+		000000045253655335a7ff6701bac9960400000000086552ab656352635200000000", "6aac51", 0, 1444414211,
+		"502a2435fd02898d2ff3ab08a3c19078414b32ec9b73d64a944834efc9dae10c");
+	}
+
+	#[test]
+	fn legacy_sighash_single_anyonecanpay() {
+		run_test_sighash("9ff618e60136f8e6bb7eabaaac7d6e2535f5fba95854be6d2726f986eaa9537cb283c701ff02\
+		000000026a65ffffffff012d1c0905000000000865ab00ac6a516a652f9ad240", "51515253635351ac", 0, 1571304387,
+		"659cd3203095d4a8672646add7d77831a1926fc5b66128801979939383695a79");
+	}
+
+	#[test]
+	fn legacy_sighash_single_bug() {
+		// 4 inputs and a single output
+		let tx = Transaction::decode_raw("32fa0b0804e6ea101e137665a041cc2350b794e59bf42d9b09088b01cde8\
+		06ec1bbea077df0200000008515153650000006506a11c55904258fa418e57b88b12724b81153260d3f4c9f08043\
+		9789a391ab147aabb0fa0000000007000052ac51ab510986f2a15c0d5e05d20dc876dd2dafa435276d53da7b47c3\
+		93f20900e55f163b97ce0b800000000008ab526a520065636a8087df7d4d9c985fb42308fb09dce704650719140a\
+		a6050e8955fa5d2ea46b464a333f870000000009636300636a6565006affffffff01994a0d040000000002536500\
+		000000".decode_hex_be().unwrap()).unwrap();
+
+		assert_eq!(*tx.legacy_signature_hash(2, &[0x51], SIGHASH_SINGLE).unwrap(), UINT256_ONE);
+		assert_ne!(*tx.legacy_signature_hash(0, &[0x51], SIGHASH_SINGLE).unwrap(), UINT256_ONE);
+		// out of range inputs are treated the same way
+		assert_eq!(*tx.legacy_signature_hash(4, &[0x51], SIGHASH_SINGLE).unwrap(), UINT256_ONE);
+	}
+
+	#[test]
+	fn code_separators_are_removed() {
+		let script = "ab7601abab".decode_hex_be().unwrap();
+		// the 0xab following OP_PUSHBYTES_1 is data, not an opcode
+		assert_eq!(remove_code_separators(&script), vec![0x76, 0x01, 0xab]);
+	}
+
+	// BIP143 test vectors https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
+	#[test]
+	fn segwit_v0_sighash_p2wpkh() {
+		let mut tx = Transaction::decode_raw("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3\
+		edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d\
+		57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d\
+		5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000"
+			.decode_hex_be().unwrap()).unwrap();
+
+		assert_eq!(tx.hash_prevouts().unwrap().encode_hex_be(), "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37");
+		assert_eq!(tx.hash_sequence().unwrap().encode_hex_be(), "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b");
+		assert_eq!(tx.hash_outputs().encode_hex_be(), "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5");
+
+		let script_code = p2wpkh_script_code(&"1d0f172a0ecb48aee1be1f2687d2963ae33f71a1".decode_hex_be().unwrap());
+		assert_eq!(script_code.encode_hex_be(), "76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac");
+
+		let expected = "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670";
+		let sighash = tx.segwit_v0_signature_hash(1, &script_code, Some(600000000), SIGHASH_ALL).unwrap();
+		assert_eq!(sighash.encode_hex_be(), expected);
+
+		// the amount and script code can also come from the prevout
+		assert_eq!(tx.segwit_v0_signature_hash(1, &script_code, None, SIGHASH_ALL), Err(SighashError::MissingPrevout(1)));
+		tx.inputs[1].prevout = Some(Output {
+			amount: 600000000,
+			script_pub_key: ScriptPubKey::from_str("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap(),
+		});
+		let script_code = tx.segwit_v0_script_code(1).unwrap();
+		let sighash = tx.segwit_v0_signature_hash(1, &script_code, None, SIGHASH_ALL).unwrap();
+		assert_eq!(sighash.encode_hex_be(), expected);
+
+		assert_eq!(tx.segwit_v0_signature_hash(2, &script_code, None, SIGHASH_ALL), Err(SighashError::InputIndexOutOfRange(2)));
+	}
+
+	#[test]
+	fn segwit_v0_sighash_p2sh_p2wpkh() {
+		let mut tx = Transaction::decode_raw("0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb6\
+		6092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f3\
+		5b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000"
+			.decode_hex_be().unwrap()).unwrap();
+
+		assert_eq!(tx.hash_prevouts().unwrap().encode_hex_be(), "b0287b4a252ac05af83d2dcef00ba313af78a3e9c329afa216eb3aa2a7b4613a");
+		assert_eq!(tx.hash_sequence().unwrap().encode_hex_be(), "18606b350cd8bf565266bc352f0caddcf01e8fa789dd8a15386327cf8cabe198");
+		assert_eq!(tx.hash_outputs().encode_hex_be(), "de984f44532e2173ca0d64314fcefe6d30da6f8cf27bafa706da61df8a226c83");
+
+		// the script sig pushes the witness program
+		tx.inputs[0].script_sig = crate::script::ScriptSig::from_str("16001479091972186c449eb1ded22b78e40d009bdf0089").unwrap();
+		let script_code = tx.segwit_v0_script_code(0).unwrap();
+		assert_eq!(script_code.encode_hex_be(), "76a91479091972186c449eb1ded22b78e40d009bdf008988ac");
+
+		let sighash = tx.segwit_v0_signature_hash(0, &script_code, Some(1000000000), SIGHASH_ALL).unwrap();
+		assert_eq!(sighash.encode_hex_be(), "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6");
+	}
+
+	#[test]
+	fn segwit_v0_sighash_p2sh_p2wsh() {
+		let tx = Transaction::decode_raw("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1c\
+		a29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe26\
+		88acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000"
+			.decode_hex_be().unwrap()).unwrap();
+
+		let witness_script = "56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b\
+		28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957\
+		b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987\
+		e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee\
+		2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae".decode_hex_be().unwrap();
+
+		let expected = [
+			(SIGHASH_ALL, "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c"),
+			(0x02, "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36"),
+			(0x03, "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea"),
+			(0x81, "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e"),
+			(0x82, "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a"),
+			(0x83, "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b"),
+		];
+		for (sighash_type, hash) in expected {
+			let sighash = tx.segwit_v0_signature_hash(0, &witness_script, Some(987654321), sighash_type).unwrap();
+			assert_eq!(sighash.encode_hex_be(), hash);
+		}
+	}
+
+	// Taproot test vectors are from rust-bitcoin, which took them from Bitcoin Core's functional
+	// tests. `spent_outputs` is a serialized list of outputs and the expected hashes are in the
+	// byte order that is signed.
+	fn run_test_taproot_sighash(tx: &str, spent_outputs: &str, input_index: usize, sighash_type: u32, annex: Option<&str>, leaf_hash: Option<HexBytes>, expected: &str) {
+		let tx = Transaction::decode_raw(tx.decode_hex_be().unwrap()).unwrap();
+
+		let mut stream = Cursor::new(spent_outputs.decode_hex_be().unwrap());
+		let mut outputs = Vec::new();
+		for _ in 0..stream.read_compact_size().unwrap() {
+			let amount = stream.read_u64_le().unwrap();
+			let length = stream.read_compact_size().unwrap();
+			let script_pub_key = ScriptPubKey::from_bytes(stream.read_hex_var(length).unwrap());
+			outputs.push(Output { amount, script_pub_key });
+		}
+		let spent_outputs: Vec<&Output> = outputs.iter().collect();
+
+		let annex = annex.map(|a| a.decode_hex_be().unwrap());
+		let leaf = leaf_hash.as_ref().map(|leaf_hash| TapLeaf { leaf_hash, code_separator_position: 0xffffffff });
+		let sighash = tx.taproot_signature_hash(input_index, &spent_outputs, annex.as_deref(), leaf, sighash_type).unwrap();
+		assert_eq!(sighash.encode_hex_be(), expected);
+	}
+
+	#[test]
+	fn tagged_hash() {
+		let msg = "00011b96877db45ffa23b307e9f0ac87b80ef9a80b4c5f0db3fbe734422453e83cc5576f3d542c5d4898fb2b696c15d43\
+		332534a7c1d1255fda38993545882df92c3e353ff6d36fbfadc4d168452afd8467f02fe53d71714fcea5dfe2ea759bd00185c4cb02bc76d42\
+		620393ca358a1a713f4997f9fc222911890afb3fe56c6a19b202df7bffdcfad08003821294279043746631b00e2dc5e52a111e213bbfe6ef\
+		09a19428d418dab0d50000000000".decode_hex_be().unwrap();
+		let hash = crate::hash::tagged_hash("TapSighash", &msg);
+		assert_eq!(hash.encode_hex_be(), "04e808aad07a40b3767a1442fead79af6ef7e7c9316d82dec409bb31e77699b0");
+	}
+
+	#[test]
+	fn taproot_sighash_key_path() {
+		run_test_taproot_sighash("020000000164eb050a5e3da0c2a65e4786f26d753b7bc69691fabccafb11f7acef36641f1846010000003101b2b404392a22000000000017a9147f2bde86fe78bf68a0544a4f290e12f0b7e0a08c87580200000000000017a91425d11723074ecfb96a0a83c3956bfaf362ae0c908758020000000000001600147e20f938993641de67bb0cdd71682aa34c4d29ad5802000000000000160014c64984dc8761acfa99418bd6bedc79b9287d652d72000000",
+			"01365724000000000023542156b39dab4f8f3508e0432cfb41fab110170acaa2d4c42539cb90a4dc7c093bc500",
+			0, SIGHASH_DEFAULT, None, None, "33ca0ebfb4a945eeee9569fc0f5040221275f88690b7f8592ada88ce3bdf6703");
+
+		run_test_taproot_sighash("0200000002fff49be59befe7566050737910f6ccdc5e749c7f8860ddc140386463d88c5ad0f3000000002cf68eb4a3d67f9d4c079249f7e4f27b8854815cb1ed13842d4fbf395f9e217fd605ee24090100000065235d9203f458520000000000160014b6d48333bb13b4c644e57c43a9a26df3a44b785e58020000000000001976a914eea9461a9e1e3f765d3af3e726162e0229fe3eb688ac58020000000000001976a9143a8869c9f2b5ea1d4ff3aeeb6a8fb2fffb1ad5fe88ac0ad7125c",
+			"02591f220000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece48fb310000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece",
+			1, SIGHASH_ALL, None, None, "626ab955d58c9a8a600a0c580549d06dc7da4e802eb2a531f62a588e430967a8");
+
+		run_test_taproot_sighash("0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae000000001611d0d603d9dc0e000000000017a914459b6d7d6bbb4d8837b4bf7e9a4556f952da2f5c8758020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f",
+			"01c4811000000000002251201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e2d22e65c",
+			0, SIGHASH_ALL | SIGHASH_ANYONECANPAY, None, None, "dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c");
+
+		run_test_taproot_sighash("020000000185bed1a6da2bffbd60ec681a1bfb71c5111d6395b99b3f8b2bf90167111bcb18f5010000007c83ace802ded24a00000000001600142c4698f9f7a773866879755aa78c516fb332af8e5802000000000000160014d38639dfbac4259323b98a472405db0c461b31fa61073747",
+			"0144c84d0000000000225120e3f2107989c88e67296ab2faca930efa2e3a5bd3ff0904835a11c9e807458621",
+			0, SIGHASH_NONE, None, None, "3129de36a5d05fff97ffca31eb75fcccbbbc27b3147a7a36a9e4b45d8b625067");
+
+		run_test_taproot_sighash("eb93dbb901028c8515589dac980b6e7f8e4088b77ed866ca0d6d210a7218b6fd0f6b22dd6d7300000000eb4740a9047efc0e0000000000160014913da2128d8fcf292b3691db0e187414aa1783825802000000000000160014913da2128d8fcf292b3691db0e187414aa178382580200000000000017a9143dd27f01c6f7ef9bb9159937b17f17065ed01a0c875802000000000000160014d7630e19df70ada9905ede1722b800c0005f246641000000",
+			"013fed110000000000225120eb536ae8c33580290630fc495046e998086a64f8f33b93b07967d9029b265c55",
+			0, SIGHASH_NONE | SIGHASH_ANYONECANPAY, None, None, "2441e8b0e063a2083ee790f14f2045022f07258ddde5ee01de543c9e789d80ae");
+
+		run_test_taproot_sighash("02000000017836b409a5fed32211407e44b971591f2032053f14701fb5b3a30c0ff382f2cc9c0100000061ac55f60288fb5600000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ac58020000000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ace4000000",
+			"01efa558000000000022512007071ea3dc7e331b0687d0193d1e6d6ed10e645ef36f10ef8831d5e522ac9e80",
+			0, SIGHASH_SINGLE, None, None, "30239345177cadd0e3ea413d49803580abb6cb27971b481b7788a78d35117a88");
+
+		run_test_taproot_sighash("0100000001aa6deae89d5e0aaca58714fc76ef6f3c8284224888089232d4e663843ed3ab3eae010000008b6657a60450cb4c0000000000160014a3d42b5413ef0c0701c4702f3cd7d4df222c147058020000000000001976a91430b4ed8723a4ee8992aa2c8814cfe5c3ad0ab9d988ac5802000000000000160014365b1166a6ed0a5e8e9dff17a6d00bbb43454bc758020000000000001976a914bc98c51a84fe7fad5dc380eb8b39586eff47241688ac4f313247",
+			"0107af4e00000000002251202c36d243dfc06cb56a248e62df27ecba7417307511a81ae61aa41c597a929c69",
+			0, SIGHASH_SINGLE | SIGHASH_ANYONECANPAY, None, None, "bf9c83f26c6dd16449e4921f813f551c4218e86f2ec906ca8611175b41b566df");
+	}
+
+	#[test]
+	fn taproot_sighash_with_annex() {
+		let annex = "507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e";
+		// the annex is picked out of the witness
+		let witness = WitnessStack(vec!["aa".repeat(64), annex.to_string()]);
+		assert_eq!(witness.annex().unwrap().encode_hex_be(), annex);
+
+		run_test_taproot_sighash("0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000",
+			"01ea49260000000000225120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010",
+			0, SIGHASH_SINGLE | SIGHASH_ANYONECANPAY, Some(annex), None, "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c");
+	}
+
+	#[test]
+	fn taproot_sighash_script_path() {
+		let leaf_hash = tap_leaf_hash(TAPROOT_LEAF_TAPSCRIPT, &"20cc4e1107aea1d170c5ff5b6817e1303010049724fb3caa7941792ea9d29b3e2bacab".decode_hex_be().unwrap());
+		assert_eq!(leaf_hash.encode_hex_be(), "15a2530514e399f8b5cf0b3d3112cf5b289eaa3e308ba2071b58392fdc6da68a");
+
+		run_test_taproot_sighash("020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae29062195010000004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab958020000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac580200000000000017a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49a0bae3956df9c17cb4440a673ac0df6f010000",
+			"011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893d79f7182",
+			0, SIGHASH_ALL, None, Some(leaf_hash), "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e");
+	}
+
+	#[test]
+	fn taproot_sighash_with_annex_and_script_path() {
+		// OP_DROP <pubkey> followed by OP_2DUP OP_CHECKSIGVERIFY 60 times, then OP_CHECKSIG
+		let script = format!("7520ab9160dd8299dc1367659be3e8f66781fe440d52940c7f8d314a89b9f2698d40{}ac", "6ead".repeat(60));
+		let leaf_hash = tap_leaf_hash(TAPROOT_LEAF_TAPSCRIPT, &script.decode_hex_be().unwrap());
+
+		run_test_taproot_sighash("020000000132fb72cb8fba496755f027a9743e2d698c831fdb8304e4d1a346ac92cbf51acba50100000026bdc7df044aad34000000000017a9144fa2554ed6174586854fa3bc01de58dcf33567d0875802000000000000160014950367e1e62cdf240b35b883fc2f5e39f0eb9ab95802000000000000160014950367e1e62cdf240b35b883fc2f5e39f0eb9ab958020000000000001600141b31217d48ccc8760dcc0710fade5866d628e733a02d5122",
+			"011458360000000000225120a7baec3fb9f84614e3899fcc010c638f80f13539344120e1f4d8b68a9a011a13",
+			0, SIGHASH_ALL, Some("50a6272b470e1460e3332ade7bb14b81671c564fb6245761bd5bd531394b28860e0b3808ab229fb51791fb6ae6fa82d915b2efb8f6df83ae1f5ab3db13e30928875e2a22b749d89358de481f19286cd4caa792ce27f9559082d227a731c5486882cc707f83da361c51b7aadd9a0cf68fe7480c410fa137b454482d9a1ebf0f96d760b4d61426fc109c6e8e99a508372c45caa7b000a41f8251305da3f206c1849985ba03f3d9592832b4053afbd23ab25d0465df0bc25a36c223aacf8e04ec736a418c72dc319e4da3e972e349713ca600965e7c665f2090d5a70e241ac164115a1f5639f28b1773327715ca307ace64a2de7f0e3df70a2ffee3857689f909c0dad46d8a20fa373a4cc6eed6d4c9806bf146f0d76baae1"),
+			Some(leaf_hash), "a0042aa434f9a75904b64043f2a283f8b4c143c7f4f7f49a6cbe5b9f745f4c15");
+	}
+
+	#[test]
+	fn taproot_sighash_errors() {
+		let tx = Transaction::decode_raw("02000000017836b409a5fed32211407e44b971591f2032053f14701fb5b3a30c0ff382f2cc9c0100000061ac55f60288fb5600000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ac58020000000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ace4000000"
+			.decode_hex_be().unwrap()).unwrap();
+		let spent_output = Output { amount: 0, script_pub_key: ScriptPubKey::from_str("51").unwrap() };
+
+		assert_eq!(tx.taproot_signature_hash(1, &[&spent_output], None, None, SIGHASH_ALL), Err(SighashError::InputIndexOutOfRange(1)));
+		assert_eq!(tx.taproot_signature_hash(0, &[], None, None, SIGHASH_ALL),
+			Err(SighashError::SpentOutputsMismatch { inputs: 1, spent_outputs: 0 }));
+		assert_eq!(tx.taproot_signature_hash(0, &[&spent_output], None, None, 0x04), Err(SighashError::InvalidSighashType(0x04)));
+		assert_eq!(tx.taproot_signature_hash(0, &[&spent_output], None, None, 0x80), Err(SighashError::InvalidSighashType(0x80)));
+		assert!(tx.taproot_signature_hash(0, &[&spent_output], None, None, SIGHASH_SINGLE).is_ok());
+		assert_eq!(tx.spent_outputs(), Err(SighashError::MissingPrevout(0)));
+	}
+}
@@ -0,0 +1,603 @@
+// ECDSA signing and verification of transaction inputs. Signing fills the script sig and/or the
+// witness of an input based on the script pub key it spends, verifying runs the input through the
+// interpreter with a checker that knows about the transaction.
+
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use secp256k1::{ecdsa, Message, SECP256K1};
+use crate::hash;
+use crate::interpreter::{self, ExecError, Interpreter, SigVersion, SignatureChecker};
+use crate::interpreter::{LOCKTIME_THRESHOLD, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_MASK, SEQUENCE_LOCKTIME_TYPE_FLAG};
+use crate::key::{PrivateKey, PublicKey};
+use crate::opcodes::all;
+use crate::script::{parse_multisig, Script, ScriptBuilder, ScriptPubKey};
+use crate::sighash::{self, SighashError, SIGHASH_ALL};
+use crate::transaction::{Output, Transaction, WitnessStack};
+use crate::txio::{Decodable, Encodable, HexBytes, UserReadExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignError {
+	InputIndexOutOfRange(usize),
+	/// The output spent by the input is needed to know how to sign it.
+	MissingPrevout(usize),
+	/// The script being spent isn't P2PK, P2PKH, multisig or a P2SH/P2WSH wrapping one of these,
+	/// or the signer doesn't have the key or the redeem/witness script needed to sign it.
+	CannotSign(usize),
+	/// Fewer keys than signatures required by a multisig.
+	NotEnoughKeys { input_index: usize, required: usize, found: usize },
+	Sighash(SighashError),
+}
+
+impl fmt::Display for SignError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SignError::InputIndexOutOfRange(i) => write!(f, "input {} does not exist", i),
+			SignError::MissingPrevout(i) => write!(f, "the output spent by input {} is unknown", i),
+			SignError::CannotSign(i) => write!(f, "don't know how to sign input {}", i),
+			SignError::NotEnoughKeys { input_index, required, found } => {
+				write!(f, "input {} needs {} signatures but only {} keys were found", input_index, required, found)
+			},
+			SignError::Sighash(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl Error for SignError {}
+
+impl From<SighashError> for SignError {
+	fn from(e: SighashError) -> Self {
+		SignError::Sighash(e)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+	InputIndexOutOfRange(usize),
+	/// The script pub key and amount of the spent output are needed to verify an input.
+	MissingPrevout(usize),
+	/// A witness item of the input isn't valid hex.
+	InvalidWitness(usize),
+	/// The outpoints and sequences signed by the input can't be serialized.
+	Sighash(SighashError),
+	/// The input's scripts failed to execute.
+	Script { input_index: usize, error: ExecError },
+}
+
+impl fmt::Display for VerifyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			VerifyError::InputIndexOutOfRange(i) => write!(f, "input {} does not exist", i),
+			VerifyError::MissingPrevout(i) => write!(f, "the output spent by input {} is unknown", i),
+			VerifyError::InvalidWitness(i) => write!(f, "the witness of input {} is not valid hex", i),
+			VerifyError::Sighash(e) => write!(f, "{}", e),
+			VerifyError::Script { input_index, error } => write!(f, "input {}: {}", input_index, error),
+		}
+	}
+}
+
+impl Error for VerifyError {}
+
+/// Checks signatures and locktimes against the input of a transaction.
+pub struct TransactionSignatureChecker<'a> {
+	tx: &'a Transaction,
+	input_index: usize,
+	/// Amount of the output being spent. Only segwit signatures commit to it.
+	amount: u64,
+	sequence: u32,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+	pub fn new(tx: &'a Transaction, input_index: usize, amount: u64) -> Result<Self, VerifyError> {
+		let input = tx.inputs.get(input_index).ok_or(VerifyError::InputIndexOutOfRange(input_index))?;
+		let sequence = sequence_value(&input.sequence)
+			.ok_or(VerifyError::Sighash(SighashError::InvalidSequence(input_index)))?;
+		Ok(TransactionSignatureChecker { tx, input_index, amount, sequence })
+	}
+
+	fn sequence(&self) -> i64 {
+		self.sequence as i64
+	}
+}
+
+impl<'a> SignatureChecker for TransactionSignatureChecker<'a> {
+	fn check_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8], sig_version: SigVersion) -> bool {
+		let (sighash_type, der) = match sig.split_last() {
+			Some((sighash_type, der)) => (*sighash_type as u32, der),
+			None => return false,
+		};
+		let pubkey = match PublicKey::from_bytes(pubkey) {
+			Ok(pubkey) => pubkey,
+			Err(_) => return false,
+		};
+		// Consensus accepts signatures that aren't strict DER and signatures with a high S
+		let mut signature = match ecdsa::Signature::from_der_lax(der) {
+			Ok(signature) => signature,
+			Err(_) => return false,
+		};
+		signature.normalize_s();
+
+		let sighash = match sig_version {
+			SigVersion::Base => self.tx.legacy_signature_hash(self.input_index, script_code, sighash_type),
+			SigVersion::WitnessV0 => self.tx.segwit_v0_signature_hash(self.input_index, script_code, Some(self.amount), sighash_type),
+		};
+		let sighash = match sighash {
+			Ok(sighash) => sighash,
+			Err(_) => return false,
+		};
+		let message = Message::from_slice(&sighash).expect("sighash is 32 bytes");
+		SECP256K1.verify_ecdsa(&message, &signature, pubkey.inner()).is_ok()
+	}
+
+	fn check_lock_time(&self, lock_time: i64) -> bool {
+		let tx_lock_time = self.tx.lock_time as i64;
+		// Both have to be block heights or both timestamps
+		if (tx_lock_time < LOCKTIME_THRESHOLD) != (lock_time < LOCKTIME_THRESHOLD) {
+			return false;
+		}
+		if lock_time > tx_lock_time {
+			return false;
+		}
+		// The locktime of the transaction is ignored if the input is final
+		self.sequence() != 0xffffffff
+	}
+
+	fn check_sequence(&self, sequence: i64) -> bool {
+		let tx_sequence = self.sequence();
+		// Relative locktimes only apply from version 2 (BIP68)
+		if self.tx.version < 2 {
+			return false;
+		}
+		if tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+			return false;
+		}
+
+		let mask = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+		let tx_sequence = tx_sequence & mask;
+		let sequence = sequence & mask;
+		if (tx_sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) != (sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) {
+			return false;
+		}
+		sequence <= tx_sequence
+	}
+}
+
+impl Transaction {
+	/// Verify the signatures (and the rest of the scripts) of an input against the output it
+	/// spends, which has to be set as the input's prevout.
+	pub fn verify_input(&self, input_index: usize) -> Result<(), VerifyError> {
+		let input = self.inputs.get(input_index).ok_or(VerifyError::InputIndexOutOfRange(input_index))?;
+		let prevout = input.prevout.as_ref().ok_or(VerifyError::MissingPrevout(input_index))?;
+		// a malformed input would only show up as a failed signature check
+		self.check_inputs().map_err(VerifyError::Sighash)?;
+
+		let witness: Vec<Vec<u8>> = match self.witness_data.as_ref().and_then(|w| w.get(input_index)) {
+			Some(stack) => stack.0.iter()
+				.map(|item| item.decode_hex_be().map(|bytes| bytes.to_vec()))
+				.collect::<Result<_, _>>()
+				.map_err(|_| VerifyError::InvalidWitness(input_index))?,
+			None => Vec::new(),
+		};
+
+		let checker = TransactionSignatureChecker::new(self, input_index, prevout.amount)?;
+		let mut interpreter = Interpreter::new(checker);
+		interpreter.verify_with_witness(&input.script_sig, &prevout.script_pub_key, &witness)
+			.map_err(|error| VerifyError::Script { input_index, error })
+	}
+
+	/// Verify every input of the transaction.
+	pub fn verify(&self) -> Result<(), VerifyError> {
+		(0..self.inputs.len()).try_for_each(|i| self.verify_input(i))
+	}
+}
+
+/// Signs inputs with a set of private keys. Scripts hidden behind a hash, i.e. P2SH redeem scripts
+/// and P2WSH witness scripts, have to be added so that the signer knows what it's signing.
+pub struct Signer {
+	keys: Vec<PrivateKey>,
+	scripts: Vec<HexBytes>,
+	sighash_type: u32,
+}
+
+impl Signer {
+	pub fn new(keys: Vec<PrivateKey>) -> Self {
+		Signer {
+			keys,
+			scripts: Vec::new(),
+			sighash_type: SIGHASH_ALL,
+		}
+	}
+
+	/// Add a redeem script or witness script.
+	pub fn with_script(mut self, script: HexBytes) -> Self {
+		self.scripts.push(script);
+		self
+	}
+
+	/// Sign with a sighash type other than SIGHASH_ALL.
+	pub fn with_sighash_type(mut self, sighash_type: u32) -> Self {
+		self.sighash_type = sighash_type;
+		self
+	}
+
+	/// Sign every input of the transaction.
+	pub fn sign(&self, tx: &mut Transaction) -> Result<(), SignError> {
+		(0..tx.inputs.len()).try_for_each(|i| self.sign_input(tx, i))
+	}
+
+	/// Sign an input, replacing its script sig and witness. The input's prevout has to be set.
+	///
+	/// Supports P2PK, P2PKH and bare multisig, either directly or wrapped in P2SH, P2WSH or
+	/// P2SH-P2WSH, as well as P2WPKH and P2SH-P2WPKH.
+	pub fn sign_input(&self, tx: &mut Transaction, input_index: usize) -> Result<(), SignError> {
+		let input = tx.inputs.get(input_index).ok_or(SignError::InputIndexOutOfRange(input_index))?;
+		let prevout = input.prevout.as_ref().ok_or(SignError::MissingPrevout(input_index))?;
+		let amount = prevout.amount;
+
+		let mut script = prevout.script_pub_key.script.to_vec();
+		let mut redeem_script = None;
+		if prevout.script_pub_key.is_p2sh() {
+			let redeem = self.find_redeem_script(&script[2..22]).ok_or(SignError::CannotSign(input_index))?;
+			script = redeem.clone();
+			redeem_script = Some(redeem);
+		}
+
+		let (mut script_sig, witness) = match interpreter::witness_program(&script) {
+			Some((0, program)) if program.len() == 20 => {
+				let script_code = sighash::p2wpkh_script_code(program);
+				let witness = self.sign_script(tx, input_index, &script_code, amount, SigVersion::WitnessV0)?;
+				(Vec::new(), Some(witness))
+			},
+			Some((0, program)) if program.len() == 32 => {
+				let witness_script = self.scripts.iter()
+					.find(|s| hash::sha256(s) == program)
+					.ok_or(SignError::CannotSign(input_index))?;
+				let mut witness = self.sign_script(tx, input_index, witness_script, amount, SigVersion::WitnessV0)?;
+				witness.push(witness_script.to_vec());
+				(Vec::new(), Some(witness))
+			},
+			Some(_) => return Err(SignError::CannotSign(input_index)),
+			None => (self.sign_script(tx, input_index, &script, amount, SigVersion::Base)?, None),
+		};
+
+		if let Some(redeem_script) = redeem_script {
+			script_sig.push(redeem_script);
+		}
+		let mut builder = ScriptBuilder::new();
+		for item in &script_sig {
+			builder.push_slice(item);
+		}
+		tx.inputs[input_index].script_sig = builder.into_script();
+
+		if let Some(witness) = witness {
+			// Every input needs a witness (possibly empty) once one of them has one
+			tx.flag = Some(1);
+			let witness_data = tx.witness_data.get_or_insert_with(Vec::new);
+			while witness_data.len() < tx.inputs.len() {
+				witness_data.push(WitnessStack(Vec::new()));
+			}
+			witness_data[input_index] = WitnessStack(witness.iter().map(|item| item.encode_hex_be()).collect());
+		}
+		Ok(())
+	}
+
+	/// P2SH-P2WPKH redeem scripts are derived from the keys, other redeem scripts have to be added.
+	fn find_redeem_script(&self, script_hash: &[u8]) -> Option<Vec<u8>> {
+		let p2wpkh_programs = self.keys.iter().map(|key| {
+			let mut program = vec![all::OP_PUSHBYTES_0.into_u8(), all::OP_PUSHBYTES_20.into_u8()];
+			program.extend_from_slice(&key.public_key().pubkey_hash());
+			program
+		});
+		self.scripts.iter()
+			.map(|script| script.to_vec())
+			.chain(p2wpkh_programs)
+			.find(|script| hash::hash160(script) == script_hash)
+	}
+
+	/// Create the stack items that satisfy a P2PK, P2PKH or multisig script.
+	fn sign_script(&self, tx: &Transaction, input_index: usize, script: &[u8], amount: u64, sig_version: SigVersion) -> Result<Vec<Vec<u8>>, SignError> {
+		let script_pub_key = ScriptPubKey::from_bytes(script.to_vec().into_boxed_slice());
+
+		if script_pub_key.is_p2pk() {
+			let pubkey = &script[1..script.len() - 1];
+			let key = self.find_key(pubkey).ok_or(SignError::CannotSign(input_index))?;
+			let sig = self.signature(tx, input_index, key, script, amount, sig_version)?;
+			Ok(vec![sig])
+		} else if script_pub_key.is_p2pkh() {
+			let key = self.keys.iter()
+				.find(|key| *key.public_key().pubkey_hash() == script[3..23])
+				.ok_or(SignError::CannotSign(input_index))?;
+			let sig = self.signature(tx, input_index, key, script, amount, sig_version)?;
+			Ok(vec![sig, key.public_key().as_bytes().to_vec()])
+		} else if let Some((required, pubkeys)) = parse_multisig(script) {
+			// Signatures have to be in the same order as the public keys
+			let mut stack = vec![Vec::new()];
+			for pubkey in pubkeys {
+				if stack.len() > required { break }
+				if let Some(key) = self.find_key(pubkey) {
+					stack.push(self.signature(tx, input_index, key, script, amount, sig_version)?);
+				}
+			}
+			if stack.len() <= required {
+				return Err(SignError::NotEnoughKeys { input_index, required, found: stack.len() - 1 });
+			}
+			Ok(stack)
+		} else {
+			Err(SignError::CannotSign(input_index))
+		}
+	}
+
+	fn find_key(&self, pubkey: &[u8]) -> Option<&PrivateKey> {
+		self.keys.iter().find(|key| *key.public_key().as_bytes() == *pubkey)
+	}
+
+	/// DER encoded signature with the sighash type appended.
+	fn signature(&self, tx: &Transaction, input_index: usize, key: &PrivateKey, script_code: &[u8], amount: u64, sig_version: SigVersion) -> Result<Vec<u8>, SignError> {
+		let sighash = match sig_version {
+			SigVersion::Base => tx.legacy_signature_hash(input_index, script_code, self.sighash_type)?,
+			SigVersion::WitnessV0 => tx.segwit_v0_signature_hash(input_index, script_code, Some(amount), self.sighash_type)?,
+		};
+		let message = Message::from_slice(&sighash).expect("sighash is 32 bytes");
+		let mut sig = SECP256K1.sign_ecdsa(&message, key.secret_key()).serialize_der().to_vec();
+		sig.push(self.sighash_type as u8);
+		Ok(sig)
+	}
+}
+
+/// Sequences are stored as hex in display order, i.e. they read as a big-endian number.
+fn sequence_value(sequence: &str) -> Option<u32> {
+	if sequence.len() != 8 {
+		return None;
+	}
+	u32::from_str_radix(sequence, 16).ok()
+}
+
+/// Ask for the outputs being spent, the private keys and scripts needed to sign each input of a
+/// transaction created with `Transaction::encode_raw`.
+pub fn sign_interactive<R: BufRead>(tx: &mut Transaction, mut reader: R) -> Result<(), SignError> {
+	for i in 0..tx.inputs.len() {
+		println!("Input {}:", i);
+		println!("---- Amount of the output being spent (in sats):");
+		let amount = reader.user_read_u64();
+		println!("---- Script pubkey of the output being spent (hex):");
+		let script_pub_key = ScriptPubKey::from_bytes(reader.user_read_hex_var());
+		tx.inputs[i].prevout = Some(Output { amount, script_pub_key });
+
+		println!("---- Private keys in WIF, one per line. Leave empty when done:");
+		let mut keys = Vec::new();
+		loop {
+			let mut line = String::new();
+			if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() { break }
+			match PrivateKey::from_wif(line.trim()) {
+				Ok(key) => keys.push(key),
+				Err(e) => println!("{}. Try again.", e),
+			}
+		}
+
+		println!("---- Redeem or witness scripts in hex, one per line. Leave empty when done:");
+		let mut signer = Signer::new(keys);
+		loop {
+			let script = reader.user_read_hex_var();
+			if script.is_empty() { break }
+			signer = signer.with_script(script);
+		}
+
+		signer.sign_input(tx, i)?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+	use crate::interpreter::ScriptError;
+	use crate::key::{PrivateKey, PublicKey};
+	use crate::network::Network;
+	use crate::opcodes::all;
+	use crate::script::{Script, ScriptBuilder, ScriptPubKey, ScriptSig};
+	use crate::transaction::{Output, Transaction};
+	use crate::txio::{Decodable, Encodable};
+	use crate::{Deserialize, Serialize};
+	use crate::sighash::{SighashError, SIGHASH_ALL};
+	use super::{SignError, Signer, VerifyError};
+
+	fn key(hex: &str) -> PrivateKey {
+		PrivateKey::from_bytes(&hex.decode_hex_be().unwrap(), true, Network::Testnet).unwrap()
+	}
+
+	fn multisig(required: u8, keys: &[PrivateKey]) -> Vec<u8> {
+		let mut builder = ScriptBuilder::new();
+		builder.push(&format!("OP_{}", required)).unwrap();
+		for key in keys {
+			builder.push_key(key.public_key().as_bytes());
+		}
+		builder.push(&format!("OP_{}", keys.len())).unwrap();
+		builder.push("OP_CHECKMULTISIG").unwrap();
+		builder.into_script::<ScriptSig>().script.to_vec()
+	}
+
+	fn p2wsh(script: &[u8]) -> ScriptPubKey {
+		ScriptPubKey::from_str(&("0020".to_string() + &crate::hash::sha256(script).encode_hex_be())).unwrap()
+	}
+
+	fn unsigned_tx(script_pub_key: ScriptPubKey) -> Transaction {
+		let mut tx = Transaction::decode_raw("0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a547701000000\
+		00feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1e\
+		e6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000".decode_hex_be().unwrap()).unwrap();
+		tx.inputs[0].prevout = Some(Output { amount: 1000000000, script_pub_key });
+		tx
+	}
+
+	// The unsigned BIP143 P2WPKH example. Input 0 is P2PK and input 1 is P2WPKH.
+	fn bip143_p2wpkh_example() -> Transaction {
+		let mut tx = Transaction::decode_raw("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000\
+		0000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb20600000000\
+		1976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa8\
+		15988ac11000000".decode_hex_be().unwrap()).unwrap();
+		tx.inputs[0].prevout = Some(Output {
+			amount: 625000000,
+			script_pub_key: ScriptPubKey::from_str("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac").unwrap(),
+		});
+		tx.inputs[1].prevout = Some(Output {
+			amount: 600000000,
+			script_pub_key: ScriptPubKey::from_str("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap(),
+		});
+		tx
+	}
+
+	fn bip143_p2wpkh_signer() -> Signer {
+		Signer::new(vec![
+			key("bbc27228ddcb9209d7fd6f36b02f7dfa6252af40bb2f1cbc7a557da8027ff866"),
+			key("619c335025c7f4012e556c2a58b2506e30b8511b53ade95ea316fd8c3286feb9"),
+		])
+	}
+
+	// Signatures are deterministic (RFC6979) so signing the BIP143 example gives the same
+	// transaction as the BIP.
+	#[test]
+	fn sign_bip143_p2wpkh_example() {
+		let mut tx = bip143_p2wpkh_example();
+		// the script sig is empty, there's no signature on the stack
+		assert_eq!(tx.verify_input(0), Err(VerifyError::Script {
+			input_index: 0,
+			error: crate::interpreter::ExecError {
+				stage: crate::interpreter::Stage::ScriptPubKey,
+				position: 34,
+				opcode: Some(all::OP_CHECKSIG),
+				error: ScriptError::InvalidStackOperation,
+			},
+		}));
+
+		bip143_p2wpkh_signer().sign(&mut tx).unwrap();
+
+		assert_eq!(tx.as_hex(), "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000\
+		00000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1\
+		c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec\
+		68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976\
+		a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12f\
+		b1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e\
+		292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000");
+		assert_eq!(tx.verify(), Ok(()));
+
+		// the amount is signed by segwit inputs
+		tx.inputs[1].prevout.as_mut().unwrap().amount += 1;
+		assert!(matches!(tx.verify_input(1), Err(VerifyError::Script { input_index: 1, .. })));
+	}
+
+	#[test]
+	fn verify_existing_p2pkh_signature() {
+		// decode_transaction_pre_segwit_3
+		let mut tx = Transaction::decode_raw("0100000001e11af7c4292505f99a4a5f4ff0818ac86c197bb16261f91af3f5cac661259c8800000000\
+		6a473044022045c7199ffc8069a498135b7bb2678da16e8b5d49455b4a7ace755928c9339c7a022051cbf72024cf273444640f7b993b2bf3d\
+		329124b03e6744edaed5158a30e29b8012103fd9bc1e9803e739720e0f1c63e580a94656c7d0cab6cd083f0c0dfb221b90662ffffffff020\
+		0b080f6450100001976a9143b9552116adcc2fbd74fad44a4da603a727c816e88aca05ecf1c000100001976a914f90ce447f14847e841d4d\
+		2ecc76299b5bc77166188ac00000000".decode_hex_be().unwrap()).unwrap();
+		let pubkey = PublicKey::from_str("03fd9bc1e9803e739720e0f1c63e580a94656c7d0cab6cd083f0c0dfb221b90662").unwrap();
+		tx.inputs[0].prevout = Some(Output { amount: 0, script_pub_key: ScriptPubKey::new_p2pkh(pubkey.as_bytes()) });
+		assert_eq!(tx.verify(), Ok(()));
+
+		// changing an output invalidates the signature
+		tx.outputs[0].amount -= 1;
+		assert!(tx.verify().is_err());
+	}
+
+	#[test]
+	fn verify_malformed_input() {
+		let key = PrivateKey::new_random(true, Network::Testnet);
+		let mut tx = unsigned_tx(ScriptPubKey::new_p2wpkh(key.public_key().as_bytes()));
+		tx.witness_data = Some(vec![crate::transaction::WitnessStack(vec!["zz".to_string()])]);
+		assert_eq!(tx.verify_input(0), Err(VerifyError::InvalidWitness(0)));
+
+		Signer::new(vec![key]).sign(&mut tx).unwrap();
+		tx.inputs[0].sequence = "fffffffg".to_string();
+		assert_eq!(tx.verify_input(0), Err(VerifyError::Sighash(SighashError::InvalidSequence(0))));
+	}
+
+	#[test]
+	fn malformed_outpoint_of_another_input() {
+		// valid hex but not 32 bytes, every input signs the outpoints of the others
+		let mut tx = bip143_p2wpkh_example();
+		tx.inputs[1].previous_tx = "abcd".to_string();
+		assert_eq!(bip143_p2wpkh_signer().sign(&mut tx), Err(SignError::Sighash(SighashError::InvalidOutpoint(1))));
+		assert_eq!(tx.legacy_signature_hash(0, &[0x51], SIGHASH_ALL), Err(SighashError::InvalidOutpoint(1)));
+		assert_eq!(tx.verify_input(0), Err(VerifyError::Sighash(SighashError::InvalidOutpoint(1))));
+
+		let mut tx = bip143_p2wpkh_example();
+		bip143_p2wpkh_signer().sign(&mut tx).unwrap();
+		tx.inputs[1].sequence = "ffff".to_string();
+		assert_eq!(tx.verify(), Err(VerifyError::Sighash(SighashError::InvalidSequence(1))));
+		tx.inputs[1].sequence = "ffffffff".to_string();
+		tx.inputs[0].previous_tx = "zz".repeat(32);
+		assert_eq!(tx.verify_input(1), Err(VerifyError::Sighash(SighashError::InvalidOutpoint(0))));
+	}
+
+	#[test]
+	fn sign_p2pkh() {
+		let key = PrivateKey::new_random(true, Network::Testnet);
+		let mut tx = unsigned_tx(ScriptPubKey::new_p2pkh(key.public_key().as_bytes()));
+
+		assert_eq!(Signer::new(vec![PrivateKey::new_random(true, Network::Testnet)]).sign(&mut tx), Err(SignError::CannotSign(0)));
+		Signer::new(vec![key]).sign(&mut tx).unwrap();
+		assert_eq!(tx.verify(), Ok(()));
+		assert!(tx.witness_data.is_none());
+	}
+
+	#[test]
+	fn sign_p2sh_multisig() {
+		let keys: Vec<PrivateKey> = (0..3).map(|_| PrivateKey::new_random(true, Network::Testnet)).collect();
+		let redeem_script = multisig(2, &keys);
+		let mut tx = unsigned_tx(ScriptPubKey::new_p2sh(redeem_script.clone().into_boxed_slice()));
+
+		// the redeem script is needed
+		assert_eq!(Signer::new(keys.clone()).sign(&mut tx), Err(SignError::CannotSign(0)));
+		let signer = Signer::new(vec![keys[2]]).with_script(redeem_script.clone().into_boxed_slice());
+		assert_eq!(signer.sign(&mut tx), Err(SignError::NotEnoughKeys { input_index: 0, required: 2, found: 1 }));
+
+		let signer = Signer::new(vec![keys[2], keys[0]]).with_script(redeem_script.into_boxed_slice());
+		signer.sign(&mut tx).unwrap();
+		assert_eq!(tx.verify(), Ok(()));
+	}
+
+	#[test]
+	fn sign_p2sh_p2wpkh() {
+		let key = PrivateKey::new_random(true, Network::Testnet);
+		let mut program = vec![0x00, 0x14];
+		program.extend_from_slice(&key.public_key().pubkey_hash());
+		let mut tx = unsigned_tx(ScriptPubKey::new_p2sh(program.clone().into_boxed_slice()));
+
+		Signer::new(vec![key]).sign(&mut tx).unwrap();
+		assert_eq!(tx.inputs[0].script_sig.script[1..], program);
+		assert_eq!(tx.witness_data.as_ref().unwrap()[0].0.len(), 2);
+		assert_eq!(tx.verify(), Ok(()));
+	}
+
+	#[test]
+	fn sign_p2wsh_multisig() {
+		let keys: Vec<PrivateKey> = (0..3).map(|_| PrivateKey::new_random(true, Network::Testnet)).collect();
+		let witness_script = multisig(2, &keys);
+		let mut tx = unsigned_tx(p2wsh(&witness_script));
+
+		let signer = Signer::new(keys.clone()).with_script(witness_script.clone().into_boxed_slice());
+		signer.sign(&mut tx).unwrap();
+		// OP_0 <sig> <sig> <witness script>
+		let witness = &tx.witness_data.as_ref().unwrap()[0].0;
+		assert_eq!(witness.len(), 4);
+		assert!(witness[0].is_empty());
+		assert!(tx.inputs[0].script_sig.script.is_empty());
+		assert_eq!(tx.verify(), Ok(()));
+
+		// round trip
+		let decoded = Transaction::decode_raw(tx.as_hex().decode_hex_be().unwrap()).unwrap();
+		assert_eq!(decoded, tx);
+	}
+
+	#[test]
+	fn sign_with_anyonecanpay() {
+		let key = PrivateKey::new_random(false, Network::Testnet);
+		let mut tx = unsigned_tx(ScriptPubKey::new_p2pk(key.public_key().as_bytes()));
+		let sighash_type = crate::sighash::SIGHASH_ALL | crate::sighash::SIGHASH_ANYONECANPAY;
+		Signer::new(vec![key]).with_sighash_type(sighash_type).sign(&mut tx).unwrap();
+		assert_eq!(*tx.inputs[0].script_sig.script.last().unwrap(), 0x81);
+		assert_eq!(tx.verify(), Ok(()));
+	}
+}
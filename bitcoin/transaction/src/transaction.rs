@@ -1,54 +1,107 @@
 use std::error::Error;
-use std::io::{BufRead, Cursor, self, Seek, Read};
+use std::io::{self, BufRead, Cursor, Write};
+use crate::error::DecodeError;
+use crate::network::Network;
+use crate::prevout::{PrevoutError, PrevoutProvider};
 use crate::script::{Script, ScriptSig, ScriptPubKey};
-use crate::txio::{Encodable, Decodable, ReadExt, WriteExt, UserReadExt, HexBytes};
-use crate::{Serialize, Deserialize};
+use crate::txio::{check_consumed, compact_size, Encodable, Decodable, ReadExt, UserReadExt, HexBytes};
+use crate::{Serialize, Deserialize, hash};
 use derivative::Derivative;
 
 #[derive(Derivative)]
 #[derivative(Debug, PartialEq)]
 pub struct Transaction {
-	version: u32,
-	flag: Option<u16>,
-	in_counter: u64, // varint -> byte size 1-9
-	inputs: Vec<Input>,
-	out_counter: u64, // varint -> byte size 1-9
-	outputs: Vec<Output>,
-	witness_data: Option<Vec<WitnessStack>>,
-	lock_time: u32,
+	pub version: u32,
+	pub flag: Option<u16>,
+	pub in_counter: u64, // varint -> byte size 1-9
+	pub inputs: Vec<Input>,
+	pub out_counter: u64, // varint -> byte size 1-9
+	pub outputs: Vec<Output>,
+	pub witness_data: Option<Vec<WitnessStack>>,
+	pub lock_time: u32,
 	#[derivative(PartialEq="ignore")]
-	extra_info: Option<ExtraInfo>,
+	pub extra_info: Option<ExtraInfo>,
 }
 
 #[derive(Derivative)]
 #[derivative(Debug, PartialEq)]
 pub struct Input {
 	/// Previous transaction hash. Doubled SHA256-hashed.
-	previous_tx: String,
+	pub previous_tx: String,
 	/// Index of an output
-	tx_index: u32,
+	pub tx_index: u32,
 	/// <signature> <original script>
-	script_sig: ScriptSig,
+	pub script_sig: ScriptSig,
 	/// Relative locktime of the input
-	sequence: String,
+	pub sequence: String,
 	/// Previous output
 	#[derivative(PartialEq="ignore")]
-	prevout: Option<Output>,
+	pub prevout: Option<Output>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Output {
-	amount: u64,
-	script_pub_key: ScriptPubKey,
+	pub amount: u64,
+	pub script_pub_key: ScriptPubKey,
 }
 
+/// First byte of the annex in a taproot witness.
+pub const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+
 #[derive(Debug, PartialEq)]
-pub struct WitnessStack(Vec<String>);
+pub struct WitnessStack(pub Vec<String>);
+
+impl WitnessStack {
+	/// BIP341: if there are at least two witness elements and the first byte of the last one is
+	/// 0x50, that last element is the annex. It's removed before the rest of the witness is
+	/// evaluated but it is committed to by taproot signatures.
+	pub fn annex(&self) -> Option<HexBytes> {
+		if self.0.len() < 2 { return None }
+		let last = self.0.last()?.decode_hex_be().ok()?;
+		if last.first() == Some(&TAPROOT_ANNEX_PREFIX) { Some(last) } else { None }
+	}
+
+	/// The witness elements without the annex.
+	pub fn without_annex(&self) -> &[String] {
+		match self.annex() {
+			Some(_) => &self.0[..self.0.len() - 1],
+			None => &self.0,
+		}
+	}
+}
 
+/// Fee and size information of a decoded transaction. Only available when the outputs spent by
+/// every input are known.
 #[derive(Debug, PartialEq)]
 pub struct ExtraInfo {
-	miner_fee: u64,
-	tx_size: u64,
+	/// Sum of the inputs minus the sum of the outputs, in sats.
+	pub miner_fee: u64,
+	/// Size in bytes without the marker, flag and witness.
+	base_size: u64,
+	/// Size in bytes of the full serialization.
+	total_size: u64,
+	/// BIP141 weight units: base_size * 3 + total_size.
+	weight: u64,
+	/// weight / 4 rounded up.
+	vsize: u64,
+	/// Fee rate in sat/vB.
+	pub fee_rate: f64,
+}
+
+impl ExtraInfo {
+	pub fn new(miner_fee: u64, base_size: u64, total_size: u64) -> Self {
+		// Witness bytes are counted once and the rest 4 times
+		let weight = base_size * 3 + total_size;
+		let vsize = weight.div_ceil(4);
+		ExtraInfo {
+			miner_fee,
+			base_size,
+			total_size,
+			weight,
+			vsize,
+			fee_rate: miner_fee as f64 / vsize as f64,
+		}
+	}
 }
 
 impl Serialize for Transaction {
@@ -117,91 +170,182 @@ impl Serialize for Transaction {
 	}
 
 	fn as_hex(&self) -> String {
-		let mut stream = Cursor::new(Vec::new());
-		stream.write_u32_le(self.version);
-		if let Some(flag) = self.flag {
-			stream.write_u16_be(flag);
+		self.as_bytes().encode_hex_be()
+	}
+}
+
+impl Transaction {
+	/// The transaction id is the hash256 of the transaction without its witness, so it doesn't
+	/// change when the signatures of segwit inputs do. Shown in reverse byte order like the
+	/// previous transaction of an input.
+	pub fn txid(&self) -> String {
+		hash::hash256(&self.serialize(false)).encode_hex_le()
+	}
+
+	/// Same as the txid but including the witness (BIP141). It's equal to the txid for
+	/// transactions without witness data.
+	pub fn wtxid(&self) -> String {
+		hash::hash256(&self.serialize(true)).encode_hex_le()
+	}
+
+	/// Size in bytes of the transaction including the witness.
+	pub fn size(&self) -> usize {
+		self.serialize(true).len()
+	}
+
+	/// BIP141 weight: bytes without the witness count 4 times, witness bytes once.
+	pub fn weight(&self) -> usize {
+		self.serialize(false).len() * 3 + self.size()
+	}
+
+	/// Virtual size, the weight divided by 4 and rounded up. Fee rates are based on it.
+	pub fn vsize(&self) -> usize {
+		self.weight().div_ceil(4)
+	}
+
+	/// Look up the outputs spent by the inputs, then the fee if all of them are known. Inputs the
+	/// provider doesn't know keep no prevout.
+	pub fn fetch_prevouts<P: PrevoutProvider + ?Sized>(&mut self, provider: &P) -> Result<(), PrevoutError> {
+		for input in self.inputs.iter_mut() {
+			input.prevout = provider.get_prevout(&input.previous_tx, input.tx_index)?;
+		}
+
+		// Amounts come from the data so the sums can overflow, and the outputs can spend more than
+		// the inputs. There's no fee to show then.
+		let total_input_amount = self.inputs.iter()
+			.try_fold(0u64, |acc, x| acc.checked_add(x.prevout.as_ref()?.amount));
+		let total_output_amount = self.outputs.iter()
+			.try_fold(0u64, |acc, x| acc.checked_add(x.amount));
+		let miner_fee = match (total_input_amount, total_output_amount) {
+			(Some(input), Some(output)) => input.checked_sub(output),
+			_ => None,
+		};
+		self.extra_info = miner_fee.map(|miner_fee| {
+			ExtraInfo::new(miner_fee, self.serialize(false).len() as u64, self.size() as u64)
+		});
+		Ok(())
+	}
+
+	/// Show the addresses of the outputs, and of the outputs spent by the inputs, for a network.
+	pub fn set_network(&mut self, network: Network) {
+		let prevouts = self.inputs.iter_mut().filter_map(|input| input.prevout.as_mut());
+		for output in self.outputs.iter_mut().chain(prevouts) {
+			output.script_pub_key.set_network(network);
+		}
+	}
+
+	/// Consensus serialization of the transaction, with or without the marker, flag and witness.
+	/// The txid is the hash of the serialization without them.
+	pub fn serialize(&self, with_witness: bool) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		self.consensus_encode(&mut bytes, with_witness).expect("fields of a transaction are valid hex");
+		bytes
+	}
+
+	/// Write the consensus serialization into any sink, like a file, a socket or a hasher. Returns
+	/// the number of bytes written. Fails if the sink does or if a hex field isn't valid hex.
+	pub fn consensus_encode<W: Write>(&self, writer: &mut W, with_witness: bool) -> io::Result<usize> {
+		let mut len = 0;
+		let mut write = |bytes: &[u8]| -> io::Result<()> {
+			writer.write_all(bytes)?;
+			len += bytes.len();
+			Ok(())
+		};
+		let decode = |hex: Result<HexBytes, DecodeError>| {
+			hex.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+		};
+
+		write(&self.version.to_le_bytes())?;
+		if let (Some(flag), true) = (self.flag, with_witness) {
+			write(&flag.to_be_bytes())?;
 		}
 
-		stream.write_varint(self.in_counter);
+		write(&compact_size(self.inputs.len() as u64))?;
 
 		for input in &self.inputs {
-			stream.write_hex(input.previous_tx.decode_hex_le().expect("shouldn't fail"), false);
-			stream.write_u32_le(input.tx_index);
-			stream.write_hex(input.script_sig.script.clone(), true);
-			stream.write_hex(input.sequence.decode_hex_le().expect("shouldn't fail"), false);
+			write(&decode(input.previous_tx.decode_hex_le())?)?;
+			write(&input.tx_index.to_le_bytes())?;
+			write(&compact_size(input.script_sig.script.len() as u64))?;
+			write(&input.script_sig.script)?;
+			write(&decode(input.sequence.decode_hex_le())?)?;
 		}
 
-		stream.write_varint(self.out_counter);
+		write(&compact_size(self.outputs.len() as u64))?;
 
 		for output in &self.outputs {
-			stream.write_u64_le(output.amount);
-			stream.write_hex(output.script_pub_key.script.clone(), true);
+			write(&output.amount.to_le_bytes())?;
+			write(&compact_size(output.script_pub_key.script.len() as u64))?;
+			write(&output.script_pub_key.script)?;
 		}
 
-		if let Some(witness_data) = self.witness_data.as_ref() {
-			for witnesses in witness_data {
-				stream.write_varint(witnesses.0.len() as u64);
-				for w in &witnesses.0 {
-					stream.write_hex(w.decode_hex_be().expect("shouldn't fail"), true);
-
+		if let (Some(_), true) = (self.flag, with_witness) {
+			// every input has a witness, inputs without one have an empty stack
+			let witness_data = self.witness_data.as_deref().unwrap_or_default();
+			for i in 0..self.inputs.len() {
+				let witnesses = witness_data.get(i).map(|stack| stack.0.as_slice()).unwrap_or_default();
+				write(&compact_size(witnesses.len() as u64))?;
+				for w in witnesses {
+					let w = decode(w.decode_hex_be())?;
+					write(&compact_size(w.len() as u64))?;
+					write(&w)?;
 				}
 			}
 		}
 
-		stream.write_u32_le(self.lock_time);
-
-		stream.seek(io::SeekFrom::Start(0)).expect("Stream is empty?");
-
-		let mut raw_transaction: Vec<u8> = Vec::new();
-		stream.read_to_end(&mut raw_transaction).expect("Couldn't read till end");
-		raw_transaction.encode_hex_be()
+		write(&self.lock_time.to_le_bytes())?;
+		Ok(len)
 	}
 }
 
 impl Deserialize for Transaction {
 	fn decode_raw(bytes: HexBytes) -> Result<Self, Box<dyn Error>> {
 		let mut stream = Cursor::new(bytes);
+		let transaction = Transaction::read_from(&mut stream)?;
+		check_consumed(&stream)?;
+		Ok(transaction)
+	}
+
+	fn as_bytes(&self) -> HexBytes {
+		self.serialize(true).into_boxed_slice()
+	}
+}
 
+impl Transaction {
+	/// Decode a transaction starting at the current position of the stream, which is left right
+	/// after the transaction. Blocks are transactions one after the other.
+	pub(crate) fn read_from(stream: &mut Cursor<HexBytes>) -> Result<Self, DecodeError> {
 		// version: always 4 bytes long
 		let version = stream.read_u32_le()?;
 
 		// optional, always 0001 if present
 		let mut flag = Some(stream.read_u16_be()?);
 		if flag != Some(1) {
-			stream.seek_from_curr(-2);
+			stream.seek_from_curr(-2)?;
 			flag = None
 		}
 
 		// number of inputs
 		let in_counter = stream.read_compact_size()?;
-		assert_ne!(in_counter, 0);
+		if in_counter == 0 {
+			return Err(DecodeError::NoInputs);
+		}
 
 		let mut inputs: Vec<Input> = Vec::new();
-		println!("Number of inputs {} {}", in_counter, stream.position());
 		for _ in 0..in_counter {
 			let previous_tx = stream.read_hex256()?.encode_hex_le();
 			let tx_index = stream.read_u32_le()?;
 			// question: why are there n extra bytes in script_sig? in/out_script_length specifies it
 			let in_script_length = stream.read_compact_size()?;
-			let script_sig;
-			if in_script_length == 0 {
-				script_sig = ScriptSig::from_str("00");
-			} else {
-				script_sig = ScriptSig::from_bytes(stream.read_hex_var(in_script_length)?);
-			}
+			let script_sig = ScriptSig::from_bytes(stream.read_hex_var(in_script_length)?);
 			let sequence = stream.read_hex32()?.encode_hex_le();
-			let prevout = match get_prevout(&previous_tx, tx_index) {
-				Ok(output) => Some(output),
-				Err(_) => None
-			};
 
+			// see `fetch_prevouts`
 			let input = Input {
 				previous_tx,
 				tx_index,
 				script_sig,
 				sequence,
-				prevout
+				prevout: None,
 			};
 
 			inputs.push(input);
@@ -210,7 +354,9 @@ impl Deserialize for Transaction {
 
 		// number of outputs
 		let out_counter = stream.read_compact_size()?;
-		assert_ne!(out_counter, 0);
+		if out_counter == 0 {
+			return Err(DecodeError::NoOutputs);
+		}
 
 		let mut outputs: Vec<Output> = Vec::new();
 		for _ in 0..out_counter {
@@ -227,14 +373,12 @@ impl Deserialize for Transaction {
 		}
 
 		// list of witnesses
-		let witness_data: Option<Vec<WitnessStack>>;
-		if flag.is_some() {
+		let witness_data: Option<Vec<WitnessStack>> = if flag.is_some() {
 			let mut _witness_data = Vec::new();
 			// number of witnesses = number of inputs
-			for i in 0..in_counter {
+			for _ in 0..in_counter {
 				// If a txin is not associated with any witness data, its corresponding witness 
 				// field is an exact 0x00, indicating that the number of witness stack items is zero.
-				if inputs[i as usize].script_sig == ScriptSig::from_str("00") { continue }
 				let mut stack = WitnessStack(Vec::new());
 				let stack_count = stream.read_compact_size()?;
 				for _ in 0..stack_count {
@@ -244,35 +388,18 @@ impl Deserialize for Transaction {
 				}
 				_witness_data.push(stack);
 			}
-			witness_data = Some(_witness_data);
+			// the marker and flag are only allowed when there is a witness
+			if _witness_data.iter().all(|stack| stack.0.is_empty()) {
+				return Err(DecodeError::SuperfluousWitness);
+			}
+			Some(_witness_data)
 		} else {
-			witness_data = None;
-		}
+			None
+		};
 
-		
 		// always 4 bytes long
 		let lock_time = stream.read_u32_le()?;
 
-		let extra_info: Option<ExtraInfo>;
-
-		if inputs.iter().all(|x| x.prevout.is_some()) {
-			// not sure if the x.prevout.to_owned().unwrap() is the best solution here.
-			let total_input_amount = inputs.iter().fold(0, |acc, x| acc + x.prevout.as_ref().unwrap().amount);
-			let total_output_amount = outputs.iter().fold(0, |acc, x| acc + x.amount);
-			assert!(total_output_amount <= total_input_amount);
-			let miner_fee = total_input_amount - total_output_amount;
-
-			let tx_size = stream.position();
-
-			extra_info = Some(ExtraInfo { 
-				miner_fee,
-				tx_size 
-			});
-
-		} else {
-			extra_info = None;
-		}
-
 		let transaction = Transaction {
 			version,
 			flag,
@@ -282,46 +409,49 @@ impl Deserialize for Transaction {
 			outputs,
 			witness_data,
 			lock_time,
-			extra_info,
+			extra_info: None,
 		};
 
 		Ok(transaction)
 	}
-
-	fn as_bytes(&self) -> HexBytes {
-		todo!()
-	}
-}
-
-// TODO: where should I place this? Ideally private inside Transaction impl since it's used there.
-fn get_prevout(previous_tx: &str, index: u32) -> Result<Output, Box<dyn Error>> {
-	let endpoint = "https://blockstream.info/api/tx/".to_string() + previous_tx;
-	let response = reqwest::blocking::get(endpoint)?.json::<serde_json::Value>()?;
-
-	let prevouts = response["vout"].as_array().unwrap();
-
-	let amount = prevouts[index as usize]["value"].as_u64().unwrap_or(0); 
-	let hex = prevouts[index as usize]["scriptpubkey"].to_string().replace("\"", "");
-	let script_pub_key = ScriptPubKey::from_bytes(hex.decode_hex_be()?);
-
-	let output = Output {
-		amount,
-		script_pub_key,
-	};
-
-	Ok(output)
 }
 
 #[cfg(test)]
 mod tests {
-	use std::io::{Cursor, Error};
+	use std::io::{self, Cursor, Error};
 	use std::io::prelude::*;
+	use crate::error::DecodeError;
 	use crate::script::{ScriptSig, Script, ScriptPubKey};
-use crate::txio::Decodable;
-use crate::{Serialize, Deserialize};
-	use crate::transaction::{Input, Output, Transaction, WitnessStack};
+use crate::txio::{Decodable, Encodable};
+use crate::{Serialize, Deserialize, hash};
+	use crate::prevout::{MemoryPrevouts, NoPrevouts};
+	use crate::transaction::{ExtraInfo, Input, Output, Transaction, WitnessStack};
+
+	// decode_transaction_p2wpkh: a native P2WPKH input
+	const P2WPKH_TX: &str = "020000000001015ce1d4ffc716022f83cc0d557e6dad0500eeff9e9623bde014bdc09c5b672d750000000000fd\
+	ffffff025fb7460b000000001600142cf4c1dc0352e0658971ca62a7457a1cd8c3389c4ce3a2000000000016001433f57fe374c6ceab61\
+	c8639128c038ac2a8c8db60247304402203cb50efb5c4a9aa7fd369ab6f4b226db99f44f9c610b5b50bc42f343a6aa401302201af79154\
+	2eee6c1b11705e8895cc5adc36458910dc91aadcafb76a6478a29b9f01210242e811e66fd17e9a6e4ef772766c668d6e0595ca1d7f0583\
+	148bc460b575fbfdf0df0b00";
+
+	// decode_transaction_segwit_1: three P2SH-P2WPKH inputs
+	const SEGWIT_TX: &str = "02000000000103d19441b832d4e24e4e10c08413b57c017785ea7407b373d4566e11\
+	ad94d8134c1c000000171600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fcffffffff3d416a5941422eeec\
+	bcc0e3fe6aa7a88d00d22b67df149293e3c5bee10c4719a2c000000171600147c846a806f4d9e516c9fb2fe364f\
+	28eac4e3c3fcffffffff4f22589a292781a3cc2d636b9f1932f367305625a7874f8573b72b98ad7369960000000\
+	0171600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fcffffffff02f56468040000000017a9142c21151d54\
+	bd219dcc4c52e1cb38672dab8e36cc87c0276544000000001976a91439b1050dba04b1d1bc556c2dcdcb3874ba3\
+	dc11e88ac0247304402203ccede7995b26185574a050373cfe607f475f7d8ee6927647c496e3b45bf61a302202b\
+	d1ff88c7f4ee0b6f0c98f687dff9033f770b23985f590d178b9085df589101012103789a9d83798d4cbf688f996\
+	9a94084ee1655059e137b43492ee94dc4538790ab02483045022100b46ab18056655cc56b1778fd61a56f895c2f\
+	44c97f055ea0269d991efd181fb402206d651a5fb51081cfdb247a1d489b182f41e52434d7c4575bea30d2ce3d2\
+	4087d012103789a9d83798d4cbf688f9969a94084ee1655059e137b43492ee94dc4538790ab02473044022069bf\
+	2ac34569565a62a1e0c12750104f494a906fefd2f2a462199c0d4bc235d902200c37ef333b453966cc5e84b178e\
+	c62125cbed83e0c0df4448c0fb331efa49e51012103789a9d83798d4cbf688f9969a94084ee1655059e137b4349\
+	2ee94dc4538790ab00000000";
 
     #[test]
+	#[allow(clippy::unused_io_amount)]
     fn encode_transaction_pre_segwit() -> Result<(), Error> {
 		let mut stream = Cursor::new(Vec::new());
 
@@ -359,13 +489,13 @@ use crate::{Serialize, Deserialize};
 		let inputs = vec![Input {
 			previous_tx: "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d".to_string(),
 			tx_index: 0,
-			script_sig: ScriptSig::from_str("a91430fc33f7b86c02f3edb60ea373ca5f467cf507b787"),
+			script_sig: ScriptSig::from_str("a91430fc33f7b86c02f3edb60ea373ca5f467cf507b787").unwrap(),
 			sequence: "ffffffff".to_string(),
 			prevout: None,
 		}];
 		let outputs = vec![Output {
 			amount: 1000,
-			script_pub_key: ScriptPubKey::from_str("abcdef"),
+			script_pub_key: ScriptPubKey::from_str("abcdef").unwrap(),
 		}];
 		let witness_data = None;
 		let transaction = Transaction {
@@ -389,13 +519,13 @@ use crate::{Serialize, Deserialize};
 		let inputs = vec![Input {
 			previous_tx: "656aa8c5894c179b2745fa8a0fb68cb10688daa7389fd47900a055cc2526cb5d".to_string(),
 			tx_index: 0,
-			script_sig: ScriptSig::from_str("76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac"),
+			script_sig: ScriptSig::from_str("76a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88ac").unwrap(),
 			sequence: "ffffffff".to_string(),
 			prevout: None,
 		}];
 		let outputs = vec![Output {
 			amount: 1000,
-			script_pub_key: ScriptPubKey::from_str("abcdef"),
+			script_pub_key: ScriptPubKey::from_str("abcdef").unwrap(),
 		}];
 		let witness_data = None;
 		let transaction = Transaction {
@@ -432,7 +562,7 @@ use crate::{Serialize, Deserialize};
 			previous_tx: "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9".to_string(),
 			tx_index: 0,
 			script_sig: ScriptSig::from_str("47304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61\
-			548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901"),
+			548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901").unwrap(),
 			sequence: "ffffffff".to_string(),
 			prevout: None,
 		}];
@@ -441,12 +571,12 @@ use crate::{Serialize, Deserialize};
 			Output {
 				amount: 1000000000,
 				script_pub_key: ScriptPubKey::from_str("4104ae1a62fe09c5f51b13905f07f06b99a2f7159b2\
-				225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac"),
+				225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac").unwrap(),
 			},
 			Output {
 				amount: 4000000000,
 				script_pub_key: ScriptPubKey::from_str("410411db93e1dcdb8a016b49840f8c53bc1eb68a382\
-				e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac"),
+				e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac").unwrap(),
 			}
 		];
 		
@@ -491,7 +621,7 @@ use crate::{Serialize, Deserialize};
 			tx_index: 0,
 			script_sig: ScriptSig::from_str("473044022045c7199ffc8069a498135b7bb2678da16e8b5d49455b\
 			4a7ace755928c9339c7a022051cbf72024cf273444640f7b993b2bf3d329124b03e6744edaed5158a30e29b\
-			8012103fd9bc1e9803e739720e0f1c63e580a94656c7d0cab6cd083f0c0dfb221b90662"),
+			8012103fd9bc1e9803e739720e0f1c63e580a94656c7d0cab6cd083f0c0dfb221b90662").unwrap(),
 			sequence: "ffffffff".to_string(),
 			prevout: None,
 		}];
@@ -499,11 +629,11 @@ use crate::{Serialize, Deserialize};
 		let outputs = vec![
 			Output {
 				amount: 1400000000000,
-				script_pub_key: ScriptPubKey::from_str("76a9143b9552116adcc2fbd74fad44a4da603a727c816e88ac")
+				script_pub_key: ScriptPubKey::from_str("76a9143b9552116adcc2fbd74fad44a4da603a727c816e88ac").unwrap()
 			},
 			Output {
 				amount: 1099994980000,
-				script_pub_key: ScriptPubKey::from_str("76a914f90ce447f14847e841d4d2ecc76299b5bc77166188ac"),
+				script_pub_key: ScriptPubKey::from_str("76a914f90ce447f14847e841d4d2ecc76299b5bc77166188ac").unwrap(),
 			}
 		];
 		let witness_data = None;
@@ -548,7 +678,7 @@ use crate::{Serialize, Deserialize};
 			303cfc36b91372e47d5fa0b22104e7c25bb5a8dcccd15c423620d5700304014c69522102047464f518269c6\
 			cba42b859d28e872ef8f6bb47d93e24d5c11ac6eca8a2845721029b48417598a2d2dab54ddddfca8e1a9c8d\
 			4967002180961f53a7748710c2176521036b1023b6c7ed689aaf3bc8ca9ee5c55da383ae0c44fc8b0fec91d\
-			6965dae5d5e53ae"),
+			6965dae5d5e53ae").unwrap(),
 			sequence: "ffffffff".to_string(),
 			prevout: None,
 		}];
@@ -556,19 +686,19 @@ use crate::{Serialize, Deserialize};
 		let outputs = vec![
 			Output {
 				amount: 1170000,
-				script_pub_key: ScriptPubKey::from_str("00141e129251311437eea493fce2a3644a5a1af8d407"),
+				script_pub_key: ScriptPubKey::from_str("00141e129251311437eea493fce2a3644a5a1af8d407").unwrap(),
 			},
 			Output {
 				amount: 1930000,
-				script_pub_key: ScriptPubKey::from_str("76a9140ac4423b045a0c8ed5f4fb992256ed293a313ae088ac"),
+				script_pub_key: ScriptPubKey::from_str("76a9140ac4423b045a0c8ed5f4fb992256ed293a313ae088ac").unwrap(),
 			},
 			Output {
 				amount: 10185620,
-				script_pub_key: ScriptPubKey::from_str("a914cd38af19a803de11ddcee3a45221ed9ac491404787"),
+				script_pub_key: ScriptPubKey::from_str("a914cd38af19a803de11ddcee3a45221ed9ac491404787").unwrap(),
 			},
 			Output {
 				amount: 1519708769,
-				script_pub_key: ScriptPubKey::from_str("a9143572de0bb360f212ef8813a9e012f63a7035c9c987"),
+				script_pub_key: ScriptPubKey::from_str("a9143572de0bb360f212ef8813a9e012f63a7035c9c987").unwrap(),
 			}
 		];
 
@@ -588,7 +718,6 @@ use crate::{Serialize, Deserialize};
 
 		println!("{:#?}", transaction);
 
-		// txid: db6e06ff6e53356cc22cd1b9b8d951ddf70dc6bb275ee76880a0b951c1c290e6
 		let raw_transaction = "02000000016dbad361f6a9f0c60e8b032e2008aa0a9151c7bf691464274c89315d2f\
 		6c52cc19000000fc0047304402204945c3e4f824d263bb22e117a12bfff741d996d594f07551c93e0fde77910d3\
 		2022016c2b69daec51bd4afdd81bf90f76667dda515773b3da91174043fc7299acb5301473044022053c71a4730\
@@ -602,6 +731,11 @@ use crate::{Serialize, Deserialize};
 		let bytes = raw_transaction.decode_hex_be().expect("shouldn't fail");
 
 		assert_eq!(transaction.as_hex(), raw_transaction.to_string());
+		assert_eq!(transaction.txid(), "db6e06ff6e53356cc22cd1b9b8d951ddf70dc6bb275ee76880a0b951c1c290e6");
+		assert_eq!(transaction.wtxid(), transaction.txid());
+		assert_eq!(transaction.size(), 432);
+		assert_eq!(transaction.weight(), 432 * 4);
+		assert_eq!(transaction.vsize(), 432);
 
 		// round trip
 		let tx = match Transaction::decode_raw(bytes) {
@@ -613,26 +747,27 @@ use crate::{Serialize, Deserialize};
 	}
 
 	#[test]
+	#[allow(clippy::init_numbered_fields)]
 	fn decode_transaction_segwit_1() {
 		let inputs = vec![
 			Input {
 				previous_tx: "4c13d894ad116e56d473b30774ea8577017cb51384c0104e4ee2d432b84194d1".to_string(),
 				tx_index: 28,
-				script_sig: ScriptSig::from_str("1600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fc"),
+				script_sig: ScriptSig::from_str("1600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fc").unwrap(),
 				sequence: "ffffffff".to_string(),
 				prevout: None,
 			},
 			Input {
 				previous_tx: "9a71c410ee5b3c3e2949f17db6220dd0887aaae63f0ecccbee2e4241596a413d".to_string(),
 				tx_index: 44,
-				script_sig: ScriptSig::from_str("1600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fc"),
+				script_sig: ScriptSig::from_str("1600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fc").unwrap(),
 				sequence: "ffffffff".to_string(),
 				prevout: None,
 			},
 			Input {
 				previous_tx: "966973ad982bb773854f87a725563067f332199f6b632dcca38127299a58224f".to_string(),
 				tx_index: 0,
-				script_sig: ScriptSig::from_str("1600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fc"),
+				script_sig: ScriptSig::from_str("1600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fc").unwrap(),
 				sequence: "ffffffff".to_string(),
 				prevout: None,
 			},
@@ -642,11 +777,11 @@ use crate::{Serialize, Deserialize};
 		let outputs = vec![
 			Output {
 				amount: 73950453,
-				script_pub_key: ScriptPubKey::from_str("a9142c21151d54bd219dcc4c52e1cb38672dab8e36cc87"),
+				script_pub_key: ScriptPubKey::from_str("a9142c21151d54bd219dcc4c52e1cb38672dab8e36cc87").unwrap(),
 			},
 			Output {
 				amount: 1147480000,
-				script_pub_key: ScriptPubKey::from_str("76a91439b1050dba04b1d1bc556c2dcdcb3874ba3dc11e88ac"),
+				script_pub_key: ScriptPubKey::from_str("76a91439b1050dba04b1d1bc556c2dcdcb3874ba3dc11e88ac").unwrap(),
 			}
 		];
 
@@ -682,24 +817,16 @@ use crate::{Serialize, Deserialize};
 
 		println!("{:#?}", transaction);
 
-		// txid: 68333a10b368e0d002098827fa3f348135fb728ade74d265e6abf41dfcb60a1c
-		let raw_transaction = "02000000000103d19441b832d4e24e4e10c08413b57c017785ea7407b373d4566e11\
-		ad94d8134c1c000000171600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fcffffffff3d416a5941422eeec\
-		bcc0e3fe6aa7a88d00d22b67df149293e3c5bee10c4719a2c000000171600147c846a806f4d9e516c9fb2fe364f\
-		28eac4e3c3fcffffffff4f22589a292781a3cc2d636b9f1932f367305625a7874f8573b72b98ad7369960000000\
-		0171600147c846a806f4d9e516c9fb2fe364f28eac4e3c3fcffffffff02f56468040000000017a9142c21151d54\
-		bd219dcc4c52e1cb38672dab8e36cc87c0276544000000001976a91439b1050dba04b1d1bc556c2dcdcb3874ba3\
-		dc11e88ac0247304402203ccede7995b26185574a050373cfe607f475f7d8ee6927647c496e3b45bf61a302202b\
-		d1ff88c7f4ee0b6f0c98f687dff9033f770b23985f590d178b9085df589101012103789a9d83798d4cbf688f996\
-		9a94084ee1655059e137b43492ee94dc4538790ab02483045022100b46ab18056655cc56b1778fd61a56f895c2f\
-		44c97f055ea0269d991efd181fb402206d651a5fb51081cfdb247a1d489b182f41e52434d7c4575bea30d2ce3d2\
-		4087d012103789a9d83798d4cbf688f9969a94084ee1655059e137b43492ee94dc4538790ab02473044022069bf\
-		2ac34569565a62a1e0c12750104f494a906fefd2f2a462199c0d4bc235d902200c37ef333b453966cc5e84b178e\
-		c62125cbed83e0c0df4448c0fb331efa49e51012103789a9d83798d4cbf688f9969a94084ee1655059e137b4349\
-		2ee94dc4538790ab00000000";
+		let raw_transaction = SEGWIT_TX;
 		let bytes = raw_transaction.decode_hex_be().expect("shouldn't fail");
 
 		assert_eq!(transaction.as_hex(), raw_transaction.to_string());
+		assert_eq!(transaction.txid(), "68333a10b368e0d002098827fa3f348135fb728ade74d265e6abf41dfcb60a1c");
+		assert_eq!(transaction.wtxid(), "2fc02a456c819ff147ffcf0bb1e8e353752aad7e855b5b198a37dc68be8d351e");
+		// 268 bytes without the witness
+		assert_eq!(transaction.size(), 592);
+		assert_eq!(transaction.weight(), 1396);
+		assert_eq!(transaction.vsize(), 349);
 
 		// round trip
 		let tx = match Transaction::decode_raw(bytes) {
@@ -709,4 +836,255 @@ use crate::{Serialize, Deserialize};
 
 		assert_eq!(transaction, tx);
 	}
+
+	#[test]
+	fn extra_info_discounts_witness() {
+		// bitcoind's decoderawtransaction gives a size of 222, a vsize of 141 and a weight of 561:
+		// 113 bytes without the marker, flag and witness count 4 times, the 109 others once
+		let mut tx = Transaction::decode_raw(P2WPKH_TX.decode_hex_be().unwrap()).unwrap();
+		assert_eq!((tx.size(), tx.weight(), tx.vsize()), (222, 561, 141));
+
+		// a fee of 10 sat/vB
+		let total_output_amount: u64 = tx.outputs.iter().map(|output| output.amount).sum();
+		let mut prevouts = MemoryPrevouts::new();
+		let script_pub_key = ScriptPubKey::from_str("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+		prevouts.insert(&tx.inputs[0].previous_tx, tx.inputs[0].tx_index, Output { amount: total_output_amount + 1410, script_pub_key });
+		tx.fetch_prevouts(&prevouts).unwrap();
+		let info = tx.extra_info.as_ref().unwrap();
+		assert_eq!((info.base_size, info.total_size), (113, 222));
+		assert_eq!((info.weight, info.vsize), (561, 141));
+		assert_eq!(info.miner_fee, 1410);
+		assert_eq!(info.fee_rate, 10.0);
+
+		// no witness, weight is 4 times the size
+		let info = ExtraInfo::new(1000, 432, 432);
+		assert_eq!(info.weight, 1728);
+		assert_eq!(info.vsize, 432);
+
+		// rounded up
+		let info = ExtraInfo::new(0, 100, 101);
+		assert_eq!(info.weight, 401);
+		assert_eq!(info.vsize, 101);
+	}
+
+	#[test]
+	fn witness_stack_annex() {
+		// key path spend with an annex
+		let stack = WitnessStack(vec!["aa".repeat(64), "50abcd".to_string()]);
+		assert_eq!(stack.annex(), Some("50abcd".decode_hex_be().unwrap()));
+		assert_eq!(stack.without_annex(), &["aa".repeat(64)]);
+
+		// a single element is never an annex
+		let stack = WitnessStack(vec!["50abcd".to_string()]);
+		assert_eq!(stack.annex(), None);
+		assert_eq!(stack.without_annex().len(), 1);
+
+		// script path spend without an annex: <sig> <script> <control block>
+		let stack = WitnessStack(vec!["aa".repeat(64), "51".to_string(), "c0".to_string() + &"bb".repeat(32)]);
+		assert_eq!(stack.annex(), None);
+		assert_eq!(stack.without_annex().len(), 3);
+	}
+
+	#[test]
+	fn decode_malformed_transactions() {
+		let decode = |hex: &str| {
+			let error = Transaction::decode_raw(hex.decode_hex_be().unwrap()).unwrap_err();
+			error.downcast_ref::<DecodeError>().cloned().unwrap()
+		};
+		let outpoint = "5dcb2625cc55a00079d49f38a7da8806b18cb60f8afa45279b174c89c5a86a6500000000";
+
+		assert_eq!(decode("010000"), DecodeError::UnexpectedEof);
+		assert_eq!(decode("010000000000000000"), DecodeError::NoInputs);
+		assert_eq!(decode(&format!("0100000001{}00ffffffff00", outpoint)), DecodeError::NoOutputs);
+		// the outpoint ends early
+		assert_eq!(decode(&format!("0100000001{}", &outpoint[..40])), DecodeError::UnexpectedEof);
+		// a script sig of 2^32 - 1 bytes
+		assert_eq!(decode(&format!("0100000001{}feffffffff", outpoint)), DecodeError::OversizedCompactSize(0xffffffff));
+		assert_eq!(decode(&format!("0100000001{}fd0500", outpoint)), DecodeError::NonCanonicalCompactSize);
+		// the output script is longer than the rest of the data
+		assert_eq!(decode(&format!("0100000001{}00ffffffff01e80300000000000010abcdef", outpoint)), DecodeError::UnexpectedEof);
+
+		// like bitcoind, a script sig too big to execute still decodes, it only fails verification
+		let hex = format!("0100000001{}fd1127{}ffffffff01e8030000000000000000000000", outpoint, "00".repeat(10001));
+		let tx = Transaction::decode_raw(hex.decode_hex_be().unwrap()).unwrap();
+		assert_eq!(tx.inputs[0].script_sig.script.len(), 10001);
+		assert_eq!(tx.as_hex(), hex);
+	}
+
+	#[test]
+	fn decode_truncated_and_padded_transactions() {
+		let decode = |bytes: &[u8]| {
+			let error = Transaction::decode_raw(bytes.into()).unwrap_err();
+			error.downcast_ref::<DecodeError>().cloned().unwrap()
+		};
+
+		let pre_segwit = "01000000015dcb2625cc55a00079d49f38a7da8806b18cb60f8afa45279b174c89c5a86a65000000001976a9\
+		1488fed7b8154069b5d2ace12fa4b7f96ab73d59df88acffffffff01e80300000000000003abcdef00000000".decode_hex_be().unwrap();
+		let segwit = SEGWIT_TX.decode_hex_be().unwrap();
+		assert!(Transaction::decode_raw(pre_segwit.clone()).is_ok());
+		assert!(Transaction::decode_raw(segwit.clone()).is_ok());
+
+		// every field cut short
+		for len in 0..pre_segwit.len() {
+			assert_eq!(decode(&pre_segwit[..len]), DecodeError::UnexpectedEof, "{} bytes", len);
+		}
+		for len in 0..segwit.len() {
+			assert_eq!(decode(&segwit[..len]), DecodeError::UnexpectedEof, "{} bytes", len);
+		}
+
+		// anything after the locktime
+		for tx in [&pre_segwit, &segwit] {
+			for padding in [&[0u8][..], &[0, 0, 0, 0], &[0xff; 9], tx] {
+				let padded = [&tx[..], padding].concat();
+				assert_eq!(decode(&padded), DecodeError::TrailingBytes(padding.len()));
+			}
+		}
+	}
+
+	fn assert_round_trip(raw_transaction: &str, txid: &str, wtxid: &str, weight: usize) -> Transaction {
+		let tx = Transaction::decode_raw(raw_transaction.decode_hex_be().unwrap()).unwrap();
+		assert_eq!(tx.as_hex(), raw_transaction);
+		assert_eq!(tx.txid(), txid);
+		assert_eq!(tx.wtxid(), wtxid);
+		assert_eq!(tx.size(), raw_transaction.len() / 2);
+		assert_eq!(tx.weight(), weight);
+		// every input has a witness stack
+		assert_eq!(tx.witness_data.as_ref().unwrap().len(), tx.inputs.len());
+		tx
+	}
+
+	#[test]
+	fn decode_transaction_p2wpkh() {
+		let tx = assert_round_trip(P2WPKH_TX,
+		"663becacc6368150a46725e404ccdfa34d1fffbececa784c31f0a7849b4dad08",
+		"b9dc70441ac50850dccd1ee371add14d1d79866996b67b17bf2a5e00b264626a", 561);
+		// <signature> <public key>
+		assert!(tx.inputs[0].script_sig.script.is_empty());
+		assert_eq!(tx.witness_data.unwrap()[0].0.len(), 2);
+	}
+
+	#[test]
+	fn fetch_prevouts() {
+		let mut tx = Transaction::decode_raw(P2WPKH_TX.decode_hex_be().unwrap()).unwrap();
+		assert!(tx.inputs[0].prevout.is_none());
+		tx.fetch_prevouts(&NoPrevouts).unwrap();
+		assert_eq!(tx.extra_info, None);
+
+		// the input spends 1000 sats more than the outputs
+		let total_output_amount: u64 = tx.outputs.iter().map(|output| output.amount).sum();
+		let mut prevouts = MemoryPrevouts::new();
+		let script_pub_key = ScriptPubKey::from_str("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+		prevouts.insert(&tx.inputs[0].previous_tx, tx.inputs[0].tx_index, Output { amount: total_output_amount + 1000, script_pub_key });
+		tx.fetch_prevouts(&prevouts).unwrap();
+		assert!(tx.inputs[0].prevout.as_ref().unwrap().script_pub_key.is_p2wpkh());
+		let info = tx.extra_info.as_ref().unwrap();
+		assert_eq!(info.miner_fee, 1000);
+		assert_eq!((info.base_size, info.total_size, info.weight, info.vsize), (113, 222, 561, 141));
+
+		// outputs spending more than the inputs
+		let mut prevouts = MemoryPrevouts::new();
+		let script_pub_key = ScriptPubKey::from_str("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+		prevouts.insert(&tx.inputs[0].previous_tx, tx.inputs[0].tx_index, Output { amount: total_output_amount - 1, script_pub_key });
+		tx.fetch_prevouts(&prevouts).unwrap();
+		assert!(tx.inputs[0].prevout.is_some());
+		assert_eq!(tx.extra_info, None);
+	}
+
+	#[test]
+	fn encode_into_writers() {
+		let bytes = P2WPKH_TX.decode_hex_be().unwrap();
+		let mut tx = Transaction::decode_raw(bytes.clone()).unwrap();
+		assert_eq!(tx.as_bytes(), bytes);
+		assert_eq!(tx.as_hex(), P2WPKH_TX);
+		// the counts come from the inputs and outputs, not the counters
+		tx.in_counter = 5;
+		tx.out_counter = 0;
+		assert_eq!(tx.as_bytes(), bytes);
+
+		// without the marker, flag and witness
+		let stripped = tx.serialize(false);
+		assert_eq!(stripped.len(), 113);
+		assert_eq!(hash::hash256(&stripped).encode_hex_le(), tx.txid());
+		let mut stripped_tx = Transaction::decode_raw(stripped.into_boxed_slice()).unwrap();
+		assert_eq!(stripped_tx.flag, None);
+		stripped_tx.flag = tx.flag;
+		stripped_tx.witness_data = tx.witness_data.take();
+		assert_eq!(stripped_tx.as_bytes(), bytes);
+
+		// a sink that isn't a Vec
+		let mut buffer = [0u8; 300];
+		let len = stripped_tx.consensus_encode(&mut &mut buffer[..], true).unwrap();
+		assert_eq!(&buffer[..len], &bytes[..]);
+		assert_eq!(stripped_tx.consensus_encode(&mut io::sink(), false).unwrap(), 113);
+		// too small
+		let error = stripped_tx.consensus_encode(&mut &mut buffer[..100], true).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+
+		stripped_tx.inputs[0].sequence = "fffffffg".to_string();
+		let error = stripped_tx.consensus_encode(&mut Vec::new(), true).unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn decode_transaction_p2wsh() {
+		let tx = assert_round_trip("010000000001011b9eb4122976fad8f809ee4cea8ac8d1c5b6b8e0d0f9f93327a5d78c9a3945280000000000ff\
+		ffffff02ba3e0d00000000002200201c3b09401aaa7c9709d118a75d301bdb2180fb68b2e9b3ade8ad4ff7281780cfa586010000000000\
+		220020a41d0d894799879ca1bd88c1c3f1c2fd4b1592821cc3c5bfd5be5238b904b09f040047304402201c7563e876d67b5702aea5726c\
+		d202bf92d0b1dc52c4acd03435d6073e630bac022032b64b70d7fba0cb8be30b882ea06c5f8ec7288d113459dd5d3e294214e2c9620148\
+		3045022100f532f7e3b8fd01a0edc86de4870db4e04858964d0a609df81deb99d9581e6c2e02206d9e9b6ab661176be8194faded62f518\
+		cdc6ee74dba919e0f35d77cff81f38e5014752210289da5da9d3700156db2d01e6362491733f6c886971791deda74b4e9d707190b22103\
+		23c437f30384498be79df2990ce5a8de00844e768c0ccce914335b6c26adea7352ae00000000",
+		"2bb157363e7a62d70b92082a9b2c9bb6f329154f816b8d239bd58c35c789a96a",
+		"0726d619cae9212af8a557a8c062e4cb9d0479c80502420e0b8c74c63ccbc542", 769);
+		// 2 of 2 multisig: <empty> <signature> <signature> <witness script>
+		let witness = &tx.witness_data.unwrap()[0];
+		assert_eq!(witness.0.len(), 4);
+		assert!(witness.0[0].is_empty());
+		assert!(witness.0[3].ends_with("52ae"));
+	}
+
+	#[test]
+	fn decode_transaction_p2tr() {
+		let tx = assert_round_trip("01000000000101b5cee87f1a60915c38bb0bc26aaf2b67be2b890bbc54bb4be1e40272e0d2fe0b0000000000ff\
+		ffffff025529000000000000225120106daad8a5cb2e6fc74783714273bad554a148ca2d054e7a19250e9935366f303376000000000000\
+		2200205e6d83c44f57484fd2ef2a62b6d36cdcd6b3e06b661e33fd65588a28ad0dbe060141df9d1bfce71f90d68bf9e9461910b3716466\
+		bfe035c7dbabaa7791383af6c7ef405a3a1f481488a91d33cd90b098d13cb904323a3e215523aceaa04e1bb35cdb0100000000",
+		"9ba9d23c3e21d1d2f3fd658cd60043553c80cdae7885b5ee29c10cda4b5b715f",
+		"422e5be8c7c506f4be0b64f5e2c2bc2a5a463bc6f6a4c6f2bafdccff1831c55d", 617);
+		// key path spend, a single 65 byte signature (with a sighash type)
+		let witness = &tx.witness_data.unwrap()[0];
+		assert_eq!(witness.0.len(), 1);
+		assert_eq!(witness.0[0].len(), 65 * 2);
+	}
+
+	#[test]
+	fn decode_transaction_mixed_inputs() {
+		// two P2PKH inputs followed by a P2WPKH input
+		let tx = assert_round_trip("010000000001036b6b6ac7e34e97c53c1cc74c99c7948af2e6aac75d8778004ae458d813456764000000006a47\
+		3044022001deec7d9075109306320b3754188f81a8236d0d232b44bc69f8309115638b8f02204e17a5194a519cf994d0afeea1268740bd\
+		c10616b031a521113681cc415e815c012103488d3272a9fad78ee887f0684cb8ebcfc06d0945e1401d002e590c7338b163feffffffffc7\
+		5bd7aa6424aee972789ec28ba181254ee6d8311b058d165bd045154d7660b0000000006b483045022100c8641bcbee3e4c47a004178750\
+		15d8c5d5ea918fb7e96f18c6ffe51bc555b401022074e2c46f5b1109cd79e39a9aa203eadd1d75356415e51d80928a5fb5feb0efee0121\
+		033504b4c6dfc3a5daaf7c425aead4c2dbbe4e7387ce8e6be2648805939ecf7054ffffffff494df3b205cd9430a26f8e8c0dc0bb80496f\
+		bc555a524d6ea307724bc7e60eee0100000000ffffffff026d861500000000001976a9145c54ed1360072ebaf56e87693b88482d2c6a10\
+		1588ace407000000000000160014761e31e2629c6e11936f2f9888179d60a5d4c1f900000247304402201fa38a67a63e58b67b6cfffd02\
+		f59121ca1c8a1b22e1efe2573ae7e4b4f06c2b022002b9b431b58f6e36b3334fb14eaecee7d2f06967a77ef50d8d5f90dda1057f0c0121\
+		0257dc6ce3b1100903306f518ee8fa113d778e403f118c080b50ce079fba40e09a00000000",
+		"c71d9a4fc38ee16dfc06ccc14e2192ffc4f1875d22282e3c7a9a1573a5c1d6e3",
+		"61f28185550e76267d5b685cf014ced7a6493ee2481d66abc508fc105476b8f4", 1755);
+		let witness_data = tx.witness_data.as_ref().unwrap();
+		assert!(witness_data[0].0.is_empty() && witness_data[1].0.is_empty());
+		assert_eq!(witness_data[2].0.len(), 2);
+		assert!(!tx.inputs[0].script_sig.script.is_empty());
+		assert!(tx.inputs[2].script_sig.script.is_empty());
+	}
+
+	#[test]
+	fn decode_superfluous_witness() {
+		// decode_transaction_pre_segwit_1 with a marker and flag but only an empty witness
+		let raw_transaction = "010000000001015dcb2625cc55a00079d49f38a7da8806b18cb60f8afa45279b174c89c5a86a65000000001976\
+		a91488fed7b8154069b5d2ace12fa4b7f96ab73d59df88acffffffff01e80300000000000003abcdef0000000000";
+		let error = Transaction::decode_raw(raw_transaction.decode_hex_be().unwrap()).unwrap_err();
+		assert_eq!(error.downcast_ref::<DecodeError>(), Some(&DecodeError::SuperfluousWitness));
+	}
 }
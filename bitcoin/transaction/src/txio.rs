@@ -1,10 +1,10 @@
 use std::fmt::{write, LowerHex};
-use std::io::{Seek, SeekFrom, BufRead, Write, Error, ErrorKind};
-use std::num::ParseIntError;
+use std::io::{Cursor, Seek, SeekFrom, BufRead, Read, Error, ErrorKind};
+use crate::error::DecodeError;
 use crate::script::{ScriptBuilder, Script, ScriptPubKey};
 
-// TODO:
-// - Custom errors
+/// Largest compact size Core accepts when reading, no message can be bigger than 32 MB.
+pub const MAX_SIZE: u64 = 0x02000000;
 
 /// Discussion on Vec<u8> vs Box<[u8]>
 /// https://github.com/ipld/libipld/issues/36
@@ -28,9 +28,9 @@ pub trait Encodable {
 /// endianness.
 pub trait Decodable {
 	/// Read a hex string into bytes in little-endian.
-	fn decode_hex_le(&self) -> Result<HexBytes, ParseIntError>;
+	fn decode_hex_le(&self) -> Result<HexBytes, DecodeError>;
 	/// Read a hex string into bytes in big-endian.
-	fn decode_hex_be(&self) -> Result<HexBytes, ParseIntError>;
+	fn decode_hex_be(&self) -> Result<HexBytes, DecodeError>;
 }
 
 // TODO: Is this *too* generic?
@@ -53,52 +53,52 @@ impl<T: Sized + LowerHex> Encodable for [T] {
 }
 
 impl<T: Sized + ToString> Decodable for T {
-	fn decode_hex_le(&self) -> Result<HexBytes, ParseIntError> {
-		let s =  &self.to_string();
-		(0..s.len())
-			.step_by(2)
-			.rev()
-			.map(|i| u8::from_str_radix(&s[i..i + 2], 16))
-			.collect()
+	fn decode_hex_le(&self) -> Result<HexBytes, DecodeError> {
+		let mut bytes = self.decode_hex_be()?;
+		bytes.reverse();
+		Ok(bytes)
 	}
 
 	/// Read a hex string into bytes
-	fn decode_hex_be(&self) -> Result<HexBytes, ParseIntError> {
-		let s =  &self.to_string();
-		(0..s.len())
-			.step_by(2)
-			.map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+	fn decode_hex_be(&self) -> Result<HexBytes, DecodeError> {
+		let s = self.to_string();
+		if !s.len().is_multiple_of(2) {
+			return Err(DecodeError::BadHex);
+		}
+		// work on bytes, slicing the string could split a multi-byte character
+		s.as_bytes()
+			.chunks(2)
+			.map(|pair| Ok(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
 			.collect()
 	}
 }
 
+fn hex_digit(c: u8) -> Result<u8, DecodeError> {
+	(c as char).to_digit(16).map(|d| d as u8).ok_or(DecodeError::BadHex)
+}
+
 /// Extension of Read to decode data according to Bitcoin's spec.
 pub trait ReadExt {
 	/// Read 8-bits in where the source is in little-endian format.
-	fn read_u8_le(&mut self) -> Result<u8, Error>;
+	fn read_u8_le(&mut self) -> Result<u8, DecodeError>;
 	/// Read 16-bits in where the source is in little-endian format.
-	fn read_u16_le(&mut self) -> Result<u16, Error>;
+	fn read_u16_le(&mut self) -> Result<u16, DecodeError>;
 	/// Read 32-bits in where the source is in little-endian format.
-	fn read_u32_le(&mut self) -> Result<u32, Error>;
+	fn read_u32_le(&mut self) -> Result<u32, DecodeError>;
 	/// Read 32-bits in where the source is in little-endian format.
-	fn read_u64_le(&mut self) -> Result<u64, Error>;
+	fn read_u64_le(&mut self) -> Result<u64, DecodeError>;
 
-	/// Read 8-bits in where the source is in big-endian format.
-	fn read_u8_be(&mut self) -> Result<u8, Error>;
 	/// Read 16-bits in where the source is in big-endian format.
-	fn read_u16_be(&mut self) -> Result<u16, Error>;
-	/// Read 32-bits in where the source is in big-endian format.
-	fn read_u32_be(&mut self) -> Result<u32, Error>;
-	/// Read 64-bits in where the source is in big-endian format.
-	fn read_u64_be(&mut self) -> Result<u64, Error>;
+	fn read_u16_be(&mut self) -> Result<u16, DecodeError>;
 
 	/// Read 32-bits as bytes (which are in hex format).
-	fn read_hex32(&mut self) -> Result<HexBytes, Error>;
+	fn read_hex32(&mut self) -> Result<HexBytes, DecodeError>;
 	/// Read 256-bits as bytes (which are in hex format).
-	fn read_hex256(&mut self) -> Result<HexBytes, Error>;
+	fn read_hex256(&mut self) -> Result<HexBytes, DecodeError>;
 	/// Read an arbitrary number of bits as bytes (which are in hex format).
-	fn read_hex_var(&mut self, len: u64) -> Result<HexBytes, Error>;
+	fn read_hex_var(&mut self, len: u64) -> Result<HexBytes, DecodeError>;
 
+	#[allow(clippy::tabs_in_doc_comments)]
 	/**
 	 *
 	 * Compact Size
@@ -112,25 +112,24 @@ pub trait ReadExt {
 	 * fe -> 0000 0000 (254 + 4 bytes)
 	 * ff -> 0000 0000 0000 0000 (255 + 8 bytes)
 	 * check bitcoin/src/serialize.h file
+	 *
+	 * Sizes that fit in a shorter encoding and sizes above MAX_SIZE are rejected like Core does.
 	 */
-	fn read_compact_size(&mut self) -> Result<u64, Error>;
+	fn read_compact_size(&mut self) -> Result<u64, DecodeError>;
 
 	/// Seek the current buffer forward or backward by a specified amount.
-	fn seek_from_curr(&mut self, length: i64);
+	fn seek_from_curr(&mut self, length: i64) -> Result<(), DecodeError>;
 }
 
-/// Extension of Write to decode data according to Bitcoin's spec.
+/// Extension of Vec<u8> to encode data according to Bitcoin's spec. Writing to memory can't fail.
 pub trait WriteExt {
-	/// Write 16-bits on to the buffer in little-endian format. Return the number of bytes written.
-	fn write_u16_le(&mut self, val: u16) -> usize;
+	/// Write 8-bits on to the buffer. Return the number of bytes written.
+	fn write_u8_le(&mut self, val: u8) -> usize;
 	/// Write 32-bits on to the buffer in little-endian format. Return the number of bytes written.
 	fn write_u32_le(&mut self, val: u32) -> usize;
 	/// Write 64-bits on to the buffer in little-endian format. Return the number of bytes written.
 	fn write_u64_le(&mut self, val: u64) -> usize;
 
-	/// Write 16-bits on to the buffer in big-endian format. Return the number of bytes written.
-	fn write_u16_be(&mut self, val: u16) -> usize;
-
 	/// Write an array of hex bytes on to the buffer (in big-endian). Specify if the len as
 	/// compact-size should be written first. This is useful for scripts.
 	/// Returns the number of bytes written.
@@ -143,9 +142,9 @@ pub trait WriteExt {
 // ---- Buffer reading ----
 macro_rules! impl_read_int_le {
 	($ty: ty, $len: expr, $fn_name: ident) => {
-		fn $fn_name(&mut self) -> Result<$ty, Error> {
+		fn $fn_name(&mut self) -> Result<$ty, DecodeError> {
 			let mut bytes = [0; $len];
-			self.read(&mut bytes)?;
+			self.read_exact(&mut bytes)?;
 			Ok(<$ty>::from_le_bytes(bytes))
 		}
 	};
@@ -153,9 +152,9 @@ macro_rules! impl_read_int_le {
 
 macro_rules! impl_read_int_be {
 	($ty: ty, $len: expr, $fn_name: ident) => {
-		fn $fn_name(&mut self) -> Result<$ty, Error> {
+		fn $fn_name(&mut self) -> Result<$ty, DecodeError> {
 			let mut bytes = [0; $len];
-			self.read(&mut bytes)?;
+			self.read_exact(&mut bytes)?;
 			Ok(<$ty>::from_be_bytes(bytes))
 		}
 	};
@@ -163,9 +162,9 @@ macro_rules! impl_read_int_be {
 
 macro_rules! impl_read_hex {
 	($len: expr, $fn_name: ident) => {
-		fn $fn_name(&mut self) -> Result<HexBytes, Error> {
+		fn $fn_name(&mut self) -> Result<HexBytes, DecodeError> {
 			let mut bytes = [0; $len];
-			self.read(&mut bytes)?;
+			self.read_exact(&mut bytes)?;
 			Ok(Box::new(bytes))
 		}
 	};
@@ -177,118 +176,98 @@ impl<R: BufRead + Seek> ReadExt for R {
 	impl_read_int_le!(u32, 4, read_u32_le);
 	impl_read_int_le!(u64, 8, read_u64_le);
 
-	impl_read_int_be!(u8, 1, read_u8_be);
 	impl_read_int_be!(u16, 2, read_u16_be);
-	impl_read_int_be!(u32, 4, read_u32_be);
-	impl_read_int_be!(u64, 8, read_u64_be);
 
 	impl_read_hex!(4, read_hex32);
 	impl_read_hex!(32, read_hex256);
 
-	fn read_hex_var(&mut self, length: u64) -> Result<HexBytes, Error> {
-		let mut bytes = vec![0; length as usize];
-		self.read(&mut bytes)?;
-		// There has to be a better way...
-		Ok(bytes.encode_hex_be().decode_hex_be().expect("unable to parse"))
+	fn read_hex_var(&mut self, length: u64) -> Result<HexBytes, DecodeError> {
+		// The length comes from the data, don't allocate it upfront
+		let mut bytes = Vec::new();
+		self.by_ref().take(length).read_to_end(&mut bytes)?;
+		if (bytes.len() as u64) < length {
+			return Err(DecodeError::UnexpectedEof);
+		}
+		Ok(bytes.into_boxed_slice())
 	}
 
-	fn read_compact_size(&mut self) -> Result<u64, Error> {
-		let  varint_size: u8 = self.read_u8_le()?;
-		let size: u64;
-
-		if varint_size < 253 {
-			size = varint_size as u64;
-		} else if varint_size == 253 {
-			size = self.read_u16_le()? as u64;
-			assert!(size > 253);
-		} else if varint_size == 254 {
-			size = self.read_u32_le()? as u64;
-			assert!(size > 0x10000);
-		} else if varint_size == 255 {
-			size = self.read_u64_le()?;
-			assert!(size > 0x100000000);
-		} else {
-			// TODO: Should i return an error over here? How?
-			panic!()
+	fn read_compact_size(&mut self) -> Result<u64, DecodeError> {
+		let varint_size: u8 = self.read_u8_le()?;
+		let (size, min) = match varint_size {
+			0..=252 => (varint_size as u64, 0),
+			253 => (self.read_u16_le()? as u64, 253),
+			254 => (self.read_u32_le()? as u64, 0x10000),
+			255 => (self.read_u64_le()?, 0x100000000),
+		};
+		if size < min {
+			return Err(DecodeError::NonCanonicalCompactSize);
+		}
+		if size > MAX_SIZE {
+			return Err(DecodeError::OversizedCompactSize(size));
 		}
-
-		assert!(size != 0);
 		Ok(size)
 	}
 
-	fn seek_from_curr(&mut self, length: i64) {
-		match self.seek(SeekFrom::Current(length)) {
-			Ok(_) => (),
-			Err(e) => panic!("{}", e)
-		}
+	fn seek_from_curr(&mut self, length: i64) -> Result<(), DecodeError> {
+		self.seek(SeekFrom::Current(length))?;
+		Ok(())
 	}
 }
 
-macro_rules! impl_write_int_le {
-	($ty: ty, $fn_name: ident) => {
-		fn $fn_name(&mut self, val: $ty) -> usize {
-			let bytes = val.to_le_bytes();
-			match self.write(&bytes) {
-				Ok(n) => n,
-				Err(e) => panic!("{}", e)
-			}
-		}
-	};
+/// Decoding has to use all of the data, anything left after the end is an error.
+pub fn check_consumed(stream: &Cursor<HexBytes>) -> Result<(), DecodeError> {
+	let remaining = stream.get_ref().len().saturating_sub(stream.position() as usize);
+	if remaining != 0 {
+		return Err(DecodeError::TrailingBytes(remaining));
+	}
+	Ok(())
 }
 
-macro_rules! impl_write_int_be {
+/// Encode a size in Bitcoin varint/compact-size format: one byte below 253, otherwise a marker
+/// byte followed by the size in 2, 4 or 8 bytes.
+pub fn compact_size(size: u64) -> Vec<u8> {
+	let mut bytes: Vec<u8> = Vec::new();
+	if size < 253 {
+		bytes.push(size as u8);
+	} else if size < 0x10000 {
+		bytes.push(253);
+		bytes.extend_from_slice(&(size as u16).to_le_bytes());
+	} else if size < 0x100000000 {
+		bytes.push(254);
+		bytes.extend_from_slice(&(size as u32).to_le_bytes());
+	} else {
+		bytes.push(255);
+		bytes.extend_from_slice(&size.to_le_bytes());
+	}
+	bytes
+}
+
+macro_rules! impl_write_int_le {
 	($ty: ty, $fn_name: ident) => {
 		fn $fn_name(&mut self, val: $ty) -> usize {
-			let bytes = val.to_be_bytes();
-			match self.write(&bytes) {
-				Ok(n) => n,
-				Err(e) => panic!("{}", e)
-			}
+			let bytes = val.to_le_bytes();
+			self.extend_from_slice(&bytes);
+			bytes.len()
 		}
 	};
 }
 
 
-impl<W: Write> WriteExt for W {
-	impl_write_int_le!(u16, write_u16_le);
+impl WriteExt for Vec<u8> {
+	impl_write_int_le!(u8, write_u8_le);
 	impl_write_int_le!(u32, write_u32_le);
 	impl_write_int_le!(u64, write_u64_le);
 
-	impl_write_int_be!(u16, write_u16_be);
-
 	fn write_hex(&mut self, bytes: HexBytes, with_varint: bool) -> usize {
 		if with_varint { self.write_varint(bytes.len() as u64); }
-		match self.write(&bytes) {
-			Ok(n) => n,
-			Err(e) => panic!("{}", e)
-		}
+		self.extend_from_slice(&bytes);
+		bytes.len()
 	}
 
 	fn write_varint(&mut self, size: u64) -> usize {
-		let mut bytes: Vec<u8> = Vec::new();
-		if size < 253 {
-			bytes.push(size as u8);
-		} else if size < 0x100 {
-			bytes.push(253);
-			bytes.push(size as u8);
-		} else if size < 0x10000 {
-			bytes.push(254);
-			bytes.push((size % 0x100) as u8);
-			bytes.push((size / 0x100) as u8);
-		} else if size < 0x100000000 {
-			bytes.push(255);
-			bytes.push((size % 0x100) as u8);
-			bytes.push(((size / 0x100) % 0x100) as u8);
-			bytes.push(((size % 0x10000) % 0x100) as u8);
-			bytes.push((size / 0x1000000) as u8);
-		} else {
-			panic!()
-		}
-
-		match self.write(&bytes) {
-			Ok(n) => n,
-			Err(e) => panic!("{}", e)
-		}
+		let bytes = compact_size(size);
+		self.extend_from_slice(&bytes);
+		bytes.len()
 	}
 }
 
@@ -349,7 +328,7 @@ macro_rules! user_read_hex {
 							Err(e) => println!("{}. Try again.", e)
 						}
 					},
-					_ => panic!("Error! Try again.")
+					_ => println!("Error! Enter {} bytes of hex. Try again.", $len)
 				}
 			}
 		}
@@ -433,3 +412,71 @@ impl StrExt for str {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use crate::error::DecodeError;
+	use super::{Decodable, Encodable, HexBytes, ReadExt, WriteExt};
+
+	fn read_compact_size(hex: &str) -> Result<u64, DecodeError> {
+		let bytes: HexBytes = hex.decode_hex_be().unwrap();
+		Cursor::new(bytes).read_compact_size()
+	}
+
+	#[test]
+	fn compact_size() {
+		for size in [0, 252, 253, 0xffff, 0x10000, 0x02000000] {
+			let mut bytes = Vec::new();
+			bytes.write_varint(size);
+			assert_eq!(Cursor::new(bytes).read_compact_size(), Ok(size));
+		}
+
+		// fits in a shorter encoding
+		assert_eq!(read_compact_size("fdfc00"), Err(DecodeError::NonCanonicalCompactSize));
+		assert_eq!(read_compact_size("feffff0000"), Err(DecodeError::NonCanonicalCompactSize));
+		assert_eq!(read_compact_size("ffffffffff00000000"), Err(DecodeError::NonCanonicalCompactSize));
+		assert_eq!(read_compact_size("fe01000002"), Err(DecodeError::OversizedCompactSize(0x02000001)));
+		assert_eq!(read_compact_size("fd01"), Err(DecodeError::UnexpectedEof));
+		assert_eq!(read_compact_size(""), Err(DecodeError::UnexpectedEof));
+	}
+
+	#[test]
+	fn write_varint() {
+		for (size, hex) in [
+			(0, "00"),
+			(252, "fc"),
+			(253, "fdfd00"),
+			(0xffff, "fdffff"),
+			(0x10000, "fe00000100"),
+			(0xffffffff, "feffffffff"),
+			(0x100000000, "ff0000000001000000"),
+		] {
+			let mut bytes = Vec::new();
+			assert_eq!(bytes.write_varint(size), hex.len() / 2);
+			assert_eq!(bytes.encode_hex_be(), hex);
+		}
+	}
+
+	#[test]
+	fn short_reads() {
+		let bytes: HexBytes = "010203".decode_hex_be().unwrap();
+		assert_eq!(Cursor::new(bytes.clone()).read_u32_le(), Err(DecodeError::UnexpectedEof));
+		assert_eq!(Cursor::new(bytes.clone()).read_hex256(), Err(DecodeError::UnexpectedEof));
+		// the length isn't allocated before reading
+		assert_eq!(Cursor::new(bytes.clone()).read_hex_var(u64::MAX), Err(DecodeError::UnexpectedEof));
+		assert_eq!(Cursor::new(bytes).read_hex_var(3).unwrap().to_vec(), vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn decode_hex() {
+		assert_eq!("00ff7A".decode_hex_be().unwrap().to_vec(), vec![0x00, 0xff, 0x7a]);
+		assert_eq!("00ff7a".decode_hex_le().unwrap().to_vec(), vec![0x7a, 0xff, 0x00]);
+		assert_eq!("".decode_hex_be().unwrap().len(), 0);
+		assert_eq!("abc".decode_hex_be(), Err(DecodeError::BadHex));
+		assert_eq!("zz".decode_hex_be(), Err(DecodeError::BadHex));
+		assert_eq!("+1".decode_hex_be(), Err(DecodeError::BadHex));
+		// multi-byte characters
+		assert_eq!("é0".decode_hex_be(), Err(DecodeError::BadHex));
+	}
+}